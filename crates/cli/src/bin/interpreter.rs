@@ -1,13 +1,22 @@
-use std::{fs::File, io::Read, path::PathBuf, process::ExitCode};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write as _},
+    path::PathBuf,
+    process::ExitCode,
+};
 
 use anyhow::Error;
 use clap::{Parser, Subcommand};
 use fabricator_cli::TestingStdlibContext as _;
 use fabricator_compiler::{
-    CompileError, CompileSettings,
+    CompileError, CompileErrors, CompileSettings,
+    cache::{CacheStatus, CodeCache},
     compiler::{CompileErrorKind, Compiler, ImportItems},
+    lints::LintLevel,
     parser::{ParseError, ParseErrorKind},
 };
+use fabricator_debugger::{DebugServer, Transport};
+use fabricator_profiler::Profiler;
 use fabricator_stdlib::string::debug_value;
 use fabricator_vm as vm;
 
@@ -17,6 +26,21 @@ struct Cli {
     command: Command,
     #[arg(long, short = 'O', default_value_t = 2)]
     opt_level: u8,
+    /// Directory to cache compiled bytecode in, used by the `run` command.
+    #[arg(long)]
+    code_cache: Option<PathBuf>,
+    /// Debug the `run` command with a Debug Adapter Protocol client, either over "stdio" or by
+    /// listening on the given TCP address.
+    ///
+    /// When using "stdio", scripts must not print anything to stdout.
+    #[arg(long)]
+    debug_adapter: Option<Transport>,
+    /// Profile the `run` command, writing a Chrome trace to the given path and folded stacks next
+    /// to it with a `.folded` extension.
+    ///
+    /// A summary of the most expensive functions is printed to stderr.
+    #[arg(long, conflicts_with = "debug_adapter")]
+    profile: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -36,26 +60,77 @@ fn main() -> Result<ExitCode, Error> {
 
             let settings = CompileSettings::from_path(&path).set_optimization_passes(cli.opt_level);
 
-            interpreter.enter(|ctx| {
-                let output = Compiler::compile_chunk(
-                    ctx,
-                    "",
-                    ImportItems::with_magic(&ctx, ctx.testing_stdlib()),
-                    settings,
-                    vm::SharedStr::new(&path.to_string_lossy()),
-                    &code,
-                )?;
-                let closure = vm::Closure::new(&ctx, output.chunk_prototype, None).unwrap();
+            let debug_server = match &cli.debug_adapter {
+                Some(transport) => {
+                    let server = DebugServer::start(transport)?;
+                    server.wait_for_configuration();
+                    Some(server)
+                }
+                None => None,
+            };
+
+            let profiler = cli.profile.is_some().then(Profiler::new);
+
+            let exit_code = interpreter.enter(|ctx| -> Result<_, Error> {
+                let mut compiler =
+                    Compiler::new(ctx, "", ImportItems::with_magic(&ctx, ctx.testing_stdlib()));
+                compiler.add_chunk(settings, vm::SharedStr::new(&path.to_string_lossy()), &code);
+
+                let output = match &cli.code_cache {
+                    Some(dir) => {
+                        let (output, status) = compiler.compile_with_cache(&CodeCache::new(dir))?;
+                        if let CacheStatus::Failed(err) = status {
+                            eprintln!("warning: could not write to code cache: {err}");
+                        }
+                        output
+                    }
+                    None => compiler.compile()?,
+                };
+                for warning in &output.warnings {
+                    eprintln!("{warning}");
+                }
+                let closure = vm::Closure::new(&ctx, output.chunks[0], None).unwrap();
 
                 let thread = vm::Thread::new(&ctx);
+                if let Some(server) = &debug_server {
+                    thread.set_hook(&ctx, server.hook());
+                }
+                if let Some(profiler) = &profiler {
+                    profiler.name_callbacks(ctx, &ctx.testing_stdlib());
+                    thread.set_hook(&ctx, profiler.hook());
+                }
+
                 Ok(match thread.run(ctx, closure) {
-                    Ok(()) => ExitCode::SUCCESS,
+                    Ok(()) => 0,
                     Err(err) => {
-                        println!("error: {}", err);
-                        ExitCode::FAILURE
+                        match &debug_server {
+                            Some(server) => server.output(&format!("error: {err}\n")),
+                            None => println!("error: {}", err),
+                        }
+                        1
                     }
                 })
-            })
+            })?;
+
+            if let Some(server) = &debug_server {
+                server.exited(exit_code);
+            }
+
+            if let (Some(profiler), Some(trace_path)) = (&profiler, &cli.profile) {
+                let profile = profiler.profile();
+
+                let mut trace = BufWriter::new(File::create(trace_path)?);
+                profile.write_chrome_trace(&mut trace)?;
+                trace.flush()?;
+
+                let mut folded = BufWriter::new(File::create(trace_path.with_extension("folded"))?);
+                profile.write_folded(&mut folded)?;
+                folded.flush()?;
+
+                profile.write_summary(io::stderr().lock())?;
+            }
+
+            Ok(ExitCode::from(exit_code as u8))
         }
         Command::Dump { path } => {
             let mut code = String::new();
@@ -103,21 +178,26 @@ fn main() -> Result<ExitCode, Error> {
             let mut editor = rustyline::DefaultEditor::new()?;
             let thread = interpreter.enter(|ctx| ctx.stash(vm::Thread::new(&ctx)));
 
-            let settings = CompileSettings::strict().set_optimization_passes(cli.opt_level);
+            let mut settings = CompileSettings::strict().set_optimization_passes(cli.opt_level);
+            // Each line is compiled separately, so lints like unused variables would be reported
+            // for almost every line.
+            settings.lints.set_all(LintLevel::Allow);
 
             let mut imports = interpreter
                 .enter(|ctx| ctx.stash(ImportItems::with_magic(&ctx, ctx.testing_stdlib())));
 
-            fn is_end_of_stream_err(e: &CompileError) -> bool {
+            // The input is incomplete if the first error is an unexpected end of stream, any later
+            // errors may just be the parser failing to recover from it.
+            fn is_end_of_stream_err(e: &CompileErrors) -> bool {
                 matches!(
-                    e,
-                    CompileError {
+                    e.0.first(),
+                    Some(CompileError {
                         kind: CompileErrorKind::Parsing(ParseError {
                             kind: ParseErrorKind::EndOfStream { .. },
                             ..
                         }),
                         ..
-                    }
+                    })
                 )
             }
 
@@ -0,0 +1,66 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use anyhow::{Context as _, Error};
+use clap::Parser;
+
+/// Replay a recorded game headlessly, printing a hash of the game state after every tick.
+///
+/// Comparing the output of two builds finds the first tick where they diverge.
+#[derive(Parser)]
+struct Cli {
+    project_file: PathBuf,
+    recording: PathBuf,
+    #[arg(long, default_value = "default")]
+    config: String,
+    /// Compare against the output of a previous replay, stopping at the first tick with a
+    /// different state hash.
+    #[arg(long)]
+    expect: Option<PathBuf>,
+}
+
+fn main() -> Result<ExitCode, Error> {
+    let cli = Cli::parse();
+
+    let expected = match &cli.expect {
+        Some(path) => Some(
+            fs::read_to_string(path)?
+                .lines()
+                .map(|line| line.to_owned())
+                .collect::<Vec<_>>(),
+        ),
+        None => None,
+    };
+
+    let recording = fabricator::Recording::load(&cli.recording)
+        .with_context(|| format!("could not load recording {:?}", cli.recording))?;
+    let tick_count = recording.ticks.len();
+    let project = fabricator::Project::load(&cli.project_file)?;
+    let mut game = fabricator::Game::with_options(
+        project,
+        &cli.config,
+        fabricator::GameOptions {
+            replay: Some(recording),
+            ..Default::default()
+        },
+    )?;
+
+    let mut render = fabricator::Render::default();
+    for tick in 0..tick_count {
+        game.replay_tick(&mut render)
+            .with_context(|| format!("error replaying tick {tick}"))?;
+        let line = format!("{tick} {:016x}", game.state_hash());
+        println!("{line}");
+
+        if let Some(expected) = &expected
+            && expected.get(tick) != Some(&line)
+        {
+            eprintln!(
+                "diverged at tick {tick}, expected {:?}",
+                expected.get(tick).map_or("nothing", |l| l.as_str())
+            );
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
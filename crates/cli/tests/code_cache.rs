@@ -0,0 +1,74 @@
+use std::{env, fs, process};
+
+use fabricator_cli::TestingStdlibContext as _;
+use fabricator_compiler::{
+    self as compiler,
+    cache::{CacheStatus, CodeCache},
+};
+use fabricator_vm as vm;
+
+fn compile_and_run(cache: &CodeCache, code: &str) -> (CacheStatus, i64) {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let mut compiler = compiler::Compiler::new(
+            ctx,
+            "default",
+            compiler::ImportItems::with_magic(&ctx, ctx.testing_stdlib()),
+        );
+        compiler.add_chunk(
+            compiler::CompileSettings::strict(),
+            "exports".into(),
+            r#"
+                function add(a, b) {
+                    return a + b;
+                }
+            "#,
+        );
+        compiler.add_chunk(compiler::CompileSettings::strict(), "main".into(), code);
+
+        let (output, status) = compiler.compile_with_cache(cache).unwrap();
+
+        let thread = vm::Thread::new(&ctx);
+        let mut ret = vm::Value::Undefined;
+        for chunk in output.chunks {
+            let closure = vm::Closure::new(&ctx, chunk, None).unwrap();
+            ret = thread.exec(ctx, |mut exec| {
+                exec.call(ctx, closure).unwrap();
+                exec.stack().get(0)
+            });
+        }
+
+        let vm::Value::Integer(ret) = ret else {
+            panic!("script did not return an integer");
+        };
+        (status, ret)
+    })
+}
+
+#[test]
+fn test_code_cache() {
+    let dir = env::temp_dir().join(format!("fabricator-code-cache-test-{}", process::id()));
+    let cache = CodeCache::new(&dir);
+
+    let code = r#"
+        let f = closure(x) {
+            return add(x, 2);
+        };
+        return f(3);
+    "#;
+
+    let (status, ret) = compile_and_run(&cache, code);
+    assert!(matches!(status, CacheStatus::Stored));
+    assert_eq!(ret, 5);
+
+    let (status, ret) = compile_and_run(&cache, code);
+    assert!(matches!(status, CacheStatus::Hit));
+    assert_eq!(ret, 5);
+
+    let (status, ret) = compile_and_run(&cache, "return add(1, 1);");
+    assert!(matches!(status, CacheStatus::Stored));
+    assert_eq!(ret, 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,92 @@
+use fabricator_cli::TestingStdlibContext as _;
+use fabricator_compiler::{
+    self as compiler,
+    compiler::{CompileErrorKind, CompileErrors},
+};
+use fabricator_vm as vm;
+
+fn compile(chunks: &[(&str, &str)]) -> Result<(), CompileErrors> {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let mut compiler = compiler::Compiler::new(
+            ctx,
+            "default",
+            compiler::ImportItems::with_magic(&ctx, ctx.testing_stdlib()),
+        );
+        for &(name, code) in chunks {
+            compiler.add_chunk(compiler::CompileSettings::strict(), name.into(), code);
+        }
+        compiler.compile().map(|_| ())
+    })
+}
+
+fn summarize(errors: &CompileErrors) -> Vec<(&str, usize, &'static str)> {
+    errors
+        .0
+        .iter()
+        .map(|err| {
+            let kind = match err.kind {
+                CompileErrorKind::Lexing(_) => "lexing",
+                CompileErrorKind::Macro(_) => "macro",
+                CompileErrorKind::Parsing(_) => "parsing",
+                CompileErrorKind::IrGen(_) => "ir_gen",
+                CompileErrorKind::Lint(_) => "lint",
+                _ => "other",
+            };
+            (err.chunk_name.as_str(), err.line_number.0, kind)
+        })
+        .collect()
+}
+
+#[test]
+fn test_multiple_errors() {
+    let errors = compile(&[
+        ("a", "let x = ;\nlet y = 1;\nlet z = );\n"),
+        ("b", "let s = \"unfinished;\n"),
+        ("c", "#macro\nlet w = 2;\n"),
+        ("d", "\nfunction f() {\n    return +;\n}\n"),
+    ])
+    .unwrap_err();
+
+    assert_eq!(
+        summarize(&errors),
+        [
+            ("b", 0, "lexing"),
+            ("c", 0, "macro"),
+            ("a", 0, "parsing"),
+            ("a", 2, "parsing"),
+            ("d", 2, "parsing"),
+        ]
+    );
+}
+
+#[test]
+fn test_multiple_ir_gen_errors() {
+    let errors = compile(&[
+        ("a", "let x = 1;\nbreak;\n"),
+        ("b", "function f() {\n    continue;\n}\n"),
+        ("c", "let y = 2;\n"),
+    ])
+    .unwrap_err();
+
+    assert_eq!(summarize(&errors), [("b", 1, "ir_gen"), ("a", 1, "ir_gen")]);
+
+    assert!(compile(&[("a", "let x = 1;\nreturn x;\n")]).is_ok());
+}
+
+#[test]
+fn test_denied_lints() {
+    let errors = compile(&[
+        (
+            "a",
+            "gml_pragma(\"lint\", \"deny\", \"unused_variable\");\nlet x = 1;\n",
+        ),
+        ("b", "let y = 2;\n"),
+        ("c", "function f(a, b) {\n    return a;\n    b += 1;\n}\n"),
+    ])
+    .unwrap_err();
+
+    // Lints at their default level are not errors.
+    assert_eq!(summarize(&errors), [("a", 1, "lint")]);
+}
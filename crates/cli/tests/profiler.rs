@@ -0,0 +1,115 @@
+use fabricator_compiler as compiler;
+use fabricator_profiler::Profiler;
+use fabricator_stdlib::{StdlibContext as _, util::MagicExt as _};
+use fabricator_vm as vm;
+use gc_arena::Gc;
+
+#[test]
+fn test_profiler() {
+    let interpreter = vm::Interpreter::new();
+    let profiler = Profiler::new();
+
+    interpreter.enter(|ctx| {
+        let mut magic = vm::MagicSet::new();
+        magic.merge(&ctx.stdlib());
+
+        magic.insert_constant(
+            ctx,
+            "test_callback",
+            vm::Callback::from_fn(ctx, |_ctx, mut exec| {
+                exec.stack().clear();
+                Ok(())
+            }),
+        );
+
+        let magic = Gc::new(&ctx, magic);
+
+        let output = compiler::Compiler::compile_chunk(
+            ctx,
+            "default",
+            compiler::ImportItems::with_magic(&ctx, magic),
+            compiler::CompileSettings::strict(),
+            "profiler test".into(),
+            r#"
+                function inner(n) {
+                    var sum = 0;
+                    for (var i = 0; i < n; i += 1) {
+                        sum += i;
+                    }
+                    test_callback();
+                    return sum;
+                }
+
+                function outer() {
+                    for (var i = 0; i < 10; i += 1) {
+                        inner(i);
+                    }
+                }
+
+                outer();
+                outer();
+            "#,
+        )
+        .unwrap();
+        let closure = vm::Closure::new(&ctx, output.chunk_prototype, None).unwrap();
+
+        profiler.name_callbacks(ctx, &magic);
+
+        let thread = vm::Thread::new(&ctx);
+        thread.set_hook(&ctx, profiler.hook());
+        thread.run(ctx, closure).unwrap();
+    });
+
+    let profile = profiler.profile();
+    let stats = |name: &str| {
+        profile
+            .functions
+            .iter()
+            .find(|(info, _)| info.name == name)
+            .map(|(_, stats)| *stats)
+            .unwrap()
+    };
+
+    let chunk = stats("<chunk>");
+    let outer = stats("outer");
+    let inner = stats("inner");
+    let callback = stats("test_callback");
+
+    assert_eq!(chunk.calls, 1);
+    assert_eq!(outer.calls, 2);
+    assert_eq!(inner.calls, 20);
+    assert_eq!(callback.calls, 20);
+
+    assert_eq!(callback.inclusive_instructions, 0);
+    assert!(inner.exclusive_instructions > 0);
+    assert_eq!(inner.inclusive_instructions, inner.exclusive_instructions);
+    assert_eq!(
+        outer.inclusive_instructions,
+        outer.exclusive_instructions + inner.inclusive_instructions
+    );
+    assert_eq!(
+        chunk.inclusive_instructions,
+        chunk.exclusive_instructions + outer.inclusive_instructions
+    );
+    assert!(chunk.inclusive_time >= outer.inclusive_time);
+
+    let total_instructions = profile.stacks.values().sum::<u64>();
+    assert_eq!(total_instructions, chunk.inclusive_instructions);
+
+    assert_eq!(profile.events.len(), 43);
+    assert_eq!(profile.dropped_events, 0);
+
+    let mut folded = Vec::new();
+    profile.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+    assert!(
+        folded
+            .lines()
+            .any(|l| l.starts_with("<chunk> (profiler test:1);outer (profiler test:11);inner"))
+    );
+
+    let mut trace = Vec::new();
+    profile.write_chrome_trace(&mut trace).unwrap();
+    let trace: serde_json::Value = serde_json::from_slice(&trace).unwrap();
+    assert_eq!(trace["traceEvents"].as_array().unwrap().len(), 43);
+}
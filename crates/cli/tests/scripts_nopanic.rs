@@ -59,6 +59,7 @@ fn run_code(
         fn on_step(
             &mut self,
             _ctx: vm::Context<'gc>,
+            _frames: vm::FrameStack<'gc, '_>,
             instruction_count: u32,
         ) -> Result<u32, vm::RuntimeError> {
             self.insts_available = self.insts_available.saturating_sub(instruction_count);
@@ -0,0 +1,10 @@
+global.leak = { items: [ {}, {} ] };
+
+var snapshot = json_parse(debug_heap_snapshot(global.leak.items));
+
+assert(snapshot.target != undefined);
+assert(array_length(snapshot.target.retaining_path) >= 2);
+assert(snapshot.stats.array.count >= 1);
+assert(snapshot.stats.object.count >= 4);
+
+return true;
@@ -0,0 +1,76 @@
+function fail(a) {
+    return a + {};
+}
+
+var err;
+try {
+    fail(1);
+} catch(e) {
+    err = e;
+}
+assert(is_struct(err));
+assert(is_string(err.message) && err.message != "");
+assert(err.script == "fail");
+assert(err.line == 2);
+assert(is_array(err.stacktrace));
+assert(err.stacktrace[0] == "fail (line 2)");
+assert(array_length(err.stacktrace) == 2);
+assert(string_pos("(line 7)", err.stacktrace[1]) > 0);
+assert(string_pos("<anonymous>", err.stacktrace[1]) == 0);
+assert(string_pos(err.message, err.longMessage) == 1);
+assert(string_pos("fail (line 2)", err.longMessage) > 0);
+
+// Thrown values are caught unchanged.
+var thrown = { message: "custom" };
+var caught;
+try {
+    throw thrown;
+} catch(e) {
+    caught = e;
+}
+assert(caught == thrown);
+
+// Rethrown exception structs are caught as the same struct.
+var rethrown;
+try {
+    try {
+        fail(2);
+    } catch(e) {
+        throw e;
+    }
+} catch(e) {
+    rethrown = e;
+}
+assert(rethrown.script == "fail");
+
+// Errors inside of try blocks are reported in the function containing the try block.
+function catch_direct() {
+    try {
+        return 1 + {};
+    } catch(e) {
+        return e;
+    }
+}
+var direct = catch_direct();
+assert(direct.script == "catch_direct");
+assert(direct.line == 49);
+assert(array_length(direct.stacktrace) == 2);
+assert(direct.stacktrace[0] == "catch_direct (line 49)");
+
+function catch_nested() {
+    try {
+        try {
+            fail(3);
+        } catch(e) {
+            return e;
+        }
+    } catch(e) {
+    }
+}
+var nested = catch_nested();
+assert(nested.script == "fail");
+assert(array_length(nested.stacktrace) == 3);
+assert(nested.stacktrace[0] == "fail (line 2)");
+assert(nested.stacktrace[1] == "catch_nested (line 63)");
+
+return true;
@@ -0,0 +1,23 @@
+var target = { value: 42 };
+var list = [1, 2];
+
+var ref = weak_ref_create(target);
+assert(weak_ref_alive(ref));
+assert(ref.ref == target);
+assert(ref.ref.value == 42);
+
+var refs = [weak_ref_create(list), weak_ref_create(function() {})];
+assert(weak_ref_any_alive(refs));
+assert(weak_ref_any_alive(refs, 1));
+assert(weak_ref_any_alive(refs, 0, 1));
+assert(!weak_ref_any_alive(refs, 0, 0));
+
+var created = true;
+try {
+    weak_ref_create(1);
+} catch (e) {
+    created = false;
+}
+assert(!created);
+
+return true;
@@ -0,0 +1,268 @@
+use std::thread;
+
+use fabricator_compiler as compiler;
+use fabricator_stdlib::StdlibContext as _;
+use fabricator_vm as vm;
+
+fn run_limited(
+    ctx: vm::Context<'_>,
+    limits: vm::ThreadLimits,
+    code: &str,
+) -> Result<(), vm::ExternVmError> {
+    let output = compiler::Compiler::compile_chunk(
+        ctx,
+        "default",
+        compiler::ImportItems::with_magic(&ctx, ctx.stdlib()),
+        compiler::CompileSettings::compat(),
+        "thread limits test".into(),
+        code,
+    )
+    .unwrap();
+    let closure = vm::Closure::new(&ctx, output.chunk_prototype, None).unwrap();
+
+    let thread = vm::Thread::new(&ctx);
+    thread.set_limits(&ctx, limits);
+    thread.run(ctx, closure)
+}
+
+fn is_runtime_err<E: std::error::Error + Send + Sync + 'static>(
+    res: Result<(), vm::ExternVmError>,
+) -> bool {
+    match res {
+        Err(vm::ExternVmError {
+            error: vm::ExternError::Runtime(runtime_err),
+            ..
+        }) => runtime_err.is::<E>(),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_instruction_limit() {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let limits = vm::ThreadLimits {
+            max_instructions: Some(10_000),
+            ..Default::default()
+        };
+
+        let res = run_limited(
+            ctx,
+            limits,
+            r#"
+                try {
+                    while true {}
+                } catch (e) {
+                }
+            "#,
+        );
+        assert!(is_runtime_err::<vm::InstructionLimitError>(res));
+
+        // A coroutine created by a limited thread is limited the same way.
+        let res = run_limited(
+            ctx,
+            limits,
+            r#"
+                var co = coroutine_create(function() {
+                    while true {}
+                });
+                try {
+                    coroutine_resume(co);
+                } catch (e) {
+                }
+            "#,
+        );
+        assert!(is_runtime_err::<vm::InstructionLimitError>(res));
+
+        // Runs which stay within the limit succeed, and the limit applies to each run separately.
+        let output = compiler::Compiler::compile_chunk(
+            ctx,
+            "default",
+            compiler::ImportItems::with_magic(&ctx, ctx.stdlib()),
+            compiler::CompileSettings::compat(),
+            "thread limits test".into(),
+            r#"
+                for (var i = 0; i < 100; ++i) {}
+            "#,
+        )
+        .unwrap();
+        let closure = vm::Closure::new(&ctx, output.chunk_prototype, None).unwrap();
+
+        let thread = vm::Thread::new(&ctx);
+        thread.set_limits(&ctx, limits);
+        for _ in 0..200 {
+            thread.run(ctx, closure).unwrap();
+        }
+    });
+}
+
+#[test]
+fn test_frame_depth_limit() {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let limits = vm::ThreadLimits {
+            max_frame_depth: Some(64),
+            ..Default::default()
+        };
+
+        let res = run_limited(
+            ctx,
+            limits,
+            r#"
+                function recurse(n) {
+                    try {
+                        return recurse(n + 1);
+                    } catch (e) {
+                        return n;
+                    }
+                }
+                recurse(0);
+            "#,
+        );
+        assert!(is_runtime_err::<vm::FrameDepthLimitError>(res));
+
+        run_limited(
+            ctx,
+            limits,
+            r#"
+                function recurse(n) {
+                    if n > 0 {
+                        recurse(n - 1);
+                    }
+                }
+                recurse(32);
+            "#,
+        )
+        .unwrap();
+    });
+}
+
+#[test]
+fn test_memory_limit() {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let limits = vm::ThreadLimits {
+            max_memory: Some(ctx.metrics().total_allocation() + 1024 * 1024),
+            ..Default::default()
+        };
+
+        let res = run_limited(
+            ctx,
+            limits,
+            r#"
+                var arrays = [];
+                for (var i = 0; true; ++i) {
+                    try {
+                        array_push(arrays, [i]);
+                    } catch (e) {
+                    }
+                }
+            "#,
+        );
+        assert!(is_runtime_err::<vm::MemoryLimitError>(res));
+    });
+}
+
+#[test]
+fn test_coroutine_limits() {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let limits = vm::ThreadLimits {
+            max_instructions: Some(10_000),
+            ..Default::default()
+        };
+
+        // Every coroutine stays well within the limit on its own, but they all share the budget of
+        // the thread which runs them.
+        let res = run_limited(
+            ctx,
+            limits,
+            r#"
+                function spin() {
+                    for (var i = 0; i < 500; ++i) {}
+                }
+                for (var i = 0; i < 100; ++i) {
+                    try {
+                        coroutine_resume(coroutine_create(spin));
+                    } catch (e) {
+                    }
+                }
+            "#,
+        );
+        assert!(is_runtime_err::<vm::InstructionLimitError>(res));
+
+        // Resuming a coroutine does not start a new budget either.
+        let res = run_limited(
+            ctx,
+            limits,
+            r#"
+                var co = coroutine_create(function() {
+                    while true {
+                        for (var i = 0; i < 500; ++i) {}
+                        coroutine_yield();
+                    }
+                });
+                for (var i = 0; i < 100; ++i) {
+                    try {
+                        coroutine_resume(co);
+                    } catch (e) {
+                    }
+                }
+            "#,
+        );
+        assert!(is_runtime_err::<vm::InstructionLimitError>(res));
+
+        // Frames of nested coroutines count towards the frame depth of the thread running them.
+        let res = run_limited(
+            ctx,
+            vm::ThreadLimits {
+                max_frame_depth: Some(64),
+                ..Default::default()
+            },
+            r#"
+                function nest(n) {
+                    try {
+                        return coroutine_resume(coroutine_create(nest), n + 1);
+                    } catch (e) {
+                        return n;
+                    }
+                }
+                nest(0);
+            "#,
+        );
+        assert!(is_runtime_err::<vm::FrameDepthLimitError>(res));
+    });
+}
+
+#[test]
+fn test_nested_coroutines_bounded() {
+    // Every nested coroutine runs deeper on the Rust stack, which can be large per nested thread in
+    // debug builds, so give this test as much stack as a main thread usually has.
+    thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(|| {
+            let interpreter = vm::Interpreter::new();
+
+            interpreter.enter(|ctx| {
+                // Nesting is bounded even without any limits set.
+                let res = run_limited(
+                    ctx,
+                    vm::ThreadLimits::default(),
+                    r#"
+                        function nest(n) {
+                            return coroutine_resume(coroutine_create(nest), n + 1);
+                        }
+                        nest(0);
+                    "#,
+                );
+                assert!(is_runtime_err::<vm::NestedThreadsError>(res));
+            });
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -317,6 +317,7 @@ fn test_vm_step_hook() {
             fn on_step(
                 &mut self,
                 _ctx: vm::Context<'gc>,
+                _frames: vm::FrameStack<'gc, '_>,
                 inst_count: u32,
             ) -> Result<u32, vm::RuntimeError> {
                 self.0 = self.0.saturating_sub(inst_count);
@@ -340,3 +341,195 @@ fn test_vm_step_hook() {
         }
     });
 }
+
+#[test]
+fn test_vm_frame_inspection() {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let mut magic = vm::MagicSet::new();
+        magic.merge(&ctx.stdlib());
+
+        magic.insert_constant(
+            ctx,
+            "inspect",
+            vm::Callback::from_fn(ctx, |_ctx, _exec| Ok(())),
+        );
+
+        let magic = Gc::new(&ctx, magic);
+
+        let output = compiler::Compiler::compile_chunk(
+            ctx,
+            "default",
+            compiler::ImportItems::with_magic(&ctx, magic),
+            compiler::CompileSettings::strict(),
+            "vm hook test".into(),
+            r#"
+                let counter = 3;
+                let f = method({ tag: "me" }, closure() {
+                    let g = closure() {
+                        return counter;
+                    };
+                    inspect();
+                    return g();
+                });
+                f();
+            "#,
+        )
+        .unwrap();
+        let closure = vm::Closure::new(&ctx, output.chunk_prototype, None).unwrap();
+
+        #[derive(Collect)]
+        #[collect(require_static)]
+        struct TestHook {
+            found_upvalue: Rc<Cell<bool>>,
+            found_this: Rc<Cell<bool>>,
+        }
+
+        impl<'gc> vm::Hook<'gc> for TestHook {
+            fn on_call(
+                &mut self,
+                ctx: vm::Context<'gc>,
+                frames: vm::FrameStack<'gc, '_>,
+            ) -> Result<(), vm::RuntimeError> {
+                if frames.frame_depth() < 3 {
+                    return Ok(());
+                }
+
+                assert!(frames.registers(0).is_none());
+                assert!(frames.registers(1).is_some());
+
+                let vm::StackFrame::Closure(caller) = frames.frame(1) else {
+                    panic!("caller is not a closure");
+                };
+                for i in 0..caller.closure.prototype().heap_vars().len() {
+                    if frames.heap_var(1, i) == Some(vm::Value::Integer(3)) {
+                        self.found_upvalue.set(true);
+                    }
+                }
+
+                if let Some(vm::Value::Object(this)) = frames.this(ctx, 1) {
+                    if this.find_field(ctx, "tag") == Some(vm::Value::String(ctx.intern("me"))) {
+                        self.found_this.set(true);
+                    }
+                }
+
+                Ok(())
+            }
+        }
+
+        let thread = vm::Thread::new(&ctx);
+
+        let found_upvalue = Rc::new(Cell::new(false));
+        let found_this = Rc::new(Cell::new(false));
+        thread.set_hook(
+            &ctx,
+            TestHook {
+                found_upvalue: found_upvalue.clone(),
+                found_this: found_this.clone(),
+            },
+        );
+
+        thread.run(ctx, closure).unwrap();
+
+        assert!(found_upvalue.get());
+        assert!(found_this.get());
+    });
+}
+
+#[test]
+fn test_vm_local_variable_names() {
+    let interpreter = vm::Interpreter::new();
+
+    interpreter.enter(|ctx| {
+        let mut magic = vm::MagicSet::new();
+        magic.merge(&ctx.stdlib());
+
+        magic.insert_constant(
+            ctx,
+            "inspect",
+            vm::Callback::from_fn(ctx, |_ctx, _exec| Ok(())),
+        );
+
+        let magic = Gc::new(&ctx, magic);
+
+        let output = compiler::Compiler::compile_chunk(
+            ctx,
+            "default",
+            compiler::ImportItems::with_magic(&ctx, magic),
+            compiler::CompileSettings::strict(),
+            "vm hook test".into(),
+            r#"
+                let counter = 3;
+                let f = closure(a) {
+                    let b = a + 1;
+                    inspect();
+                    return b + counter;
+                };
+                f(10);
+            "#,
+        )
+        .unwrap();
+        let closure = vm::Closure::new(&ctx, output.chunk_prototype, None).unwrap();
+
+        #[derive(Collect)]
+        #[collect(require_static)]
+        struct TestHook {
+            found_register: Rc<Cell<bool>>,
+            found_upvalue: Rc<Cell<bool>>,
+        }
+
+        impl<'gc> vm::Hook<'gc> for TestHook {
+            fn on_call(
+                &mut self,
+                _ctx: vm::Context<'gc>,
+                frames: vm::FrameStack<'gc, '_>,
+            ) -> Result<(), vm::RuntimeError> {
+                if frames.frame_depth() < 3 {
+                    return Ok(());
+                }
+
+                let vm::StackFrame::Closure(caller) = frames.frame(1) else {
+                    panic!("caller is not a closure");
+                };
+                let prototype = caller.closure.prototype();
+                for var in prototype.local_variables_at(caller.instruction) {
+                    match (&*var.name, var.location) {
+                        ("b", vm::VariableLocation::Register(reg)) => {
+                            let registers = frames.registers(1).unwrap();
+                            assert_eq!(registers[reg.0 as usize], vm::Value::Integer(11));
+                            self.found_register.set(true);
+                        }
+                        ("counter", vm::VariableLocation::Heap(heap)) => {
+                            assert_eq!(
+                                frames.heap_var(1, heap.0 as usize),
+                                Some(vm::Value::Integer(3))
+                            );
+                            self.found_upvalue.set(true);
+                        }
+                        _ => {}
+                    }
+                }
+
+                Ok(())
+            }
+        }
+
+        let thread = vm::Thread::new(&ctx);
+
+        let found_register = Rc::new(Cell::new(false));
+        let found_upvalue = Rc::new(Cell::new(false));
+        thread.set_hook(
+            &ctx,
+            TestHook {
+                found_register: found_register.clone(),
+                found_upvalue: found_upvalue.clone(),
+            },
+        );
+
+        thread.run(ctx, closure).unwrap();
+
+        assert!(found_register.get());
+        assert!(found_upvalue.get());
+    });
+}
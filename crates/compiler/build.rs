@@ -0,0 +1,47 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest as _, Sha256};
+
+// Hash every compiler source file into `FABRICATOR_COMPILER_FINGERPRINT`, which is part of every
+// code cache key. Any change to the compiler, even one which keeps the same instruction set,
+// invalidates previously cached bytecode.
+fn main() -> io::Result<()> {
+    let src_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("src");
+    println!("cargo::rerun-if-changed={}", src_dir.display());
+
+    let mut files = Vec::new();
+    collect_files(&src_dir, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for path in files {
+        let relative = path.strip_prefix(&src_dir).unwrap();
+        let contents = fs::read(&path)?;
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+
+    let fingerprint: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    println!("cargo::rustc-env=FABRICATOR_COMPILER_FINGERPRINT={fingerprint}");
+    Ok(())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
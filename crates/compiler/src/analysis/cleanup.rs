@@ -2,8 +2,8 @@ use fabricator_util::index_containers::IndexSet;
 
 use crate::{graph::dfs::depth_first_search, ir};
 
-/// Remove any blocks that are not reachable from the `start_block`.
-pub fn clean_unreachable_blocks<S>(ir: &mut ir::Function<S>) {
+/// Find every block that is reachable from the `start_block`.
+pub fn reachable_blocks<S>(ir: &ir::Function<S>) -> IndexSet {
     let mut reachable_blocks = IndexSet::new();
 
     depth_first_search(
@@ -15,6 +15,12 @@ pub fn clean_unreachable_blocks<S>(ir: &mut ir::Function<S>) {
         |_| {},
     );
 
+    reachable_blocks
+}
+
+/// Remove any blocks that are not reachable from the `start_block`.
+pub fn clean_unreachable_blocks<S>(ir: &mut ir::Function<S>) {
+    let reachable_blocks = reachable_blocks(ir);
     ir.blocks
         .retain(|id, _| reachable_blocks.contains(id.index() as usize));
 }
@@ -39,34 +45,49 @@ pub fn clean_instructions<S>(ir: &mut ir::Function<S>) {
 
     ir.instructions
         .retain(|inst_id, _| used_instructions.contains(inst_id.index() as usize));
+    ir.instruction_names
+        .retain(|inst_id, _| used_instructions.contains(inst_id.index() as usize));
 }
 
-/// Remove all variables that are never used in any block.
-///
-/// Both `GetVariable` and `SetVariable` count as a use, as well as `Closure` instructions whose
-/// closure references an upper variable in this function.
-///
-/// If a variable is unused, any `OpenVariable` or `CloseVariable` instructions for that variable
-/// will also be removed.
+/// The variables of a function which are used by any instruction, see [`variable_uses`].
+#[derive(Debug, Default)]
+pub struct VariableUses {
+    /// Variables read by a `GetVariable` instruction, or referenced as an upper variable by the
+    /// closure of a `Closure` instruction.
+    pub read: IndexSet,
+    /// Variables written by a `SetVariable` instruction.
+    pub written: IndexSet,
+}
+
+impl VariableUses {
+    pub fn is_used(&self, var_id: ir::VarId) -> bool {
+        let index = var_id.index() as usize;
+        self.read.contains(index) || self.written.contains(index)
+    }
+}
+
+/// Find every variable that is read or written in any block.
 ///
 /// Checks all blocks, does not consider block reachability.
-pub fn clean_unused_variables<S>(ir: &mut ir::Function<S>) {
-    let mut used_variables = IndexSet::new();
+pub fn variable_uses<S>(ir: &ir::Function<S>) -> VariableUses {
+    let mut uses = VariableUses::default();
 
     for block in ir.blocks.values() {
         for &inst_id in &block.instructions {
             let inst = &ir.instructions[inst_id];
             match inst.kind {
-                ir::InstructionKind::GetVariable(var_id)
-                | ir::InstructionKind::SetVariable(var_id, _) => {
-                    used_variables.insert(var_id.index() as usize);
+                ir::InstructionKind::GetVariable(var_id) => {
+                    uses.read.insert(var_id.index() as usize);
+                }
+                ir::InstructionKind::SetVariable(var_id, _) => {
+                    uses.written.insert(var_id.index() as usize);
                 }
                 ir::InstructionKind::Closure { func, .. } => {
                     for var in ir.functions[func].variables.values() {
                         // Creating a closure uses every upper variable that the closure closes
                         // over.
                         if let &ir::Variable::Upper(var_id) = var {
-                            used_variables.insert(var_id.index() as usize);
+                            uses.read.insert(var_id.index() as usize);
                         }
                     }
                 }
@@ -75,6 +96,20 @@ pub fn clean_unused_variables<S>(ir: &mut ir::Function<S>) {
         }
     }
 
+    uses
+}
+
+/// Remove all variables that are never used in any block.
+///
+/// Both reads and writes count as a use, see [`variable_uses`].
+///
+/// If a variable is unused, any `OpenVariable` or `CloseVariable` instructions for that variable
+/// will also be removed.
+///
+/// Checks all blocks, does not consider block reachability.
+pub fn clean_unused_variables<S>(ir: &mut ir::Function<S>) {
+    let uses = variable_uses(ir);
+
     // Remove open / close instructions for any unused variables.
     for block in ir.blocks.values() {
         for &inst_id in &block.instructions {
@@ -82,7 +117,7 @@ pub fn clean_unused_variables<S>(ir: &mut ir::Function<S>) {
             match inst.kind {
                 ir::InstructionKind::OpenVariable(var_id)
                 | ir::InstructionKind::CloseVariable(var_id)
-                    if !used_variables.contains(var_id.index() as usize) =>
+                    if !uses.is_used(var_id) =>
                 {
                     inst.kind = ir::InstructionKind::NoOp;
                 }
@@ -91,8 +126,7 @@ pub fn clean_unused_variables<S>(ir: &mut ir::Function<S>) {
         }
     }
 
-    ir.variables
-        .retain(|id, _| used_variables.contains(id.index() as usize));
+    ir.variables.retain(|id, _| uses.is_used(id));
 }
 
 /// Remove all shadow variables that are never used in any block.
@@ -31,6 +31,13 @@ pub fn eliminate_copies<S>(ir: &mut ir::Function<S>) {
 
                 copies.insert(inst_id, real_source);
 
+                // Any variable names given to the copy now belong to its real source.
+                if let Some(names) = ir.instruction_names.remove(inst_id) {
+                    ir.instruction_names
+                        .get_or_insert_default(real_source)
+                        .extend(names);
+                }
+
                 // We're removing every copy, so the existing copy instruction should be unused.
                 inst.kind = ir::InstructionKind::NoOp;
             }
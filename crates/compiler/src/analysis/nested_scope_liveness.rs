@@ -464,6 +464,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         assert!(matches!(
@@ -525,6 +527,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         assert!(matches!(
@@ -592,6 +596,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         assert!(matches!(
@@ -672,6 +678,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         assert!(matches!(
@@ -750,6 +758,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         let liveness = ThisScopeLiveness::compute(&ir).unwrap();
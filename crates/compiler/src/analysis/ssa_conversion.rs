@@ -22,7 +22,10 @@ use crate::{
 /// required `Upsilon` instructions without a previous assignment.
 ///
 /// The resulting phi placement will be *minimal* but not *pruned*.
-pub fn convert_to_ssa<S>(ir: &mut ir::Function<S>) {
+///
+/// The names of converted variables are recorded in `instruction_names` for every instruction which
+/// now holds their value, for debug info.
+pub fn convert_to_ssa<S: Clone>(ir: &mut ir::Function<S>) {
     // We don't do SSA conversion of any shared variables: static variables, upvalues, and any owned
     // variables shared to a lower function.
     let mut skip_vars = FxHashSet::default();
@@ -100,14 +103,17 @@ pub fn convert_to_ssa<S>(ir: &mut ir::Function<S>) {
     for block_id in dominators.topological_order() {
         let block = &mut ir.blocks[block_id];
         if let Some(phi_functions) = phi_functions.get(block_id) {
-            for &shadow_var in phi_functions.values() {
-                inst_change_set.insert(
-                    0,
-                    ir.instructions.insert(ir::Instruction {
-                        kind: ir::InstructionKind::Phi(shadow_var),
-                        span: Span::null(),
-                    }),
-                );
+            for (&var_id, &shadow_var) in phi_functions {
+                let phi_inst = ir.instructions.insert(ir::Instruction {
+                    kind: ir::InstructionKind::Phi(shadow_var),
+                    span: Span::null(),
+                });
+                if let Some(name) = ir.variable_names.get(var_id) {
+                    ir.instruction_names
+                        .get_or_insert_default(phi_inst)
+                        .push(name.clone());
+                }
+                inst_change_set.insert(0, phi_inst);
             }
             inst_change_set.apply(&mut block.instructions);
         }
@@ -153,6 +159,11 @@ pub fn convert_to_ssa<S>(ir: &mut ir::Function<S>) {
                         if let Some(stack) = current_vars.get_mut(var_id) {
                             inst.kind = ir::InstructionKind::NoOp;
                             stack.push(source);
+                            if let Some(name) = ir.variable_names.get(var_id) {
+                                ir.instruction_names
+                                    .get_or_insert_default(source)
+                                    .push(name.clone());
+                            }
                         } else {
                             assert!(skip_vars.contains(&var_id));
                         }
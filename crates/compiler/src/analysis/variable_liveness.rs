@@ -246,6 +246,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         assert!(matches!(
@@ -307,6 +309,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         assert!(matches!(
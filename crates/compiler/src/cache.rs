@@ -0,0 +1,617 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    str,
+};
+
+use fabricator_vm::{
+    self as vm,
+    closure::PrototypeVerificationError,
+    instructions::{ByteCodeDeserializationError, HeapIdx, INSTRUCTION_SET_SIGNATURE, RegIdx},
+};
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+
+use crate::{
+    code_gen::{HeapVarDescriptor, Prototype},
+    constant::Constant,
+    lints::{Lint, LintDiagnostic},
+};
+
+/// Bump this whenever the serialized format of a [`CachedUnit`] changes.
+///
+/// Changes to the instruction set do not require bumping this, they are detected automatically
+/// through [`INSTRUCTION_SET_SIGNATURE`].
+const FORMAT_VERSION: u32 = 3;
+
+const HEADER: &[u8; 8] = b"FABCODE\0";
+
+#[derive(Debug, Error)]
+pub enum CacheDecodeError {
+    #[error("unexpected end of cache data")]
+    UnexpectedEnd,
+    #[error("bad cache header")]
+    BadHeader,
+    #[error("unsupported cache format version {0}")]
+    BadVersion(u32),
+    #[error("invalid {0} tag {1}")]
+    BadTag(&'static str, u8),
+    #[error("invalid span")]
+    BadSpan,
+    #[error("string is not valid UTF-8")]
+    BadString,
+    #[error("trailing data at end of cache")]
+    TrailingData,
+    #[error("bytecode error: {0}")]
+    ByteCode(#[from] ByteCodeDeserializationError),
+}
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+    #[error("cache I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("corrupt cache entry: {0}")]
+    Decode(#[from] CacheDecodeError),
+    #[error("cached prototype failed verification: {0}")]
+    Verification(#[from] PrototypeVerificationError),
+    #[error("cached compilation unit does not match preprocessed source")]
+    Mismatch,
+}
+
+/// The result of consulting a [`CodeCache`] during compilation.
+#[derive(Debug)]
+pub enum CacheStatus {
+    /// Every prototype was loaded from the cache, IR generation and codegen were skipped.
+    Hit,
+    /// Nothing usable was in the cache, so every prototype was compiled and then stored, replacing
+    /// any missing or corrupt entry.
+    Stored,
+    /// The compilation unit contained chunks which were provided pre-lexed, so there was no source
+    /// code to derive a cache key from.
+    Uncacheable,
+    /// The compiled prototypes could not be written to the cache. Compilation still succeeded.
+    Failed(CacheError),
+}
+
+/// A hash which uniquely identifies the inputs to a compilation unit.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CacheKey([u8; 32]);
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CacheKey({self})")
+    }
+}
+
+/// Incrementally builds a [`CacheKey`].
+///
+/// Every key starts out incorporating the cache format version, the VM instruction set, the
+/// compiler version, and a fingerprint of the compiler source, so that changing any of them
+/// invalidates every existing cache entry.
+#[derive(Clone)]
+pub struct CacheKeyBuilder(Sha256);
+
+impl Default for CacheKeyBuilder {
+    fn default() -> Self {
+        let mut this = Self(Sha256::new());
+        this.write_u32(FORMAT_VERSION);
+        this.write_str(INSTRUCTION_SET_SIGNATURE);
+        this.write_str(env!("CARGO_PKG_VERSION"));
+        this.write_str(env!("FABRICATOR_COMPILER_FINGERPRINT"));
+        this
+    }
+}
+
+impl CacheKeyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_u32(&mut self, v: u32) {
+        self.0.update(v.to_le_bytes());
+    }
+
+    /// Writes a length-prefixed string, so that adjacent strings cannot be confused with each
+    /// other.
+    pub fn write_str(&mut self, s: &str) {
+        self.0.update((s.len() as u64).to_le_bytes());
+        self.0.update(s.as_bytes());
+    }
+
+    /// Hash any value with a `Hash` impl into the key.
+    ///
+    /// The `Hash` impls for primitive types are not portable, so keys built this way should only be
+    /// relied on for the machine that built them.
+    pub fn write_hash(&mut self, v: &impl Hash) {
+        struct DigestHasher<'a>(&'a mut Sha256);
+
+        impl Hasher for DigestHasher<'_> {
+            fn finish(&self) -> u64 {
+                panic!("`DigestHasher` is only used to feed bytes into a digest");
+            }
+
+            fn write(&mut self, bytes: &[u8]) {
+                self.0.update(bytes);
+            }
+        }
+
+        v.hash(&mut DigestHasher(&mut self.0));
+    }
+
+    pub fn finish(&self) -> CacheKey {
+        CacheKey(self.0.clone().finalize()[..].try_into().unwrap())
+    }
+}
+
+/// Every prototype generated by a single compilation unit.
+///
+/// Only the generated prototypes and lint warnings are stored. Macros, enums and exports are cheap
+/// to recompute by preprocessing, and are required to validate that the cached prototypes still
+/// match their source.
+#[derive(Debug, Clone, Default)]
+pub struct CachedUnit {
+    /// One prototype for each function export, in export order.
+    pub exports: Vec<Prototype<Box<str>>>,
+    /// One prototype for each chunk, in chunk order.
+    pub chunks: Vec<Prototype<Box<str>>>,
+    /// Every lint reported at the `warn` level, in chunk order.
+    pub warnings: Vec<CachedWarning>,
+}
+
+/// A lint warning reported while compiling a [`CachedUnit`].
+#[derive(Debug, Clone)]
+pub struct CachedWarning {
+    /// The index of the chunk the lint was found in.
+    pub chunk_index: usize,
+    pub lint: LintDiagnostic,
+}
+
+impl CachedUnit {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(HEADER);
+        write_u32(buf, FORMAT_VERSION);
+
+        for list in [&self.exports, &self.chunks] {
+            write_len(buf, list.len());
+            for proto in list {
+                write_prototype(buf, proto);
+            }
+        }
+
+        write_len(buf, self.warnings.len());
+        for warning in &self.warnings {
+            write_len(buf, warning.chunk_index);
+            buf.push(warning.lint.lint as u8);
+            write_str(buf, &warning.lint.message);
+            write_span(buf, warning.lint.span);
+        }
+    }
+
+    pub fn deserialize(mut bytes: &[u8]) -> Result<Self, CacheDecodeError> {
+        let bytes = &mut bytes;
+
+        let (header, rest) = bytes
+            .split_first_chunk::<8>()
+            .ok_or(CacheDecodeError::UnexpectedEnd)?;
+        if header != HEADER {
+            return Err(CacheDecodeError::BadHeader);
+        }
+        *bytes = rest;
+
+        let version = read_u32(bytes)?;
+        if version != FORMAT_VERSION {
+            return Err(CacheDecodeError::BadVersion(version));
+        }
+
+        let mut read_list = || -> Result<_, CacheDecodeError> {
+            let len = read_u32(bytes)? as usize;
+            let mut list = Vec::new();
+            for _ in 0..len {
+                list.push(read_prototype(bytes)?);
+            }
+            Ok(list)
+        };
+
+        let exports = read_list()?;
+        let chunks = read_list()?;
+
+        let warning_count = read_u32(bytes)? as usize;
+        let mut warnings = Vec::new();
+        for _ in 0..warning_count {
+            let chunk_index = read_u32(bytes)? as usize;
+            let tag = read_u8(bytes)?;
+            let lint = *Lint::ALL
+                .get(tag as usize)
+                .ok_or(CacheDecodeError::BadTag("lint", tag))?;
+            let message = read_str(bytes)?.into();
+            let span = read_span(bytes)?;
+            warnings.push(CachedWarning {
+                chunk_index,
+                lint: LintDiagnostic {
+                    lint,
+                    message,
+                    span,
+                },
+            });
+        }
+
+        if !bytes.is_empty() {
+            return Err(CacheDecodeError::TrailingData);
+        }
+
+        Ok(Self {
+            exports,
+            chunks,
+            warnings,
+        })
+    }
+}
+
+/// A directory of [`CachedUnit`]s, one file per [`CacheKey`].
+#[derive(Debug, Clone)]
+pub struct CodeCache {
+    dir: PathBuf,
+}
+
+impl CodeCache {
+    /// The directory does not need to exist, it will be created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Load the entry for the given key, if it exists.
+    pub fn load(&self, key: CacheKey) -> Result<Option<CachedUnit>, CacheError> {
+        match fs::read(self.entry_path(key)) {
+            Ok(bytes) => Ok(Some(CachedUnit::deserialize(&bytes)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Store an entry for the given key, replacing any existing entry.
+    pub fn store(&self, key: CacheKey, unit: &CachedUnit) -> Result<(), CacheError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut buf = Vec::new();
+        unit.serialize(&mut buf);
+
+        // Write to a temporary file first so that a concurrent or interrupted write can never
+        // leave behind a partial entry.
+        let tmp_path = self.dir.join(format!("{key}.tmp"));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp_path, self.entry_path(key))?;
+
+        Ok(())
+    }
+
+    fn entry_path(&self, key: CacheKey) -> PathBuf {
+        self.dir.join(format!("{key}.fabc"))
+    }
+}
+
+fn write_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn write_len(buf: &mut Vec<u8>, len: usize) {
+    write_u32(buf, len.try_into().expect("length does not fit in u32"));
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_len(buf, s.len());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_span(buf: &mut Vec<u8>, span: vm::Span) {
+    buf.extend_from_slice(&(span.start() as u64).to_le_bytes());
+    buf.extend_from_slice(&(span.end() as u64).to_le_bytes());
+}
+
+fn write_constant(buf: &mut Vec<u8>, constant: &Constant<Box<str>>) {
+    match constant {
+        Constant::Undefined => buf.push(0),
+        Constant::Boolean(b) => {
+            buf.push(1);
+            buf.push(*b as u8);
+        }
+        Constant::Integer(i) => {
+            buf.push(2);
+            buf.extend_from_slice(&i.to_le_bytes());
+        }
+        Constant::Float(f) => {
+            buf.push(3);
+            buf.extend_from_slice(&f.to_bits().to_le_bytes());
+        }
+        Constant::String(s) => {
+            buf.push(4);
+            write_str(buf, s);
+        }
+    }
+}
+
+fn write_prototype(buf: &mut Vec<u8>, proto: &Prototype<Box<str>>) {
+    match &proto.reference {
+        vm::FunctionRef::Named(name, span) => {
+            buf.push(0);
+            write_str(buf, name);
+            write_span(buf, *span);
+        }
+        vm::FunctionRef::Expression(span) => {
+            buf.push(1);
+            write_span(buf, *span);
+        }
+        vm::FunctionRef::Chunk => buf.push(2),
+    }
+
+    proto.bytecode.serialize(buf);
+
+    write_len(buf, proto.constants.len());
+    for constant in &proto.constants {
+        write_constant(buf, constant);
+    }
+
+    write_len(buf, proto.prototypes.len());
+    for inner in &proto.prototypes {
+        write_prototype(buf, inner);
+    }
+
+    write_len(buf, proto.heap_vars.len());
+    for heap_var in &proto.heap_vars {
+        match heap_var {
+            HeapVarDescriptor::Owned(idx) => {
+                buf.push(0);
+                buf.extend_from_slice(&idx.0.to_le_bytes());
+            }
+            HeapVarDescriptor::Static(constant) => {
+                buf.push(1);
+                write_constant(buf, constant);
+            }
+            HeapVarDescriptor::UpValue(idx) => {
+                buf.push(2);
+                buf.extend_from_slice(&idx.0.to_le_bytes());
+            }
+        }
+    }
+
+    write_len(buf, proto.local_variables.len());
+    for var in &proto.local_variables {
+        write_str(buf, &var.name);
+        match var.location {
+            vm::VariableLocation::Register(idx) => {
+                buf.push(0);
+                buf.extend_from_slice(&idx.0.to_le_bytes());
+            }
+            vm::VariableLocation::Heap(idx) => {
+                buf.push(1);
+                buf.extend_from_slice(&idx.0.to_le_bytes());
+            }
+        }
+        write_len(buf, var.start);
+        write_len(buf, var.end);
+    }
+}
+
+fn read_array<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], CacheDecodeError> {
+    let (head, rest) = bytes
+        .split_first_chunk::<N>()
+        .ok_or(CacheDecodeError::UnexpectedEnd)?;
+    *bytes = rest;
+    Ok(*head)
+}
+
+fn read_u8(bytes: &mut &[u8]) -> Result<u8, CacheDecodeError> {
+    Ok(read_array::<1>(bytes)?[0])
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, CacheDecodeError> {
+    Ok(u32::from_le_bytes(read_array(bytes)?))
+}
+
+fn read_u64(bytes: &mut &[u8]) -> Result<u64, CacheDecodeError> {
+    Ok(u64::from_le_bytes(read_array(bytes)?))
+}
+
+fn read_str(bytes: &mut &[u8]) -> Result<Box<str>, CacheDecodeError> {
+    let len = read_u32(bytes)? as usize;
+    if bytes.len() < len {
+        return Err(CacheDecodeError::UnexpectedEnd);
+    }
+    let (s, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(str::from_utf8(s)
+        .map_err(|_| CacheDecodeError::BadString)?
+        .into())
+}
+
+fn read_span(bytes: &mut &[u8]) -> Result<vm::Span, CacheDecodeError> {
+    let start = read_u64(bytes)?;
+    let end = read_u64(bytes)?;
+    match (usize::try_from(start), usize::try_from(end)) {
+        (Ok(start), Ok(end)) if start <= end => Ok(vm::Span::new(start, end)),
+        (Ok(usize::MAX), Ok(0)) => Ok(vm::Span::null()),
+        _ => Err(CacheDecodeError::BadSpan),
+    }
+}
+
+fn read_constant(bytes: &mut &[u8]) -> Result<Constant<Box<str>>, CacheDecodeError> {
+    Ok(match read_u8(bytes)? {
+        0 => Constant::Undefined,
+        1 => match read_u8(bytes)? {
+            0 => Constant::Boolean(false),
+            1 => Constant::Boolean(true),
+            b => return Err(CacheDecodeError::BadTag("boolean", b)),
+        },
+        2 => Constant::Integer(i64::from_le_bytes(read_array(bytes)?)),
+        3 => Constant::Float(f64::from_bits(read_u64(bytes)?)),
+        4 => Constant::String(read_str(bytes)?),
+        tag => return Err(CacheDecodeError::BadTag("constant", tag)),
+    })
+}
+
+fn read_prototype(bytes: &mut &[u8]) -> Result<Prototype<Box<str>>, CacheDecodeError> {
+    let reference = match read_u8(bytes)? {
+        0 => {
+            let name = read_str(bytes)?;
+            vm::FunctionRef::Named(name, read_span(bytes)?)
+        }
+        1 => vm::FunctionRef::Expression(read_span(bytes)?),
+        2 => vm::FunctionRef::Chunk,
+        tag => return Err(CacheDecodeError::BadTag("function reference", tag)),
+    };
+
+    let bytecode = vm::ByteCode::deserialize(bytes)?;
+
+    // Lengths are not trusted for pre-allocation, a corrupt length will simply hit the end of the
+    // data.
+    let mut constants = Vec::new();
+    for _ in 0..read_u32(bytes)? {
+        constants.push(read_constant(bytes)?);
+    }
+
+    let mut prototypes = Vec::new();
+    for _ in 0..read_u32(bytes)? {
+        prototypes.push(read_prototype(bytes)?);
+    }
+
+    let mut heap_vars = Vec::new();
+    for _ in 0..read_u32(bytes)? {
+        heap_vars.push(match read_u8(bytes)? {
+            0 => HeapVarDescriptor::Owned(HeapIdx(u16::from_le_bytes(read_array(bytes)?))),
+            1 => HeapVarDescriptor::Static(read_constant(bytes)?),
+            2 => HeapVarDescriptor::UpValue(HeapIdx(u16::from_le_bytes(read_array(bytes)?))),
+            tag => return Err(CacheDecodeError::BadTag("heap variable", tag)),
+        });
+    }
+
+    let mut local_variables = Vec::new();
+    for _ in 0..read_u32(bytes)? {
+        let name = read_str(bytes)?;
+        let location = match read_u8(bytes)? {
+            0 => vm::VariableLocation::Register(RegIdx(read_u8(bytes)?)),
+            1 => vm::VariableLocation::Heap(HeapIdx(u16::from_le_bytes(read_array(bytes)?))),
+            tag => return Err(CacheDecodeError::BadTag("local variable location", tag)),
+        };
+        let start = read_u32(bytes)? as usize;
+        let end = read_u32(bytes)? as usize;
+        local_variables.push(vm::LocalVariable {
+            name,
+            location,
+            start,
+            end,
+        });
+    }
+
+    Ok(Prototype {
+        reference,
+        bytecode,
+        constants: constants.into_boxed_slice(),
+        prototypes: prototypes.into_boxed_slice(),
+        heap_vars: heap_vars.into_boxed_slice(),
+        local_variables: local_variables.into_boxed_slice(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fabricator_vm::instructions::{ConstIdx, Instruction};
+
+    use super::*;
+
+    #[test]
+    fn test_unit_round_trip() {
+        let inner = Prototype {
+            reference: vm::FunctionRef::Expression(vm::Span::new(3, 10)),
+            bytecode: vm::ByteCode::encode([(Instruction::Return {}, vm::Span::null())]).unwrap(),
+            constants: Box::new([]),
+            prototypes: Box::new([]),
+            heap_vars: Box::new([HeapVarDescriptor::UpValue(HeapIdx(0))]),
+            local_variables: Box::new([]),
+        };
+
+        let outer = Prototype {
+            reference: vm::FunctionRef::Named("outer".into(), vm::Span::new(0, 20)),
+            bytecode: vm::ByteCode::encode([
+                (
+                    Instruction::LoadConstant {
+                        dest: RegIdx(0),
+                        constant: ConstIdx(1),
+                    },
+                    vm::Span::new(1, 2),
+                ),
+                (Instruction::Return {}, vm::Span::new(2, 3)),
+            ])
+            .unwrap(),
+            constants: Box::new([
+                Constant::Float(1.5),
+                Constant::String("hello".into()),
+                Constant::Boolean(true),
+            ]),
+            prototypes: Box::new([inner]),
+            heap_vars: Box::new([
+                HeapVarDescriptor::Owned(HeapIdx(0)),
+                HeapVarDescriptor::Static(Constant::Integer(-7)),
+            ]),
+            local_variables: Box::new([vm::LocalVariable {
+                name: "x".into(),
+                location: vm::VariableLocation::Register(RegIdx(0)),
+                start: 1,
+                end: 2,
+            }]),
+        };
+
+        let unit = CachedUnit {
+            exports: vec![outer.clone()],
+            chunks: vec![outer],
+            warnings: vec![CachedWarning {
+                chunk_index: 0,
+                lint: LintDiagnostic {
+                    lint: Lint::UnusedVariable,
+                    message: "unused variable `x`".to_owned(),
+                    span: vm::Span::new(4, 5),
+                },
+            }],
+        };
+
+        let mut buf = Vec::new();
+        unit.serialize(&mut buf);
+        let decoded = CachedUnit::deserialize(&buf).unwrap();
+
+        let mut rebuf = Vec::new();
+        decoded.serialize(&mut rebuf);
+        assert_eq!(buf, rebuf);
+
+        let proto = &decoded.chunks[0];
+        assert!(matches!(&proto.reference, vm::FunctionRef::Named(n, _) if &**n == "outer"));
+        assert_eq!(proto.constants[1], Constant::String("hello".into()));
+        assert_eq!(proto.prototypes.len(), 1);
+        assert_eq!(&*proto.local_variables[0].name, "x");
+        assert_eq!(
+            proto.local_variables[0].location,
+            vm::VariableLocation::Register(RegIdx(0))
+        );
+
+        assert_eq!(decoded.warnings[0].lint.lint, Lint::UnusedVariable);
+        assert_eq!(decoded.warnings[0].lint.span, vm::Span::new(4, 5));
+
+        assert!(CachedUnit::deserialize(&buf[..buf.len() - 1]).is_err());
+    }
+}
@@ -93,14 +93,20 @@ fn codegen_function<S: Clone + Eq + Hash>(
     let mut vm_instructions = Vec::new();
     let mut block_vm_starts = SecondaryMap::<ir::BlockId, usize>::new();
     let mut block_vm_jumps = Vec::new();
+    let mut local_variables = Vec::new();
 
     for (order_index, &block_id) in block_order.iter().enumerate() {
         let block = &ir.blocks[block_id];
         block_vm_starts.insert(block_id, vm_instructions.len());
 
+        // The VM instruction index at which each IR instruction starts, followed by the start of
+        // the block exit and the end of the block.
+        let mut inst_vm_starts = vec![None; block.instructions.len() + 2];
+
         let mut inst_iter = block.instructions.iter().enumerate().peekable();
 
         while let Some((inst_index, &inst_id)) = inst_iter.next() {
+            inst_vm_starts[inst_index] = Some(vm_instructions.len());
             let inst = &ir.instructions[inst_id];
 
             match inst.kind {
@@ -707,6 +713,8 @@ fn codegen_function<S: Clone + Eq + Hash>(
             }
         }
 
+        inst_vm_starts[block.instructions.len()] = Some(vm_instructions.len());
+
         match block.exit.kind {
             ir::ExitKind::Exit => {
                 vm_instructions.push((Instruction::PushStackFrame {}, block.exit.span));
@@ -820,6 +828,46 @@ fn codegen_function<S: Clone + Eq + Hash>(
                 }
             }
         }
+
+        inst_vm_starts[block.instructions.len() + 1] = Some(vm_instructions.len());
+
+        // Instructions which were combined into a previous instruction do not have their own start,
+        // so they start where the next instruction does.
+        let mut next_start = vm_instructions.len();
+        let mut inst_vm_starts = inst_vm_starts
+            .into_iter()
+            .rev()
+            .map(|start| {
+                next_start = start.unwrap_or(next_start);
+                next_start
+            })
+            .collect::<Vec<_>>();
+        inst_vm_starts.reverse();
+
+        block_local_variables(
+            ir,
+            block_id,
+            &inst_vm_starts,
+            &instruction_liveness,
+            &variable_liveness,
+            &reg_alloc,
+            &heap_alloc,
+            &mut local_variables,
+        );
+    }
+
+    // Variables which are not owned by this function hold their value for the entire function.
+    for (var_id, var) in ir.variables.iter() {
+        if !var.is_heap()
+            && let Some(name) = ir.variable_names.get(var_id)
+        {
+            local_variables.push(vm::LocalVariable {
+                name: name.clone(),
+                location: vm::VariableLocation::Heap(heap_alloc.heap_indexes[var_id]),
+                start: 0,
+                end: vm_instructions.len(),
+            });
+        }
     }
 
     for (index, block_id) in block_vm_jumps {
@@ -860,5 +908,96 @@ fn codegen_function<S: Clone + Eq + Hash>(
         constants: constants.into_boxed_slice(),
         prototypes: prototypes.into_boxed_slice(),
         heap_vars: heap_alloc.heap_var_descriptors.into_boxed_slice(),
+        local_variables: merge_local_variables(local_variables).into_boxed_slice(),
     })
 }
+
+/// Record debug info for every named instruction and owned variable which is live in the given
+/// block.
+///
+/// The `inst_vm_starts` slice must contain the VM instruction index at which every IR instruction
+/// in the block starts, followed by the start of the block exit and the end of the block.
+#[allow(clippy::too_many_arguments)]
+fn block_local_variables<S: Clone>(
+    ir: &ir::Function<S>,
+    block_id: ir::BlockId,
+    inst_vm_starts: &[usize],
+    instruction_liveness: &InstructionLiveness,
+    variable_liveness: &VariableLiveness,
+    reg_alloc: &RegisterAllocation,
+    heap_alloc: &HeapAllocation<S>,
+    local_variables: &mut Vec<vm::LocalVariable<S>>,
+) {
+    let block_start = inst_vm_starts[0];
+    let block_end = *inst_vm_starts.last().unwrap();
+
+    // A value becomes available after the instruction which starts its range and is held until
+    // after the instruction which ends it.
+    let vm_range = |start: Option<usize>, end: Option<usize>| {
+        (
+            start.map(|i| inst_vm_starts[i + 1]).unwrap_or(block_start),
+            end.map(|i| inst_vm_starts[i + 1]).unwrap_or(block_end),
+        )
+    };
+
+    for (inst_id, range) in instruction_liveness.live_for_block(block_id) {
+        let (Some(names), Some(&reg)) = (
+            ir.instruction_names.get(inst_id),
+            reg_alloc.instruction_registers.get(inst_id),
+        ) else {
+            continue;
+        };
+
+        let (start, end) = vm_range(range.start, range.end);
+        if start < end {
+            for name in names {
+                local_variables.push(vm::LocalVariable {
+                    name: name.clone(),
+                    location: vm::VariableLocation::Register(reg),
+                    start,
+                    end,
+                });
+            }
+        }
+    }
+
+    for (var_id, range) in variable_liveness.live_for_block(block_id) {
+        let Some(name) = ir.variable_names.get(var_id) else {
+            continue;
+        };
+
+        let (start, end) = vm_range(range.start, range.end);
+        if start < end {
+            local_variables.push(vm::LocalVariable {
+                name: name.clone(),
+                location: vm::VariableLocation::Heap(heap_alloc.heap_indexes[var_id]),
+                start,
+                end,
+            });
+        }
+    }
+}
+
+/// Sort local variable entries by where they start, and merge entries for the same variable in the
+/// same location which directly follow each other.
+fn merge_local_variables<S: Clone + Eq + Hash>(
+    mut local_variables: Vec<vm::LocalVariable<S>>,
+) -> Vec<vm::LocalVariable<S>> {
+    local_variables.sort_by_key(|v| (v.start, v.location));
+
+    let mut merged: Vec<vm::LocalVariable<S>> = Vec::new();
+    // Map from the end, location, and name of a merged entry to its index.
+    let mut entry_ends = FxHashMap::<(usize, vm::VariableLocation, S), usize>::default();
+
+    for var in local_variables {
+        if let Some(index) = entry_ends.remove(&(var.start, var.location, var.name.clone())) {
+            merged[index].end = var.end;
+            entry_ends.insert((var.end, var.location, var.name), index);
+        } else {
+            entry_ends.insert((var.end, var.location, var.name.clone()), merged.len());
+            merged.push(var);
+        }
+    }
+
+    merged
+}
@@ -47,6 +47,7 @@ pub struct Prototype<S> {
     pub constants: Box<[Constant<S>]>,
     pub prototypes: Box<[Prototype<S>]>,
     pub heap_vars: Box<[HeapVarDescriptor<S>]>,
+    pub local_variables: Box<[vm::LocalVariable<S>]>,
 }
 
 impl<S> Prototype<S> {
@@ -57,12 +58,17 @@ impl<S> Prototype<S> {
             constants,
             prototypes,
             heap_vars,
+            local_variables,
         } = self;
 
         let reference = reference.map_string(&map);
         let constants = constants.into_iter().map(|c| c.map_string(&map)).collect();
         let prototypes = prototypes.into_iter().map(|p| p.map_string(&map)).collect();
         let heap_vars = heap_vars.into_iter().map(|h| h.map_string(&map)).collect();
+        let local_variables = local_variables
+            .into_iter()
+            .map(|v| v.map_string(&map))
+            .collect();
 
         Prototype {
             reference,
@@ -70,18 +76,38 @@ impl<S> Prototype<S> {
             constants,
             prototypes,
             heap_vars,
+            local_variables,
         }
     }
 }
 
 impl<'gc> Prototype<vm::String<'gc>> {
     /// The given `MagicSet` pointer must match the magic variables provided during codegen.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prototype fails VM verification, which should never happen for a prototype
+    /// produced by codegen.
     pub fn into_vm(
         self,
         mc: &Mutation<'gc>,
         chunk: vm::Chunk<'gc>,
         magic: Gc<'gc, vm::MagicSet<'gc>>,
     ) -> Gc<'gc, vm::Prototype<'gc>> {
+        self.try_into_vm(mc, chunk, magic).unwrap()
+    }
+
+    /// A version of [`Prototype::into_vm`] which returns an error if this prototype or any of its
+    /// children fail VM verification.
+    ///
+    /// Useful for prototypes which were not directly produced by codegen, such as those loaded
+    /// from a [`CodeCache`](crate::cache::CodeCache).
+    pub fn try_into_vm(
+        self,
+        mc: &Mutation<'gc>,
+        chunk: vm::Chunk<'gc>,
+        magic: Gc<'gc, vm::MagicSet<'gc>>,
+    ) -> Result<Gc<'gc, vm::Prototype<'gc>>, closure::PrototypeVerificationError> {
         fn const_conv<'gc>(c: Constant<vm::String<'gc>>) -> vm::Constant<'gc> {
             match c {
                 Constant::Undefined => vm::Constant::Undefined,
@@ -98,15 +124,20 @@ impl<'gc> Prototype<vm::String<'gc>> {
             constants,
             prototypes,
             heap_vars,
+            local_variables,
         } = self;
 
         let reference = reference.map_string(|s| s.as_shared().clone());
         let constants = constants.into_iter().map(const_conv).collect();
+        let local_variables = local_variables
+            .into_iter()
+            .map(|v| v.map_string(|s| s.as_shared().clone()))
+            .collect();
 
         let prototypes = prototypes
             .into_iter()
-            .map(|p| p.into_vm(mc, chunk, magic))
-            .collect();
+            .map(|p| p.try_into_vm(mc, chunk, magic))
+            .collect::<Result<_, _>>()?;
 
         let mut static_vars = Vec::new();
         let heap_vars = heap_vars
@@ -127,7 +158,7 @@ impl<'gc> Prototype<vm::String<'gc>> {
             })
             .collect();
 
-        Gc::new(
+        Ok(Gc::new(
             mc,
             vm::Prototype::new(
                 mc,
@@ -139,8 +170,8 @@ impl<'gc> Prototype<vm::String<'gc>> {
                 prototypes,
                 static_vars.into_boxed_slice(),
                 heap_vars,
-            )
-            .unwrap(),
-        )
+            )?
+            .with_local_variables(local_variables),
+        ))
     }
 }
@@ -165,6 +165,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         let shadow_liveness = ShadowLiveness::compute(&ir).unwrap();
@@ -235,6 +237,8 @@ mod tests {
             call_scopes: Default::default(),
             functions: Default::default(),
             start_block: block_a_id,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         let shadow_liveness = ShadowLiveness::compute(&ir).unwrap();
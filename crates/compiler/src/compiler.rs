@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{fmt, path::Path};
 
 use fabricator_util::index_containers::IndexMap;
 use fabricator_vm as vm;
@@ -29,12 +29,16 @@ use crate::{
         verify_references::{ReferenceVerificationError, verify_references},
         verify_upvars::{UpVarVerificationError, verify_no_root_upvars, verify_upvars},
     },
+    cache::{
+        CacheError, CacheKey, CacheKeyBuilder, CacheStatus, CachedUnit, CachedWarning, CodeCache,
+    },
     code_gen::{Prototype, gen_prototype},
     enums::{EnumError, EnumEvaluationError, EnumResolutionError, EnumSet},
     exports::{DuplicateExportError, Export},
     ir,
     ir_gen::{FreeVarMode, IrGenError, IrGenSettings, VarDict},
     lexer::LexError,
+    lints::{Lint, LintDiagnostic, LintLevel, LintLevels, lint_chunk, lint_func_stmt},
     macros::{MacroError, MacroSet, RecursiveMacro},
     parser::{ParseError, ParseSettings},
     preprocessing::{
@@ -66,6 +70,8 @@ pub enum CompileErrorKind {
     ShadowsSpecial(#[source] ShadowsSpecialError),
     #[error("IR gen error: {0}")]
     IrGen(#[source] IrGenError),
+    #[error("lint error: {0}")]
+    Lint(#[source] LintDiagnostic),
 }
 
 #[derive(Debug, Error)]
@@ -75,14 +81,18 @@ pub struct CompileError {
     pub kind: CompileErrorKind,
     pub chunk_name: vm::SharedStr,
     pub line_number: vm::LineNumber,
+    /// The span of the error within the named chunk.
+    pub span: vm::Span,
 }
 
 impl From<ChunkLexError> for CompileError {
     fn from(err: ChunkLexError) -> Self {
+        let span = err.error.span;
         Self {
             kind: CompileErrorKind::Lexing(err.error),
             chunk_name: err.chunk_name,
             line_number: err.line_number,
+            span,
         }
     }
 }
@@ -104,17 +114,54 @@ impl From<PreprocessError> for CompileError {
             kind,
             chunk_name: err.chunk_name,
             line_number: err.line_number,
+            span: err.span,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// Every error encountered while compiling a compilation unit.
+///
+/// Lexing, preprocessing, and IR generation errors are collected from every chunk. IR generation
+/// is only performed if every chunk lexes and preprocesses successfully, so IR generation errors
+/// are only reported in the absence of any other kind of error.
+#[derive(Debug)]
+pub struct CompileErrors(pub Vec<CompileError>);
+
+impl fmt::Display for CompileErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileErrors {}
+
+/// A lint reported at the [`LintLevel::Warn`] level.
+#[derive(Debug, Error)]
+#[error("warning: {lint} at {chunk_name}:{line_number}")]
+pub struct CompileWarning {
+    pub lint: LintDiagnostic,
+    pub chunk_name: vm::SharedStr,
+    pub line_number: vm::LineNumber,
+    pub span: vm::Span,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct CompileSettings {
     pub parse: ParseSettings,
     pub ir_gen: IrGenSettings,
     pub optimization_passes: u8,
     pub export_top_level_functions: bool,
     pub verify_ir: bool,
+    /// The level of every lint, which may be further changed by lint pragmas within a chunk.
+    ///
+    /// See [`LintLevels::apply_pragmas`].
+    pub lints: LintLevels,
 }
 
 impl CompileSettings {
@@ -125,6 +172,7 @@ impl CompileSettings {
             optimization_passes: 2,
             export_top_level_functions: true,
             verify_ir: cfg!(debug_assertions),
+            lints: LintLevels::default(),
         }
     }
 
@@ -135,6 +183,7 @@ impl CompileSettings {
             optimization_passes: 2,
             export_top_level_functions: true,
             verify_ir: cfg!(debug_assertions),
+            lints: LintLevels::default(),
         }
     }
 
@@ -168,6 +217,11 @@ impl CompileSettings {
         self.verify_ir = verify_ir;
         self
     }
+
+    pub fn set_lint_level(mut self, lint: Lint, level: LintLevel) -> Self {
+        self.lints.set(lint, level);
+        self
+    }
 }
 
 #[derive(Debug, Error)]
@@ -315,6 +369,10 @@ pub struct Compiler<'gc> {
     global_vars: vm::StringSet<'gc>,
     magic: vm::MagicSet<'gc>,
     ir_compile_settings: Vec<IrCompileSettings>,
+    // Set to `None` once a chunk is added without source code.
+    cache_key: Option<CacheKeyBuilder>,
+    // Lexing errors from added chunks, reported by `Compiler::compile`.
+    errors: Vec<CompileError>,
 }
 
 impl<'gc> Compiler<'gc> {
@@ -329,14 +387,15 @@ impl<'gc> Compiler<'gc> {
         compile_settings: CompileSettings,
         chunk_name: vm::SharedStr,
         code: &str,
-    ) -> Result<ChunkOutput<'gc>, CompileError> {
+    ) -> Result<ChunkOutput<'gc>, CompileErrors> {
         let mut this = Self::new(ctx, config, imports);
-        this.add_chunk(compile_settings, chunk_name, code)?;
+        this.add_chunk(compile_settings, chunk_name, code);
         let output = this.compile()?;
         Ok(ChunkOutput {
             exported_imports: output.exported_imports,
             chunk_prototype: output.chunks[0],
             all_prototypes: output.all_prototypes,
+            warnings: output.warnings,
         })
     }
 
@@ -345,11 +404,13 @@ impl<'gc> Compiler<'gc> {
         config: impl Into<String>,
         imports: ImportItems<'gc>,
     ) -> Self {
+        let config: String = config.into();
         let macros = imports.macros.as_ref().clone();
         let enums = imports.enums.as_ref().clone();
         let global_vars = imports.global_vars.as_ref().clone();
         let magic = imports.magic.as_ref().clone();
 
+        let cache_key = imports_cache_key(&config, &macros, &enums, &global_vars, &magic);
         let preprocessor = Preprocessor::new(ctx, config, macros, enums);
 
         Self {
@@ -358,32 +419,47 @@ impl<'gc> Compiler<'gc> {
             global_vars,
             magic,
             ir_compile_settings: Vec::new(),
+            cache_key: Some(cache_key),
+            errors: Vec::new(),
         }
     }
 
-    pub fn add_chunk(
-        &mut self,
-        settings: CompileSettings,
-        chunk_name: vm::SharedStr,
-        code: &str,
-    ) -> Result<(), CompileError> {
-        self.preprocessor.add_chunk(
+    /// Lex and add a chunk to this compilation unit.
+    ///
+    /// If the chunk fails to lex, it is not added, and the error is reported along with any other
+    /// errors by [`Compiler::compile`].
+    pub fn add_chunk(&mut self, settings: CompileSettings, chunk_name: vm::SharedStr, code: &str) {
+        if let Some(cache_key) = &mut self.cache_key {
+            cache_key.write_hash(&settings);
+            cache_key.write_str(chunk_name.as_str());
+            cache_key.write_str(code);
+        }
+
+        if let Err(err) = self.preprocessor.add_chunk(
             settings.parse,
             settings.export_top_level_functions,
             chunk_name,
             code,
-        )?;
+        ) {
+            self.errors.push(err.into());
+            return;
+        }
 
         self.ir_compile_settings.push(IrCompileSettings {
             ir_gen: settings.ir_gen,
             optimization_passes: settings.optimization_passes,
             verify_ir: settings.verify_ir,
+            lints: settings.lints,
         });
-
-        Ok(())
     }
 
+    /// Add a chunk which has already been lexed.
+    ///
+    /// Since there is no source code to hash, adding a lexed chunk makes the compilation unit
+    /// uncacheable.
     pub fn add_lexed_chunk(&mut self, lexed_chunk: LexedChunk<'gc>, settings: CompileSettings) {
+        self.cache_key = None;
+
         self.preprocessor.add_lexed_chunk(
             lexed_chunk,
             settings.parse,
@@ -394,6 +470,7 @@ impl<'gc> Compiler<'gc> {
             ir_gen: settings.ir_gen,
             optimization_passes: settings.optimization_passes,
             verify_ir: settings.verify_ir,
+            lints: settings.lints,
         });
     }
 
@@ -401,7 +478,42 @@ impl<'gc> Compiler<'gc> {
         self.preprocessor.chunk_len()
     }
 
-    pub fn compile(self) -> Result<CompileOutput<'gc>, CompileError> {
+    /// A key uniquely identifying every input to this compilation unit, or `None` if any chunk
+    /// was added pre-lexed.
+    ///
+    /// The key covers the compiler configuration, every provided import, and the name, source and
+    /// settings of every chunk added so far.
+    pub fn cache_key(&self) -> Option<CacheKey> {
+        self.cache_key.as_ref().map(|k| k.finish())
+    }
+
+    pub fn compile(self) -> Result<CompileOutput<'gc>, CompileErrors> {
+        Ok(self.compile_inner(None)?.0)
+    }
+
+    /// Compile, re-using previously generated prototypes from the given cache when every input to
+    /// this compilation unit is unchanged.
+    ///
+    /// Preprocessing is always performed, since it is required to produce the exported imports.
+    /// On a cache hit, IR generation, optimization, and codegen are skipped entirely, and
+    /// [`CompileOutput::all_prototypes`] will be empty.
+    ///
+    /// Failing to read or write the cache is not a compile error, it is instead reported in the
+    /// returned [`CacheStatus`].
+    pub fn compile_with_cache(
+        self,
+        cache: &CodeCache,
+    ) -> Result<(CompileOutput<'gc>, CacheStatus), CompileErrors> {
+        match self.cache_key() {
+            Some(key) => self.compile_inner(Some((cache, key))),
+            None => Ok((self.compile()?, CacheStatus::Uncacheable)),
+        }
+    }
+
+    fn compile_inner(
+        self,
+        cache: Option<(&CodeCache, CacheKey)>,
+    ) -> Result<(CompileOutput<'gc>, CacheStatus), CompileErrors> {
         fn optimize_and_generate_proto<'gc>(
             compile_settings: IrCompileSettings,
             ir: &mut ir::Function<vm::String<'gc>>,
@@ -437,8 +549,13 @@ impl<'gc> Compiler<'gc> {
             mut global_vars,
             mut magic,
             ir_compile_settings,
+            cache_key: _,
+            mut errors,
         } = self;
 
+        let preprocessed = preprocessor
+            .preprocess(|ident| global_vars.contains(&ident) || magic.find(ident).is_some());
+
         let PreprocessOutput {
             preprocessed_chunks,
             macros,
@@ -446,8 +563,14 @@ impl<'gc> Compiler<'gc> {
             exports,
             export_chunk_indexes,
             ..
-        } = preprocessor
-            .preprocess(|ident| global_vars.contains(&ident) || magic.find(ident).is_some())?;
+        } = match preprocessed {
+            Ok(output) if errors.is_empty() => output,
+            Ok(_) => return Err(CompileErrors(errors)),
+            Err(preprocess_errors) => {
+                errors.extend(preprocess_errors.into_iter().map(CompileError::from));
+                return Err(CompileErrors(errors));
+            }
+        };
 
         assert_eq!(preprocessed_chunks.len(), ir_compile_settings.len());
         let compiling_chunks = preprocessed_chunks
@@ -480,42 +603,178 @@ impl<'gc> Compiler<'gc> {
         let magic = Gc::new(&ctx, magic);
         let magic_write = Gc::write(&ctx, magic);
 
+        let export_chunk_index =
+            |i: usize| match export_chunk_indexes.binary_search_by(|j| j.cmp(&i)) {
+                Ok(i) => i,
+                Err(i) => i.checked_sub(1).unwrap(),
+            };
+
+        let imports = ImportItems {
+            macros: Gc::new(&ctx, macros),
+            enums: Gc::new(&ctx, enums),
+            global_vars: Gc::new(&ctx, global_vars),
+            magic,
+        };
+
+        let var_dict = CompilerVarDict {
+            enums: &imports.enums,
+            global_vars: &imports.global_vars,
+            magic: &magic,
+        };
+
+        // Lint pragmas apply to an entire chunk, including any functions exported from it.
+
+        let mut chunk_lints = compiling_chunks
+            .iter()
+            .map(|(_, block, compile_settings)| {
+                let mut levels = compile_settings.lints;
+                let diagnostics = levels.apply_pragmas(block);
+                (levels, diagnostics)
+            })
+            .collect::<Vec<_>>();
+
+        for (i, export) in exports.iter().enumerate() {
+            if let Export::Function(func_stmt) = export {
+                let (levels, diagnostics) = &mut chunk_lints[export_chunk_index(i)];
+                diagnostics.extend(levels.apply_pragmas(func_stmt));
+            }
+        }
+
+        // Lints are checked on freshly generated IR, so a cached unit stores the warnings it was
+        // compiled with instead. Denied lints are compile errors, which are never cached.
+        if let Some((cache, key)) = cache {
+            let cached = cache.load(key).and_then(|unit| {
+                let Some(unit) = unit else {
+                    return Ok(None);
+                };
+
+                // Every cached prototype must be converted and verified before we modify any magic
+                // variables, so that a bad entry can still fall back to normal compilation.
+
+                let function_exports = exports
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| matches!(e, Export::Function(_)))
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>();
+
+                if unit.exports.len() != function_exports.len()
+                    || unit.chunks.len() != compiling_chunks.len()
+                    || unit
+                        .warnings
+                        .iter()
+                        .any(|w| w.chunk_index >= compiling_chunks.len())
+                {
+                    return Err(CacheError::Mismatch);
+                }
+
+                let mut export_protos = Vec::new();
+                for (i, proto) in function_exports.into_iter().zip(unit.exports) {
+                    let (chunk, _, _) = compiling_chunks[export_chunk_index(i)];
+                    let proto = proto
+                        .map_string(|s| ctx.intern(&s))
+                        .try_into_vm(&ctx, chunk, magic)?;
+                    export_protos.push((export_magic_indexes[i], proto));
+                }
+
+                let mut chunk_protos = Vec::new();
+                for (&(chunk, _, _), proto) in compiling_chunks.iter().zip(unit.chunks) {
+                    chunk_protos.push(
+                        proto
+                            .map_string(|s| ctx.intern(&s))
+                            .try_into_vm(&ctx, chunk, magic)?,
+                    );
+                }
+
+                Ok(Some((export_protos, chunk_protos, unit.warnings)))
+            });
+
+            match cached {
+                Ok(Some((export_protos, chunks, cached_warnings))) => {
+                    for (magic_index, proto) in export_protos {
+                        let closure = vm::Closure::new(&ctx, proto, None).unwrap();
+                        vm::MagicSet::replace(
+                            magic_write,
+                            magic_index,
+                            vm::MagicConstant::new_ptr(&ctx, closure),
+                        )
+                        .unwrap();
+                    }
+
+                    let warnings = cached_warnings
+                        .into_iter()
+                        .map(|warning| {
+                            let (chunk, _, _) = compiling_chunks[warning.chunk_index];
+                            CompileWarning {
+                                chunk_name: chunk.name().clone(),
+                                line_number: chunk.line_number(warning.lint.span.start()),
+                                span: warning.lint.span,
+                                lint: warning.lint,
+                            }
+                        })
+                        .collect();
+
+                    return Ok((
+                        CompileOutput {
+                            exported_imports: imports,
+                            chunks,
+                            all_prototypes: Vec::new(),
+                            warnings,
+                        },
+                        CacheStatus::Hit,
+                    ));
+                }
+                // A missing or unusable entry is simply replaced by the freshly compiled unit.
+                Ok(None) | Err(_) => {}
+            }
+        }
+
+        let mut cached_unit = cache.map(|_| CachedUnit::default());
+        let mut warnings = Vec::new();
+
         let mut all_prototypes = Vec::new();
 
         // Compile each exported function and place the result into the reserved stub magic
         // variable.
 
         for (i, export) in exports.iter().enumerate() {
-            let chunk_index = match export_chunk_indexes.binary_search_by(|j| j.cmp(&i)) {
-                Ok(i) => i,
-                Err(i) => i.checked_sub(1).unwrap(),
-            };
+            let chunk_index = export_chunk_index(i);
             let (chunk, _, compile_settings) = compiling_chunks[chunk_index];
 
             if let Export::Function(func_stmt) = export {
                 let magic_index = export_magic_indexes[i];
 
-                let mut ir = compile_settings
-                    .ir_gen
-                    .gen_func_stmt_ir(
-                        &mut VmInterner::new(ctx),
-                        func_stmt,
-                        &CompilerVarDict {
-                            enums: &enums,
-                            global_vars: &global_vars,
-                            magic: &magic,
-                        },
-                    )
-                    .map_err(|e| {
-                        let line_number = chunk.line_number(e.span.start());
-                        CompileError {
-                            kind: CompileErrorKind::IrGen(e),
-                            chunk_name: chunk.name().clone(),
-                            line_number,
-                        }
-                    })?;
+                let mut ir = match compile_settings.ir_gen.gen_func_stmt_ir(
+                    &mut VmInterner::new(ctx),
+                    func_stmt,
+                    &var_dict,
+                ) {
+                    Ok(ir) => ir,
+                    Err(err) => {
+                        errors.push(ir_gen_error(chunk, err));
+                        continue;
+                    }
+                };
+
+                let (levels, diagnostics) = &mut chunk_lints[chunk_index];
+                diagnostics.extend(lint_func_stmt(
+                    compile_settings.ir_gen,
+                    func_stmt,
+                    &var_dict,
+                    &ir,
+                    levels,
+                ));
+
+                // Once there are errors nothing will be output, but we keep generating IR to find
+                // any further errors.
+                if !errors.is_empty() {
+                    continue;
+                }
 
                 let proto = optimize_and_generate_proto(compile_settings, &mut ir, &magic);
+                if let Some(cached_unit) = &mut cached_unit {
+                    cached_unit.exports.push(owned_strings(&proto));
+                }
                 let vm_proto = proto.into_vm(&ctx, chunk, magic);
                 let closure = vm::Closure::new(&ctx, vm_proto, None).unwrap();
 
@@ -534,46 +793,146 @@ impl<'gc> Compiler<'gc> {
 
         let mut chunks = Vec::new();
 
-        for (chunk, block, compile_settings) in compiling_chunks {
-            let mut ir = compile_settings
-                .ir_gen
-                .gen_chunk_ir(
-                    &mut VmInterner::new(self.ctx),
+        for (chunk_index, ((chunk, block, compile_settings), (levels, mut diagnostics))) in
+            compiling_chunks.into_iter().zip(chunk_lints).enumerate()
+        {
+            let ir =
+                compile_settings
+                    .ir_gen
+                    .gen_chunk_ir(&mut VmInterner::new(ctx), &block, &var_dict);
+
+            if let Ok(ir) = &ir {
+                diagnostics.extend(lint_chunk(
+                    compile_settings.ir_gen,
                     &block,
-                    &CompilerVarDict {
-                        enums: &enums,
-                        global_vars: &global_vars,
-                        magic: &magic,
-                    },
-                )
-                .map_err(|e| {
-                    let line_number = chunk.line_number(e.span.start());
-                    CompileError {
-                        kind: CompileErrorKind::IrGen(e),
-                        chunk_name: chunk.name().clone(),
-                        line_number,
+                    &var_dict,
+                    ir,
+                    &levels,
+                ));
+            }
+
+            for lint in diagnostics {
+                let chunk_name = chunk.name().clone();
+                let line_number = chunk.line_number(lint.span.start());
+                let span = lint.span;
+                match levels.get(lint.lint) {
+                    LintLevel::Allow => {}
+                    LintLevel::Warn => {
+                        if let Some(cached_unit) = &mut cached_unit {
+                            cached_unit.warnings.push(CachedWarning {
+                                chunk_index,
+                                lint: lint.clone(),
+                            });
+                        }
+                        warnings.push(CompileWarning {
+                            lint,
+                            chunk_name,
+                            line_number,
+                            span,
+                        });
                     }
-                })?;
+                    LintLevel::Deny => errors.push(CompileError {
+                        kind: CompileErrorKind::Lint(lint),
+                        chunk_name,
+                        line_number,
+                        span,
+                    }),
+                }
+            }
+
+            let mut ir = match ir {
+                Ok(ir) => ir,
+                Err(err) => {
+                    errors.push(ir_gen_error(chunk, err));
+                    continue;
+                }
+            };
+
+            if !errors.is_empty() {
+                continue;
+            }
 
             let proto = optimize_and_generate_proto(compile_settings, &mut ir, &magic);
+            if let Some(cached_unit) = &mut cached_unit {
+                cached_unit.chunks.push(owned_strings(&proto));
+            }
             let vm_proto = proto.into_vm(&ctx, chunk, magic);
             all_prototypes.push((ir, vm_proto));
             chunks.push(vm_proto);
         }
 
-        let imports = ImportItems {
-            macros: Gc::new(&ctx, macros),
-            enums: Gc::new(&ctx, enums),
-            global_vars: Gc::new(&ctx, global_vars),
-            magic,
+        if !errors.is_empty() {
+            return Err(CompileErrors(errors));
+        }
+
+        let cache_status = match (cache, cached_unit) {
+            (Some((cache, key)), Some(cached_unit)) => match cache.store(key, &cached_unit) {
+                Ok(()) => CacheStatus::Stored,
+                Err(err) => CacheStatus::Failed(err),
+            },
+            _ => CacheStatus::Uncacheable,
         };
 
-        Ok(CompileOutput {
-            exported_imports: imports,
-            chunks,
-            all_prototypes,
-        })
+        Ok((
+            CompileOutput {
+                exported_imports: imports,
+                chunks,
+                all_prototypes,
+                warnings,
+            },
+            cache_status,
+        ))
+    }
+}
+
+fn ir_gen_error(chunk: vm::Chunk<'_>, err: IrGenError) -> CompileError {
+    CompileError {
+        line_number: chunk.line_number(err.span.start()),
+        chunk_name: chunk.name().clone(),
+        span: err.span,
+        kind: CompileErrorKind::IrGen(err),
+    }
+}
+
+fn owned_strings<'gc>(proto: &Prototype<vm::String<'gc>>) -> Prototype<Box<str>> {
+    proto.clone().map_string(|s| s.as_str().into())
+}
+
+fn imports_cache_key<'gc>(
+    config: &str,
+    macros: &MacroSet<vm::String<'gc>>,
+    enums: &EnumSet<vm::String<'gc>>,
+    global_vars: &vm::StringSet<'gc>,
+    magic: &vm::MagicSet<'gc>,
+) -> CacheKeyBuilder {
+    let mut key = CacheKeyBuilder::new();
+    key.write_str(config);
+
+    // Macros and enums have no span information, so their debug representation is a complete and
+    // deterministic description of their contents.
+    key.write_u32(macros.len() as u32);
+    for macro_ in macros.iter() {
+        key.write_str(&format!("{macro_:?}"));
+    }
+    key.write_u32(enums.len() as u32);
+    for enum_ in enums.iter() {
+        key.write_str(&format!("{enum_:?}"));
+    }
+
+    let mut global_vars = global_vars.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+    global_vars.sort();
+    key.write_u32(global_vars.len() as u32);
+    for var in global_vars {
+        key.write_str(var);
     }
+
+    // Generated bytecode refers to magic variables by index, so the order matters here.
+    for (name, index) in magic.names() {
+        key.write_str(name.as_str());
+        key.write_hash(&magic.get(index).unwrap().read_only());
+    }
+
+    key
 }
 
 pub struct CompileOutput<'gc> {
@@ -587,6 +946,9 @@ pub struct CompileOutput<'gc> {
     /// A prototype for every input chunk and function export, paired with the final IR used to
     /// generate the prototype.
     pub all_prototypes: Vec<(ir::Function<vm::String<'gc>>, Gc<'gc, vm::Prototype<'gc>>)>,
+
+    /// Every lint reported at the `warn` level, in chunk order.
+    pub warnings: Vec<CompileWarning>,
 }
 
 /// A version of [`CompileOutput`] for a single chunk.
@@ -594,6 +956,7 @@ pub struct ChunkOutput<'gc> {
     pub exported_imports: ImportItems<'gc>,
     pub chunk_prototype: Gc<'gc, vm::Prototype<'gc>>,
     pub all_prototypes: Vec<(ir::Function<vm::String<'gc>>, Gc<'gc, vm::Prototype<'gc>>)>,
+    pub warnings: Vec<CompileWarning>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -601,6 +964,7 @@ struct IrCompileSettings {
     ir_gen: IrGenSettings,
     optimization_passes: u8,
     verify_ir: bool,
+    lints: LintLevels,
 }
 
 struct CompilerVarDict<'gc, 'a> {
@@ -1,7 +1,7 @@
 use std::fmt;
 
 use arrayvec::ArrayVec;
-use fabricator_util::typed_id_map::{IdMap, new_id_type};
+use fabricator_util::typed_id_map::{IdMap, SecondaryMap, new_id_type};
 use fabricator_vm::{FunctionRef, Span};
 
 use crate::constant::Constant;
@@ -649,6 +649,12 @@ pub struct Function<S> {
     pub functions: FunctionMap<S>,
 
     pub start_block: BlockId,
+
+    /// The source names of declared variables, used only for debug info.
+    pub variable_names: SecondaryMap<VarId, S>,
+    /// The source names of variables whose values are held by an instruction once they have been
+    /// converted to SSA form, used only for debug info.
+    pub instruction_names: SecondaryMap<InstId, Vec<S>>,
 }
 
 impl<S: AsRef<str>> Function<S> {
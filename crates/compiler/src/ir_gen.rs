@@ -1,5 +1,6 @@
 use std::{array, collections::hash_map, hash::Hash};
 
+use fabricator_util::typed_id_map::SecondaryMap;
 use fabricator_vm::{BuiltIns, FunctionRef, Span};
 use rustc_hash::FxHashMap;
 use thiserror::Error;
@@ -77,7 +78,7 @@ pub struct IrGenError {
     pub span: Span,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct IrGenSettings {
     /// Allow `var` and `static` variable declarations, which do not use function scoping.
     ///
@@ -461,6 +462,8 @@ where
             call_scopes,
             functions,
             start_block,
+            variable_names: SecondaryMap::new(),
+            instruction_names: SecondaryMap::new(),
         };
 
         Self {
@@ -1678,15 +1681,16 @@ where
             });
         }
 
-        // Desugar try / catch statements as `pcall` around an inner closure.
+        // Desugar try / catch statements as a protected call of an inner closure.
 
         let allow_break = !self.break_target_stack.is_empty();
         let allow_continue = !self.continue_target_stack.is_empty();
 
         let closure_span = try_catch_stmt.try_block.span();
 
-        let pcall_name = self.interner.intern_static(BuiltIns::PCALL);
-        let pcall = self.push_instruction(closure_span, ir::InstructionKind::GetMagic(pcall_name));
+        let try_catch_name = self.interner.intern_static(BuiltIns::TRY_CATCH);
+        let try_catch =
+            self.push_instruction(closure_span, ir::InstructionKind::GetMagic(try_catch_name));
 
         let exit_code_var = self.function.variables.insert(ir::Variable::Heap);
         self.push_instruction(
@@ -1734,7 +1738,7 @@ where
             ir::InstructionKind::Call {
                 scope: call_scope,
                 stack_base: 0,
-                func: pcall,
+                func: try_catch,
                 this: None,
             },
         );
@@ -3148,6 +3152,9 @@ where
 
                 let is_heap = variable.is_heap();
                 let var_id = self.function.variables.insert(variable);
+                self.function
+                    .variable_names
+                    .insert(var_id, vname.inner.clone());
 
                 if is_heap {
                     // This is a new heap variable, so we need to open it.
@@ -3208,7 +3215,6 @@ where
         var_id: ir::VarId,
     ) -> Result<(), IrGenError> {
         let top_scope_index = self.block_scopes.len() - 1;
-        let top_scope = self.block_scopes.last_mut().unwrap();
 
         if self.var_dict.is_reserved(&vname) {
             return Err(IrGenError {
@@ -3217,6 +3223,11 @@ where
             });
         }
 
+        self.function
+            .variable_names
+            .insert(var_id, vname.inner.clone());
+
+        let top_scope = self.block_scopes.last_mut().unwrap();
         let shadowing = top_scope.visible.insert(vname.clone(), var_id).is_some();
 
         let scope_list = self.block_variable_lookup.entry(vname).or_default();
@@ -1,5 +1,6 @@
 pub mod analysis;
 pub mod ast;
+pub mod cache;
 pub mod code_gen;
 pub mod compiler;
 pub mod constant;
@@ -10,10 +11,11 @@ pub mod ir;
 pub mod ir_gen;
 pub mod lexer;
 pub mod line_numbers;
+pub mod lints;
 pub mod macros;
 pub mod parser;
 pub mod preprocessing;
 pub mod string_interner;
 pub mod tokens;
 
-pub use self::compiler::{CompileError, CompileSettings, Compiler, ImportItems};
+pub use self::compiler::{CompileError, CompileErrors, CompileSettings, Compiler, ImportItems};
@@ -0,0 +1,1102 @@
+use std::{convert::Infallible, fmt, hash::Hash, ops::ControlFlow, str::FromStr};
+
+use fabricator_util::{index_containers::IndexSet, typed_id_map::SecondaryMap};
+use fabricator_vm::Span;
+use rustc_hash::FxHashSet;
+use thiserror::Error;
+
+use crate::{
+    analysis::{
+        cleanup::{clean_unused_variables, reachable_blocks, variable_uses},
+        ssa_conversion::convert_to_ssa,
+    },
+    ast::{self, Visitor, Walk as _},
+    constant::Constant,
+    graph::{dfs::depth_first_search, predecessors::Predecessors},
+    ir,
+    ir_gen::{FreeVarMode, IrGenSettings, VarDict},
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Lint {
+    /// A local variable which is never read.
+    UnusedVariable,
+    /// A function parameter which is never read.
+    ///
+    /// Not reported for functions which access their arguments through `argument` or
+    /// `argument_count`.
+    UnusedParameter,
+    /// A value assigned to a local variable which is always overwritten before it is read.
+    UnusedAssignment,
+    /// Code which can never run, like statements following a `return`, `exit`, `break`, or
+    /// `continue`.
+    UnreachableCode,
+    /// A `let` declaration which shadows a variable of the same name.
+    ShadowedLet,
+    /// A free variable which implicitly refers to a field of `self`.
+    ImplicitSelf,
+    /// A lint pragma with an unknown lint name or level.
+    UnknownLint,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::UnusedAssignment,
+        Lint::UnreachableCode,
+        Lint::ShadowedLet,
+        Lint::ImplicitSelf,
+        Lint::UnknownLint,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused_variable",
+            Lint::UnusedParameter => "unused_parameter",
+            Lint::UnusedAssignment => "unused_assignment",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::ShadowedLet => "shadowed_let",
+            Lint::ImplicitSelf => "implicit_self",
+            Lint::UnknownLint => "unknown_lint",
+        }
+    }
+
+    /// Every lint is allowed by default except for `unknown_lint`, which is only reported for lint
+    /// pragmas that a project wrote itself. Other lints must be enabled through
+    /// [`CompileSettings::set_lint_level`](crate::compiler::CompileSettings::set_lint_level) or a
+    /// lint pragma, so existing projects don't get warnings they never asked for.
+    pub fn default_level(self) -> LintLevel {
+        match self {
+            Lint::UnknownLint => LintLevel::Warn,
+            _ => LintLevel::Allow,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("unknown lint")]
+pub struct ParseLintError;
+
+impl FromStr for Lint {
+    type Err = ParseLintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .into_iter()
+            .find(|l| l.name() == s)
+            .ok_or(ParseLintError)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LintLevel {
+    /// The lint is not reported.
+    Allow,
+    /// The lint is reported as a compile warning.
+    Warn,
+    /// The lint is reported as a compile error.
+    Deny,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown lint level")]
+pub struct ParseLintLevelError;
+
+impl FromStr for LintLevel {
+    type Err = ParseLintLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            _ => Err(ParseLintLevelError),
+        }
+    }
+}
+
+/// The level of every [`Lint`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct LintLevels([LintLevel; Lint::ALL.len()]);
+
+impl Default for LintLevels {
+    fn default() -> Self {
+        Self(Lint::ALL.map(Lint::default_level))
+    }
+}
+
+impl LintLevels {
+    pub fn get(&self, lint: Lint) -> LintLevel {
+        self.0[lint as usize]
+    }
+
+    pub fn set(&mut self, lint: Lint, level: LintLevel) {
+        self.0[lint as usize] = level;
+    }
+
+    pub fn set_all(&mut self, level: LintLevel) {
+        self.0 = [level; Lint::ALL.len()];
+    }
+
+    /// Apply every lint pragma found anywhere in the given AST.
+    ///
+    /// A lint pragma is a call of the form `gml_pragma("lint", "<level>", "<lint>", ...)`, which
+    /// sets the level of every listed lint. Other pragmas are ignored.
+    ///
+    /// Returns an `UnknownLint` diagnostic for every pragma with an unrecognized level or lint.
+    pub fn apply_pragmas<S: AsRef<str>>(
+        &mut self,
+        node: &impl ast::Walk<S>,
+    ) -> Vec<LintDiagnostic> {
+        struct PragmaVisitor<'a> {
+            levels: &'a mut LintLevels,
+            diagnostics: Vec<LintDiagnostic>,
+        }
+
+        impl<'a> PragmaVisitor<'a> {
+            fn pragma<S: AsRef<str>>(&mut self, call: &ast::Call<S>) {
+                let ast::Expression::Ident(func) = &*call.base else {
+                    return;
+                };
+
+                if func.inner.as_ref() != "gml_pragma" {
+                    return;
+                }
+
+                let mut args = call.arguments.iter().map(|arg| match arg {
+                    ast::Expression::Constant(Constant::String(s), span) => {
+                        (Some(s.as_ref()), *span)
+                    }
+                    arg => (None, arg.span()),
+                });
+
+                if !matches!(args.next(), Some((Some("lint"), _))) {
+                    return;
+                }
+
+                let level = match args.next() {
+                    Some((Some(level), span)) => match level.parse::<LintLevel>() {
+                        Ok(level) => level,
+                        Err(_) => {
+                            self.unknown(span, format!("unknown lint level `{level}`"));
+                            return;
+                        }
+                    },
+                    Some((None, span)) => {
+                        self.unknown(span, "lint level must be a string literal".to_owned());
+                        return;
+                    }
+                    None => {
+                        self.unknown(call.span, "lint pragma has no level".to_owned());
+                        return;
+                    }
+                };
+
+                for (name, span) in args {
+                    match name {
+                        Some(name) => match name.parse::<Lint>() {
+                            Ok(lint) => self.levels.set(lint, level),
+                            Err(_) => self.unknown(span, format!("unknown lint `{name}`")),
+                        },
+                        None => self.unknown(span, "lint name must be a string literal".to_owned()),
+                    }
+                }
+            }
+
+            fn unknown(&mut self, span: Span, message: String) {
+                self.diagnostics.push(LintDiagnostic {
+                    lint: Lint::UnknownLint,
+                    message,
+                    span,
+                });
+            }
+        }
+
+        impl<'a, S: AsRef<str>> Visitor<S> for PragmaVisitor<'a> {
+            type Break = Infallible;
+
+            fn visit_stmt(&mut self, stmt: &ast::Statement<S>) -> ControlFlow<Infallible> {
+                if let ast::Statement::Call(call) = stmt {
+                    self.pragma(call);
+                }
+                stmt.walk(self)
+            }
+        }
+
+        let mut visitor = PragmaVisitor {
+            levels: self,
+            diagnostics: Vec::new(),
+        };
+        let _ = node.walk(&mut visitor);
+        visitor.diagnostics
+    }
+}
+
+/// A single lint found in a chunk.
+#[derive(Debug, Clone, Error)]
+#[error("{message} [{lint}]")]
+pub struct LintDiagnostic {
+    pub lint: Lint,
+    pub message: String,
+    pub span: Span,
+}
+
+/// Check a chunk for lints.
+///
+/// Lints are checked against the same AST and variable dictionary given to IR generation, along
+/// with the IR generated from them, which must not have been optimized yet. Unused variables,
+/// unused assignments, and unreachable code are all found in the IR by the same analysis passes
+/// used for optimization.
+///
+/// Lints which are allowed by `levels` are not checked. Returned diagnostics are sorted by
+/// position.
+pub fn lint_chunk<S>(
+    settings: IrGenSettings,
+    block: &ast::Block<S>,
+    var_dict: &dyn VarDict<S>,
+    ir: &ir::Function<S>,
+    levels: &LintLevels,
+) -> Vec<LintDiagnostic>
+where
+    S: Eq + Hash + Clone + AsRef<str>,
+{
+    let mut linter = Linter::new(settings, var_dict, levels);
+    linter
+        .functions
+        .push(FunctionScope::new(FxHashSet::default()));
+    linter.block(block);
+    linter.finish(ir)
+}
+
+/// Check a function statement for lints, the equivalent of [`lint_chunk`] for exported functions.
+pub fn lint_func_stmt<S>(
+    settings: IrGenSettings,
+    func_stmt: &ast::FunctionStmt<S>,
+    var_dict: &dyn VarDict<S>,
+    ir: &ir::Function<S>,
+    levels: &LintLevels,
+) -> Vec<LintDiagnostic>
+where
+    S: Eq + Hash + Clone + AsRef<str>,
+{
+    let mut linter = Linter::new(settings, var_dict, levels);
+    linter.function(
+        &func_stmt.parameters,
+        func_stmt.inherit.as_ref(),
+        &func_stmt.body,
+        false,
+    );
+    linter.finish(ir)
+}
+
+// Declarations found in the AST which change how their variables are reported, keyed by the span
+// of the declared name. This is also the span of the `OpenVariable` instruction for the variable.
+#[derive(Default)]
+struct Declarations {
+    parameters: FxHashSet<Span>,
+    // Variables which are never reported as unused: function statement names, which also set a
+    // field on `self`, and `catch` error variables.
+    exempt: FxHashSet<Span>,
+}
+
+// The names of every variable visible within a function.
+struct FunctionScope<S> {
+    // Variables from enclosing functions which are visible to this one.
+    upper: FxHashSet<S>,
+    function_vars: FxHashSet<S>,
+    block_scopes: Vec<FxHashSet<S>>,
+}
+
+impl<S> FunctionScope<S> {
+    fn new(upper: FxHashSet<S>) -> Self {
+        Self {
+            upper,
+            function_vars: FxHashSet::default(),
+            block_scopes: Vec::new(),
+        }
+    }
+}
+
+// Checks the lints which depend on how names are resolved in the AST, and collects the
+// declarations needed by the lints checked in the IR.
+struct Linter<'a, S> {
+    settings: IrGenSettings,
+    var_dict: &'a dyn VarDict<S>,
+    levels: &'a LintLevels,
+    functions: Vec<FunctionScope<S>>,
+    declarations: Declarations,
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+impl<'a, S> Linter<'a, S>
+where
+    S: Eq + Hash + Clone + AsRef<str>,
+{
+    fn new(settings: IrGenSettings, var_dict: &'a dyn VarDict<S>, levels: &'a LintLevels) -> Self {
+        Self {
+            settings,
+            var_dict,
+            levels,
+            functions: Vec::new(),
+            declarations: Declarations::default(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn finish(mut self, ir: &ir::Function<S>) -> Vec<LintDiagnostic> {
+        let ir_lints = [
+            Lint::UnusedVariable,
+            Lint::UnusedParameter,
+            Lint::UnusedAssignment,
+            Lint::UnreachableCode,
+        ];
+
+        if ir_lints
+            .into_iter()
+            .any(|lint| self.levels.get(lint) != LintLevel::Allow)
+        {
+            // Checking the IR runs analysis passes which modify it.
+            let mut ir = ir.clone();
+            lint_ir(
+                &mut ir,
+                &self.declarations,
+                self.levels,
+                &mut self.diagnostics,
+            );
+        }
+
+        self.diagnostics.sort_by_key(|d| d.span.start());
+        self.diagnostics
+    }
+
+    fn function(
+        &mut self,
+        parameters: &ast::ParameterList<S>,
+        inherit: Option<&ast::Call<S>>,
+        body: &ast::Block<S>,
+        capture_outer: bool,
+    ) {
+        let upper = if capture_outer {
+            self.visible_vars()
+        } else {
+            FxHashSet::default()
+        };
+        self.functions.push(FunctionScope::new(upper));
+
+        for param in &parameters.fixed {
+            self.declarations.parameters.insert(param.name.span);
+            self.declare_function_var(&param.name);
+            if let Some(default) = &param.default {
+                self.expression(default);
+            }
+        }
+
+        if let Some(inherit) = inherit {
+            self.call(inherit);
+        }
+
+        self.block(body);
+
+        self.functions.pop();
+    }
+
+    fn block(&mut self, block: &ast::Block<S>) {
+        self.push_scope();
+        for stmt in &block.statements {
+            self.statement(stmt);
+        }
+        self.pop_scope();
+    }
+
+    fn statement(&mut self, stmt: &ast::Statement<S>) {
+        match stmt {
+            ast::Statement::Empty(_)
+            | ast::Statement::Enum(_)
+            | ast::Statement::GlobalVar(_)
+            | ast::Statement::Exit(_)
+            | ast::Statement::Break(_)
+            | ast::Statement::Continue(_) => {}
+            ast::Statement::Block(block_stmt) => self.block(&block_stmt.block),
+            ast::Statement::Function(func_stmt) => {
+                self.function(
+                    &func_stmt.parameters,
+                    func_stmt.inherit.as_ref(),
+                    &func_stmt.body,
+                    false,
+                );
+                self.declarations.exempt.insert(func_stmt.name.span);
+                self.declare_function_var(&func_stmt.name);
+            }
+            ast::Statement::Closure(closure_stmt) => {
+                self.function(&closure_stmt.parameters, None, &closure_stmt.body, true);
+                self.declare_function_var(&closure_stmt.name);
+            }
+            ast::Statement::Var(var_stmt) | ast::Statement::Static(var_stmt) => {
+                for (name, value) in &var_stmt.vars {
+                    if let Some(value) = value {
+                        self.expression(value);
+                    }
+                    self.declare_function_var(name);
+                }
+            }
+            ast::Statement::Let(let_stmt) | ast::Statement::StaticLet(let_stmt) => {
+                for expr in &let_stmt.exprs {
+                    self.expression(expr);
+                }
+                for name in &let_stmt.vars {
+                    self.declare_let_var(name);
+                }
+            }
+            ast::Statement::Assignment(assign_stmt) => {
+                self.mutable_target(&assign_stmt.target);
+                self.expression(&assign_stmt.value);
+            }
+            ast::Statement::Return(ret_stmt) => {
+                for value in &ret_stmt.values {
+                    self.expression(value);
+                }
+            }
+            ast::Statement::If(if_stmt) => {
+                self.expression(&if_stmt.condition);
+                self.scoped_statement(&if_stmt.then_stmt);
+                if let Some(else_stmt) = &if_stmt.else_stmt {
+                    self.scoped_statement(else_stmt);
+                }
+            }
+            ast::Statement::For(for_stmt) => {
+                self.push_scope();
+                self.statement(&for_stmt.initializer);
+                self.expression(&for_stmt.condition);
+                self.scoped_statement(&for_stmt.body);
+                self.scoped_statement(&for_stmt.iterator);
+                self.pop_scope();
+            }
+            ast::Statement::While(loop_stmt)
+            | ast::Statement::Repeat(loop_stmt)
+            | ast::Statement::With(loop_stmt) => {
+                self.expression(&loop_stmt.target);
+                self.scoped_statement(&loop_stmt.body);
+            }
+            ast::Statement::Switch(switch_stmt) => {
+                self.expression(&switch_stmt.target);
+                for case in &switch_stmt.cases {
+                    self.expression(&case.compare);
+                    self.block(&case.body);
+                }
+                if let Some(default) = &switch_stmt.default {
+                    self.block(default);
+                }
+            }
+            ast::Statement::TryCatch(try_catch_stmt) => {
+                // The `try` block is compiled as a closure, so variables it declares are not
+                // visible afterwards.
+                let upper = self.visible_vars();
+                self.functions.push(FunctionScope::new(upper));
+                self.scoped_statement(&try_catch_stmt.try_block);
+                self.functions.pop();
+
+                self.push_scope();
+                self.declarations
+                    .exempt
+                    .insert(try_catch_stmt.err_ident.span);
+                self.declare_block_var(&try_catch_stmt.err_ident);
+                self.scoped_statement(&try_catch_stmt.catch_block);
+                self.pop_scope();
+            }
+            ast::Statement::Throw(throw_stmt) => self.expression(&throw_stmt.target),
+            ast::Statement::Call(call) => self.call(call),
+            ast::Statement::Prefix(mutation) | ast::Statement::Postfix(mutation) => {
+                self.mutable_target(&mutation.target);
+            }
+        }
+    }
+
+    fn scoped_statement(&mut self, stmt: &ast::Statement<S>) {
+        self.push_scope();
+        self.statement(stmt);
+        self.pop_scope();
+    }
+
+    fn expression(&mut self, expr: &ast::Expression<S>) {
+        let _ = ExprVisitor(self).visit_expr(expr);
+    }
+
+    fn call(&mut self, call: &ast::Call<S>) {
+        let _ = call.walk(&mut ExprVisitor(self));
+    }
+
+    fn mutable_target(&mut self, target: &ast::MutableExpr<S>) {
+        match target {
+            ast::MutableExpr::Ident(ident) => self.resolve(ident),
+            target => {
+                let _ = target.walk(&mut ExprVisitor(self));
+            }
+        }
+    }
+
+    fn resolve(&mut self, ident: &ast::Ident<S>) {
+        if !self.is_visible(&ident.inner)
+            && self.settings.allow_implicit_self
+            && matches!(self.var_dict.free_var_mode(&ident.inner), FreeVarMode::This)
+        {
+            self.report(
+                Lint::ImplicitSelf,
+                ident.span,
+                format!(
+                    "`{}` implicitly refers to a field of `self`",
+                    ident.inner.as_ref()
+                ),
+            );
+        }
+    }
+
+    fn is_visible(&self, name: &S) -> bool {
+        let function = self.function();
+        function
+            .block_scopes
+            .iter()
+            .any(|scope| scope.contains(name))
+            || function.function_vars.contains(name)
+            || function.upper.contains(name)
+    }
+
+    // Every variable visible from the current point of the current function, used as the upper
+    // variables of a new closure.
+    fn visible_vars(&self) -> FxHashSet<S> {
+        let function = self.function();
+        let mut visible = function.upper.clone();
+        visible.extend(function.function_vars.iter().cloned());
+        for scope in &function.block_scopes {
+            visible.extend(scope.iter().cloned());
+        }
+        visible
+    }
+
+    fn declare_function_var(&mut self, name: &ast::Ident<S>) {
+        self.function_mut().function_vars.insert(name.inner.clone());
+    }
+
+    fn declare_let_var(&mut self, name: &ast::Ident<S>) {
+        if self.is_visible(&name.inner) {
+            self.report(
+                Lint::ShadowedLet,
+                name.span,
+                format!("`{}` shadows an existing variable", name.inner.as_ref()),
+            );
+        }
+
+        self.declare_block_var(name);
+    }
+
+    fn declare_block_var(&mut self, name: &ast::Ident<S>) {
+        self.function_mut()
+            .block_scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.inner.clone());
+    }
+
+    fn push_scope(&mut self) {
+        self.function_mut().block_scopes.push(FxHashSet::default());
+    }
+
+    fn pop_scope(&mut self) {
+        self.function_mut().block_scopes.pop();
+    }
+
+    fn function(&self) -> &FunctionScope<S> {
+        self.functions.last().unwrap()
+    }
+
+    fn function_mut(&mut self) -> &mut FunctionScope<S> {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn report(&mut self, lint: Lint, span: Span, message: String) {
+        if self.levels.get(lint) != LintLevel::Allow {
+            self.diagnostics.push(LintDiagnostic {
+                lint,
+                message,
+                span,
+            });
+        }
+    }
+}
+
+struct ExprVisitor<'a, 'b, S>(&'a mut Linter<'b, S>);
+
+impl<'a, 'b, S> Visitor<S> for ExprVisitor<'a, 'b, S>
+where
+    S: Eq + Hash + Clone + AsRef<str>,
+{
+    type Break = Infallible;
+
+    fn visit_expr(&mut self, expr: &ast::Expression<S>) -> ControlFlow<Infallible> {
+        let linter = &mut *self.0;
+        match expr {
+            ast::Expression::Ident(ident) => linter.resolve(ident),
+            ast::Expression::Prefix(mutation) | ast::Expression::Postfix(mutation) => {
+                linter.mutable_target(&mutation.target);
+            }
+            ast::Expression::Object(object_expr) => {
+                for field in &object_expr.fields {
+                    match field {
+                        ast::Field::Value(_, value) => self.visit_expr(value)?,
+                        // Shorthand fields read the variable with the same name.
+                        ast::Field::Init(ident) => self.0.resolve(ident),
+                    }
+                }
+            }
+            ast::Expression::Function(func_expr) => linter.function(
+                &func_expr.parameters,
+                func_expr.inherit.as_ref(),
+                &func_expr.body,
+                false,
+            ),
+            ast::Expression::Closure(closure_expr) => {
+                linter.function(&closure_expr.parameters, None, &closure_expr.body, true)
+            }
+            expr => expr.walk(self)?,
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+// Check the lints which are found by analyzing the IR of a function and all of its child
+// functions.
+//
+// The IR must not have been optimized, and is modified by the analysis.
+fn lint_ir<S: Clone + AsRef<str>>(
+    ir: &mut ir::Function<S>,
+    declarations: &Declarations,
+    levels: &LintLevels,
+    diagnostics: &mut Vec<LintDiagnostic>,
+) {
+    // Child functions are checked first, which removes the variables they never use. This way,
+    // a variable is not considered read just because a closure could have captured it.
+    for func in ir.functions.values_mut() {
+        lint_ir(func, declarations, levels, diagnostics);
+    }
+
+    let is_enabled = |lint| levels.get(lint) != LintLevel::Allow;
+
+    let reachable_blocks = reachable_blocks(ir);
+
+    if is_enabled(Lint::UnreachableCode) {
+        for span in unreachable_code(ir, &reachable_blocks) {
+            diagnostics.push(LintDiagnostic {
+                lint: Lint::UnreachableCode,
+                message: "unreachable code".to_owned(),
+                span,
+            });
+        }
+    }
+
+    // Functions which access their arguments through `argument` or `argument_count` may not need
+    // to read their parameters.
+    let uses_arguments = ir.instructions.values().any(|inst| {
+        matches!(
+            inst.kind,
+            ir::InstructionKind::Argument(_) | ir::InstructionKind::ArgumentCount
+        )
+    });
+
+    let uses = variable_uses(ir);
+
+    // Local variables which are read, any of their assignments may still be unused.
+    let mut read_locals = IndexSet::new();
+
+    for block in ir.blocks.values() {
+        for &inst_id in &block.instructions {
+            let inst = &ir.instructions[inst_id];
+            let ir::InstructionKind::OpenVariable(var_id) = inst.kind else {
+                continue;
+            };
+
+            // Hidden variables generated by IR generation have no name.
+            let Some(name) = ir.variable_names.get(var_id) else {
+                continue;
+            };
+            let name = name.as_ref();
+
+            if declarations.exempt.contains(&inst.span) {
+                continue;
+            }
+
+            let is_parameter = declarations.parameters.contains(&inst.span);
+
+            if uses.read.contains(var_id.index() as usize) {
+                if !is_parameter {
+                    read_locals.insert(var_id.index() as usize);
+                }
+                continue;
+            }
+
+            if name.starts_with('_') || (is_parameter && uses_arguments) {
+                continue;
+            }
+
+            let (lint, what) = if is_parameter {
+                (Lint::UnusedParameter, "parameter")
+            } else {
+                (Lint::UnusedVariable, "variable")
+            };
+
+            if is_enabled(lint) {
+                diagnostics.push(LintDiagnostic {
+                    lint,
+                    message: format!("unused {what} `{name}`"),
+                    span: inst.span,
+                });
+            }
+        }
+    }
+
+    clean_unused_variables(ir);
+
+    if is_enabled(Lint::UnusedAssignment) && !read_locals.is_empty() {
+        for (span, name) in unused_assignments(ir, &reachable_blocks, &read_locals) {
+            diagnostics.push(LintDiagnostic {
+                lint: Lint::UnusedAssignment,
+                message: format!("value assigned to `{name}` is never read"),
+                span,
+            });
+        }
+    }
+}
+
+// Find every region of unreachable blocks which contains code from the source, returning a span
+// that covers all of the code in each region.
+fn unreachable_code<S>(ir: &ir::Function<S>, reachable_blocks: &IndexSet) -> Vec<Span> {
+    let is_unreachable =
+        |block_id: ir::BlockId| !reachable_blocks.contains(block_id.index() as usize);
+
+    let predecessors =
+        Predecessors::compute(ir.blocks.ids(), |b| ir.blocks[b].exit.kind.successors());
+
+    // Start searching from blocks with no predecessors first, so that every region is found from
+    // its entry. Any remaining blocks are only part of unreachable loops.
+    let (mut roots, rest): (Vec<_>, Vec<_>) = ir
+        .blocks
+        .ids()
+        .filter(|&block_id| is_unreachable(block_id))
+        .partition(|&block_id| predecessors.get(block_id).len() == 0);
+    roots.extend(rest);
+
+    let mut visited = IndexSet::new();
+    let mut spans = Vec::new();
+
+    for root in roots {
+        if visited.contains(root.index() as usize) {
+            continue;
+        }
+
+        let mut region_span: Option<Span> = None;
+        depth_first_search(
+            root,
+            |block_id| {
+                visited.insert(block_id.index() as usize);
+
+                let block = &ir.blocks[block_id];
+                for &inst_id in &block.instructions {
+                    let inst = &ir.instructions[inst_id];
+                    // Scopes are still closed after a `return` or `break`, but those instructions
+                    // don't correspond to any code in the source.
+                    let is_source = !inst.span.is_empty()
+                        && !matches!(
+                            inst.kind,
+                            ir::InstructionKind::NoOp
+                                | ir::InstructionKind::OpenVariable(_)
+                                | ir::InstructionKind::CloseVariable(_)
+                        );
+                    if is_source {
+                        region_span =
+                            Some(region_span.map_or(inst.span, |span| span.combine(inst.span)));
+                    }
+                }
+
+                block
+                    .exit
+                    .kind
+                    .successors()
+                    .filter(|&succ| {
+                        is_unreachable(succ) && !visited.contains(succ.index() as usize)
+                    })
+                    .collect::<Vec<_>>()
+            },
+            |_| {},
+        );
+
+        spans.extend(region_span);
+    }
+
+    spans
+}
+
+// Find every reachable assignment to one of the given local variables which can never be read,
+// returning the span of the assignment and the name of the variable.
+//
+// Converts the IR to SSA form, which turns every assignment to a variable that is not shared with
+// another function into a use of the assigned value.
+fn unused_assignments<S: Clone + AsRef<str>>(
+    ir: &mut ir::Function<S>,
+    reachable_blocks: &IndexSet,
+    locals: &IndexSet,
+) -> Vec<(Span, String)> {
+    let mut assignments = Vec::new();
+    for (block_id, block) in ir.blocks.iter() {
+        if !reachable_blocks.contains(block_id.index() as usize) {
+            continue;
+        }
+
+        for &inst_id in &block.instructions {
+            if let ir::InstructionKind::SetVariable(var_id, source) = ir.instructions[inst_id].kind
+                && locals.contains(var_id.index() as usize)
+            {
+                assignments.push((inst_id, var_id, source));
+            }
+        }
+    }
+
+    convert_to_ssa(ir);
+
+    // A value is read if it is used by any instruction other than an `Upsilon`, or by an `Upsilon`
+    // for a `Phi` which is itself read.
+    //
+    // Dead code elimination would also consider values which are only used by dead code to be
+    // unused, but that would report assignments to variables which *are* read by something.
+    let mut upsilons: SecondaryMap<ir::ShadowVar, Vec<ir::InstId>> = SecondaryMap::new();
+    let mut worklist = Vec::new();
+    for (block_id, block) in ir.blocks.iter() {
+        if !reachable_blocks.contains(block_id.index() as usize) {
+            continue;
+        }
+
+        for &inst_id in &block.instructions {
+            match &ir.instructions[inst_id].kind {
+                &ir::InstructionKind::Upsilon(shadow_var, source) => {
+                    upsilons.get_or_insert_default(shadow_var).push(source);
+                }
+                kind => worklist.extend(kind.sources()),
+            }
+        }
+        worklist.extend(block.exit.kind.sources());
+    }
+
+    let mut read_values = IndexSet::new();
+    while let Some(inst_id) = worklist.pop() {
+        if read_values.insert(inst_id.index() as usize)
+            && let ir::InstructionKind::Phi(shadow_var) = ir.instructions[inst_id].kind
+        {
+            worklist.extend(upsilons.get(shadow_var).into_iter().flatten().copied());
+        }
+    }
+
+    assignments
+        .into_iter()
+        .filter(|&(inst_id, _, source)| {
+            // Assignments to variables which were not converted are still `SetVariable`
+            // instructions, and may be read by another function.
+            matches!(ir.instructions[inst_id].kind, ir::InstructionKind::NoOp)
+                && !read_values.contains(source.index() as usize)
+        })
+        .map(|(inst_id, var_id, _)| {
+            (
+                ir.instructions[inst_id].span,
+                ir.variable_names[var_id].as_ref().to_owned(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{lexer::Lexer, parser::ParseSettings, string_interner::StdStringInterner};
+
+    struct TestVarDict;
+
+    impl VarDict<String> for TestVarDict {
+        fn is_reserved(&self, _name: &String) -> bool {
+            false
+        }
+
+        fn free_var_mode(&self, ident: &String) -> FreeVarMode {
+            if ident == "print" {
+                FreeVarMode::Magic { is_read_only: true }
+            } else {
+                FreeVarMode::This
+            }
+        }
+    }
+
+    fn lint(settings: IrGenSettings, source: &str) -> Vec<(Lint, String)> {
+        let mut tokens = Vec::new();
+        Lexer::tokenize(StdStringInterner, source, &mut tokens).unwrap();
+        let block = ParseSettings::compat().parse(tokens).unwrap();
+        let ir = settings
+            .gen_chunk_ir(&mut StdStringInterner, &block, &TestVarDict)
+            .unwrap();
+
+        let mut levels = LintLevels::default();
+        levels.set_all(LintLevel::Warn);
+
+        lint_chunk(settings, &block, &TestVarDict, &ir, &levels)
+            .into_iter()
+            .map(|d| (d.lint, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_unused() {
+        let lints = lint(
+            IrGenSettings::strict(),
+            r#"
+                let a = 1;
+                let _b = 2;
+                let c = 3;
+                print(c);
+                let f = closure(x, _y, z) {
+                    return z;
+                };
+                let g = closure(x) {
+                    return argument[0];
+                };
+                print(g);
+            "#,
+        );
+
+        assert_eq!(
+            lints,
+            [
+                (Lint::UnusedVariable, "unused variable `a`".to_owned()),
+                (Lint::UnusedVariable, "unused variable `f`".to_owned()),
+                (Lint::UnusedParameter, "unused parameter `x`".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_assignment() {
+        let lints = lint(
+            IrGenSettings::strict(),
+            r#"
+                let a = 1;
+                a = 2;
+                print(a);
+
+                let b = 1;
+                b = b + 1;
+                print(b);
+
+                let c = 1;
+                let f = closure() {
+                    print(c);
+                };
+                c = 2;
+                f();
+
+                let d = 1;
+                while true {
+                    if d > 0 {
+                        break;
+                    }
+                    d = 2;
+                    d = 3;
+                }
+                print(d);
+
+                let e = 1;
+                if d > 1 {
+                    e = 2;
+                }
+                e = 3;
+                print(e);
+
+                let g = 1;
+                let _h = g;
+            "#,
+        );
+
+        assert_eq!(
+            lints,
+            [
+                (
+                    Lint::UnusedAssignment,
+                    "value assigned to `a` is never read".to_owned()
+                ),
+                (
+                    Lint::UnusedAssignment,
+                    "value assigned to `d` is never read".to_owned()
+                ),
+                (
+                    Lint::UnusedAssignment,
+                    "value assigned to `e` is never read".to_owned()
+                ),
+                (
+                    Lint::UnusedAssignment,
+                    "value assigned to `e` is never read".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_and_implicit_self() {
+        let lints = lint(
+            IrGenSettings::compat(),
+            r#"
+                var a = 1;
+                if (a == 1) {
+                    return 1;
+                    print(a);
+                }
+                switch a {
+                    case 1:
+                        return 2;
+                        break;
+                }
+                self.x = a;
+                y = 1;
+            "#,
+        );
+
+        assert_eq!(
+            lints.iter().map(|(l, _)| *l).collect::<Vec<_>>(),
+            [Lint::UnreachableCode, Lint::ImplicitSelf]
+        );
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let mut tokens = Vec::new();
+        Lexer::tokenize(
+            StdStringInterner,
+            r#"
+                gml_pragma("lint", "deny", "unused_variable", "shadowed_let");
+                gml_pragma("lint", "allow", "unknown");
+                gml_pragma("forceinline");
+            "#,
+            &mut tokens,
+        )
+        .unwrap();
+        let block = ParseSettings::compat().parse::<_, String>(tokens).unwrap();
+
+        let mut levels = LintLevels::default();
+        let diagnostics = levels.apply_pragmas(&block);
+
+        assert_eq!(levels.get(Lint::UnusedVariable), LintLevel::Deny);
+        assert_eq!(levels.get(Lint::ShadowedLet), LintLevel::Deny);
+        assert_eq!(levels.get(Lint::UnusedParameter), LintLevel::Allow);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint, Lint::UnknownLint);
+    }
+}
@@ -54,7 +54,7 @@ pub struct ParseError {
     pub span: Span,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct ParseSettings {
     /// Require semicolons at the end of all statements other than block-like statements like `if`
     /// and `for`.
@@ -90,7 +90,26 @@ impl ParseSettings {
         }
     }
 
+    /// Parse a token stream, returning the first error encountered.
     pub fn parse<I, S>(self, token_iter: I) -> Result<ast::Block<S>, ParseError>
+    where
+        I: IntoIterator<Item = Token<S>>,
+        S: AsRef<str>,
+    {
+        let (block, mut errors) = self.parse_with_recovery(token_iter);
+        if errors.is_empty() {
+            Ok(block)
+        } else {
+            Err(errors.swap_remove(0))
+        }
+    }
+
+    /// Parse a token stream, recovering from errors at statement boundaries so that every error
+    /// in the stream can be reported at once.
+    ///
+    /// Any statement containing an error is left out of the returned block, so the block is only
+    /// complete if there are no errors.
+    pub fn parse_with_recovery<I, S>(self, token_iter: I) -> (ast::Block<S>, Vec<ParseError>)
     where
         I: IntoIterator<Item = Token<S>>,
         S: AsRef<str>,
@@ -126,6 +145,9 @@ struct Parser<I, S> {
     token_iter: I,
     look_ahead_buffer: ArrayVec<BufferedToken<S>, 1>,
     end_of_stream_span: Span,
+    // The number of tokens consumed so far, not counting newlines.
+    position: usize,
+    errors: Vec<ParseError>,
 }
 
 impl<I, S> Parser<I, S>
@@ -139,23 +161,29 @@ where
             token_iter,
             look_ahead_buffer: ArrayVec::new(),
             end_of_stream_span: Span::null(),
+            position: 0,
+            errors: Vec::new(),
         }
     }
 
-    fn parse(&mut self) -> Result<ast::Block<S>, ParseError> {
-        self.parse_block(|t| matches!(t, TokenKind::EndOfStream))
+    fn parse(mut self) -> (ast::Block<S>, Vec<ParseError>) {
+        let block = self.parse_block(|t| matches!(t, TokenKind::EndOfStream));
+        (block, self.errors)
     }
 
-    fn parse_block(
-        &mut self,
-        stop: impl Fn(&TokenKind<S>) -> bool,
-    ) -> Result<ast::Block<S>, ParseError> {
+    /// Parse statements until the `stop` token or the end of the stream, without consuming the
+    /// final token.
+    ///
+    /// Errors in statements are recorded and parsing resumes at the next statement, so this never
+    /// fails. If the block is unterminated, it is up to the caller to report a missing closing
+    /// token.
+    fn parse_block(&mut self, stop: impl Fn(&TokenKind<S>) -> bool) -> ast::Block<S> {
         let mut statements = Vec::new();
         let mut span = Span::null();
         loop {
             self.look_ahead(1);
             let next = self.peek(0);
-            if stop(&next.kind) {
+            if stop(&next.kind) || matches!(&next.kind, TokenKind::EndOfStream) {
                 break;
             }
 
@@ -164,12 +192,71 @@ where
                 continue;
             }
 
-            let stmt = self.parse_statement()?;
-            span = span.combine(stmt.span());
-            statements.push(stmt);
+            let start_position = self.position;
+            match self.parse_statement() {
+                Ok(stmt) => {
+                    span = span.combine(stmt.span());
+                    statements.push(stmt);
+                }
+                Err(err) => {
+                    self.record_error(err);
+                    self.synchronize(&stop, self.position == start_position);
+                }
+            }
         }
 
-        Ok(ast::Block { statements, span })
+        ast::Block { statements, span }
+    }
+
+    fn record_error(&mut self, err: ParseError) {
+        // An unterminated block at the end of the stream is also an unterminated block for every
+        // enclosing block, only the innermost one is worth reporting.
+        if matches!(err.kind, ParseErrorKind::EndOfStream { .. })
+            && self
+                .errors
+                .last()
+                .is_some_and(|e| matches!(e.kind, ParseErrorKind::EndOfStream { .. }))
+        {
+            return;
+        }
+        self.errors.push(err);
+    }
+
+    // Skip tokens after a parse error until the probable start of the next statement. This is
+    // either just past a semicolon or a statement keyword at the start of a line.
+    //
+    // Braced groups are skipped as a whole, and the `stop` token of the enclosing block is never
+    // skipped. If `must_advance` is set, then the first token is always skipped, to guarantee
+    // progress when the error occurred without consuming anything.
+    fn synchronize(&mut self, stop: &impl Fn(&TokenKind<S>) -> bool, mut must_advance: bool) {
+        let mut depth: usize = 0;
+        loop {
+            self.look_ahead(1);
+            let next = self.peek(0);
+            if matches!(next.kind, TokenKind::EndOfStream) {
+                break;
+            }
+
+            if depth == 0
+                && !must_advance
+                && (stop(&next.kind) || (self.peek_newline(0) && starts_statement(&next.kind)))
+            {
+                break;
+            }
+
+            match next.kind {
+                TokenKind::LeftBrace => depth += 1,
+                TokenKind::RightBrace => depth = depth.saturating_sub(1),
+                TokenKind::SemiColon if depth == 0 => {
+                    self.advance(1);
+                    break;
+                }
+                _ => {}
+            }
+
+            self.advance(1);
+            must_advance = false;
+        }
     }
 
     /// Parse a statement including any trailing semicolon, if it is expected.
@@ -431,8 +518,8 @@ where
             }
             TokenKind::LeftBrace => {
                 self.advance(1);
-                let block = self.parse_block(|t| matches!(t, TokenKind::RightBrace))?;
-                let span = tok_span.combine(self.parse_token(TokenKind::RightBrace).unwrap());
+                let block = self.parse_block(|t| matches!(t, TokenKind::RightBrace));
+                let span = tok_span.combine(self.parse_token(TokenKind::RightBrace)?);
                 (
                     ast::Statement::Block(ast::BlockStmt { block, span }),
                     StatementTrailer::NoSemiColon,
@@ -644,8 +731,8 @@ where
         };
 
         self.parse_token(TokenKind::LeftBrace)?;
-        let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace))?;
-        span = span.combine(self.parse_token(TokenKind::RightBrace).unwrap());
+        let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace));
+        span = span.combine(self.parse_token(TokenKind::RightBrace)?);
 
         if !is_constructor && inherit.is_some() {
             return Err(ParseError {
@@ -670,8 +757,8 @@ where
         let parameters = self.parse_parameter_list()?;
 
         self.parse_token(TokenKind::LeftBrace)?;
-        let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace))?;
-        span = span.combine(self.parse_token(TokenKind::RightBrace).unwrap());
+        let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace));
+        span = span.combine(self.parse_token(TokenKind::RightBrace)?);
 
         Ok(ast::ClosureStmt {
             name,
@@ -808,7 +895,7 @@ where
                             t,
                             TokenKind::Case | TokenKind::Default | TokenKind::RightBrace
                         )
-                    })?;
+                    });
                     span = span.combine(body.span);
                     cases.push(ast::SwitchCase {
                         compare,
@@ -824,7 +911,7 @@ where
                             t,
                             TokenKind::Case | TokenKind::Default | TokenKind::RightBrace
                         )
-                    })?);
+                    }));
                 }
                 token => {
                     return Err(ParseError {
@@ -1188,8 +1275,8 @@ where
                 };
 
                 self.parse_token(TokenKind::LeftBrace)?;
-                let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace))?;
-                let span = tok_span.combine(self.parse_token(TokenKind::RightBrace).unwrap());
+                let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace));
+                let span = tok_span.combine(self.parse_token(TokenKind::RightBrace)?);
 
                 if !is_constructor && inherit.is_some() {
                     return Err(ParseError {
@@ -1212,8 +1299,8 @@ where
                 let parameters = self.parse_parameter_list()?;
 
                 self.parse_token(TokenKind::LeftBrace)?;
-                let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace))?;
-                let span = tok_span.combine(self.parse_token(TokenKind::RightBrace).unwrap());
+                let body = self.parse_block(|t| matches!(t, TokenKind::RightBrace));
+                let span = tok_span.combine(self.parse_token(TokenKind::RightBrace)?);
 
                 Ok(ast::Expression::Closure(ast::ClosureExpr {
                     parameters,
@@ -1453,6 +1540,7 @@ where
     // `Parser::look_ahead`.
     fn advance(&mut self, n: usize) {
         self.look_ahead_buffer.drain(0..n);
+        self.position += n;
     }
 
     // Returns a reference to the `n`th token ahead in the look-ahead token buffer if it exists,
@@ -1474,6 +1562,7 @@ where
     // Return the next token in the token stream if it exists and advance the stream.
     fn next(&mut self) -> Token<S> {
         self.look_ahead(1);
+        self.position += 1;
         self.look_ahead_buffer.remove(0).token
     }
 }
@@ -1569,6 +1658,31 @@ fn get_accessor_type<S>(token: &TokenKind<S>) -> Option<ast::AccessorType> {
     }
 }
 
+// Keywords which can only start a statement, used as synchronization points after a parse error.
+fn starts_statement<S>(token: &TokenKind<S>) -> bool {
+    matches!(
+        token,
+        TokenKind::Enum
+            | TokenKind::Function
+            | TokenKind::Var
+            | TokenKind::Let
+            | TokenKind::Static
+            | TokenKind::GlobalVar
+            | TokenKind::Return
+            | TokenKind::Exit
+            | TokenKind::If
+            | TokenKind::For
+            | TokenKind::While
+            | TokenKind::Repeat
+            | TokenKind::Switch
+            | TokenKind::With
+            | TokenKind::Try
+            | TokenKind::Throw
+            | TokenKind::Break
+            | TokenKind::Continue
+    )
+}
+
 fn token_indicator<S>(t: &TokenKind<S>) -> &'static str {
     match *t {
         TokenKind::EndOfStream => "<eof>",
@@ -1767,4 +1881,43 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_error_recovery() {
+        const SOURCE: &str = r#"
+            var a = ;
+            function f() {
+                let x = 1 +;
+                return x;
+            }
+            var b = 2;
+            if a {
+                b = )
+            }
+            var c = 3
+        "#;
+
+        let mut tokens = Vec::new();
+        Lexer::tokenize(StdStringInterner, SOURCE, &mut tokens).unwrap();
+        let (block, errors) = ParseSettings::strict().parse_with_recovery(tokens);
+
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(errors[3].kind, ParseErrorKind::EndOfStream { .. }));
+
+        assert_eq!(block.statements.len(), 3);
+        let ast::Statement::Function(func) = &block.statements[0] else {
+            panic!("expected function statement");
+        };
+        assert_eq!(func.body.statements.len(), 1);
+        assert!(matches!(block.statements[1], ast::Statement::Var(_)));
+        assert!(matches!(block.statements[2], ast::Statement::If(_)));
+
+        let err = parse(ParseSettings::strict(), "function f() { if x {").unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::EndOfStream { .. }));
+
+        let mut tokens = Vec::new();
+        Lexer::tokenize(StdStringInterner, "function f() { if x {", &mut tokens).unwrap();
+        let (_, errors) = ParseSettings::strict().parse_with_recovery(tokens);
+        assert_eq!(errors.len(), 1);
+    }
 }
@@ -91,6 +91,19 @@ pub struct PreprocessError {
     pub kind: PreprocessErrorKind,
     pub chunk_name: vm::SharedStr,
     pub line_number: vm::LineNumber,
+    /// The span of the error within the named chunk.
+    pub span: vm::Span,
+}
+
+impl PreprocessError {
+    fn new(kind: PreprocessErrorKind, chunk: vm::Chunk<'_>, span: vm::Span) -> Self {
+        Self {
+            kind,
+            chunk_name: chunk.name().clone(),
+            line_number: chunk.line_number(span.start()),
+            span,
+        }
+    }
 }
 
 /// Extracts and resolves macros, then extracts and resolves enums, then extracts exported functions
@@ -162,10 +175,14 @@ impl<'gc> Preprocessor<'gc> {
     ///
     /// Macros will not recurse into identifiers which are considered "special", allowing macros to
     /// redefine specials.
+    ///
+    /// Preprocessing continues past errors in individual chunks, so that every error may be
+    /// reported at once. Errors which affect every chunk (recursive macros and unresolvable enums)
+    /// stop preprocessing early, since continuing would only produce spurious errors.
     pub fn preprocess(
         self,
         is_special: impl Fn(vm::String<'gc>) -> bool,
-    ) -> Result<PreprocessOutput<'gc>, PreprocessError> {
+    ) -> Result<PreprocessOutput<'gc>, Vec<PreprocessError>> {
         let Self {
             ctx,
             config,
@@ -174,6 +191,8 @@ impl<'gc> Preprocessor<'gc> {
             mut chunk_inputs,
         } = self;
 
+        let mut errors = Vec::new();
+
         // Extract all new macro definitions from all input chunks.
 
         let mut macro_builder = MacroSetBuilder::new();
@@ -181,16 +200,24 @@ impl<'gc> Preprocessor<'gc> {
         // List of starting macro indexes per chunk, to identify which macros come from which chunk.
         let mut macro_chunk_indexes = Vec::new();
 
+        // Chunks which failed macro extraction have an unusable token stream, and are not parsed.
+        let mut macro_failures = Vec::new();
+
         for input in &mut chunk_inputs {
             macro_chunk_indexes.push(macro_builder.len());
-            if let Err(err) = macro_builder.extract(&mut input.tokens) {
-                let line_number = input.chunk.line_number(err.span.start());
-                return Err(PreprocessError {
-                    kind: PreprocessErrorKind::Macro(err),
-                    chunk_name: input.chunk.name().clone(),
-                    line_number,
-                });
-            }
+            let failed = match macro_builder.extract(&mut input.tokens) {
+                Ok(()) => false,
+                Err(err) => {
+                    let span = err.span;
+                    errors.push(PreprocessError::new(
+                        PreprocessErrorKind::Macro(err),
+                        input.chunk,
+                        span,
+                    ));
+                    true
+                }
+            };
+            macro_failures.push(failed);
         }
 
         // Apply the given config and resolve all macro interdependencies.
@@ -211,13 +238,13 @@ impl<'gc> Preprocessor<'gc> {
                         Ok(i) => i,
                         Err(i) => i.checked_sub(1).unwrap(),
                     };
-                    let chunk_input = &chunk_inputs[chunk_index];
-                    let line_number = chunk_input.chunk.line_number(macro_.span.start());
-                    return Err(PreprocessError {
-                        kind: PreprocessErrorKind::RecursiveMacro(err),
-                        chunk_name: chunk_input.chunk.name().clone(),
-                        line_number,
-                    });
+                    let span = macro_.span;
+                    errors.push(PreprocessError::new(
+                        PreprocessErrorKind::RecursiveMacro(err),
+                        chunk_inputs[chunk_index].chunk,
+                        span,
+                    ));
+                    return Err(errors);
                 }
             };
 
@@ -226,7 +253,7 @@ impl<'gc> Preprocessor<'gc> {
 
         let mut preprocessing_chunks = Vec::new();
 
-        for input in chunk_inputs {
+        for (input, macro_failed) in chunk_inputs.into_iter().zip(macro_failures) {
             let ChunkInput {
                 chunk,
                 mut tokens,
@@ -234,19 +261,30 @@ impl<'gc> Preprocessor<'gc> {
                 export_top_level_funcs,
             } = input;
 
-            // New macros are allowed to depend on external macros, external macros are never
-            // allowed to depend on new macros, so we apply all new macros before externals.
-            new_macros.expand(&mut tokens);
-            external_macros.expand(&mut tokens);
-
-            let block = parse_settings.parse(tokens).map_err(|e| {
-                let line_number = chunk.line_number(e.span.start());
-                PreprocessError {
-                    kind: PreprocessErrorKind::Parsing(e),
-                    chunk_name: chunk.name().clone(),
-                    line_number,
+            let block = if macro_failed {
+                ast::Block {
+                    statements: Vec::new(),
+                    span: vm::Span::null(),
+                }
+            } else {
+                // New macros are allowed to depend on external macros, external macros are never
+                // allowed to depend on new macros, so we apply all new macros before externals.
+                new_macros.expand(&mut tokens);
+                external_macros.expand(&mut tokens);
+
+                // Statements with parse errors are left out of the block, the rest of the block is
+                // still preprocessed to find any further errors.
+                let (block, parse_errors) = parse_settings.parse_with_recovery(tokens);
+                for err in parse_errors {
+                    let span = err.span;
+                    errors.push(PreprocessError::new(
+                        PreprocessErrorKind::Parsing(err),
+                        chunk,
+                        span,
+                    ));
                 }
-            })?;
+                block
+            };
 
             preprocessing_chunks.push(((block, chunk), export_top_level_funcs));
         }
@@ -259,26 +297,26 @@ impl<'gc> Preprocessor<'gc> {
         // List of starting enum indexes per chunk, to identify which enums come from which chunk.
         let mut enum_chunk_indexes = Vec::new();
 
-        for ((block, chunk), _) in &mut preprocessing_chunks {
+        for &mut ((ref mut block, chunk), _) in &mut preprocessing_chunks {
             if let Err(err) = external_enums.expand(block) {
-                let line_number = chunk.line_number(err.span.start());
-                return Err(PreprocessError {
-                    kind: PreprocessErrorKind::EnumEvaluation(err),
-                    chunk_name: chunk.name().clone(),
-                    line_number,
-                });
+                let span = err.span;
+                errors.push(PreprocessError::new(
+                    PreprocessErrorKind::EnumEvaluation(err),
+                    chunk,
+                    span,
+                ));
             }
 
             let prev_enum_len = enum_builder.len();
             enum_chunk_indexes.push(prev_enum_len);
 
             if let Err(err) = enum_builder.extract(block) {
-                let line_number = chunk.line_number(err.span.start());
-                return Err(PreprocessError {
-                    kind: PreprocessErrorKind::Enum(err),
-                    chunk_name: chunk.name().clone(),
-                    line_number,
-                });
+                let span = err.span;
+                errors.push(PreprocessError::new(
+                    PreprocessErrorKind::Enum(err),
+                    chunk,
+                    span,
+                ));
             }
 
             for i in prev_enum_len..enum_builder.len() {
@@ -286,47 +324,50 @@ impl<'gc> Preprocessor<'gc> {
                 // New enums are not allowed to shadow names of external enums or specials.
                 if external_enums.find(&enum_.name.inner).is_some() || is_special(enum_.name.inner)
                 {
-                    let line_number = chunk.line_number(enum_.span.start());
-                    return Err(PreprocessError {
-                        kind: PreprocessErrorKind::ShadowsSpecial(ShadowsSpecialError {
+                    errors.push(PreprocessError::new(
+                        PreprocessErrorKind::ShadowsSpecial(ShadowsSpecialError {
                             name: enum_.name.as_str().to_owned(),
                             span: enum_.span,
                         }),
-                        chunk_name: chunk.name().clone(),
-                        line_number,
-                    });
+                        chunk,
+                        enum_.span,
+                    ));
                 }
             }
         }
 
         // Resolve all enum interdependencies.
 
-        let new_enums = enum_builder.resolve().map_err(|err| {
-            let enum_ = enum_builder.get(err.enum_index).unwrap();
-            let chunk_index = match enum_chunk_indexes.binary_search_by(|i| i.cmp(&err.enum_index))
-            {
-                Ok(i) => i,
-                Err(i) => i.checked_sub(1).unwrap(),
-            };
-            let (_, chunk) = &preprocessing_chunks[chunk_index].0;
-            let line_number = chunk.line_number(enum_.span.start());
-            PreprocessError {
-                kind: PreprocessErrorKind::EnumResolution(err),
-                chunk_name: chunk.name().clone(),
-                line_number,
+        let new_enums = match enum_builder.resolve() {
+            Ok(new_enums) => new_enums,
+            Err(err) => {
+                let enum_ = enum_builder.get(err.enum_index).unwrap();
+                let chunk_index =
+                    match enum_chunk_indexes.binary_search_by(|i| i.cmp(&err.enum_index)) {
+                        Ok(i) => i,
+                        Err(i) => i.checked_sub(1).unwrap(),
+                    };
+                let (_, chunk) = preprocessing_chunks[chunk_index].0;
+                let span = enum_.span;
+                errors.push(PreprocessError::new(
+                    PreprocessErrorKind::EnumResolution(err),
+                    chunk,
+                    span,
+                ));
+                return Err(errors);
             }
-        })?;
+        };
 
         // Apply new enum definitions.
 
-        for ((block, chunk), _) in &mut preprocessing_chunks {
+        for &mut ((ref mut block, chunk), _) in &mut preprocessing_chunks {
             if let Err(err) = new_enums.expand(block) {
-                let line_number = chunk.line_number(err.span.start());
-                return Err(PreprocessError {
-                    kind: PreprocessErrorKind::EnumEvaluation(err),
-                    chunk_name: chunk.name().clone(),
-                    line_number,
-                });
+                let span = err.span;
+                errors.push(PreprocessError::new(
+                    PreprocessErrorKind::EnumEvaluation(err),
+                    chunk,
+                    span,
+                ));
             }
         }
 
@@ -339,9 +380,7 @@ impl<'gc> Preprocessor<'gc> {
         // chunk.
         let mut export_chunk_indexes = Vec::new();
 
-        for &mut ((ref mut block, ref mut chunk), export_top_level_funcs) in
-            &mut preprocessing_chunks
-        {
+        for &mut ((ref mut block, chunk), export_top_level_funcs) in &mut preprocessing_chunks {
             let prev_exports_len = exports.len();
             export_chunk_indexes.push(prev_exports_len);
 
@@ -351,12 +390,12 @@ impl<'gc> Preprocessor<'gc> {
                     export_top_level_functions: export_top_level_funcs,
                 },
             ) {
-                let line_number = chunk.line_number(err.span.start());
-                return Err(PreprocessError {
-                    kind: PreprocessErrorKind::DuplicateExport(err),
-                    chunk_name: chunk.name().clone(),
-                    line_number,
-                });
+                let span = err.span;
+                errors.push(PreprocessError::new(
+                    PreprocessErrorKind::DuplicateExport(err),
+                    chunk,
+                    span,
+                ));
             }
 
             for i in prev_exports_len..exports.len() {
@@ -369,19 +408,22 @@ impl<'gc> Preprocessor<'gc> {
                     || new_enums.find(export_name).is_some()
                     || is_special(*export_name)
                 {
-                    let line_number = chunk.line_number(export_span.start());
-                    return Err(PreprocessError {
-                        kind: PreprocessErrorKind::ShadowsSpecial(ShadowsSpecialError {
+                    errors.push(PreprocessError::new(
+                        PreprocessErrorKind::ShadowsSpecial(ShadowsSpecialError {
                             name: export_name.as_str().to_owned(),
                             span: export_span,
                         }),
-                        chunk_name: chunk.name().clone(),
-                        line_number,
-                    });
+                        chunk,
+                        export_span,
+                    ));
                 }
             }
         }
 
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
         // Merge macro and enum sets together and offset the chunk indexes.
 
         let external_macros_len = external_macros.len();
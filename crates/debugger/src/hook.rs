@@ -0,0 +1,537 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic},
+};
+
+use fabricator_vm as vm;
+use gc_arena::Collect;
+use serde_json::json;
+
+use crate::{
+    protocol::Request,
+    server::{Command, Resume, Shared, THREAD_ID},
+};
+
+/// How many instructions the VM may execute between checks for a pause request when there are no
+/// breakpoints set and the debugger is not stepping.
+const IDLE_STEP_INSTRUCTIONS: u32 = 4096;
+
+#[derive(Debug, Copy, Clone)]
+struct Step {
+    resume: Resume,
+    depth: usize,
+}
+
+/// A `vm::Hook` which implements breakpoints, stepping, and pausing for a [`DebugServer`].
+///
+/// [`DebugServer`]: crate::DebugServer
+#[derive(Collect)]
+#[collect(require_static)]
+pub struct DebugHook {
+    shared: Arc<Shared>,
+    breakpoints: HashMap<PathBuf, BTreeSet<usize>>,
+    breakpoints_version: u64,
+    // The last line that was executed at each frame depth, if any.
+    lines: Vec<Option<usize>>,
+    step: Option<Step>,
+}
+
+impl DebugHook {
+    pub(crate) fn new(shared: Arc<Shared>) -> Self {
+        Self {
+            shared,
+            breakpoints: HashMap::new(),
+            breakpoints_version: u64::MAX,
+            lines: Vec::new(),
+            step: None,
+        }
+    }
+
+    fn refresh_breakpoints(&mut self) {
+        let version = self
+            .shared
+            .breakpoints_version
+            .load(atomic::Ordering::SeqCst);
+        if version != self.breakpoints_version {
+            self.breakpoints = self.shared.breakpoints.lock().unwrap().clone();
+            self.breakpoints_version = version;
+        }
+    }
+
+    fn has_breakpoint(&self, chunk_name: &str, line: usize) -> bool {
+        let chunk_path = Path::new(chunk_name);
+        self.breakpoints.iter().any(|(path, lines)| {
+            lines.contains(&line)
+                && (path == chunk_path || path.ends_with(chunk_path) || chunk_path.ends_with(path))
+        })
+    }
+
+    // Block the VM until the client tells it to resume, answering any inspection requests in the
+    // meantime.
+    fn pause<'gc>(
+        &mut self,
+        ctx: vm::Context<'gc>,
+        frames: &vm::FrameStack<'gc, '_>,
+        reason: &str,
+    ) {
+        let shared = &*self.shared;
+        shared.paused.store(true, atomic::Ordering::SeqCst);
+        shared.connection.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        );
+
+        let mut inspector = Inspector {
+            ctx,
+            frames,
+            references: Vec::new(),
+        };
+
+        let commands = shared.commands.lock().unwrap();
+        loop {
+            match commands.recv() {
+                Ok(Command::Inspect(request)) => match inspector.inspect(&request) {
+                    Ok(body) => shared.connection.respond(&request, body),
+                    Err(err) => shared.connection.respond_error(&request, err),
+                },
+                Ok(Command::Resume(request, resume)) => {
+                    self.step = match resume {
+                        Resume::Continue => None,
+                        resume => Some(Step {
+                            resume,
+                            depth: frames.frame_depth(),
+                        }),
+                    };
+                    shared.paused.store(false, atomic::Ordering::SeqCst);
+                    shared
+                        .connection
+                        .respond(&request, json!({ "allThreadsContinued": true }));
+                    return;
+                }
+                Ok(Command::Disconnect) | Err(_) => {
+                    self.step = None;
+                    shared.paused.store(false, atomic::Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl<'gc> vm::Hook<'gc> for DebugHook {
+    fn on_call(
+        &mut self,
+        _ctx: vm::Context<'gc>,
+        frames: vm::FrameStack<'gc, '_>,
+    ) -> Result<(), vm::RuntimeError> {
+        let depth = frames.frame_depth();
+        self.lines.truncate(depth - 1);
+        self.lines.resize(depth, None);
+        Ok(())
+    }
+
+    fn on_return(&mut self, _ctx: vm::Context<'gc>, frames: vm::FrameStack<'gc, '_>) {
+        self.lines.truncate(frames.frame_depth() - 1);
+    }
+
+    fn on_step(
+        &mut self,
+        ctx: vm::Context<'gc>,
+        frames: vm::FrameStack<'gc, '_>,
+        _instruction_count: u32,
+    ) -> Result<u32, vm::RuntimeError> {
+        if self.shared.disconnected.load(atomic::Ordering::SeqCst) {
+            return Ok(0);
+        }
+
+        self.refresh_breakpoints();
+
+        let depth = frames.frame_depth();
+        if self.lines.len() < depth {
+            self.lines.resize(depth, None);
+        }
+
+        let vm::StackFrame::Closure(frame) = frames.frame(0) else {
+            unreachable!("top frame is not a closure frame");
+        };
+
+        // We only consider stopping when execution moves to a new line in this frame.
+        let mut new_line = None;
+        if let Some(line) = frame_line(&frame) {
+            if self.lines[depth - 1] != Some(line) {
+                self.lines[depth - 1] = Some(line);
+                new_line = Some(line);
+            }
+        }
+
+        let reason = if self
+            .shared
+            .pause_requested
+            .swap(false, atomic::Ordering::SeqCst)
+        {
+            Some("pause")
+        } else if let Some(line) = new_line {
+            let step_done = self.step.is_some_and(|step| match step.resume {
+                Resume::Continue => false,
+                Resume::StepIn => true,
+                Resume::StepOver => depth <= step.depth,
+                Resume::StepOut => depth < step.depth,
+            });
+
+            if step_done {
+                Some("step")
+            } else if self.has_breakpoint(frame.chunk_name(), line) {
+                Some("breakpoint")
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            self.step = None;
+            self.pause(ctx, &frames, reason);
+        }
+
+        Ok(
+            if self.step.is_some() || self.breakpoints.values().any(|l| !l.is_empty()) {
+                1
+            } else {
+                IDLE_STEP_INSTRUCTIONS
+            },
+        )
+    }
+}
+
+// Returns the 1-indexed source line of the current instruction of a closure frame, if there is one.
+fn frame_line(frame: &vm::thread::ClosureStackFrame<'_>) -> Option<usize> {
+    let prototype = frame.closure.prototype();
+    let bytecode = prototype.bytecode();
+    if frame.instruction >= bytecode.instruction_len() {
+        return None;
+    }
+
+    let span = bytecode.span(frame.instruction);
+    if span.is_null() || span.is_everywhere() {
+        return None;
+    }
+
+    Some(prototype.chunk().line_number(span.start()).0 + 1)
+}
+
+enum Reference<'gc> {
+    Registers(usize),
+    HeapVars(usize),
+    Value(vm::Value<'gc>),
+}
+
+// Answers inspection requests while the VM is paused.
+//
+// Frame IDs are frame indexes as understood by `vm::FrameStack`, and variable references are
+// indexes (offset by one) into a list of references that is discarded when the VM resumes.
+struct Inspector<'gc, 'a, 'b> {
+    ctx: vm::Context<'gc>,
+    frames: &'a vm::FrameStack<'gc, 'b>,
+    references: Vec<Reference<'gc>>,
+}
+
+impl<'gc, 'a, 'b> Inspector<'gc, 'a, 'b> {
+    fn inspect(&mut self, request: &Request) -> Result<serde_json::Value, String> {
+        let args = &request.arguments;
+        match request.command.as_str() {
+            "stackTrace" => Ok(self.stack_trace(
+                args["startFrame"].as_u64().unwrap_or(0) as usize,
+                args["levels"]
+                    .as_u64()
+                    .filter(|&l| l > 0)
+                    .map(|l| l as usize),
+            )),
+            "scopes" => self.scopes(self.frame_id(&args["frameId"])?),
+            "variables" => {
+                let reference = args["variablesReference"]
+                    .as_u64()
+                    .ok_or("missing variables reference")?;
+                self.variables(reference as usize)
+            }
+            "evaluate" => {
+                let frame = match args.get("frameId") {
+                    Some(frame_id) => self.frame_id(frame_id)?,
+                    None => 0,
+                };
+                self.evaluate(frame, args["expression"].as_str().unwrap_or_default())
+            }
+            command => Err(format!("unsupported request {command:?}")),
+        }
+    }
+
+    fn frame_id(&self, frame_id: &serde_json::Value) -> Result<usize, String> {
+        frame_id
+            .as_u64()
+            .map(|f| f as usize)
+            .filter(|&f| f < self.frames.frame_depth())
+            .ok_or_else(|| "invalid frame id".to_owned())
+    }
+
+    fn stack_trace(&mut self, start: usize, levels: Option<usize>) -> serde_json::Value {
+        let depth = self.frames.frame_depth();
+        let end = levels.map_or(depth, |l| depth.min(start.saturating_add(l)));
+
+        let stack_frames = (start.min(end)..end)
+            .map(|index| match self.frames.frame(index) {
+                vm::StackFrame::Closure(frame) => {
+                    let prototype = frame.closure.prototype();
+                    let name = match prototype.reference() {
+                        vm::FunctionRef::Named(name, _) => name.to_string(),
+                        vm::FunctionRef::Expression(_) => "<anonymous>".to_owned(),
+                        vm::FunctionRef::Chunk => "<chunk>".to_owned(),
+                    };
+                    let chunk_name = frame.chunk_name().as_str();
+                    let path =
+                        fs::canonicalize(chunk_name).unwrap_or_else(|_| PathBuf::from(chunk_name));
+                    json!({
+                        "id": index,
+                        "name": name,
+                        "source": {
+                            "name": path.file_name().map(|n| n.to_string_lossy()),
+                            "path": path.to_string_lossy(),
+                        },
+                        "line": frame_line(&frame).unwrap_or(frame.line_number().0 + 1),
+                        "column": 1,
+                    })
+                }
+                vm::StackFrame::Callback(_) => json!({
+                    "id": index,
+                    "name": "<callback>",
+                    "line": 0,
+                    "column": 0,
+                    "presentationHint": "subtle",
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "stackFrames": stack_frames,
+            "totalFrames": depth,
+        })
+    }
+
+    fn scopes(&mut self, frame: usize) -> Result<serde_json::Value, String> {
+        let Some(this) = self.frames.this(self.ctx, frame) else {
+            // Callback frames have nothing to inspect.
+            return Ok(json!({ "scopes": [] }));
+        };
+
+        let registers = self.add_reference(Reference::Registers(frame));
+        let heap_vars = self.add_reference(Reference::HeapVars(frame));
+        let this = self.add_reference(Reference::Value(this));
+
+        Ok(json!({
+            "scopes": [
+                {
+                    "name": "Registers",
+                    "presentationHint": "locals",
+                    "variablesReference": registers,
+                    "expensive": false,
+                },
+                {
+                    "name": "Heap Variables",
+                    "variablesReference": heap_vars,
+                    "expensive": false,
+                },
+                {
+                    "name": "Self",
+                    "variablesReference": this,
+                    "expensive": false,
+                },
+            ],
+        }))
+    }
+
+    fn variables(&mut self, reference: usize) -> Result<serde_json::Value, String> {
+        let mut variables = Vec::new();
+
+        match reference
+            .checked_sub(1)
+            .and_then(|r| self.references.get(r))
+            .ok_or("invalid variables reference")?
+        {
+            &Reference::Registers(frame) => {
+                let mut names = self.local_names(frame);
+                let registers = self.frames.registers(frame).unwrap_or_default();
+                for (i, &value) in registers.iter().enumerate() {
+                    let name = vm::instructions::RegIdx::try_from(i)
+                        .ok()
+                        .and_then(|r| names.remove(&vm::VariableLocation::Register(r)))
+                        .unwrap_or_else(|| format!("r{i}"));
+                    variables.push((name, value));
+                }
+            }
+            &Reference::HeapVars(frame) => {
+                let vm::StackFrame::Closure(closure_frame) = self.frames.frame(frame) else {
+                    return Err("not a closure frame".to_owned());
+                };
+                let mut names = self.local_names(frame);
+                let prototype = closure_frame.closure.prototype();
+                for (i, desc) in prototype.heap_vars().iter().enumerate() {
+                    let name = vm::instructions::HeapIdx::try_from(i)
+                        .ok()
+                        .and_then(|h| names.remove(&vm::VariableLocation::Heap(h)))
+                        .unwrap_or_else(|| match desc {
+                            vm::closure::HeapVarDescriptor::Owned(_) => format!("owned{i}"),
+                            vm::closure::HeapVarDescriptor::Static(_) => format!("static{i}"),
+                            vm::closure::HeapVarDescriptor::UpValue(_) => format!("upvalue{i}"),
+                        });
+                    if let Some(value) = self.frames.heap_var(frame, i) {
+                        variables.push((name, value));
+                    }
+                }
+            }
+            &Reference::Value(vm::Value::Object(object)) => {
+                let Ok(map) = object.try_borrow() else {
+                    return Err("object is borrowed".to_owned());
+                };
+                let mut fields = map
+                    .iter()
+                    .map(|(key, value)| (key.as_str().to_owned(), value))
+                    .collect::<Vec<_>>();
+                fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                variables.extend(fields);
+            }
+            &Reference::Value(vm::Value::Array(array)) => {
+                let Ok(array) = array.try_borrow() else {
+                    return Err("array is borrowed".to_owned());
+                };
+                for (i, value) in array.iter().enumerate() {
+                    variables.push((format!("[{i}]"), value));
+                }
+            }
+            Reference::Value(_) => {}
+        }
+
+        let variables = variables
+            .into_iter()
+            .map(|(name, value)| {
+                json!({
+                    "name": name,
+                    "value": describe_value(value),
+                    "type": value.type_name(),
+                    "variablesReference": self.value_reference(value),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    // The names of the local variables held in each register or heap variable at the current
+    // instruction of a closure frame. Locations holding several variables at once (such as after
+    // `var b = a;`) list every name.
+    fn local_names(&self, frame: usize) -> HashMap<vm::VariableLocation, String> {
+        let mut names = HashMap::<vm::VariableLocation, String>::new();
+        if let vm::StackFrame::Closure(closure_frame) = self.frames.frame(frame) {
+            let prototype = closure_frame.closure.prototype();
+            for var in prototype.local_variables_at(closure_frame.instruction) {
+                names
+                    .entry(var.location)
+                    .and_modify(|name| {
+                        name.push_str(", ");
+                        name.push_str(&var.name);
+                    })
+                    .or_insert_with(|| var.name.to_string());
+            }
+        }
+        names
+    }
+
+    // The value of the named local variable at the current instruction of a closure frame.
+    fn local_value(&self, frame: usize, name: &str) -> Option<vm::Value<'gc>> {
+        let vm::StackFrame::Closure(closure_frame) = self.frames.frame(frame) else {
+            return None;
+        };
+        let prototype = closure_frame.closure.prototype();
+        let var = prototype
+            .local_variables_at(closure_frame.instruction)
+            .find(|var| &*var.name == name)?;
+        match var.location {
+            vm::VariableLocation::Register(reg) => {
+                self.frames.registers(frame)?.get(reg.0 as usize).copied()
+            }
+            vm::VariableLocation::Heap(heap) => self.frames.heap_var(frame, heap.0 as usize),
+        }
+    }
+
+    // Evaluate a bare identifier by looking it up in the frame's local variables, then in `self`,
+    // and then in globals.
+    //
+    // This is enough for hovering over variables, fields, and globals in an editor. Full
+    // expressions would require the compiler.
+    fn evaluate(&mut self, frame: usize, expression: &str) -> Result<serde_json::Value, String> {
+        let expression = expression.trim();
+        if expression.is_empty()
+            || !expression
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err("only identifiers can be evaluated".to_owned());
+        }
+
+        if let Some(value) = self.local_value(frame, expression) {
+            return Ok(json!({
+                "result": describe_value(value),
+                "type": value.type_name(),
+                "variablesReference": self.value_reference(value),
+            }));
+        }
+
+        let key = self.ctx.intern(expression);
+        let mut scopes = Vec::new();
+        if let Some(vm::Value::Object(this)) = self.frames.this(self.ctx, frame) {
+            scopes.push(this);
+        }
+        scopes.push(self.ctx.globals());
+
+        let value = scopes
+            .into_iter()
+            .find_map(|obj| obj.try_find(key).ok().flatten())
+            .ok_or_else(|| format!("{expression:?} not found"))?;
+
+        Ok(json!({
+            "result": describe_value(value),
+            "type": value.type_name(),
+            "variablesReference": self.value_reference(value),
+        }))
+    }
+
+    fn value_reference(&mut self, value: vm::Value<'gc>) -> usize {
+        match value {
+            vm::Value::Object(_) | vm::Value::Array(_) => {
+                self.add_reference(Reference::Value(value))
+            }
+            _ => 0,
+        }
+    }
+
+    fn add_reference(&mut self, reference: Reference<'gc>) -> usize {
+        self.references.push(reference);
+        self.references.len()
+    }
+}
+
+fn describe_value(value: vm::Value<'_>) -> String {
+    match value {
+        vm::Value::String(s) => format!("{:?}", s.as_str()),
+        vm::Value::Object(_) => "struct".to_owned(),
+        vm::Value::Array(array) => match array.try_borrow() {
+            Ok(array) => format!("array[{}]", array.len()),
+            Err(_) => "array".to_owned(),
+        },
+        value => value.to_string(),
+    }
+}
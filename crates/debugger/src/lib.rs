@@ -0,0 +1,18 @@
+//! A [Debug Adapter Protocol] server for FML scripts.
+//!
+//! The server supports line breakpoints, pausing, stepping, stack traces, and inspection of values
+//! in paused frames. A paused frame shows its registers and heap variables under the names of the
+//! local variables they hold at the current instruction, using the local variable debug info
+//! recorded in compiled prototypes, and only falls back to raw slot names for unnamed temporaries.
+//!
+//! [Debug Adapter Protocol]: https://microsoft.github.io/debug-adapter-protocol/
+
+mod hook;
+mod protocol;
+mod server;
+
+pub use self::{
+    hook::DebugHook,
+    protocol::ProtocolError,
+    server::{DebugServer, Transport},
+};
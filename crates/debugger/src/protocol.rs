@@ -0,0 +1,156 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        Mutex,
+        atomic::{self, AtomicI64},
+    },
+};
+
+use serde::Deserialize;
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("missing Content-Length header")]
+    MissingContentLength,
+    #[error("bad header {0:?}")]
+    BadHeader(String),
+    #[error("bad message: {0}")]
+    BadMessage(#[from] serde_json::Error),
+}
+
+/// A Debug Adapter Protocol request sent from the client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    pub seq: i64,
+    pub command: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+/// Read a single DAP request.
+///
+/// Returns `None` if the reader reaches EOF before the start of a message. Messages which are not
+/// requests are skipped.
+pub fn read_request(reader: &mut impl BufRead) -> Result<Option<Request>, ProtocolError> {
+    loop {
+        let mut content_length = None;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            let header = line.trim_end_matches(['\r', '\n']);
+            if header.is_empty() {
+                break;
+            }
+
+            let (name, value) = header
+                .split_once(':')
+                .ok_or_else(|| ProtocolError::BadHeader(header.to_owned()))?;
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| ProtocolError::BadHeader(header.to_owned()))?,
+                );
+            }
+        }
+
+        let mut content = vec![0; content_length.ok_or(ProtocolError::MissingContentLength)?];
+        reader.read_exact(&mut content)?;
+
+        let message: serde_json::Value = serde_json::from_slice(&content)?;
+        if message.get("type").and_then(|t| t.as_str()) == Some("request") {
+            return Ok(Some(serde_json::from_value(message)?));
+        }
+    }
+}
+
+/// The sending half of a DAP connection, shared between the server thread and the VM thread.
+pub struct Connection {
+    writer: Mutex<Box<dyn Write + Send>>,
+    seq: AtomicI64,
+}
+
+impl Connection {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            seq: AtomicI64::new(1),
+        }
+    }
+
+    pub fn respond(&self, request: &Request, body: serde_json::Value) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    pub fn respond_error(&self, request: &Request, message: impl Into<String>) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request.seq,
+            "command": request.command,
+            "success": false,
+            "message": message.into(),
+        }));
+    }
+
+    pub fn event(&self, event: &str, body: serde_json::Value) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+
+    fn send(&self, mut message: serde_json::Value) {
+        message["seq"] = self.seq.fetch_add(1, atomic::Ordering::Relaxed).into();
+        let content = message.to_string();
+
+        let mut writer = self.writer.lock().unwrap();
+        let res = write!(
+            writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .and_then(|_| writer.flush());
+        if let Err(err) = res {
+            log::error!("error writing debug adapter message: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_request() {
+        let input = concat!(
+            "Content-Length: 38\r\n\r\n",
+            r#"{"seq":1,"type":"event","event":"x"}  "#,
+            "Content-Length: 52\r\n\r\n",
+            r#"{"seq":2,"type":"request","command":"threads"}      "#,
+        );
+
+        let mut reader = input.as_bytes();
+        let request = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(request.seq, 2);
+        assert_eq!(request.command, "threads");
+        assert!(request.arguments.is_null());
+        assert!(read_request(&mut reader).unwrap().is_none());
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    io::{self, BufReader},
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{self, AtomicBool, AtomicU64},
+        mpsc,
+    },
+    thread,
+};
+
+use serde_json::json;
+
+use crate::{
+    hook::DebugHook,
+    protocol::{Connection, Request, read_request},
+};
+
+/// How a [`DebugServer`] communicates with a debug adapter client.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Transport {
+    /// Use the process's stdin and stdout.
+    ///
+    /// Nothing else in the process may write to stdout while the server is running.
+    Stdio,
+    /// Listen on the given address and accept a single connection.
+    Tcp(SocketAddr),
+}
+
+impl FromStr for Transport {
+    type Err = std::net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("stdio") {
+            Ok(Transport::Stdio)
+        } else {
+            Ok(Transport::Tcp(s.parse()?))
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Stdio => write!(f, "stdio"),
+            Transport::Tcp(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+/// The single thread ID reported to debug adapter clients.
+pub(crate) const THREAD_ID: i64 = 1;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Resume {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+}
+
+pub(crate) enum Command {
+    // A request that can only be answered while the VM is paused.
+    Inspect(Request),
+    Resume(Request, Resume),
+    Disconnect,
+}
+
+/// State shared between the server thread and every [`DebugHook`].
+pub(crate) struct Shared {
+    pub(crate) connection: Connection,
+    // Line breakpoints (1-indexed) for each source path.
+    pub(crate) breakpoints: Mutex<HashMap<PathBuf, BTreeSet<usize>>>,
+    // Incremented whenever `breakpoints` changes, so hooks know when to refresh their copy.
+    pub(crate) breakpoints_version: AtomicU64,
+    pub(crate) pause_requested: AtomicBool,
+    pub(crate) paused: AtomicBool,
+    pub(crate) disconnected: AtomicBool,
+    pub(crate) commands: Mutex<mpsc::Receiver<Command>>,
+    configured: Mutex<bool>,
+    configured_cond: Condvar,
+}
+
+/// A Debug Adapter Protocol server.
+///
+/// The server handles protocol messages on a background thread. Execution of scripts is
+/// controlled by installing the hook returned by [`DebugServer::hook`] on every `vm::Thread` that
+/// should be debugged. While paused, all inspection of the VM happens inside of the hook, which
+/// blocks the VM until it is told to resume.
+///
+/// Only a single VM thread is ever reported to the client, so the hook should only be installed on
+/// threads that do not run concurrently with each other.
+pub struct DebugServer {
+    shared: Arc<Shared>,
+}
+
+impl DebugServer {
+    /// Start a debug adapter server using the given transport.
+    ///
+    /// When using [`Transport::Tcp`], this blocks until a client connects.
+    pub fn start(transport: &Transport) -> io::Result<Self> {
+        match transport {
+            Transport::Stdio => Ok(Self::with_io(io::stdin(), io::stdout())),
+            &Transport::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)?;
+                log::info!("debug adapter listening on {}", listener.local_addr()?);
+                let (stream, peer) = listener.accept()?;
+                log::info!("debug adapter client connected from {peer}");
+                Ok(Self::with_io(stream.try_clone()?, stream))
+            }
+        }
+    }
+
+    /// Start a debug adapter server which reads requests from `reader` and writes responses and
+    /// events to `writer`.
+    pub fn with_io(
+        reader: impl io::Read + Send + 'static,
+        writer: impl io::Write + Send + 'static,
+    ) -> Self {
+        let (command_sender, command_receiver) = mpsc::channel();
+
+        let shared = Arc::new(Shared {
+            connection: Connection::new(writer),
+            breakpoints: Default::default(),
+            breakpoints_version: AtomicU64::new(0),
+            pause_requested: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            disconnected: AtomicBool::new(false),
+            commands: Mutex::new(command_receiver),
+            configured: Mutex::new(false),
+            configured_cond: Condvar::new(),
+        });
+
+        thread::Builder::new()
+            .name("debug adapter".to_owned())
+            .spawn({
+                let shared = shared.clone();
+                move || serve(&shared, BufReader::new(reader), command_sender)
+            })
+            .expect("could not spawn debug adapter thread");
+
+        Self { shared }
+    }
+
+    /// Block until the client has finished sending its initial configuration (breakpoints and so
+    /// on), or until the client disconnects.
+    ///
+    /// This should be called before running any scripts so that early breakpoints are not missed.
+    pub fn wait_for_configuration(&self) {
+        let mut configured = self.shared.configured.lock().unwrap();
+        while !*configured {
+            configured = self.shared.configured_cond.wait(configured).unwrap();
+        }
+    }
+
+    /// Create a new hook to install on a `vm::Thread`.
+    pub fn hook(&self) -> DebugHook {
+        DebugHook::new(self.shared.clone())
+    }
+
+    /// Send text to be displayed in the client's debug console.
+    pub fn output(&self, text: &str) {
+        self.shared.connection.event(
+            "output",
+            json!({
+                "category": "console",
+                "output": text,
+            }),
+        );
+    }
+
+    /// Tell the client that the debugged program has finished.
+    pub fn exited(&self, exit_code: i32) {
+        let connection = &self.shared.connection;
+        connection.event("exited", json!({ "exitCode": exit_code }));
+        connection.event("terminated", json!({}));
+    }
+}
+
+fn serve(shared: &Shared, mut reader: impl io::BufRead, commands: mpsc::Sender<Command>) {
+    let connection = &shared.connection;
+
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(err) => {
+                log::error!("error reading debug adapter request: {err}");
+                break;
+            }
+        };
+
+        let is_paused = shared.paused.load(atomic::Ordering::SeqCst);
+
+        match request.command.as_str() {
+            "initialize" => {
+                connection.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsEvaluateForHovers": true,
+                    }),
+                );
+                connection.event("initialized", json!({}));
+            }
+            "launch" | "attach" => {
+                // The program is started by the process hosting the server, so there is nothing to
+                // launch or attach to.
+                connection.respond(&request, json!({}));
+            }
+            "setBreakpoints" => {
+                let args = &request.arguments;
+                let Some(path) = args["source"]["path"].as_str() else {
+                    connection.respond_error(&request, "breakpoint source has no path");
+                    continue;
+                };
+
+                let lines = args["breakpoints"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|b| b["line"].as_u64())
+                    .map(|l| l as usize)
+                    .collect::<BTreeSet<_>>();
+
+                let body = json!({
+                    "breakpoints": lines
+                        .iter()
+                        .map(|&line| json!({ "verified": true, "line": line }))
+                        .collect::<Vec<_>>(),
+                });
+
+                shared
+                    .breakpoints
+                    .lock()
+                    .unwrap()
+                    .insert(PathBuf::from(path), lines);
+                shared
+                    .breakpoints_version
+                    .fetch_add(1, atomic::Ordering::SeqCst);
+
+                connection.respond(&request, body);
+            }
+            "setExceptionBreakpoints" => {
+                connection.respond(&request, json!({}));
+            }
+            "configurationDone" => {
+                connection.respond(&request, json!({}));
+                set_configured(shared);
+            }
+            "threads" => {
+                connection.respond(
+                    &request,
+                    json!({
+                        "threads": [{ "id": THREAD_ID, "name": "main" }],
+                    }),
+                );
+            }
+            "pause" => {
+                shared.pause_requested.store(true, atomic::Ordering::SeqCst);
+                connection.respond(&request, json!({}));
+            }
+            command @ ("continue" | "next" | "stepIn" | "stepOut") => {
+                if is_paused {
+                    let resume = match command {
+                        "continue" => Resume::Continue,
+                        "next" => Resume::StepOver,
+                        "stepIn" => Resume::StepIn,
+                        _ => Resume::StepOut,
+                    };
+                    let _ = commands.send(Command::Resume(request, resume));
+                } else {
+                    connection.respond(&request, json!({ "allThreadsContinued": true }));
+                }
+            }
+            "stackTrace" | "scopes" | "variables" | "evaluate" => {
+                if is_paused {
+                    let _ = commands.send(Command::Inspect(request));
+                } else {
+                    connection.respond_error(&request, "not paused");
+                }
+            }
+            "disconnect" | "terminate" => {
+                connection.respond(&request, json!({}));
+                break;
+            }
+            _ => {
+                connection.respond_error(
+                    &request,
+                    format!("unsupported request {:?}", request.command),
+                );
+            }
+        }
+    }
+
+    // Once the client is gone, let the program run freely.
+    shared.disconnected.store(true, atomic::Ordering::SeqCst);
+    shared.breakpoints.lock().unwrap().clear();
+    shared
+        .breakpoints_version
+        .fetch_add(1, atomic::Ordering::SeqCst);
+    let _ = commands.send(Command::Disconnect);
+    set_configured(shared);
+}
+
+fn set_configured(shared: &Shared) {
+    *shared.configured.lock().unwrap() = true;
+    shared.configured_cond.notify_all();
+}
@@ -0,0 +1,81 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use cpal::traits::{DeviceTrait as _, HostTrait as _, StreamTrait as _};
+use fabricator as fab;
+
+/// The most audio that will be buffered before the oldest samples are dropped, in seconds.
+///
+/// The game mixes audio as it ticks rather than when the output device asks for it, so if the two
+/// drift apart this keeps the latency bounded.
+const MAX_BUFFERED: f64 = 0.2;
+
+/// Plays audio mixed by a [`fab::Game`] on the default output device.
+pub struct AudioOutput {
+    buffer: Arc<Mutex<VecDeque<f32>>>,
+    _stream: cpal::Stream,
+}
+
+impl AudioOutput {
+    /// Open the default output device as a stereo stream at [`fab::SAMPLE_RATE`].
+    ///
+    /// Returns `None` (and logs why) if there is no usable output device.
+    pub fn new() -> Option<Self> {
+        let Some(device) = cpal::default_host().default_output_device() else {
+            log::warn!("no audio output device, audio is disabled");
+            return None;
+        };
+
+        let config = cpal::StreamConfig {
+            channels: 2,
+            sample_rate: cpal::SampleRate(fab::SAMPLE_RATE),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let buffer = Arc::new(Mutex::new(VecDeque::<f32>::new()));
+        let stream = device.build_output_stream(
+            &config,
+            {
+                let buffer = buffer.clone();
+                move |data: &mut [f32], _| {
+                    let mut buffer = buffer.lock().unwrap();
+                    for sample in data {
+                        *sample = buffer.pop_front().unwrap_or(0.0);
+                    }
+                }
+            },
+            |err| log::error!("audio output error: {err}"),
+            None,
+        );
+
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("could not open audio output stream, audio is disabled: {err}");
+                return None;
+            }
+        };
+        if let Err(err) = stream.play() {
+            log::warn!("could not start audio output stream, audio is disabled: {err}");
+            return None;
+        }
+
+        Some(Self {
+            buffer,
+            _stream: stream,
+        })
+    }
+
+    /// Queue interleaved stereo samples to be played.
+    pub fn push(&self, samples: &[f32]) {
+        let max_len = (MAX_BUFFERED * fab::SAMPLE_RATE as f64) as usize * 2;
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.extend(samples);
+        if buffer.len() > max_len {
+            let excess = buffer.len() - max_len;
+            buffer.drain(..excess);
+        }
+    }
+}
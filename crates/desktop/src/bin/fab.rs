@@ -1,5 +1,5 @@
 use std::{
-    mem,
+    iter, mem,
     ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
@@ -8,7 +8,9 @@ use std::{
 
 use clap::Parser;
 use fabricator as fab;
+use fabricator_debugger::{DebugServer, Transport};
 use fabricator_desktop::{
+    audio::AudioOutput,
     geometry::{Geometry, GpuGeometry},
     pipeline,
 };
@@ -19,6 +21,7 @@ use winit::{
     application::ApplicationHandler,
     event::{MouseButton, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop, OwnedDisplayHandle},
+    keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowId},
 };
 
@@ -29,6 +32,119 @@ struct TextureEntry {
     texture_coords: Box2<f32>,
 }
 
+// Surfaces are stored with premultiplied alpha, since that is what blending produces.
+struct SurfaceEntry {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    size: Vec2<u32>,
+    // Set when a pass changes the contents, which must then be read back and reported to the game.
+    changed: bool,
+}
+
+// Part of a surface copied into a buffer, which can be read once the commands which copy it have
+// been submitted and the buffer has been mapped.
+struct SurfaceReadback {
+    buffer: wgpu::Buffer,
+    size: Vec2<u32>,
+    bytes_per_row: u32,
+}
+
+impl SurfaceReadback {
+    fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &SurfaceEntry,
+        region: Box2<u32>,
+    ) -> Option<Self> {
+        let region = Box2::new(
+            region.min.zip(surface.size, u32::min),
+            region.max.zip(surface.size, u32::min),
+        );
+        let size = region.size();
+        if size[0] == 0 || size[1] == 0 {
+            return None;
+        }
+
+        let bytes_per_row = (size[0] * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("surface readback"),
+            size: u64::from(bytes_per_row) * u64::from(size[1]),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &surface.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.min[0],
+                    y: region.min[1],
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Some(Self {
+            buffer,
+            size,
+            bytes_per_row,
+        })
+    }
+
+    fn map(&self) {
+        self.buffer.slice(..).map_async(wgpu::MapMode::Read, |_| ());
+    }
+
+    // Convert the mapped buffer into straight (not premultiplied) sRGB, like every other image.
+    fn read(&self) -> image::RgbaImage {
+        let data = self.buffer.slice(..).get_mapped_range();
+        let image = image::RgbaImage::from_fn(self.size[0], self.size[1], |x, y| {
+            let offset = (y * self.bytes_per_row + x * 4) as usize;
+            let texel = &data[offset..offset + 4];
+            let alpha = texel[3] as f64 / 255.0;
+            if alpha == 0.0 {
+                return image::Rgba([0; 4]);
+            }
+            let straight = |c: u8| {
+                let linear = (srgb_to_linear(c as f64 / 255.0) / alpha).min(1.0);
+                (linear_to_srgb(linear) * 255.0).round() as u8
+            };
+            image::Rgba([
+                straight(texel[0]),
+                straight(texel[1]),
+                straight(texel[2]),
+                texel[3],
+            ])
+        });
+        drop(data);
+        self.buffer.unmap();
+        image
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum BatchTexture {
+    // A single white texel, used to draw untextured triangles.
+    White,
+    Page(fab::TexturePageId),
+    Surface(fab::SurfaceId),
+}
+
 struct AppState {
     window: Arc<Window>,
     device: wgpu::Device,
@@ -40,16 +156,25 @@ struct AppState {
     input: fab::InputState,
     game: fab::Game,
     render: fab::Render,
+    audio: Option<AudioOutput>,
 
     pipeline: pipeline::Pipeline,
     texture_page_bind_groups: SecondaryMap<fab::TexturePageId, wgpu::BindGroup>,
+    white_bind_group: wgpu::BindGroup,
+    surface_sampler: wgpu::Sampler,
     parameters_buffer: wgpu::Buffer,
     parameters_bind_group: wgpu::BindGroup,
     textures: SecondaryMap<fab::TextureId, TextureEntry>,
+    surfaces: SecondaryMap<fab::SurfaceId, SurfaceEntry>,
+    // Surfaces copied by `SurfacePass::Save`, which are written once the frame is submitted.
+    surface_saves: Vec<(SurfaceReadback, PathBuf)>,
     geometry: Geometry<pipeline::Vertex, u32>,
-    batches: Vec<(Range<u32>, fab::TexturePageId)>,
+    batches: Vec<(Range<u32>, BatchTexture)>,
+    // The screen area of each view, and the range of batches drawn for it.
+    passes: Vec<(Box2<u32>, Range<usize>)>,
     last_render: Instant,
     frames_behind: f64,
+    record: Option<PathBuf>,
 }
 
 impl AppState {
@@ -58,6 +183,9 @@ impl AppState {
         window: Arc<Window>,
         project_file: &Path,
         config: &str,
+        code_cache: Option<&Path>,
+        debug_server: Option<&DebugServer>,
+        record: Option<&Path>,
     ) -> AppState {
         let instance = wgpu::Instance::new(
             wgpu::InstanceDescriptor::new_with_display_handle_from_env(Box::new(display_handle)),
@@ -78,7 +206,17 @@ impl AppState {
         let surface_format = cap.formats[0];
 
         let project = fab::Project::load(project_file).unwrap();
-        let game = fab::Game::new(project, config).unwrap();
+        let code_cache = code_cache.map(fab::CodeCache::new);
+        let mut options = fab::GameOptions {
+            code_cache: code_cache.as_ref(),
+            record: record.is_some(),
+            ..Default::default()
+        };
+        if let Some(server) = debug_server {
+            let hook = server.hook();
+            options.set_init_thread(move |ctx, thread| thread.set_hook(&ctx, hook));
+        }
+        let game = fab::Game::with_options(project, config, options).unwrap();
 
         let pipeline = pipeline::Pipeline::new(&device, surface_format.add_srgb_suffix());
 
@@ -95,6 +233,48 @@ impl AppState {
         let parameters_bind_group = pipeline
             .create_parameters_bind_group(&device, parameters_buffer.as_entire_buffer_binding());
 
+        // Vertices are transformed into clip space before being uploaded, since every render target
+        // has a different size.
+        queue.write_buffer(
+            &parameters_buffer,
+            0,
+            bytemuck::bytes_of(&pipeline::ParametersUniform::new(Affine2::new())),
+        );
+
+        let white_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("white"),
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            white_texture.as_image_copy(),
+            &[0xff; 4],
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        let white_bind_group = pipeline.create_texture_bind_group(
+            &device,
+            &white_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            &device.create_sampler(&wgpu::SamplerDescriptor::default()),
+        );
+
         let mut textures = SecondaryMap::<fab::TextureId, TextureEntry>::new();
 
         log::info!("loading textures...");
@@ -224,17 +404,29 @@ impl AppState {
             surface,
             surface_format,
             render: Default::default(),
+            audio: AudioOutput::new(),
             input: Default::default(),
             game,
             pipeline,
             texture_page_bind_groups,
+            white_bind_group,
+            surface_sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                label: None,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }),
             parameters_buffer,
             parameters_bind_group,
             textures,
+            surfaces: Default::default(),
+            surface_saves: Vec::new(),
             geometry: Default::default(),
             batches: Default::default(),
+            passes: Default::default(),
             last_render: Instant::now(),
             frames_behind: 1.0,
+            record: record.map(Path::to_owned),
         };
 
         state.configure_surface();
@@ -242,10 +434,35 @@ impl AppState {
         state
     }
 
+    fn save_recording(&self) {
+        if let Some(path) = &self.record
+            && let Some(recording) = self.game.recording()
+        {
+            match recording.save(path) {
+                Ok(()) => log::info!("saved recording to {path:?}"),
+                Err(err) => log::error!("could not save recording to {path:?}: {err}"),
+            }
+        }
+    }
+
     fn get_window(&self) -> &Window {
         &self.window
     }
 
+    fn logical_size(&self) -> Vec2<f32> {
+        let logical_size = self.size.to_logical(self.window.scale_factor());
+        Vec2::new(logical_size.width, logical_size.height)
+    }
+
+    // The game screen is stretched to fill the whole window.
+    fn screen_size(&self) -> Vec2<f32> {
+        if self.render.screen_size[0] == 0 || self.render.screen_size[1] == 0 {
+            self.logical_size()
+        } else {
+            self.render.screen_size.cast()
+        }
+    }
+
     fn configure_surface(&self) {
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -274,11 +491,23 @@ impl AppState {
         self.frames_behind += since_last_render.as_secs_f64() * self.game.tick_rate();
         self.frames_behind = self.frames_behind.min(MAX_FRAMES_BEHIND as f64);
 
+        let window_size = self.size.to_logical(self.window.scale_factor());
+        self.input.window_size = Vec2::new(window_size.width, window_size.height);
+
         while self.frames_behind >= 1.0 {
-            self.game.tick(&self.input, &mut self.render).unwrap();
+            if let Err(err) = self.game.tick(&mut self.input, &mut self.render) {
+                self.save_recording();
+                panic!("{err:?}");
+            }
+            self.input.end_tick();
             self.frames_behind -= 1.0;
         }
 
+        let audio = self.game.take_audio();
+        if let Some(output) = &self.audio {
+            output.push(&audio);
+        }
+
         let surface_texture = match self.surface.get_current_texture() {
             wgpu::CurrentSurfaceTexture::Success(surface_texture) => surface_texture,
             wgpu::CurrentSurfaceTexture::Suboptimal(_) | wgpu::CurrentSurfaceTexture::Outdated => {
@@ -293,6 +522,28 @@ impl AppState {
             }
         };
 
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+
+        // Surface passes must be executed before the screen is drawn, which may draw surfaces.
+        for pass in self.render.take_surface_passes() {
+            self.surface_pass(&mut encoder, pass);
+        }
+
+        // Read back every surface which has changed, so that scripts can read their contents.
+        let mut surface_readbacks = Vec::new();
+        for (surface_id, surface) in self.surfaces.iter_mut() {
+            if mem::take(&mut surface.changed)
+                && let Some(readback) = SurfaceReadback::new(
+                    &self.device,
+                    &mut encoder,
+                    surface,
+                    Box2::with_size(Vec2::zero(), surface.size),
+                )
+            {
+                surface_readbacks.push((surface_id, readback));
+            }
+        }
+
         let texture_view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor {
@@ -300,42 +551,36 @@ impl AppState {
                 ..Default::default()
             });
 
-        let logical_size = self.size.to_logical(self.window.scale_factor());
-        let ndc_transform = Affine2::new()
-            .scale(Vec2::splat(2.0) / Vec2::new(logical_size.width, logical_size.height))
-            .translate(Vec2::splat(-1.0))
-            .scale(Vec2::new(1.0, -1.0));
-
-        self.queue.write_buffer(
-            &self.parameters_buffer,
-            0,
-            bytemuck::bytes_of(&pipeline::ParametersUniform::new(ndc_transform)),
-        );
+        let screen_size = self.screen_size();
+        let ndc_transform = ndc_transform(screen_size);
 
         self.geometry.clear();
         self.batches.clear();
+        self.passes.clear();
 
-        for quad in &self.render.quads {
-            let index_start = self.geometry.indices.len();
-
-            let texture = &self.textures[quad.texture];
-            let vertexes = [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]].map(|[x, y]| {
-                pipeline::Vertex::new(
-                    quad.transform.transform_point(
-                        Box2::with_size(texture.crop_offset, texture.crop_size).eval([x, y]),
-                    ),
-                    texture.texture_coords.eval([x, y]),
-                )
-            });
-            self.geometry.draw_quad(vertexes);
-
-            self.batches.push((
-                cast::cast(index_start)..cast::cast(self.geometry.indices.len()),
-                texture.page_id,
-            ));
+        // GUI commands are drawn last, on top of every view.
+        let commands = self.render.sorted_commands();
+        let gui_commands = self.render.sorted_gui_commands();
+        let passes = self
+            .render
+            .passes()
+            .into_iter()
+            .map(|view| (view, &commands))
+            .chain(iter::once((self.render.gui_pass(), &gui_commands)));
+        for (view, commands) in passes {
+            let batch_start = self.batches.len();
+            push_commands(
+                &mut self.geometry,
+                &mut self.batches,
+                &self.textures,
+                &self.surfaces,
+                view.transform.then(ndc_transform),
+                commands,
+            );
+            self.passes
+                .push((view.port, batch_start..self.batches.len()));
         }
 
-        let mut encoder = self.device.create_command_encoder(&Default::default());
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -369,14 +614,27 @@ impl AppState {
                 gpu_geometry.index_format,
             );
 
-            for (range, page_id) in self.batches.iter().cloned() {
-                rpass.set_bind_group(
-                    pipeline::Pipeline::TEXTURE_BIND_GROUP,
-                    &self.texture_page_bind_groups[page_id],
-                    &[],
+            let physical_size = Vec2::new(self.size.width, self.size.height);
+            let to_physical = physical_size.cast::<f32>() / screen_size;
+            for (port, batches) in self.passes.iter().cloned() {
+                let to_pixel = |p: Vec2<u32>| {
+                    (p.cast::<f32>() * to_physical)
+                        .map(|c| c.round().max(0.0) as u32)
+                        .zip(physical_size, u32::min)
+                };
+                let scissor = Box2::new(to_pixel(port.min), to_pixel(port.max));
+                let scissor_size = scissor.max.zip(scissor.min, u32::saturating_sub);
+                if scissor_size[0] == 0 || scissor_size[1] == 0 {
+                    continue;
+                }
+                rpass.set_scissor_rect(
+                    scissor.min[0],
+                    scissor.min[1],
+                    scissor_size[0],
+                    scissor_size[1],
                 );
 
-                rpass.draw_indexed(range, 0, 0..1);
+                self.draw_batches(&mut rpass, batches);
             }
         }
 
@@ -385,12 +643,377 @@ impl AppState {
         self.queue.submit([encoder.finish()]);
         self.window.pre_present_notify();
         surface_texture.present();
+
+        self.finish_readbacks(surface_readbacks);
+    }
+
+    // Wait for every readback submitted this frame, then report surface contents to the game and
+    // write saved surfaces.
+    fn finish_readbacks(&mut self, surface_readbacks: Vec<(fab::SurfaceId, SurfaceReadback)>) {
+        let surface_saves = mem::take(&mut self.surface_saves);
+        if surface_readbacks.is_empty() && surface_saves.is_empty() {
+            return;
+        }
+
+        for (_, readback) in &surface_readbacks {
+            readback.map();
+        }
+        for (readback, _) in &surface_saves {
+            readback.map();
+        }
+        if let Err(err) = self.device.poll(wgpu::PollType::wait_indefinitely()) {
+            log::error!("could not read back surfaces: {err}");
+            return;
+        }
+
+        for (surface_id, readback) in surface_readbacks {
+            self.game.set_surface_contents(surface_id, readback.read());
+        }
+        for (readback, path) in surface_saves {
+            if let Err(err) = readback.read().save(&path) {
+                log::error!("could not save surface to {path:?}: {err}");
+            }
+        }
+    }
+
+    fn surface_pass(&mut self, encoder: &mut wgpu::CommandEncoder, pass: fab::SurfacePass) {
+        match pass {
+            fab::SurfacePass::Allocate { surface, size } => {
+                // New textures are zero initialized, which is transparent black.
+                let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("surface"),
+                    size: wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: pipeline::Pipeline::SURFACE_FORMAT,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::COPY_SRC
+                        | wgpu::TextureUsages::COPY_DST,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let bind_group = self.pipeline.create_texture_bind_group(
+                    &self.device,
+                    &view,
+                    &self.surface_sampler,
+                );
+                self.surfaces.insert(
+                    surface,
+                    SurfaceEntry {
+                        texture,
+                        view,
+                        bind_group,
+                        size,
+                        changed: true,
+                    },
+                );
+            }
+            fab::SurfacePass::Free { surface } => {
+                self.surfaces.remove(surface);
+            }
+            fab::SurfacePass::Draw { surface, commands } => {
+                let Some(target) = self.surfaces.get(surface) else {
+                    return;
+                };
+
+                // A texture cannot be sampled while it is being rendered to, so drawing a surface
+                // into itself draws nothing.
+                let commands = commands
+                    .iter()
+                    .filter(|command| {
+                        !matches!(command, fab::DrawCommand::Surface(quad) if quad.surface == surface)
+                    })
+                    .collect::<Vec<_>>();
+
+                self.geometry.clear();
+                self.batches.clear();
+                push_commands(
+                    &mut self.geometry,
+                    &mut self.batches,
+                    &self.textures,
+                    &self.surfaces,
+                    ndc_transform(target.size.cast()),
+                    &commands,
+                );
+                if self.batches.is_empty() {
+                    return;
+                }
+                let gpu_geometry = GpuGeometry::create(&self.device, &self.geometry);
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target.view,
+                        depth_slice: None,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                    multiview_mask: None,
+                });
+                rpass.set_pipeline(&self.pipeline.surface_pipeline);
+                rpass.set_bind_group(
+                    pipeline::Pipeline::PARAMETERS_BIND_GROUP,
+                    &self.parameters_bind_group,
+                    &[],
+                );
+                rpass.set_vertex_buffer(0, gpu_geometry.vertex_buffer.slice(..));
+                rpass.set_index_buffer(
+                    gpu_geometry.index_buffer.slice(..),
+                    gpu_geometry.index_format,
+                );
+                self.draw_batches(&mut rpass, 0..self.batches.len());
+                drop(rpass);
+
+                if let Some(target) = self.surfaces.get_mut(surface) {
+                    target.changed = true;
+                }
+            }
+            fab::SurfacePass::Clear { surface, color } => {
+                let Some(target) = self.surfaces.get_mut(surface) else {
+                    return;
+                };
+                target.changed = true;
+
+                // Clear colors are given in linear space, and surfaces are premultiplied.
+                let alpha = color[3] as f64;
+                let linear = |c: f32| srgb_to_linear(c as f64) * alpha;
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target.view,
+                        depth_slice: None,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color {
+                                r: linear(color[0]),
+                                g: linear(color[1]),
+                                b: linear(color[2]),
+                                a: alpha,
+                            }),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                    multiview_mask: None,
+                });
+            }
+            fab::SurfacePass::Copy {
+                surface,
+                position,
+                source,
+                region,
+            } => {
+                if surface == source {
+                    log::warn!("copying a surface into itself is unsupported");
+                    return;
+                }
+                let (Some(target), Some(source)) =
+                    (self.surfaces.get(surface), self.surfaces.get(source))
+                else {
+                    return;
+                };
+
+                let region = Box2::new(
+                    region.min.zip(source.size, u32::min),
+                    region.max.zip(source.size, u32::min),
+                );
+                let size = region
+                    .size()
+                    .zip(target.size.zip(position, u32::saturating_sub), u32::min);
+                if size[0] == 0 || size[1] == 0 {
+                    return;
+                }
+
+                encoder.copy_texture_to_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &source.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d {
+                            x: region.min[0],
+                            y: region.min[1],
+                            z: 0,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::TexelCopyTextureInfo {
+                        texture: &target.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d {
+                            x: position[0],
+                            y: position[1],
+                            z: 0,
+                        },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                );
+
+                if let Some(target) = self.surfaces.get_mut(surface) {
+                    target.changed = true;
+                }
+            }
+            fab::SurfacePass::Save {
+                surface,
+                region,
+                path,
+            } => {
+                if let Some(target) = self.surfaces.get(surface)
+                    && let Some(readback) =
+                        SurfaceReadback::new(&self.device, encoder, target, region)
+                {
+                    self.surface_saves.push((readback, path));
+                }
+            }
+        }
+    }
+
+    fn draw_batches(&self, rpass: &mut wgpu::RenderPass<'_>, batches: Range<usize>) {
+        for (range, texture) in self.batches[batches].iter().cloned() {
+            let bind_group = match texture {
+                BatchTexture::White => &self.white_bind_group,
+                BatchTexture::Page(page_id) => &self.texture_page_bind_groups[page_id],
+                BatchTexture::Surface(surface_id) => &self.surfaces[surface_id].bind_group,
+            };
+            rpass.set_bind_group(pipeline::Pipeline::TEXTURE_BIND_GROUP, bind_group, &[]);
+
+            rpass.draw_indexed(range, 0, 0..1);
+        }
+    }
+}
+
+// Transforms pixel coordinates of a render target with the given size into clip space.
+fn ndc_transform(size: Vec2<f32>) -> Affine2<f32> {
+    Affine2::new()
+        .scale(Vec2::splat(2.0) / size)
+        .translate(Vec2::splat(-1.0))
+        .scale(Vec2::new(1.0, -1.0))
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Add geometry and batches for a list of draw commands, `transform` maps the coordinates of the
+/// commands into clip space.
+///
+/// Commands which refer to missing surfaces are skipped.
+fn push_commands(
+    geometry: &mut Geometry<pipeline::Vertex, u32>,
+    batches: &mut Vec<(Range<u32>, BatchTexture)>,
+    textures: &SecondaryMap<fab::TextureId, TextureEntry>,
+    surfaces: &SecondaryMap<fab::SurfaceId, SurfaceEntry>,
+    transform: Affine2<f32>,
+    commands: &[&fab::DrawCommand],
+) {
+    for &command in commands {
+        let index_start = geometry.indices.len();
+
+        let texture = match command {
+            fab::DrawCommand::Quad(quad) => {
+                let texture = &textures[quad.texture];
+                let drawn = Box2::with_size(texture.crop_offset, texture.crop_size)
+                    .fintersection(quad.region);
+                if !(drawn.min[0] < drawn.max[0] && drawn.min[1] < drawn.max[1]) {
+                    continue;
+                }
+
+                let transform = quad.transform.then(transform);
+                let color = (quad.tint * quad.alpha).extend(quad.alpha);
+                let vertexes = [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]].map(|p| {
+                    let p = drawn.eval(p);
+                    pipeline::Vertex::new(
+                        transform.transform_point(p),
+                        texture
+                            .texture_coords
+                            .eval(((p - texture.crop_offset) / texture.crop_size).into_array()),
+                        color,
+                    )
+                });
+                geometry.draw_quad(vertexes);
+
+                BatchTexture::Page(texture.page_id)
+            }
+            fab::DrawCommand::Surface(quad) => {
+                let Some(surface) = surfaces.get(quad.surface) else {
+                    continue;
+                };
+                let size = surface.size.cast::<f32>();
+                let drawn = Box2::with_size(Vec2::zero(), size).fintersection(quad.region);
+                if !(drawn.min[0] < drawn.max[0] && drawn.min[1] < drawn.max[1]) {
+                    continue;
+                }
+
+                let transform = quad.transform.then(transform);
+                let color = (quad.tint * quad.alpha).extend(quad.alpha);
+                let vertexes = [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]].map(|p| {
+                    let p = drawn.eval(p);
+                    pipeline::Vertex::new(transform.transform_point(p), p / size, color)
+                });
+                geometry.draw_quad(vertexes);
+
+                BatchTexture::Surface(quad.surface)
+            }
+            fab::DrawCommand::Triangles(triangles) => {
+                let vertex_count = triangles.vertices.len() as u32;
+                geometry.draw_triangle_list(
+                    triangles.vertices.iter().map(|v| {
+                        let alpha = v.color[3];
+                        pipeline::Vertex::new(
+                            transform.transform_point(v.position),
+                            Vec2::zero(),
+                            (v.color.truncate() * alpha).extend(alpha),
+                        )
+                    }),
+                    0..vertex_count,
+                );
+
+                BatchTexture::White
+            }
+        };
+
+        batches.push((
+            cast::cast(index_start)..cast::cast(geometry.indices.len()),
+            texture,
+        ));
     }
 }
 
 struct App {
     project_file: PathBuf,
     config: String,
+    code_cache: Option<PathBuf>,
+    debug_server: Option<DebugServer>,
+    record: Option<PathBuf>,
     app_state: Option<AppState>,
 }
 
@@ -410,6 +1033,9 @@ impl ApplicationHandler for App {
             window.clone(),
             &self.project_file,
             &self.config,
+            self.code_cache.as_deref(),
+            self.debug_server.as_ref(),
+            self.record.as_deref(),
         ));
         self.app_state = Some(state);
 
@@ -420,6 +1046,7 @@ impl ApplicationHandler for App {
         let app_state = self.app_state.as_mut().unwrap();
         match event {
             WindowEvent::CloseRequested => {
+                app_state.save_recording();
                 event_loop.exit();
             }
             WindowEvent::RedrawRequested => {
@@ -452,18 +1079,150 @@ impl ApplicationHandler for App {
             },
             WindowEvent::CursorMoved { position, .. } => {
                 let logical = position.to_logical(app_state.window.scale_factor());
-                app_state.input.mouse_position = Vec2::new(logical.x, logical.y);
+                app_state.input.mouse_position = Vec2::new(logical.x, logical.y)
+                    * app_state.screen_size()
+                    / app_state.logical_size();
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let keyboard = &mut app_state.input.keyboard;
+                if let PhysicalKey::Code(code) = event.physical_key
+                    && let Some(key) = virtual_key(code)
+                {
+                    if event.state.is_pressed() {
+                        keyboard.key_down(key);
+                    } else {
+                        keyboard.key_up(key);
+                    }
+                }
+
+                if event.state.is_pressed()
+                    && let Some(text) = &event.text
+                {
+                    for c in text.chars() {
+                        keyboard.input_char(c);
+                    }
+                }
+            }
+            WindowEvent::Focused(false) => {
+                app_state.input.keyboard.release_all();
             }
             _ => (),
         }
     }
 }
 
+fn virtual_key(code: KeyCode) -> Option<fab::VirtualKey> {
+    use fab::VirtualKey as VK;
+
+    Some(match code {
+        KeyCode::KeyA => VK(b'A'),
+        KeyCode::KeyB => VK(b'B'),
+        KeyCode::KeyC => VK(b'C'),
+        KeyCode::KeyD => VK(b'D'),
+        KeyCode::KeyE => VK(b'E'),
+        KeyCode::KeyF => VK(b'F'),
+        KeyCode::KeyG => VK(b'G'),
+        KeyCode::KeyH => VK(b'H'),
+        KeyCode::KeyI => VK(b'I'),
+        KeyCode::KeyJ => VK(b'J'),
+        KeyCode::KeyK => VK(b'K'),
+        KeyCode::KeyL => VK(b'L'),
+        KeyCode::KeyM => VK(b'M'),
+        KeyCode::KeyN => VK(b'N'),
+        KeyCode::KeyO => VK(b'O'),
+        KeyCode::KeyP => VK(b'P'),
+        KeyCode::KeyQ => VK(b'Q'),
+        KeyCode::KeyR => VK(b'R'),
+        KeyCode::KeyS => VK(b'S'),
+        KeyCode::KeyT => VK(b'T'),
+        KeyCode::KeyU => VK(b'U'),
+        KeyCode::KeyV => VK(b'V'),
+        KeyCode::KeyW => VK(b'W'),
+        KeyCode::KeyX => VK(b'X'),
+        KeyCode::KeyY => VK(b'Y'),
+        KeyCode::KeyZ => VK(b'Z'),
+        KeyCode::Digit0 => VK(b'0'),
+        KeyCode::Digit1 => VK(b'1'),
+        KeyCode::Digit2 => VK(b'2'),
+        KeyCode::Digit3 => VK(b'3'),
+        KeyCode::Digit4 => VK(b'4'),
+        KeyCode::Digit5 => VK(b'5'),
+        KeyCode::Digit6 => VK(b'6'),
+        KeyCode::Digit7 => VK(b'7'),
+        KeyCode::Digit8 => VK(b'8'),
+        KeyCode::Digit9 => VK(b'9'),
+        KeyCode::Numpad0 => VK::numpad(0),
+        KeyCode::Numpad1 => VK::numpad(1),
+        KeyCode::Numpad2 => VK::numpad(2),
+        KeyCode::Numpad3 => VK::numpad(3),
+        KeyCode::Numpad4 => VK::numpad(4),
+        KeyCode::Numpad5 => VK::numpad(5),
+        KeyCode::Numpad6 => VK::numpad(6),
+        KeyCode::Numpad7 => VK::numpad(7),
+        KeyCode::Numpad8 => VK::numpad(8),
+        KeyCode::Numpad9 => VK::numpad(9),
+        KeyCode::NumpadMultiply => VK::MULTIPLY,
+        KeyCode::NumpadAdd => VK::ADD,
+        KeyCode::NumpadSubtract => VK::SUBTRACT,
+        KeyCode::NumpadDecimal => VK::DECIMAL,
+        KeyCode::NumpadDivide => VK::DIVIDE,
+        KeyCode::NumpadEnter => VK::ENTER,
+        KeyCode::F1 => VK::function(1),
+        KeyCode::F2 => VK::function(2),
+        KeyCode::F3 => VK::function(3),
+        KeyCode::F4 => VK::function(4),
+        KeyCode::F5 => VK::function(5),
+        KeyCode::F6 => VK::function(6),
+        KeyCode::F7 => VK::function(7),
+        KeyCode::F8 => VK::function(8),
+        KeyCode::F9 => VK::function(9),
+        KeyCode::F10 => VK::function(10),
+        KeyCode::F11 => VK::function(11),
+        KeyCode::F12 => VK::function(12),
+        KeyCode::Backspace => VK::BACKSPACE,
+        KeyCode::Tab => VK::TAB,
+        KeyCode::Enter => VK::ENTER,
+        KeyCode::ShiftLeft => VK::LEFT_SHIFT,
+        KeyCode::ShiftRight => VK::RIGHT_SHIFT,
+        KeyCode::ControlLeft => VK::LEFT_CONTROL,
+        KeyCode::ControlRight => VK::RIGHT_CONTROL,
+        KeyCode::AltLeft => VK::LEFT_ALT,
+        KeyCode::AltRight => VK::RIGHT_ALT,
+        KeyCode::Pause => VK::PAUSE,
+        KeyCode::Escape => VK::ESCAPE,
+        KeyCode::Space => VK::SPACE,
+        KeyCode::PageUp => VK::PAGE_UP,
+        KeyCode::PageDown => VK::PAGE_DOWN,
+        KeyCode::End => VK::END,
+        KeyCode::Home => VK::HOME,
+        KeyCode::ArrowLeft => VK::LEFT,
+        KeyCode::ArrowUp => VK::UP,
+        KeyCode::ArrowRight => VK::RIGHT,
+        KeyCode::ArrowDown => VK::DOWN,
+        KeyCode::PrintScreen => VK::PRINT_SCREEN,
+        KeyCode::Insert => VK::INSERT,
+        KeyCode::Delete => VK::DELETE,
+        _ => return None,
+    })
+}
+
 #[derive(Parser)]
 struct Cli {
     project_file: PathBuf,
     #[arg(long, default_value = "default")]
     config: String,
+    /// Directory to cache compiled scripts in, scripts which have not changed since the last run
+    /// will not be recompiled.
+    #[arg(long)]
+    code_cache: Option<PathBuf>,
+    /// Debug game scripts with a Debug Adapter Protocol client, either over "stdio" or by listening
+    /// on the given TCP address.
+    #[arg(long)]
+    debug_adapter: Option<Transport>,
+    /// Record the input for every tick to this file when the game exits, which can be replayed
+    /// with the `replay` command of `fabricator-cli`.
+    #[arg(long)]
+    record: Option<PathBuf>,
 }
 
 fn main() {
@@ -471,12 +1230,21 @@ fn main() {
 
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
+    let debug_server = cli.debug_adapter.map(|transport| {
+        let server = DebugServer::start(&transport).unwrap();
+        server.wait_for_configuration();
+        server
+    });
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
 
     let mut app = App {
         project_file: cli.project_file,
         config: cli.config,
+        code_cache: cli.code_cache,
+        debug_server,
+        record: cli.record,
         app_state: None,
     };
     event_loop.run_app(&mut app).unwrap();
@@ -1,7 +1,7 @@
 use std::mem;
 
 use bytemuck::{Pod, Zeroable};
-use fabricator_math::{Affine2, Vec2};
+use fabricator_math::{Affine2, Vec2, Vec4};
 
 #[derive(Debug, Copy, Clone, Default, Pod, Zeroable)]
 #[repr(C)]
@@ -27,13 +27,16 @@ impl ParametersUniform {
 pub struct Vertex {
     position: [f32; 2],
     uv: [f32; 2],
+    color: [f32; 4],
 }
 
 impl Vertex {
-    pub fn new(pos: Vec2<f32>, uv: Vec2<f32>) -> Self {
+    /// Create a vertex, `color` is a premultiplied RGBA color which the texture is multiplied by.
+    pub fn new(pos: Vec2<f32>, uv: Vec2<f32>, color: Vec4<f32>) -> Self {
         Self {
             position: pos.into_array(),
             uv: uv.into_array(),
+            color: color.into_array(),
         }
     }
 }
@@ -42,12 +45,17 @@ pub struct Pipeline {
     pub texture_bind_group_layout: wgpu::BindGroupLayout,
     pub parameters_bind_group_layout: wgpu::BindGroupLayout,
     pub pipeline: wgpu::RenderPipeline,
+    /// The same as `pipeline`, but for drawing into [`Pipeline::SURFACE_FORMAT`] textures.
+    pub surface_pipeline: wgpu::RenderPipeline,
 }
 
 impl Pipeline {
     pub const TEXTURE_BIND_GROUP: u32 = 0;
     pub const PARAMETERS_BIND_GROUP: u32 = 1;
 
+    /// The texture format of game surfaces.
+    pub const SURFACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
     pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -92,56 +100,60 @@ impl Pipeline {
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
-            layout: Some(
-                &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: None,
-                    bind_group_layouts: &[
-                        Some(&texture_bind_group_layout),
-                        Some(&parameters_bind_group_layout),
-                    ],
-                    immediate_size: 0,
-                }),
-            ),
-            vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: Some("vs_main"),
-                buffers: &[wgpu::VertexBufferLayout {
-                    array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                    step_mode: wgpu::VertexStepMode::Vertex,
-                    attributes: &wgpu::vertex_attr_array![
-                        0 => Float32x2,
-                        1 => Float32x2,
-                    ],
-                }],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: output_format,
-                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                cull_mode: None,
-                ..Default::default()
-            },
-            depth_stencil: None,
-            multisample: Default::default(),
-            multiview_mask: None,
-            cache: None,
+            bind_group_layouts: &[
+                Some(&texture_bind_group_layout),
+                Some(&parameters_bind_group_layout),
+            ],
+            immediate_size: 0,
         });
 
+        let create_pipeline = |format| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader_module,
+                    entry_point: Some("vs_main"),
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x2,
+                            1 => Float32x2,
+                            2 => Float32x4,
+                        ],
+                    }],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview_mask: None,
+                cache: None,
+            })
+        };
+
         Self {
+            pipeline: create_pipeline(output_format),
+            surface_pipeline: create_pipeline(Self::SURFACE_FORMAT),
             texture_bind_group_layout,
             parameters_bind_group_layout,
-            pipeline,
         }
     }
 
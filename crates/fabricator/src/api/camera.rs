@@ -0,0 +1,575 @@
+use fabricator_math::{Affine2, Box2, Vec2};
+use fabricator_vm as vm;
+use gc_arena::{Collect, Gc, Rootable};
+
+use crate::{
+    api::{
+        id_user_data::IdUserData,
+        instance::InstanceUserData,
+        magic::{MagicExt as _, create_magic_rw},
+        object::{ObjectUserData, no_one},
+    },
+    state::{
+        Camera, CameraId, CameraTarget, State,
+        camera::{VIEW_COUNT, View},
+    },
+};
+
+pub type CameraUserData = IdUserData<CameraId>;
+
+pub fn find_camera<'gc>(
+    state: &State,
+    camera: vm::UserData<'gc>,
+) -> Result<CameraId, vm::RuntimeError> {
+    let id = CameraUserData::downcast(camera)?.id;
+    if state.cameras.contains(id) {
+        Ok(id)
+    } else {
+        Err(vm::RuntimeError::msg("expired camera ID"))
+    }
+}
+
+/// Interpret a script value as a camera target, `noone` (or -1) clears the target.
+fn camera_target<'gc>(
+    ctx: vm::Context<'gc>,
+    value: vm::Value<'gc>,
+) -> Result<Option<CameraTarget>, vm::RuntimeError> {
+    match value {
+        vm::Value::Undefined => Ok(None),
+        vm::Value::UserData(ud) if ud == no_one(ctx) => Ok(None),
+        vm::Value::UserData(ud) => {
+            if let Ok(instance) = InstanceUserData::downcast(ud) {
+                Ok(Some(CameraTarget::Instance(instance.id)))
+            } else {
+                Ok(Some(CameraTarget::Object(ObjectUserData::downcast(ud)?.id)))
+            }
+        }
+        value => {
+            let id: i64 = vm::FromValue::from_value(ctx, value)?;
+            if id < 0 {
+                Ok(None)
+            } else {
+                Err(vm::RuntimeError::msg(format!("invalid camera target {id}")))
+            }
+        }
+    }
+}
+
+fn camera_target_value<'gc>(
+    ctx: vm::Context<'gc>,
+    state: &State,
+    target: Option<CameraTarget>,
+) -> vm::Value<'gc> {
+    match target {
+        Some(CameraTarget::Instance(instance_id)) => match state.instances.get(instance_id) {
+            Some(instance) => ctx.fetch(&instance.this).into(),
+            None => no_one(ctx).into(),
+        },
+        Some(CameraTarget::Object(object_id)) => {
+            ctx.fetch(&state.config.objects[object_id].userdata).into()
+        }
+        None => no_one(ctx).into(),
+    }
+}
+
+/// A 4x4 column-major matrix, as returned by GML matrix functions.
+fn matrix_array<'gc>(ctx: vm::Context<'gc>, transform: Affine2<f64>) -> vm::Array<'gc> {
+    let [x, y] = transform.matrix.into_cols();
+    let t = transform.translation;
+    vm::Array::from_iter(
+        &ctx,
+        [
+            x[0], x[1], 0.0, 0.0, //
+            y[0], y[1], 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, //
+            t[0], t[1], 0.0, 1.0,
+        ]
+        .into_iter()
+        .map(vm::Value::from),
+    )
+}
+
+fn create_camera<'gc>(ctx: vm::Context<'gc>, state: &mut State, view: Box2<f64>) -> CameraId {
+    state
+        .cameras
+        .insert_with_id(|id| Camera::new(ctx.stash(CameraUserData::new(ctx, id)), view))
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ViewProperty {
+    Camera,
+    Visible,
+    XPort,
+    YPort,
+    WPort,
+    HPort,
+}
+
+impl ViewProperty {
+    fn get<'gc>(self, ctx: vm::Context<'gc>, state: &State, view: &View) -> vm::Value<'gc> {
+        match self {
+            ViewProperty::Camera => match view.camera.and_then(|id| state.cameras.get(id)) {
+                Some(camera) => ctx.fetch(&camera.this).into(),
+                None => (-1).into(),
+            },
+            ViewProperty::Visible => view.visible.into(),
+            ViewProperty::XPort => (view.port.min[0] as i64).into(),
+            ViewProperty::YPort => (view.port.min[1] as i64).into(),
+            ViewProperty::WPort => (view.port.size()[0] as i64).into(),
+            ViewProperty::HPort => (view.port.size()[1] as i64).into(),
+        }
+    }
+
+    fn set<'gc>(
+        self,
+        ctx: vm::Context<'gc>,
+        state: &State,
+        view: &mut View,
+        value: vm::Value<'gc>,
+    ) -> Result<(), vm::RuntimeError> {
+        let port_value = |value: vm::Value<'gc>| -> Result<u32, vm::RuntimeError> {
+            let value: f64 = vm::FromValue::from_value(ctx, value)?;
+            Ok(value.max(0.0) as u32)
+        };
+
+        let size = view.port.size();
+        match self {
+            ViewProperty::Camera => {
+                view.camera = match value {
+                    vm::Value::UserData(ud) => Some(find_camera(state, ud)?),
+                    value => {
+                        let id: i64 = vm::FromValue::from_value(ctx, value)?;
+                        if id >= 0 {
+                            return Err(vm::RuntimeError::msg(format!("invalid camera {id}")));
+                        }
+                        None
+                    }
+                };
+            }
+            ViewProperty::Visible => view.visible = vm::FromValue::from_value(ctx, value)?,
+            ViewProperty::XPort => {
+                view.port = Box2::with_size(Vec2::new(port_value(value)?, view.port.min[1]), size)
+            }
+            ViewProperty::YPort => {
+                view.port = Box2::with_size(Vec2::new(view.port.min[0], port_value(value)?), size)
+            }
+            ViewProperty::WPort => {
+                view.port = Box2::with_size(view.port.min, Vec2::new(port_value(value)?, size[1]))
+            }
+            ViewProperty::HPort => {
+                view.port = Box2::with_size(view.port.min, Vec2::new(size[0], port_value(value)?))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn view_index<'gc>(
+    ctx: vm::Context<'gc>,
+    index: vm::Value<'gc>,
+) -> Result<usize, vm::RuntimeError> {
+    let index: i64 = vm::FromValue::from_value(ctx, index)?;
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i < VIEW_COUNT)
+        .ok_or_else(|| vm::RuntimeError::msg(format!("view index {index} out of range")))
+}
+
+/// One of the built-in view arrays, like `view_camera` or `view_visible`.
+struct ViewArrayUserData {
+    property: ViewProperty,
+}
+
+impl ViewArrayUserData {
+    fn new<'gc>(ctx: vm::Context<'gc>, property: ViewProperty) -> vm::UserData<'gc> {
+        let methods = ctx
+            .singleton::<Rootable![ViewArrayMethodsSingleton<'_>]>()
+            .0;
+        let ud = vm::UserData::new_static(&ctx, ViewArrayUserData { property });
+        ud.set_methods(&ctx, Some(methods));
+        ud
+    }
+
+    fn property(ud: vm::UserData<'_>) -> ViewProperty {
+        ud.downcast_static::<ViewArrayUserData>().unwrap().property
+    }
+}
+
+#[derive(Collect)]
+#[collect(no_drop)]
+struct ViewArrayMethodsSingleton<'gc>(Gc<'gc, dyn vm::UserDataMethods<'gc>>);
+
+impl<'gc> vm::Singleton<'gc> for ViewArrayMethodsSingleton<'gc> {
+    fn create(ctx: vm::Context<'gc>) -> Self {
+        struct Methods;
+
+        impl<'gc> vm::UserDataMethods<'gc> for Methods {
+            fn get_index(
+                &self,
+                ud: vm::UserData<'gc>,
+                ctx: vm::Context<'gc>,
+                indexes: &[vm::Value<'gc>],
+            ) -> Result<vm::Value<'gc>, vm::RuntimeError> {
+                if indexes.len() != 1 {
+                    return Err(vm::RuntimeError::msg("view array expects 1 index"));
+                }
+                let index = view_index(ctx, indexes[0])?;
+                let property = ViewArrayUserData::property(ud);
+                Ok(State::ctx_with(ctx, |state| {
+                    property.get(ctx, state, &state.views.views[index])
+                })?)
+            }
+
+            fn set_index(
+                &self,
+                ud: vm::UserData<'gc>,
+                ctx: vm::Context<'gc>,
+                indexes: &[vm::Value<'gc>],
+                value: vm::Value<'gc>,
+            ) -> Result<(), vm::RuntimeError> {
+                if indexes.len() != 1 {
+                    return Err(vm::RuntimeError::msg("view array expects 1 index"));
+                }
+                let index = view_index(ctx, indexes[0])?;
+                let property = ViewArrayUserData::property(ud);
+                State::ctx_with_mut(ctx, |state| {
+                    let mut view = state.views.views[index];
+                    property.set(ctx, state, &mut view, value)?;
+                    state.views.views[index] = view;
+                    Ok(())
+                })?
+            }
+        }
+
+        let methods = ctx.alloc_static(Methods);
+        Self(gc_arena::unsize!(methods => dyn vm::UserDataMethods<'gc>))
+    }
+}
+
+pub fn camera_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
+    let mut magic = vm::MagicSet::new();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_create"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                State::ctx_with_mut(ctx, |state| {
+                    let room_size = state
+                        .current_room
+                        .map(|room_id| state.config.rooms[room_id].size)
+                        .unwrap_or(Vec2::zero());
+                    let view = Box2::with_size(Vec2::zero(), room_size.cast());
+                    let camera_id = create_camera(ctx, state, view);
+                    exec.stack()
+                        .replace(ctx, ctx.fetch(&state.cameras[camera_id].this));
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_create_view"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (x, y, width, height, angle, target, speed_x, speed_y, border_x, border_y): (
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    Option<f64>,
+                    vm::Value,
+                    Option<f64>,
+                    Option<f64>,
+                    Option<f64>,
+                    Option<f64>,
+                ) = exec.stack().consume(ctx)?;
+                let target = camera_target(ctx, target)?;
+
+                State::ctx_with_mut(ctx, |state| {
+                    let view = Box2::with_size(Vec2::new(x, y), Vec2::new(width, height));
+                    let camera_id = create_camera(ctx, state, view);
+                    let camera = &mut state.cameras[camera_id];
+                    camera.angle = angle.unwrap_or(0.0);
+                    camera.target = target;
+                    camera.speed = Vec2::new(speed_x.unwrap_or(-1.0), speed_y.unwrap_or(-1.0));
+                    camera.border = Vec2::new(border_x.unwrap_or(0.0), border_y.unwrap_or(0.0));
+                    exec.stack().replace(ctx, ctx.fetch(&camera.this));
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_destroy"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let camera_ud: vm::UserData = exec.stack().consume(ctx)?;
+                State::ctx_with_mut(ctx, |state| {
+                    let camera_id = find_camera(state, camera_ud)?;
+                    state.cameras.remove(camera_id);
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    fn camera_getter<'gc>(
+        ctx: vm::Context<'gc>,
+        get: impl Fn(&Camera) -> f64 + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let camera_ud: vm::UserData = exec.stack().consume(ctx)?;
+            State::ctx_with(ctx, |state| {
+                let camera_id = find_camera(state, camera_ud)?;
+                exec.stack().replace(ctx, get(&state.cameras[camera_id]));
+                Ok(())
+            })?
+        })
+    }
+
+    for (name, getter) in [
+        ("camera_get_view_x", camera_getter(ctx, |c| c.view.min[0])),
+        ("camera_get_view_y", camera_getter(ctx, |c| c.view.min[1])),
+        (
+            "camera_get_view_width",
+            camera_getter(ctx, |c| c.view.size()[0]),
+        ),
+        (
+            "camera_get_view_height",
+            camera_getter(ctx, |c| c.view.size()[1]),
+        ),
+        ("camera_get_view_angle", camera_getter(ctx, |c| c.angle)),
+        (
+            "camera_get_view_border_x",
+            camera_getter(ctx, |c| c.border[0]),
+        ),
+        (
+            "camera_get_view_border_y",
+            camera_getter(ctx, |c| c.border[1]),
+        ),
+        (
+            "camera_get_view_speed_x",
+            camera_getter(ctx, |c| c.speed[0]),
+        ),
+        (
+            "camera_get_view_speed_y",
+            camera_getter(ctx, |c| c.speed[1]),
+        ),
+    ] {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), getter)
+            .unwrap();
+    }
+
+    fn camera_setter<'gc>(
+        ctx: vm::Context<'gc>,
+        set: impl Fn(&mut Camera, Vec2<f64>) + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let (camera_ud, x, y): (vm::UserData, f64, f64) = exec.stack().consume(ctx)?;
+            State::ctx_with_mut(ctx, |state| {
+                let camera_id = find_camera(state, camera_ud)?;
+                set(&mut state.cameras[camera_id], Vec2::new(x, y));
+                Ok(())
+            })?
+        })
+    }
+
+    for (name, setter) in [
+        (
+            "camera_set_view_pos",
+            camera_setter(ctx, |c, pos| c.view = Box2::with_size(pos, c.view.size())),
+        ),
+        (
+            "camera_set_view_size",
+            camera_setter(ctx, |c, size| c.view = Box2::with_size(c.view.min, size)),
+        ),
+        (
+            "camera_set_view_border",
+            camera_setter(ctx, |c, border| c.border = border),
+        ),
+        (
+            "camera_set_view_speed",
+            camera_setter(ctx, |c, speed| c.speed = speed),
+        ),
+    ] {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), setter)
+            .unwrap();
+    }
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_set_view_angle"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (camera_ud, angle): (vm::UserData, f64) = exec.stack().consume(ctx)?;
+                State::ctx_with_mut(ctx, |state| {
+                    let camera_id = find_camera(state, camera_ud)?;
+                    state.cameras[camera_id].angle = angle;
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_set_view_target"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (camera_ud, target): (vm::UserData, vm::Value) = exec.stack().consume(ctx)?;
+                let target = camera_target(ctx, target)?;
+                State::ctx_with_mut(ctx, |state| {
+                    let camera_id = find_camera(state, camera_ud)?;
+                    state.cameras[camera_id].target = target;
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_get_view_target"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let camera_ud: vm::UserData = exec.stack().consume(ctx)?;
+                State::ctx_with(ctx, |state| {
+                    let camera_id = find_camera(state, camera_ud)?;
+                    let target = state.cameras[camera_id].target;
+                    exec.stack()
+                        .replace(ctx, camera_target_value(ctx, state, target));
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_get_view_mat"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let camera_ud: vm::UserData = exec.stack().consume(ctx)?;
+                State::ctx_with(ctx, |state| {
+                    let camera_id = find_camera(state, camera_ud)?;
+                    let view_matrix = state.cameras[camera_id].view_matrix();
+                    exec.stack().replace(ctx, matrix_array(ctx, view_matrix));
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("camera_get_proj_mat"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let camera_ud: vm::UserData = exec.stack().consume(ctx)?;
+                State::ctx_with(ctx, |state| {
+                    let camera_id = find_camera(state, camera_ud)?;
+                    let projection_matrix = state.cameras[camera_id].projection_matrix();
+                    exec.stack()
+                        .replace(ctx, matrix_array(ctx, projection_matrix));
+                    Ok(())
+                })?
+            }),
+        )
+        .unwrap();
+
+    let view_enabled = create_magic_rw(
+        ctx,
+        |ctx| Ok(State::ctx_with(ctx, |state| state.views.enabled.into())?),
+        |ctx, value| {
+            let enabled: bool = vm::FromValue::from_value(ctx, value)?;
+            State::ctx_with_mut(ctx, |state| state.views.enabled = enabled)?;
+            Ok(())
+        },
+    );
+    magic
+        .add(ctx.intern_static("view_enabled"), view_enabled)
+        .unwrap();
+
+    for (array_name, get_name, set_name, property) in [
+        (
+            "view_camera",
+            "view_get_camera",
+            "view_set_camera",
+            ViewProperty::Camera,
+        ),
+        (
+            "view_visible",
+            "view_get_visible",
+            "view_set_visible",
+            ViewProperty::Visible,
+        ),
+        (
+            "view_xport",
+            "view_get_xport",
+            "view_set_xport",
+            ViewProperty::XPort,
+        ),
+        (
+            "view_yport",
+            "view_get_yport",
+            "view_set_yport",
+            ViewProperty::YPort,
+        ),
+        (
+            "view_wport",
+            "view_get_wport",
+            "view_set_wport",
+            ViewProperty::WPort,
+        ),
+        (
+            "view_hport",
+            "view_get_hport",
+            "view_set_hport",
+            ViewProperty::HPort,
+        ),
+    ] {
+        magic
+            .add_constant(
+                ctx,
+                ctx.intern_static(array_name),
+                ViewArrayUserData::new(ctx, property),
+            )
+            .unwrap();
+
+        let get = vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let index: vm::Value = exec.stack().consume(ctx)?;
+            let index = view_index(ctx, index)?;
+            State::ctx_with(ctx, |state| {
+                exec.stack()
+                    .replace(ctx, property.get(ctx, state, &state.views.views[index]));
+            })?;
+            Ok(())
+        });
+        magic
+            .add_constant(ctx, ctx.intern_static(get_name), get)
+            .unwrap();
+
+        let set = vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let (index, value): (vm::Value, vm::Value) = exec.stack().consume(ctx)?;
+            let index = view_index(ctx, index)?;
+            State::ctx_with_mut(ctx, |state| {
+                let mut view = state.views.views[index];
+                property.set(ctx, state, &mut view, value)?;
+                state.views.views[index] = view;
+                Ok(())
+            })?
+        });
+        magic
+            .add_constant(ctx, ctx.intern_static(set_name), set)
+            .unwrap();
+    }
+
+    magic
+}
@@ -1,5 +1,5 @@
 use fabricator_collision::{
-    bound_box_tree::BoundBoxQuery, gjk, support_ext::SupportMapExt, support_maps,
+    bound_box_tree::BoundBoxQuery, support_ext::SupportMapExt, support_maps,
 };
 use fabricator_math::Vec2;
 use fabricator_vm as vm;
@@ -34,24 +34,9 @@ pub fn collision_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
                     }
                 }
 
-                if let Some(collision) = state.instance_collision(instance_id) {
-                    let intersection = collision.intersect(line_collision);
-
-                    let res = gjk::gjk(
-                        gjk::Settings {
-                            tolerance: 0.1,
-                            max_iterations: 12,
-                            max_distance: 0.0,
-                            find_closest_point: false,
-                        },
-                        intersection,
-                        &mut Default::default(),
-                    );
-
-                    if matches!(res, gjk::Result::Touching) {
-                        exec.stack().replace(ctx, ctx.fetch(&instance.this));
-                        return Ok(());
-                    }
+                if state.instance_touches(instance_id, line_collision) {
+                    exec.stack().replace(ctx, ctx.fetch(&instance.this));
+                    return Ok(());
                 }
             }
 
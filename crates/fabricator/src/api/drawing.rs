@@ -1,4 +1,6 @@
-use fabricator_math::Vec2;
+use std::f32;
+
+use fabricator_math::{Affine2, Box2, Vec2, Vec3, Vec4};
 use fabricator_vm as vm;
 
 use crate::{
@@ -6,9 +8,9 @@ use crate::{
         id_user_data::{IdUserData, NamedIdUserData},
         magic::{DuplicateMagicName, MagicExt as _},
     },
+    game::{DrawCommand, Quad, Triangles, color_rgb},
     state::{
-        Configuration, DrawingState, DrawnSprite, DrawnSpriteFrame, EventState, SpriteId, State,
-        TexturePageId,
+        Configuration, DrawingState, EventState, Sprite, SpriteId, State, TexturePageId,
         configuration::{ShaderId, TileSetId},
     },
 };
@@ -40,8 +42,32 @@ pub fn drawing_api<'gc>(
         )?;
     }
 
-    magic.add_constant(ctx, ctx.intern_static("c_white"), 0xffffff)?;
-    magic.add_constant(ctx, ctx.intern_static("c_black"), 0x0)?;
+    for (name, color) in [
+        ("c_aqua", 0xffff00),
+        ("c_black", 0x000000),
+        ("c_blue", 0xff0000),
+        ("c_dkgray", 0x404040),
+        ("c_dkgrey", 0x404040),
+        ("c_fuchsia", 0xff00ff),
+        ("c_gray", 0x808080),
+        ("c_grey", 0x808080),
+        ("c_green", 0x008000),
+        ("c_lime", 0x00ff00),
+        ("c_ltgray", 0xc0c0c0),
+        ("c_ltgrey", 0xc0c0c0),
+        ("c_maroon", 0x000080),
+        ("c_navy", 0x800000),
+        ("c_olive", 0x008080),
+        ("c_orange", 0x40a0ff),
+        ("c_purple", 0x800080),
+        ("c_red", 0x0000ff),
+        ("c_silver", 0xc0c0c0),
+        ("c_teal", 0x808000),
+        ("c_white", 0xffffff),
+        ("c_yellow", 0x00ffff),
+    ] {
+        magic.add_constant(ctx, ctx.intern_static(name), color as i64)?;
+    }
 
     let make_color_rgb = vm::Callback::from_fn(ctx, |ctx, mut exec| {
         let (r, g, b): (u8, u8, u8) = exec.stack().consume(ctx)?;
@@ -51,29 +77,285 @@ pub fn drawing_api<'gc>(
     });
     magic.add_constant(ctx, ctx.intern_static("make_color_rgb"), make_color_rgb)?;
 
-    let draw_sprite = vm::Callback::from_fn(ctx, |ctx, mut exec| {
-        let (sprite, sub_img, x, y): (vm::UserData, i64, f64, f64) = exec.stack().consume(ctx)?;
-        let sprite = SpriteUserData::downcast(sprite)?;
+    let draw_set_colour = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let color: u32 = exec.stack().consume(ctx)?;
+        DrawingState::ctx_with_mut(ctx, |drawing_state| {
+            drawing_state.color = color & 0xffffff;
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_set_colour"), draw_set_colour)?;
+    magic.add_constant(ctx, ctx.intern_static("draw_set_color"), draw_set_colour)?;
 
-        let instance = EventState::ctx_with(ctx, |e| e.instance_id)?;
+    let draw_get_colour = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let color = DrawingState::ctx_with(ctx, |drawing_state| drawing_state.color)?;
+        exec.stack().replace(ctx, color as i64);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_get_colour"), draw_get_colour)?;
+    magic.add_constant(ctx, ctx.intern_static("draw_get_color"), draw_get_colour)?;
 
+    let draw_set_alpha = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let alpha: f64 = exec.stack().consume(ctx)?;
         DrawingState::ctx_with_mut(ctx, |drawing_state| {
-            drawing_state.drawn_sprites.push(DrawnSprite {
-                instance,
-                sprite: sprite.id,
-                sub_img: if sub_img < 0 {
-                    DrawnSpriteFrame::CurrentAnimation
-                } else {
-                    DrawnSpriteFrame::Frame(sub_img as usize)
-                },
-                position: Vec2::new(x, y),
+            drawing_state.alpha = alpha.clamp(0.0, 1.0);
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_set_alpha"), draw_set_alpha)?;
+
+    let draw_get_alpha = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let alpha = DrawingState::ctx_with(ctx, |drawing_state| drawing_state.alpha)?;
+        exec.stack().replace(ctx, alpha);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_get_alpha"), draw_get_alpha)?;
+
+    let draw_rectangle = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x1, y1, x2, y2, outline): (f32, f32, f32, f32, bool) = exec.stack().consume(ctx)?;
+        let rect = Box2::from_fpoints([Vec2::new(x1, y1), Vec2::new(x2, y2)].into_iter()).unwrap();
+        draw_triangles(ctx, |triangles, color| {
+            if outline {
+                push_rect_outline(triangles, rect, color);
+            } else {
+                triangles.push_rect(rect, color);
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_rectangle"), draw_rectangle)?;
+
+    let draw_line = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x1, y1, x2, y2): (f32, f32, f32, f32) = exec.stack().consume(ctx)?;
+        draw_triangles(ctx, |triangles, color| {
+            triangles.push_line(Vec2::new(x1, y1), Vec2::new(x2, y2), 1.0, color);
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_line"), draw_line)?;
+
+    let draw_line_width = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x1, y1, x2, y2, width): (f32, f32, f32, f32, f32) = exec.stack().consume(ctx)?;
+        draw_triangles(ctx, |triangles, color| {
+            triangles.push_line(Vec2::new(x1, y1), Vec2::new(x2, y2), width, color);
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_line_width"), draw_line_width)?;
+
+    let draw_circle = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, radius, outline): (f32, f32, f32, bool) = exec.stack().consume(ctx)?;
+        let center = Vec2::new(x, y);
+        let points = (0..=CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = i as f32 / CIRCLE_SEGMENTS as f32 * f32::consts::TAU;
+                center + Vec2::from_unit_angle(angle) * radius
             })
+            .collect::<Vec<_>>();
+
+        draw_triangles(ctx, |triangles, color| {
+            for p in points.windows(2) {
+                if outline {
+                    triangles.push_line(p[0], p[1], 1.0, color);
+                } else {
+                    triangles.push_triangle([center, p[0], p[1]], color);
+                }
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_circle"), draw_circle)?;
+
+    let draw_triangle = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x1, y1, x2, y2, x3, y3, outline): (f32, f32, f32, f32, f32, f32, bool) =
+            exec.stack().consume(ctx)?;
+        let points = [Vec2::new(x1, y1), Vec2::new(x2, y2), Vec2::new(x3, y3)];
+        draw_triangles(ctx, |triangles, color| {
+            if outline {
+                let [a, b, c] = points;
+                for [p, q] in [[a, b], [b, c], [c, a]] {
+                    triangles.push_line(p, q, 1.0, color);
+                }
+            } else {
+                triangles.push_triangle(points, color);
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_triangle"), draw_triangle)?;
+
+    let draw_healthbar = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (
+            x1,
+            y1,
+            x2,
+            y2,
+            amount,
+            back_color,
+            min_color,
+            max_color,
+            direction,
+            show_back,
+            show_border,
+        ): (f32, f32, f32, f32, f32, u32, u32, u32, u8, bool, bool) = exec.stack().consume(ctx)?;
+
+        let rect = Box2::from_fpoints([Vec2::new(x1, y1), Vec2::new(x2, y2)].into_iter()).unwrap();
+        let amount = (amount / 100.0).clamp(0.0, 1.0);
+
+        let size = rect.size();
+        let bar = match direction {
+            // The bar grows from the left, right, top or bottom side.
+            0 => Box2::with_size(rect.min, Vec2::new(size[0] * amount, size[1])),
+            1 => Box2::new(
+                Vec2::new(rect.max[0] - size[0] * amount, rect.min[1]),
+                rect.max,
+            ),
+            2 => Box2::with_size(rect.min, Vec2::new(size[0], size[1] * amount)),
+            3 => Box2::new(
+                Vec2::new(rect.min[0], rect.max[1] - size[1] * amount),
+                rect.max,
+            ),
+            _ => {
+                return Err(vm::RuntimeError::msg(format!(
+                    "invalid healthbar direction {direction}"
+                ))
+                .into());
+            }
+        };
+
+        draw_triangles(ctx, |triangles, _| {
+            let opaque = |color: u32| color_rgb(color).extend(1.0);
+            if show_back {
+                triangles.push_rect(rect, opaque(back_color));
+            }
+            let min_color = opaque(min_color);
+            let bar_color = min_color + (opaque(max_color) - min_color) * amount;
+            triangles.push_rect(bar, bar_color);
+            if show_border {
+                push_rect_outline(triangles, rect, opaque(0x0));
+            }
         })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_healthbar"), draw_healthbar)?;
+
+    let draw_sprite = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sprite, sub_img, x, y): (vm::UserData, i64, f64, f64) = exec.stack().consume(ctx)?;
+        let sprite_id = SpriteUserData::downcast(sprite)?.id;
 
+        draw_sprite_quad(ctx, sprite_id, sub_img, |sprite, quad| {
+            quad.transform = Affine2::new()
+                .translate(-sprite.origin.cast::<f64>())
+                .translate(Vec2::new(x, y))
+                .cast();
+        })?;
         Ok(())
     });
     magic.add_constant(ctx, ctx.intern_static("draw_sprite"), draw_sprite)?;
 
+    // Draws the calling instance exactly as it would be drawn if its object had no draw event.
+    let draw_self = vm::Callback::from_fn(ctx, |ctx, _| {
+        let instance_id = EventState::ctx_with(ctx, |e| e.instance_id)?;
+        let (sprite_id, transform, blend, alpha) = State::ctx_with(ctx, |state| {
+            let instance = state
+                .instances
+                .get(instance_id)
+                .ok_or_else(|| vm::RuntimeError::msg("expired instance"))?;
+            let transform = Affine2::new()
+                .scale(instance.scale)
+                .rotate(instance.rotation)
+                .translate(instance.position);
+            Ok::<_, vm::RuntimeError>((instance.sprite, transform, instance.blend, instance.alpha))
+        })??;
+
+        if let Some(sprite_id) = sprite_id {
+            draw_sprite_quad(ctx, sprite_id, -1, |sprite, quad| {
+                quad.transform = Affine2::new()
+                    .translate(-sprite.origin.cast::<f64>())
+                    .then(transform)
+                    .cast();
+                quad.tint = color_rgb(blend);
+                quad.alpha = alpha as f32;
+            })?;
+        }
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_self"), draw_self)?;
+
+    let draw_sprite_ext = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sprite, sub_img, x, y, xscale, yscale, angle, color, alpha): (
+            vm::UserData,
+            i64,
+            f64,
+            f64,
+            f64,
+            f64,
+            f64,
+            u32,
+            f64,
+        ) = exec.stack().consume(ctx)?;
+        let sprite_id = SpriteUserData::downcast(sprite)?.id;
+
+        draw_sprite_quad(ctx, sprite_id, sub_img, |sprite, quad| {
+            quad.transform = Affine2::new()
+                .translate(-sprite.origin.cast::<f64>())
+                .scale(Vec2::new(xscale, yscale))
+                .rotate(-angle.to_radians())
+                .translate(Vec2::new(x, y))
+                .cast();
+            quad.tint = color_rgb(color);
+            quad.alpha = alpha.clamp(0.0, 1.0) as f32;
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_sprite_ext"), draw_sprite_ext)?;
+
+    let draw_sprite_part = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sprite, sub_img, left, top, width, height, x, y): (
+            vm::UserData,
+            i64,
+            f64,
+            f64,
+            f64,
+            f64,
+            f64,
+            f64,
+        ) = exec.stack().consume(ctx)?;
+        let sprite_id = SpriteUserData::downcast(sprite)?.id;
+
+        // The sprite origin is ignored, the top left of the part is drawn at (x, y).
+        draw_sprite_quad(ctx, sprite_id, sub_img, |_, quad| {
+            let part = Box2::with_size(Vec2::new(left, top), Vec2::new(width, height));
+            quad.region = quad.region.fintersection(part.cast());
+            quad.transform = Affine2::new()
+                .translate(-part.min)
+                .translate(Vec2::new(x, y))
+                .cast();
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_sprite_part"), draw_sprite_part)?;
+
+    let draw_sprite_stretched = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sprite, sub_img, x, y, width, height): (vm::UserData, i64, f64, f64, f64, f64) =
+            exec.stack().consume(ctx)?;
+        let sprite_id = SpriteUserData::downcast(sprite)?.id;
+
+        // The sprite origin is ignored, the whole sprite is stretched to fill the given rectangle.
+        draw_sprite_quad(ctx, sprite_id, sub_img, |sprite, quad| {
+            quad.transform = Affine2::new()
+                .scale(Vec2::new(width, height) / sprite.size.cast::<f64>())
+                .translate(Vec2::new(x, y))
+                .cast();
+        })?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("draw_sprite_stretched"),
+        draw_sprite_stretched,
+    )?;
+
     let sprite_get_info = vm::Callback::from_fn(ctx, |ctx, mut exec| {
         let sprite: vm::UserData = exec.stack().consume(ctx)?;
         let sprite_id = SpriteUserData::downcast(sprite)?.id;
@@ -266,3 +548,102 @@ pub fn drawing_api<'gc>(
 
     Ok(magic)
 }
+
+/// The number of line segments used to draw a circle.
+const CIRCLE_SEGMENTS: usize = 24;
+
+/// The depth of the layer of the instance running the current event.
+pub fn current_depth(ctx: vm::Context<'_>) -> Result<i32, vm::RuntimeError> {
+    let instance_id = EventState::ctx_with(ctx, |e| e.instance_id)?;
+    State::ctx_with(ctx, |state| {
+        let instance = state
+            .instances
+            .get(instance_id)
+            .ok_or_else(|| vm::RuntimeError::msg("expired instance"))?;
+        Ok(state.layers[instance.layer].depth)
+    })?
+}
+
+/// Issue a list of untextured triangles.
+///
+/// The callback is given the current drawing color and alpha.
+fn draw_triangles(
+    ctx: vm::Context<'_>,
+    f: impl FnOnce(&mut Triangles, Vec4<f32>),
+) -> Result<(), vm::RuntimeError> {
+    let depth = current_depth(ctx)?;
+    DrawingState::ctx_with_mut(ctx, |drawing_state| {
+        let color = color_rgb(drawing_state.color).extend(drawing_state.alpha as f32);
+        let mut triangles = Triangles::new(depth);
+        f(&mut triangles, color);
+        drawing_state.push(DrawCommand::Triangles(triangles));
+    })?;
+    Ok(())
+}
+
+/// Push the one unit wide outline of a rectangle, drawn on the inside of the rectangle.
+fn push_rect_outline(triangles: &mut Triangles, rect: Box2<f32>, color: Vec4<f32>) {
+    let width = Vec2::splat(1.0).zip(rect.size(), f32::min);
+    let inner = Box2::new(rect.min + width, rect.max - width);
+    triangles.push_rect(
+        Box2::new(rect.min, Vec2::new(rect.max[0], inner.min[1])),
+        color,
+    );
+    triangles.push_rect(
+        Box2::new(Vec2::new(rect.min[0], inner.max[1]), rect.max),
+        color,
+    );
+    triangles.push_rect(
+        Box2::new(
+            Vec2::new(rect.min[0], inner.min[1]),
+            Vec2::new(inner.min[0], inner.max[1]),
+        ),
+        color,
+    );
+    triangles.push_rect(
+        Box2::new(
+            Vec2::new(inner.max[0], inner.min[1]),
+            Vec2::new(rect.max[0], inner.max[1]),
+        ),
+        color,
+    );
+}
+
+/// Issue a quad which draws a frame of a sprite.
+///
+/// If `sub_img` is negative, the current animation frame of the calling instance is used. The quad
+/// initially draws the whole frame untransformed and untinted, the callback adjusts it.
+fn draw_sprite_quad(
+    ctx: vm::Context<'_>,
+    sprite_id: SpriteId,
+    sub_img: i64,
+    f: impl FnOnce(&Sprite, &mut Quad),
+) -> Result<(), vm::RuntimeError> {
+    let instance_id = EventState::ctx_with(ctx, |e| e.instance_id)?;
+    let depth = current_depth(ctx)?;
+    let quad = State::ctx_with(ctx, |state| {
+        let sprite = &state.config.sprites[sprite_id];
+        let frame_index = if sub_img < 0 {
+            sprite.frame_at(state.instances[instance_id].animation_time)
+        } else {
+            sub_img as usize % sprite.frames.len()
+        };
+        let texture = sprite.frames[frame_index].texture;
+
+        let mut quad = Quad {
+            texture,
+            transform: Affine2::new(),
+            region: Box2::with_size(Vec2::zero(), state.config.textures[texture].size.cast()),
+            depth,
+            tint: Vec3::splat(1.0),
+            alpha: 1.0,
+        };
+        f(sprite, &mut quad);
+        quad
+    })?;
+
+    DrawingState::ctx_with_mut(ctx, |drawing_state| {
+        drawing_state.push(DrawCommand::Quad(quad));
+    })?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use fabricator_vm as vm;
+use gc_arena::{Collect, Gc, Lock, Rootable};
+
+use crate::api::magic::MagicExt as _;
+
+/// The function set by `exception_unhandled_handler`, if any.
+#[derive(Collect)]
+#[collect(no_drop)]
+pub struct UnhandledExceptionHandler<'gc>(Gc<'gc, Lock<Option<vm::Function<'gc>>>>);
+
+impl<'gc> vm::Singleton<'gc> for UnhandledExceptionHandler<'gc> {
+    fn create(ctx: vm::Context<'gc>) -> Self {
+        Self(Gc::new(&ctx, Lock::new(None)))
+    }
+}
+
+impl<'gc> UnhandledExceptionHandler<'gc> {
+    pub fn singleton(ctx: vm::Context<'gc>) -> &'gc Self {
+        ctx.singleton::<Rootable![UnhandledExceptionHandler<'_>]>()
+    }
+
+    pub fn get(&self) -> Option<vm::Function<'gc>> {
+        self.0.get()
+    }
+
+    /// Set the handler, returning the previous one.
+    pub fn set(
+        &self,
+        ctx: vm::Context<'gc>,
+        handler: Option<vm::Function<'gc>>,
+    ) -> Option<vm::Function<'gc>> {
+        let previous = self.0.get();
+        self.0.unlock(&ctx).set(handler);
+        previous
+    }
+}
+
+/// Call the unhandled exception handler with the exception for a script error which was not caught
+/// by any `try` / `catch`.
+///
+/// The runner stops on uncaught errors whether or not a handler is set, so the handler only gets a
+/// chance to report the error. Errors raised by the handler itself are logged and otherwise
+/// ignored, as are thread limit errors, which must stop the thread without running any more script
+/// code.
+pub fn call_unhandled_handler<'gc>(
+    ctx: vm::Context<'gc>,
+    mut exec: vm::Execution<'gc, '_>,
+    err: &vm::VmError<'gc>,
+) {
+    if vm::ThreadLimits::is_limit_error(&err.error) {
+        return;
+    }
+
+    if let Some(handler) = UnhandledExceptionHandler::singleton(ctx).get() {
+        exec.stack().replace(ctx, err.to_exception(ctx));
+        if let Err(handler_err) = exec.call(ctx, handler) {
+            log::error!("error in unhandled exception handler: {handler_err}");
+        }
+        exec.stack().clear();
+    }
+}
+
+/// Run `f` with an `Execution` on the given thread, calling the unhandled exception handler if it
+/// errors.
+pub fn run_handled<'gc>(
+    ctx: vm::Context<'gc>,
+    thread: vm::Thread<'gc>,
+    f: impl FnOnce(&mut vm::Execution<'gc, '_>) -> Result<(), vm::VmError<'gc>>,
+) -> Result<(), vm::ExternVmError> {
+    thread
+        .exec(ctx, |mut exec| {
+            let res = f(&mut exec);
+            if let Err(err) = &res {
+                call_unhandled_handler(ctx, exec, err);
+            }
+            res
+        })
+        .map_err(vm::VmError::into_extern)
+}
+
+/// Run a function on the given thread with the given value of `self`, calling the unhandled
+/// exception handler if it errors.
+pub fn run_script<'gc>(
+    ctx: vm::Context<'gc>,
+    thread: vm::Thread<'gc>,
+    function: impl Into<vm::Function<'gc>>,
+    this: impl Into<vm::Value<'gc>>,
+) -> Result<(), vm::ExternVmError> {
+    run_handled(ctx, thread, |exec| exec.with_this(this).call(ctx, function))
+}
+
+pub fn exception_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
+    let mut magic = vm::MagicSet::new();
+
+    let exception_unhandled_handler = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let handler: Option<vm::Function> = exec.stack().consume(ctx)?;
+        let previous = UnhandledExceptionHandler::singleton(ctx).set(ctx, handler);
+        exec.stack().replace(ctx, previous);
+        Ok(())
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("exception_unhandled_handler"),
+            exception_unhandled_handler,
+        )
+        .unwrap();
+
+    magic
+}
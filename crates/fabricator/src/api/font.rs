@@ -1,15 +1,19 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
+use fabricator_math::{Affine2, Box2, Vec2, Vec3};
+use fabricator_stdlib::string::value_to_string;
 use fabricator_vm as vm;
 
 use crate::{
     api::{
+        drawing::{SpriteUserData, current_depth},
         id_user_data::NamedIdUserData,
         magic::{DuplicateMagicName, MagicExt as _},
     },
+    game::{DrawCommand, Quad, color_rgb},
     state::{
-        Configuration, State,
-        configuration::{Font, FontId},
+        Configuration, DrawingState, HAlign, State, TextLayout, TextLayoutOptions, VAlign,
+        configuration::{Font, FontId, Glyph},
     },
 };
 
@@ -25,6 +29,17 @@ pub fn font_api<'gc>(
         magic.add_constant(ctx, ctx.intern(&font.name), ctx.fetch(&font.userdata))?;
     }
 
+    for (name, value) in [
+        ("fa_left", 0i64),
+        ("fa_center", 1),
+        ("fa_right", 2),
+        ("fa_top", 0),
+        ("fa_middle", 1),
+        ("fa_bottom", 2),
+    ] {
+        magic.add_constant(ctx, ctx.intern_static(name), value)?;
+    }
+
     let font_get_name = vm::Callback::from_fn(ctx, |ctx, mut exec| {
         let font: vm::UserData = exec.stack().consume(ctx)?;
         let font_id = FontUserData::downcast(font)?.id;
@@ -38,13 +53,88 @@ pub fn font_api<'gc>(
         .add_constant(ctx, ctx.intern_static("font_get_name"), font_get_name)
         .unwrap();
 
+    let font_get_info = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let font: vm::UserData = exec.stack().consume(ctx)?;
+        let font_id = FontUserData::downcast(font)?.id;
+
+        let mut info = vm::ObjectMap::new();
+        State::ctx_with(ctx, |state| {
+            let font = &state.config.fonts[font_id];
+            info.set_field(ctx, "name", ctx.intern(&font.name));
+            info.set_field(ctx, "size", font.line_height as i64);
+
+            let mut glyphs = vm::ObjectMap::new();
+            for (&c, glyph) in &font.glyphs {
+                let texture_page_id = state.config.texture_page_for_texture[glyph.texture];
+                let texture_page = &state.config.texture_pages[texture_page_id];
+                let page_position = texture_page.textures[glyph.texture] + glyph.region.min;
+                let size = glyph.region.size();
+
+                let mut glyph_obj = vm::ObjectMap::new();
+                glyph_obj.set_field(ctx, "char", c as i64);
+                glyph_obj.set_field(ctx, "x", page_position[0] as i64);
+                glyph_obj.set_field(ctx, "y", page_position[1] as i64);
+                glyph_obj.set_field(ctx, "w", size[0] as i64);
+                glyph_obj.set_field(ctx, "h", size[1] as i64);
+                glyph_obj.set_field(ctx, "shift", glyph.shift as i64);
+                glyph_obj.set_field(ctx, "offset", glyph.offset as i64);
+                glyph_obj.set_field(ctx, "texture", ctx.fetch(&texture_page.userdata));
+                glyphs.set(
+                    ctx.intern(c.encode_utf8(&mut [0; 4])),
+                    vm::Object::with_parts(&ctx, glyph_obj, None),
+                );
+            }
+            info.set_field(ctx, "glyphs", glyphs);
+        })?;
+
+        exec.stack()
+            .replace(ctx, vm::Object::with_parts(&ctx, info, None));
+        Ok(())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("font_get_info"), font_get_info)
+        .unwrap();
+
     let font_add_sprite_ext = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sprite, string_map, proportional, separation): (vm::UserData, vm::String, bool, i32) =
+            exec.stack().consume(ctx)?;
+        let sprite_id = SpriteUserData::downcast(sprite)?.id;
+
         State::ctx_with_mut(ctx, |state| {
+            let sprite = &state.config.sprites[sprite_id];
+
+            // Each character in the string map is drawn with the sprite frame at the same index.
+            let mut glyphs = HashMap::new();
+            for (c, frame) in string_map.chars().zip(&sprite.frames) {
+                let texture = &state.config.textures[frame.texture];
+                let (offset, width) = if proportional {
+                    (
+                        -(texture.cropped_offset[0] as i32),
+                        texture.cropped_size[0] as i32,
+                    )
+                } else {
+                    (0, sprite.size[0] as i32)
+                };
+                glyphs.insert(
+                    c,
+                    Glyph {
+                        texture: frame.texture,
+                        region: Box2::with_size(Vec2::zero(), texture.size),
+                        offset,
+                        shift: width + separation,
+                    },
+                );
+            }
+            let line_height = sprite.size[1];
+
             let id = state.config.fonts.insert_with_id(|id| {
                 let name = format!("dynamic_font_{}", id.index());
                 let vm_name = ctx.intern(&name);
                 Rc::new(Font {
-                    name: format!("dynamic_font_{}", id.index()),
+                    name,
+                    glyphs,
+                    kerning: HashMap::new(),
+                    line_height,
                     userdata: ctx.stash(FontUserData::new(ctx, id, vm_name)),
                 })
             });
@@ -54,8 +144,418 @@ pub fn font_api<'gc>(
         })?
     });
     magic
-        .add_constant(ctx, ctx.intern_static("font_add_sprite_ext"), font_add_sprite_ext)
+        .add_constant(
+            ctx,
+            ctx.intern_static("font_add_sprite_ext"),
+            font_add_sprite_ext,
+        )
         .unwrap();
 
+    let draw_set_font = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let font: vm::UserData = exec.stack().consume(ctx)?;
+        let font_id = FontUserData::downcast(font)?.id;
+        DrawingState::ctx_with_mut(ctx, |drawing_state| {
+            drawing_state.font = Some(font_id);
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_set_font"), draw_set_font)?;
+
+    let draw_get_font = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let font = State::ctx_with(ctx, |state| {
+            DrawingState::ctx_with(ctx, |drawing_state| {
+                drawing_state
+                    .font
+                    .map(|font_id| ctx.fetch(&state.config.fonts[font_id].userdata).into())
+                    .unwrap_or(vm::Value::Integer(-1))
+            })
+        })??;
+        exec.stack().replace(ctx, font);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_get_font"), draw_get_font)?;
+
+    let draw_set_halign = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let halign: i64 = exec.stack().consume(ctx)?;
+        let halign = match halign {
+            0 => HAlign::Left,
+            1 => HAlign::Center,
+            2 => HAlign::Right,
+            _ => {
+                return Err(vm::RuntimeError::msg(format!(
+                    "invalid horizontal alignment {halign}"
+                ))
+                .into());
+            }
+        };
+        DrawingState::ctx_with_mut(ctx, |drawing_state| {
+            drawing_state.halign = halign;
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_set_halign"), draw_set_halign)?;
+
+    let draw_get_halign = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let halign = DrawingState::ctx_with(ctx, |drawing_state| drawing_state.halign)?;
+        exec.stack().replace(
+            ctx,
+            match halign {
+                HAlign::Left => 0i64,
+                HAlign::Center => 1,
+                HAlign::Right => 2,
+            },
+        );
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_get_halign"), draw_get_halign)?;
+
+    let draw_set_valign = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let valign: i64 = exec.stack().consume(ctx)?;
+        let valign = match valign {
+            0 => VAlign::Top,
+            1 => VAlign::Middle,
+            2 => VAlign::Bottom,
+            _ => {
+                return Err(
+                    vm::RuntimeError::msg(format!("invalid vertical alignment {valign}")).into(),
+                );
+            }
+        };
+        DrawingState::ctx_with_mut(ctx, |drawing_state| {
+            drawing_state.valign = valign;
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_set_valign"), draw_set_valign)?;
+
+    let draw_get_valign = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let valign = DrawingState::ctx_with(ctx, |drawing_state| drawing_state.valign)?;
+        exec.stack().replace(
+            ctx,
+            match valign {
+                VAlign::Top => 0i64,
+                VAlign::Middle => 1,
+                VAlign::Bottom => 2,
+            },
+        );
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_get_valign"), draw_get_valign)?;
+
+    let string_width = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let value: vm::Value = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        let size = text_size(ctx, &string, -1.0, -1.0)?;
+        exec.stack().replace(ctx, size[0]);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("string_width"), string_width)?;
+
+    let string_height = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let value: vm::Value = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        let size = text_size(ctx, &string, -1.0, -1.0)?;
+        exec.stack().replace(ctx, size[1]);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("string_height"), string_height)?;
+
+    let string_width_ext = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (value, sep, w): (vm::Value, f64, f64) = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        let size = text_size(ctx, &string, sep, w)?;
+        exec.stack().replace(ctx, size[0]);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("string_width_ext"), string_width_ext)?;
+
+    let string_height_ext = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (value, sep, w): (vm::Value, f64, f64) = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        let size = text_size(ctx, &string, sep, w)?;
+        exec.stack().replace(ctx, size[1]);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("string_height_ext"),
+        string_height_ext,
+    )?;
+
+    let draw_text = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, value): (f64, f64, vm::Value) = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        issue_text(ctx, &string, TextParams::new(x, y))?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_text"), draw_text)?;
+
+    let draw_text_ext = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, value, sep, w): (f64, f64, vm::Value, f64, f64) = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        issue_text(
+            ctx,
+            &string,
+            TextParams {
+                line_separation: sep,
+                max_width: w,
+                ..TextParams::new(x, y)
+            },
+        )?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_text_ext"), draw_text_ext)?;
+
+    let draw_text_transformed = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, value, xscale, yscale, angle): (f64, f64, vm::Value, f64, f64, f64) =
+            exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        issue_text(
+            ctx,
+            &string,
+            TextParams {
+                scale: Vec2::new(xscale, yscale),
+                angle,
+                ..TextParams::new(x, y)
+            },
+        )?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("draw_text_transformed"),
+        draw_text_transformed,
+    )?;
+
+    let draw_text_ext_transformed = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, value, sep, w, xscale, yscale, angle): (
+            f64,
+            f64,
+            vm::Value,
+            f64,
+            f64,
+            f64,
+            f64,
+            f64,
+        ) = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        issue_text(
+            ctx,
+            &string,
+            TextParams {
+                line_separation: sep,
+                max_width: w,
+                scale: Vec2::new(xscale, yscale),
+                angle,
+                ..TextParams::new(x, y)
+            },
+        )?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("draw_text_ext_transformed"),
+        draw_text_ext_transformed,
+    )?;
+
+    let draw_text_colour = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, value, c1, c2, c3, c4, alpha): (f64, f64, vm::Value, u32, u32, u32, u32, f64) =
+            exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        issue_text(
+            ctx,
+            &string,
+            TextParams {
+                colors: Some([c1, c2, c3, c4]),
+                alpha: Some(alpha),
+                ..TextParams::new(x, y)
+            },
+        )?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("draw_text_colour"), draw_text_colour)?;
+    magic.add_constant(ctx, ctx.intern_static("draw_text_color"), draw_text_colour)?;
+
+    let draw_text_ext_colour = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, value, sep, w, c1, c2, c3, c4, alpha): (
+            f64,
+            f64,
+            vm::Value,
+            f64,
+            f64,
+            u32,
+            u32,
+            u32,
+            u32,
+            f64,
+        ) = exec.stack().consume(ctx)?;
+        let string = value_to_string(ctx, exec.reborrow(), value)?;
+        issue_text(
+            ctx,
+            &string,
+            TextParams {
+                line_separation: sep,
+                max_width: w,
+                colors: Some([c1, c2, c3, c4]),
+                alpha: Some(alpha),
+                ..TextParams::new(x, y)
+            },
+        )?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("draw_text_ext_colour"),
+        draw_text_ext_colour,
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("draw_text_ext_color"),
+        draw_text_ext_colour,
+    )?;
+
     Ok(magic)
 }
+
+/// The font set with `draw_set_font`, or the first font in the project if none has been set.
+fn current_font<'a>(state: &'a State, drawing_state: &DrawingState) -> Option<&'a Font> {
+    match drawing_state.font {
+        Some(font_id) => state.config.fonts.get(font_id),
+        None => state.config.fonts.values().next(),
+    }
+    .map(|font| &**font)
+}
+
+/// Layout options from the current alignment, GML uses negative values for the default line
+/// separation and for no maximum width.
+fn layout_options(drawing_state: &DrawingState, sep: f64, w: f64) -> TextLayoutOptions {
+    TextLayoutOptions {
+        line_separation: (sep >= 0.0).then_some(sep),
+        max_width: (w >= 0.0).then_some(w),
+        halign: drawing_state.halign,
+        valign: drawing_state.valign,
+    }
+}
+
+fn text_size(
+    ctx: vm::Context<'_>,
+    text: &str,
+    sep: f64,
+    w: f64,
+) -> Result<Vec2<f64>, vm::RuntimeError> {
+    let size = State::ctx_with(ctx, |state| {
+        DrawingState::ctx_with(ctx, |drawing_state| {
+            current_font(state, drawing_state)
+                .map(|font| TextLayout::new(font, text, layout_options(drawing_state, sep, w)).size)
+                .unwrap_or(Vec2::zero())
+        })
+    })??;
+    Ok(size)
+}
+
+struct TextParams {
+    position: Vec2<f64>,
+    line_separation: f64,
+    max_width: f64,
+    scale: Vec2<f64>,
+    /// Rotation in degrees counter-clockwise.
+    angle: f64,
+    /// Colors of the top left, top right, bottom right and bottom left corners of the text, if not
+    /// set then the current drawing color is used.
+    colors: Option<[u32; 4]>,
+    alpha: Option<f64>,
+}
+
+impl TextParams {
+    fn new(x: f64, y: f64) -> Self {
+        Self {
+            position: Vec2::new(x, y),
+            line_separation: -1.0,
+            max_width: -1.0,
+            scale: Vec2::splat(1.0),
+            angle: 0.0,
+            colors: None,
+            alpha: None,
+        }
+    }
+}
+
+/// Issue one quad per visible glyph of the given text.
+fn issue_text(
+    ctx: vm::Context<'_>,
+    text: &str,
+    params: TextParams,
+) -> Result<(), vm::RuntimeError> {
+    let depth = current_depth(ctx)?;
+    State::ctx_with(ctx, |state| {
+        DrawingState::ctx_with_mut(ctx, |drawing_state| {
+            let Some(font) = current_font(state, drawing_state) else {
+                return;
+            };
+
+            let options = layout_options(drawing_state, params.line_separation, params.max_width);
+            let layout = TextLayout::new(font, text, options);
+
+            let colors = params
+                .colors
+                .unwrap_or([drawing_state.color; 4])
+                .map(color_rgb);
+            let alpha = params.alpha.unwrap_or(drawing_state.alpha).clamp(0.0, 1.0) as f32;
+
+            // The corner colors are interpolated across the bounding box of the whole text, each
+            // glyph takes the color at its center.
+            let bounds = Box2::with_size(
+                Vec2::new(
+                    match options.halign {
+                        HAlign::Left => 0.0,
+                        HAlign::Center => -layout.size[0] / 2.0,
+                        HAlign::Right => -layout.size[0],
+                    },
+                    match options.valign {
+                        VAlign::Top => 0.0,
+                        VAlign::Middle => -layout.size[1] / 2.0,
+                        VAlign::Bottom => -layout.size[1],
+                    },
+                ),
+                layout.size,
+            );
+
+            let transform = Affine2::new()
+                .scale(params.scale)
+                .rotate(-params.angle.to_radians())
+                .translate(params.position);
+
+            for glyph in layout.glyphs {
+                let region = glyph.region.cast::<f64>();
+                let center = glyph.position + region.size() / 2.0;
+                drawing_state.push(DrawCommand::Quad(Quad {
+                    texture: glyph.texture,
+                    transform: Affine2::new()
+                        .translate(glyph.position - region.min)
+                        .then(transform)
+                        .cast(),
+                    region: region.cast(),
+                    depth,
+                    tint: corner_gradient(colors, bounds, center),
+                    alpha,
+                }));
+            }
+        })
+    })??;
+    Ok(())
+}
+
+fn corner_gradient(colors: [Vec3<f32>; 4], bounds: Box2<f64>, point: Vec2<f64>) -> Vec3<f32> {
+    let size = bounds.size();
+    let t = (point - bounds.min).zip(size, |p, s| {
+        if s > 0.0 {
+            (p / s).clamp(0.0, 1.0) as f32
+        } else {
+            0.0
+        }
+    });
+    let [top_left, top_right, bottom_right, bottom_left] = colors;
+    let top = top_left + (top_right - top_left) * t[0];
+    let bottom = bottom_left + (bottom_right - bottom_left) * t[0];
+    top + (bottom - top) * t[1]
+}
@@ -4,9 +4,9 @@ use fabricator_vm as vm;
 use gc_arena::{Collect, Gc, Rootable};
 
 use crate::{
-    api::{layer::find_layer, magic::MagicExt as _},
+    api::{drawing::SpriteUserData, layer::find_layer, magic::MagicExt as _},
     project::ObjectEvent,
-    state::{EventState, InstanceId, State},
+    state::{EventState, InstanceId, Motion, SpriteId, State},
 };
 
 #[derive(Debug, Copy, Clone, Collect)]
@@ -66,7 +66,47 @@ impl<'gc> vm::Singleton<'gc> for InstanceMethodsSingleton<'gc> {
                         ),
                         "x" => Some(instance.position[0].into()),
                         "y" => Some(instance.position[1].into()),
-                        "image_angle" => Some(instance.rotation.to_degrees().into()),
+                        "image_angle" => Some((-instance.rotation).to_degrees().into()),
+                        "image_xscale" => Some(instance.scale[0].into()),
+                        "image_yscale" => Some(instance.scale[1].into()),
+                        "image_alpha" => Some(instance.alpha.into()),
+                        "image_blend" => Some((instance.blend as i64).into()),
+                        "image_speed" => Some(instance.image_speed.into()),
+                        "visible" => Some(instance.visible.into()),
+                        "image_index" => Some(
+                            instance
+                                .sprite
+                                .map(|sprite_id| {
+                                    state.config.sprites[sprite_id]
+                                        .frame_at(instance.animation_time)
+                                        as i64
+                                })
+                                .unwrap_or(0)
+                                .into(),
+                        ),
+                        "image_number" => Some(
+                            instance
+                                .sprite
+                                .map(|sprite_id| {
+                                    state.config.sprites[sprite_id].frames.len() as i64
+                                })
+                                .unwrap_or(0)
+                                .into(),
+                        ),
+                        "sprite_index" => Some(sprite_value(ctx, state, instance.sprite)),
+                        "mask_index" => Some(sprite_value(ctx, state, instance.mask)),
+                        "xprevious" => Some(instance.previous_position[0].into()),
+                        "yprevious" => Some(instance.previous_position[1].into()),
+                        "xstart" => Some(instance.start_position[0].into()),
+                        "ystart" => Some(instance.start_position[1].into()),
+                        "speed" => Some(instance.motion.speed.into()),
+                        "direction" => Some(instance.motion.direction.into()),
+                        "hspeed" => Some(instance.motion.velocity()[0].into()),
+                        "vspeed" => Some(instance.motion.velocity()[1].into()),
+                        "gravity" => Some(instance.motion.gravity.into()),
+                        "gravity_direction" => Some(instance.motion.gravity_direction.into()),
+                        "friction" => Some(instance.motion.friction.into()),
+                        "alarm" => Some(InstanceAlarmsUserData::new(ctx, ud).into()),
                         _ => ctx.fetch(&instance.properties).try_find(key)?,
                     })
                 })?
@@ -103,6 +143,59 @@ impl<'gc> vm::Singleton<'gc> for InstanceMethodsSingleton<'gc> {
                         "object_index" => Err(vm::RuntimeError::msg(format!(
                             "`object_index` is read-only"
                         ))),
+                        "xprevious" => {
+                            instance.previous_position[0] = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "yprevious" => {
+                            instance.previous_position[1] = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "xstart" => {
+                            instance.start_position[0] = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "ystart" => {
+                            instance.start_position[1] = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "speed" => {
+                            instance.motion.speed = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "direction" => {
+                            let direction: f64 = vm::FromValue::from_value(ctx, value)?;
+                            instance.motion.direction = direction.rem_euclid(360.0);
+                            Ok(())
+                        }
+                        "hspeed" => {
+                            let mut velocity = instance.motion.velocity();
+                            velocity[0] = vm::FromValue::from_value(ctx, value)?;
+                            instance.motion.set_velocity(velocity);
+                            Ok(())
+                        }
+                        "vspeed" => {
+                            let mut velocity = instance.motion.velocity();
+                            velocity[1] = vm::FromValue::from_value(ctx, value)?;
+                            instance.motion.set_velocity(velocity);
+                            Ok(())
+                        }
+                        "gravity" => {
+                            instance.motion.gravity = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "gravity_direction" => {
+                            let direction: f64 = vm::FromValue::from_value(ctx, value)?;
+                            instance.motion.gravity_direction = direction.rem_euclid(360.0);
+                            Ok(())
+                        }
+                        "friction" => {
+                            instance.motion.friction = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "alarm" => Err(vm::RuntimeError::msg(
+                            "`alarm` can only be assigned by index",
+                        )),
                         "x" => {
                             instance.position[0] = vm::FromValue::from_value(ctx, value)?;
                             Ok(())
@@ -116,6 +209,52 @@ impl<'gc> vm::Singleton<'gc> for InstanceMethodsSingleton<'gc> {
                             instance.rotation = -angle_deg.to_radians() % (f64::consts::PI * 2.0);
                             Ok(())
                         }
+                        "image_xscale" => {
+                            instance.scale[0] = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "image_yscale" => {
+                            instance.scale[1] = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "image_alpha" => {
+                            let alpha: f64 = vm::FromValue::from_value(ctx, value)?;
+                            instance.alpha = alpha.clamp(0.0, 1.0);
+                            Ok(())
+                        }
+                        "image_blend" => {
+                            let blend: i64 = vm::FromValue::from_value(ctx, value)?;
+                            instance.blend = blend as u32 & 0xffffff;
+                            Ok(())
+                        }
+                        "image_speed" => {
+                            instance.image_speed = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "visible" => {
+                            instance.visible = vm::FromValue::from_value(ctx, value)?;
+                            Ok(())
+                        }
+                        "image_index" => {
+                            let index: f64 = vm::FromValue::from_value(ctx, value)?;
+                            if let Some(sprite_id) = instance.sprite {
+                                let sprite = &state.config.sprites[sprite_id];
+                                instance.animation_time = sprite.frame_start(
+                                    (index.floor() as i64).rem_euclid(sprite.frames.len() as i64)
+                                        as usize,
+                                );
+                            }
+                            Ok(())
+                        }
+                        "image_number" => Err(vm::RuntimeError::msg("`image_number` is read-only")),
+                        "sprite_index" => {
+                            instance.sprite = sprite_from_value(value)?;
+                            Ok(())
+                        }
+                        "mask_index" => {
+                            instance.mask = sprite_from_value(value)?;
+                            Ok(())
+                        }
                         _ => {
                             ctx.fetch(&instance.properties)
                                 .try_borrow_mut(&ctx)?
@@ -169,6 +308,126 @@ impl<'gc> vm::Singleton<'gc> for InstanceMethodsSingleton<'gc> {
     }
 }
 
+/// Returns the userdata for a sprite, or `-1` if there is no sprite.
+fn sprite_value<'gc>(
+    ctx: vm::Context<'gc>,
+    state: &State,
+    sprite: Option<SpriteId>,
+) -> vm::Value<'gc> {
+    match sprite {
+        Some(sprite_id) => ctx.fetch(&state.config.sprites[sprite_id].userdata).into(),
+        None => (-1i64).into(),
+    }
+}
+
+/// Accepts a sprite, or `-1` or `undefined` for no sprite.
+fn sprite_from_value<'gc>(value: vm::Value<'gc>) -> Result<Option<SpriteId>, vm::RuntimeError> {
+    match value {
+        vm::Value::UserData(ud) => Ok(Some(SpriteUserData::downcast(ud)?.id)),
+        vm::Value::Integer(-1) | vm::Value::Undefined => Ok(None),
+        _ => Err(vm::TypeError::new("sprite or -1", value.type_name()).into()),
+    }
+}
+
+/// The `alarm` array of an instance.
+///
+/// Reading and writing indexes reads and writes the instance's alarms directly.
+#[derive(Debug, Copy, Clone, Collect)]
+#[collect(no_drop)]
+struct InstanceAlarmsUserData<'gc> {
+    instance: vm::UserData<'gc>,
+}
+
+impl<'gc> InstanceAlarmsUserData<'gc> {
+    fn new(ctx: vm::Context<'gc>, instance: vm::UserData<'gc>) -> vm::UserData<'gc> {
+        let methods = ctx
+            .singleton::<Rootable![InstanceAlarmsMethodsSingleton<'_>]>()
+            .0;
+        let ud = vm::UserData::new::<Rootable![InstanceAlarmsUserData<'_>]>(
+            &ctx,
+            InstanceAlarmsUserData { instance },
+        );
+        ud.set_methods(&ctx, Some(methods));
+        ud
+    }
+
+    fn instance_id(ud: vm::UserData<'gc>) -> InstanceId {
+        let alarms = ud
+            .downcast::<Rootable![InstanceAlarmsUserData<'_>]>()
+            .unwrap();
+        InstanceUserData::downcast(alarms.instance).unwrap().id
+    }
+}
+
+#[derive(Collect)]
+#[collect(no_drop)]
+struct InstanceAlarmsMethodsSingleton<'gc>(Gc<'gc, dyn vm::UserDataMethods<'gc>>);
+
+impl<'gc> vm::Singleton<'gc> for InstanceAlarmsMethodsSingleton<'gc> {
+    fn create(ctx: vm::Context<'gc>) -> Self {
+        struct Methods;
+
+        impl<'gc> vm::UserDataMethods<'gc> for Methods {
+            fn get_index(
+                &self,
+                ud: vm::UserData<'gc>,
+                ctx: vm::Context<'gc>,
+                indexes: &[vm::Value<'gc>],
+            ) -> Result<vm::Value<'gc>, vm::RuntimeError> {
+                if indexes.len() != 1 {
+                    return Err(vm::RuntimeError::msg("alarm array expects 1 index"));
+                }
+                let alarm = alarm_index(ctx, indexes[0])?;
+                let instance_id = InstanceAlarmsUserData::instance_id(ud);
+                State::ctx_with(ctx, |state| {
+                    let instance = state
+                        .instances
+                        .get(instance_id)
+                        .ok_or_else(|| vm::RuntimeError::msg("expired instance"))?;
+                    Ok((instance.alarms[alarm] as i64).into())
+                })?
+            }
+
+            fn set_index(
+                &self,
+                ud: vm::UserData<'gc>,
+                ctx: vm::Context<'gc>,
+                indexes: &[vm::Value<'gc>],
+                value: vm::Value<'gc>,
+            ) -> Result<(), vm::RuntimeError> {
+                if indexes.len() != 1 {
+                    return Err(vm::RuntimeError::msg("alarm array expects 1 index"));
+                }
+                let alarm = alarm_index(ctx, indexes[0])?;
+                let ticks: f64 = vm::FromValue::from_value(ctx, value)?;
+                let instance_id = InstanceAlarmsUserData::instance_id(ud);
+                State::ctx_with_mut(ctx, |state| {
+                    let instance = state
+                        .instances
+                        .get_mut(instance_id)
+                        .ok_or_else(|| vm::RuntimeError::msg("expired instance"))?;
+                    instance.alarms[alarm] = ticks as i32;
+                    Ok(())
+                })?
+            }
+        }
+
+        let methods = ctx.alloc_static(Methods);
+        Self(gc_arena::unsize!(methods => dyn vm::UserDataMethods<'gc>))
+    }
+}
+
+fn alarm_index<'gc>(
+    ctx: vm::Context<'gc>,
+    index: vm::Value<'gc>,
+) -> Result<usize, vm::RuntimeError> {
+    let index: i64 = vm::FromValue::from_value(ctx, index)?;
+    usize::try_from(index)
+        .ok()
+        .filter(|&i| i < ObjectEvent::ALARM_COUNT)
+        .ok_or_else(|| vm::RuntimeError::msg(format!("alarm index {index} out of range")))
+}
+
 pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
     let mut magic = vm::MagicSet::new();
 
@@ -178,6 +437,8 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         Destroy,
         CleanUp,
         Step,
+        Alarm,
+        Draw,
         Other,
     }
 
@@ -188,6 +449,18 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         End,
     }
 
+    #[derive(Debug, Copy, Clone)]
+    enum DrawEvent {
+        Normal,
+        Begin,
+        End,
+        Pre,
+        Post,
+        GuiBegin,
+        Gui,
+        GuiEnd,
+    }
+
     #[derive(Debug, Copy, Clone)]
     enum OtherEvent {
         RoomStart,
@@ -199,6 +472,8 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         (EventType::Destroy, "ev_destroy"),
         (EventType::CleanUp, "ev_cleanup"),
         (EventType::Step, "ev_step"),
+        (EventType::Alarm, "ev_alarm"),
+        (EventType::Draw, "ev_draw"),
         (EventType::Other, "ev_other"),
     ] {
         magic
@@ -224,6 +499,25 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
             .unwrap();
     }
 
+    for (draw_event, name) in [
+        (DrawEvent::Normal, "ev_draw_normal"),
+        (DrawEvent::Begin, "ev_draw_begin"),
+        (DrawEvent::End, "ev_draw_end"),
+        (DrawEvent::Pre, "ev_draw_pre"),
+        (DrawEvent::Post, "ev_draw_post"),
+        (DrawEvent::GuiBegin, "ev_gui_begin"),
+        (DrawEvent::Gui, "ev_gui"),
+        (DrawEvent::GuiEnd, "ev_gui_end"),
+    ] {
+        magic
+            .add_constant(
+                ctx,
+                ctx.intern_static(name),
+                vm::UserData::new_static(&ctx, draw_event),
+            )
+            .unwrap();
+    }
+
     for (other_event, name) in [
         (OtherEvent::RoomStart, "ev_room_start"),
         (OtherEvent::RoomEnd, "ev_room_end"),
@@ -238,7 +532,7 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
     }
 
     let event_perform = vm::Callback::from_fn(ctx, |ctx, mut exec| {
-        let (event_type, sub_event): (vm::UserData, Option<vm::UserData>) =
+        let (event_type, sub_event): (vm::UserData, Option<vm::Value>) =
             exec.stack().consume(ctx)?;
 
         let mut instance_ud_id = None;
@@ -263,20 +557,47 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
             EventType::Destroy => ObjectEvent::Destroy,
             EventType::CleanUp => ObjectEvent::CleanUp,
             EventType::Step => {
-                match *sub_event
-                    .ok_or_else(|| vm::RuntimeError::msg("expected sub-event for `ev_step`"))?
-                    .downcast_static::<StepEvent>()?
-                {
+                let sub_event: vm::UserData = vm::FromValue::from_value(
+                    ctx,
+                    sub_event
+                        .ok_or_else(|| vm::RuntimeError::msg("expected sub-event for `ev_step`"))?,
+                )?;
+                match *sub_event.downcast_static::<StepEvent>()? {
                     StepEvent::Normal => ObjectEvent::Step,
                     StepEvent::Begin => ObjectEvent::BeginStep,
                     StepEvent::End => ObjectEvent::EndStep,
                 }
             }
+            EventType::Alarm => {
+                let sub_event = sub_event
+                    .ok_or_else(|| vm::RuntimeError::msg("expected sub-event for `ev_alarm`"))?;
+                ObjectEvent::Alarm(alarm_index(ctx, sub_event)? as u8)
+            }
+            // The normal draw event has no sub-event in GML.
+            EventType::Draw => match sub_event {
+                None => ObjectEvent::Draw,
+                Some(sub_event) => {
+                    let sub_event: vm::UserData = vm::FromValue::from_value(ctx, sub_event)?;
+                    match *sub_event.downcast_static::<DrawEvent>()? {
+                        DrawEvent::Normal => ObjectEvent::Draw,
+                        DrawEvent::Begin => ObjectEvent::DrawBegin,
+                        DrawEvent::End => ObjectEvent::DrawEnd,
+                        DrawEvent::Pre => ObjectEvent::PreDraw,
+                        DrawEvent::Post => ObjectEvent::PostDraw,
+                        DrawEvent::GuiBegin => ObjectEvent::DrawGuiBegin,
+                        DrawEvent::Gui => ObjectEvent::DrawGui,
+                        DrawEvent::GuiEnd => ObjectEvent::DrawGuiEnd,
+                    }
+                }
+            },
             EventType::Other => {
-                match *sub_event
-                    .ok_or_else(|| vm::RuntimeError::msg("expected sub-event for `ev_other`"))?
-                    .downcast_static::<OtherEvent>()?
-                {
+                let sub_event: vm::UserData = vm::FromValue::from_value(
+                    ctx,
+                    sub_event.ok_or_else(|| {
+                        vm::RuntimeError::msg("expected sub-event for `ev_other`")
+                    })?,
+                )?;
+                match *sub_event.downcast_static::<OtherEvent>()? {
                     OtherEvent::RoomStart => ObjectEvent::RoomStart,
                     OtherEvent::RoomEnd => ObjectEvent::RoomEnd,
                 }
@@ -338,6 +659,62 @@ pub fn instance_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         .add_constant(ctx, ctx.intern_static("event_inherited"), event_inherited)
         .unwrap();
 
+    let motion_set = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (direction, speed): (f64, f64) = exec.stack().consume(ctx)?;
+        let instance_id = EventState::ctx_with(ctx, |state| state.instance_id)?;
+        State::ctx_with_mut(ctx, |state| {
+            let motion = &mut state.instances[instance_id].motion;
+            motion.direction = direction.rem_euclid(360.0);
+            motion.speed = speed;
+        })?;
+        Ok(())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("motion_set"), motion_set)
+        .unwrap();
+
+    let motion_add = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (direction, speed): (f64, f64) = exec.stack().consume(ctx)?;
+        let instance_id = EventState::ctx_with(ctx, |state| state.instance_id)?;
+        State::ctx_with_mut(ctx, |state| {
+            let motion = &mut state.instances[instance_id].motion;
+            let added = Motion {
+                direction,
+                speed,
+                ..Default::default()
+            };
+            motion.set_velocity(motion.velocity() + added.velocity());
+        })?;
+        Ok(())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("motion_add"), motion_add)
+        .unwrap();
+
+    let alarm_get = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let alarm = alarm_index(ctx, exec.stack().consume(ctx)?)?;
+        let instance_id = EventState::ctx_with(ctx, |state| state.instance_id)?;
+        let ticks = State::ctx_with(ctx, |state| state.instances[instance_id].alarms[alarm])?;
+        exec.stack().replace(ctx, ticks);
+        Ok(())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("alarm_get"), alarm_get)
+        .unwrap();
+
+    let alarm_set = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (alarm, ticks): (vm::Value, f64) = exec.stack().consume(ctx)?;
+        let alarm = alarm_index(ctx, alarm)?;
+        let instance_id = EventState::ctx_with(ctx, |state| state.instance_id)?;
+        State::ctx_with_mut(ctx, |state| {
+            state.instances[instance_id].alarms[alarm] = ticks as i32;
+        })?;
+        Ok(())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("alarm_set"), alarm_set)
+        .unwrap();
+
     let instance_deactivate_layer = vm::Callback::from_fn(ctx, |ctx, mut exec| {
         let layer_id_or_name: vm::Value = exec.stack().consume(ctx)?;
         let to_deactivate = State::ctx_with(ctx, |state| -> Result<_, vm::RuntimeError> {
@@ -1,6 +1,8 @@
 pub mod asset;
+pub mod camera;
 pub mod collision;
 pub mod drawing;
+pub mod exception;
 pub mod font;
 pub mod id_user_data;
 pub mod instance;
@@ -12,4 +14,5 @@ pub mod platform;
 pub mod room;
 pub mod sound;
 pub mod stub;
+pub mod surface;
 pub mod tile;
@@ -10,7 +10,9 @@ use crate::{
         magic::{DuplicateMagicName, MagicExt as _},
     },
     project::ObjectEvent,
-    state::{Configuration, EventState, Instance, InstanceId, ObjectId, State, state::Layer},
+    state::{
+        Configuration, EventState, Instance, InstanceId, Motion, ObjectId, State, state::Layer,
+    },
 };
 
 #[derive(Debug, Copy, Clone, Collect)]
@@ -403,6 +405,8 @@ pub fn object_api<'gc>(
                 });
 
                 let event_closures = state.event_closures(object.id);
+                let object_config = &state.config.objects[object.id];
+                let (sprite, mask) = (object_config.sprite, object_config.mask);
 
                 let instance_id = state.instances.insert_with_id(|instance_id| Instance {
                     this: ctx.stash(InstanceUserData::new(ctx, instance_id)),
@@ -410,11 +414,22 @@ pub fn object_api<'gc>(
                     active: true,
                     dead: false,
                     position: Vec2::new(x, y),
+                    previous_position: Vec2::new(x, y),
+                    start_position: Vec2::new(x, y),
+                    motion: Motion::default(),
+                    sprite,
+                    mask,
+                    scale: Vec2::splat(1.0),
                     rotation: 0.0,
+                    alpha: 1.0,
+                    blend: 0xffffff,
+                    image_speed: 1.0,
+                    visible: true,
                     layer: layer_id,
                     properties: ctx.stash(vm::Object::with_parts(&ctx, properties, None)),
                     event_closures,
                     animation_time: 0.0,
+                    alarms: [-1; ObjectEvent::ALARM_COUNT],
                 });
 
                 assert!(
@@ -489,6 +504,8 @@ pub fn object_api<'gc>(
                 }
 
                 let event_closures = state.event_closures(object.id);
+                let object_config = &state.config.objects[object.id];
+                let (sprite, mask) = (object_config.sprite, object_config.mask);
 
                 let instance_id = state.instances.insert_with_id(|instance_id| Instance {
                     this: ctx.stash(InstanceUserData::new(ctx, instance_id)),
@@ -496,11 +513,22 @@ pub fn object_api<'gc>(
                     active: true,
                     dead: false,
                     position: Vec2::new(x, y),
+                    previous_position: Vec2::new(x, y),
+                    start_position: Vec2::new(x, y),
+                    motion: Motion::default(),
+                    sprite,
+                    mask,
+                    scale: Vec2::splat(1.0),
                     rotation: 0.0,
+                    alpha: 1.0,
+                    blend: 0xffffff,
+                    image_speed: 1.0,
+                    visible: true,
                     layer: layer_id,
                     properties: ctx.stash(vm::Object::with_parts(&ctx, properties, None)),
                     event_closures,
                     animation_time: 0.0,
+                    alarms: [-1; ObjectEvent::ALARM_COUNT],
                 });
 
                 assert!(
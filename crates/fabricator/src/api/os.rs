@@ -63,6 +63,41 @@ pub fn os_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         )
         .unwrap();
 
+    let get_timer = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let micros = State::ctx_with_mut(ctx, |state| state.clock.read())?;
+        exec.stack().replace(ctx, micros as i64);
+        Ok(())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("get_timer"), get_timer)
+        .unwrap();
+
+    magic
+        .add(
+            ctx.intern_static("current_time"),
+            create_magic_ro(ctx, |ctx| {
+                let micros = State::ctx_with_mut(ctx, |state| state.clock.read())?;
+                Ok(((micros / 1000) as i64).into())
+            }),
+        )
+        .unwrap();
+
+    let show_error = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let arg: vm::String = exec.stack().consume(ctx)?;
+        Err(vm::RuntimeError::msg(arg.as_str().to_owned()).into())
+    });
+    magic
+        .add_constant(ctx, ctx.intern_static("show_error"), show_error)
+        .unwrap();
+
+    magic
+}
+
+/// OS functions which access the filesystem or the host OS environment, and so are not available
+/// to sandboxed scripts.
+pub fn os_host_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
+    let mut magic = vm::MagicSet::new();
+
     let environment_get_variable = vm::Callback::from_fn(ctx, |ctx, mut exec| {
         let var_name: vm::String = exec.stack().consume(ctx)?;
         let env_var = match env::var(var_name.as_str()) {
@@ -92,36 +127,6 @@ pub fn os_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         .add_constant(ctx, ctx.intern_static("file_exists"), file_exists)
         .unwrap();
 
-    let get_timer = vm::Callback::from_fn(ctx, |ctx, mut exec| {
-        State::ctx_with(ctx, |state| {
-            exec.stack()
-                .replace(ctx, state.start_instant.elapsed().as_micros() as i64);
-            Ok(())
-        })?
-    });
-    magic
-        .add_constant(ctx, ctx.intern_static("get_timer"), get_timer)
-        .unwrap();
-
-    magic
-        .add(
-            ctx.intern_static("current_time"),
-            create_magic_ro(ctx, |ctx| {
-                State::ctx_with(ctx, |state| {
-                    Ok((state.start_instant.elapsed().as_millis() as i64).into())
-                })?
-            }),
-        )
-        .unwrap();
-
-    let show_error = vm::Callback::from_fn(ctx, |ctx, mut exec| {
-        let arg: vm::String = exec.stack().consume(ctx)?;
-        Err(vm::RuntimeError::msg(arg.as_str().to_owned()).into())
-    });
-    magic
-        .add_constant(ctx, ctx.intern_static("show_error"), show_error)
-        .unwrap();
-
     let buffer_load = vm::Callback::from_fn(ctx, |ctx, mut exec| {
         State::ctx_with(ctx, |state| {
             let file_name: vm::String = exec.stack().consume(ctx)?;
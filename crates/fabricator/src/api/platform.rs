@@ -1,27 +1,89 @@
+use fabricator_math::Vec2;
 use fabricator_vm::{self as vm};
 
 use crate::{
-    api::magic::{MagicExt as _, create_magic_ro},
-    state::{InputState, MouseButtons},
+    api::magic::{MagicExt as _, create_magic_ro, create_magic_rw},
+    state::{
+        GAMEPAD_SLOTS, Gamepad, GamepadAxis, GamepadButton, InputState, KeyboardState,
+        MouseButtons, State, VirtualKey,
+    },
 };
 
+/// The mouse position in room coordinates, as seen through the view under the mouse.
+fn mouse_room_position<'gc>(ctx: vm::Context<'gc>) -> Result<Vec2<f64>, vm::RuntimeError> {
+    let screen_position = InputState::ctx_with(ctx, |input| input.mouse_position.cast::<f64>())?;
+    Ok(State::ctx_with(ctx, |state| {
+        state.views.screen_to_room(&state.cameras, screen_position)
+    })?)
+}
+
 pub fn platform_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
     let mut magic = vm::MagicSet::new();
 
-    let mouse_x_magic = create_magic_ro(ctx, |ctx| {
-        InputState::ctx_with(ctx, |input| Ok((input.mouse_position[0] as f64).into()))?
-    });
+    let mouse_x_magic = create_magic_ro(ctx, |ctx| Ok(mouse_room_position(ctx)?[0].into()));
     magic
         .add(ctx.intern_static("mouse_x"), mouse_x_magic)
         .unwrap();
 
-    let mouse_y_magic = create_magic_ro(ctx, |ctx| {
-        InputState::ctx_with(ctx, |input| Ok((input.mouse_position[1] as f64).into()))?
-    });
+    let mouse_y_magic = create_magic_ro(ctx, |ctx| Ok(mouse_room_position(ctx)?[1].into()));
     magic
         .add(ctx.intern_static("mouse_y"), mouse_y_magic)
         .unwrap();
 
+    for (name, axis) in [("device_mouse_x_to_gui", 0), ("device_mouse_y_to_gui", 1)] {
+        let callback = vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let _device: Option<i64> = exec.stack().consume(ctx)?;
+            let position = InputState::ctx_with(ctx, |input| input.mouse_position.cast::<f64>())?;
+            let (screen_size, gui_size) = State::ctx_with(ctx, |state| {
+                (
+                    state.screen_size().cast::<f64>(),
+                    state.current_gui_size().cast::<f64>(),
+                )
+            })?;
+            let gui_position = if screen_size[axis] > 0.0 {
+                position[axis] * gui_size[axis] / screen_size[axis]
+            } else {
+                position[axis]
+            };
+            exec.stack().replace(ctx, gui_position);
+            Ok(())
+        });
+        magic
+            .add_constant(ctx, ctx.intern_static(name), callback)
+            .unwrap();
+    }
+
+    // Setting a GUI size of zero or less resets GUI space to match screen space.
+    let display_set_gui_size = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (width, height): (f64, f64) = exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| {
+            state.gui_size = if width > 0.0 && height > 0.0 {
+                Some(Vec2::new(width as u32, height as u32))
+            } else {
+                None
+            };
+        })?;
+        Ok(())
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("display_set_gui_size"),
+            display_set_gui_size,
+        )
+        .unwrap();
+
+    for (name, axis) in [("display_get_gui_width", 0), ("display_get_gui_height", 1)] {
+        let callback = vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let gui_size = State::ctx_with(ctx, |state| state.current_gui_size())?;
+            exec.stack().replace(ctx, gui_size[axis] as i64);
+            Ok(())
+        });
+        magic
+            .add_constant(ctx, ctx.intern_static(name), callback)
+            .unwrap();
+    }
+
     for (name, bits) in [
         ("mb_left", MouseButtons::Left.bits()),
         ("mb_middle", MouseButtons::Middle.bits()),
@@ -49,5 +111,352 @@ pub fn platform_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
         )
         .unwrap();
 
+    let mut virtual_keys = vec![
+        ("vk_nokey", VirtualKey::NO_KEY),
+        ("vk_anykey", VirtualKey::ANY_KEY),
+        ("vk_backspace", VirtualKey::BACKSPACE),
+        ("vk_tab", VirtualKey::TAB),
+        ("vk_enter", VirtualKey::ENTER),
+        ("vk_return", VirtualKey::ENTER),
+        ("vk_shift", VirtualKey::SHIFT),
+        ("vk_control", VirtualKey::CONTROL),
+        ("vk_alt", VirtualKey::ALT),
+        ("vk_pause", VirtualKey::PAUSE),
+        ("vk_escape", VirtualKey::ESCAPE),
+        ("vk_space", VirtualKey::SPACE),
+        ("vk_pageup", VirtualKey::PAGE_UP),
+        ("vk_pagedown", VirtualKey::PAGE_DOWN),
+        ("vk_end", VirtualKey::END),
+        ("vk_home", VirtualKey::HOME),
+        ("vk_left", VirtualKey::LEFT),
+        ("vk_up", VirtualKey::UP),
+        ("vk_right", VirtualKey::RIGHT),
+        ("vk_down", VirtualKey::DOWN),
+        ("vk_printscreen", VirtualKey::PRINT_SCREEN),
+        ("vk_insert", VirtualKey::INSERT),
+        ("vk_delete", VirtualKey::DELETE),
+        ("vk_multiply", VirtualKey::MULTIPLY),
+        ("vk_add", VirtualKey::ADD),
+        ("vk_subtract", VirtualKey::SUBTRACT),
+        ("vk_decimal", VirtualKey::DECIMAL),
+        ("vk_divide", VirtualKey::DIVIDE),
+        ("vk_lshift", VirtualKey::LEFT_SHIFT),
+        ("vk_rshift", VirtualKey::RIGHT_SHIFT),
+        ("vk_lcontrol", VirtualKey::LEFT_CONTROL),
+        ("vk_rcontrol", VirtualKey::RIGHT_CONTROL),
+        ("vk_lalt", VirtualKey::LEFT_ALT),
+        ("vk_ralt", VirtualKey::RIGHT_ALT),
+    ];
+    let numpad_names = [
+        "vk_numpad0",
+        "vk_numpad1",
+        "vk_numpad2",
+        "vk_numpad3",
+        "vk_numpad4",
+        "vk_numpad5",
+        "vk_numpad6",
+        "vk_numpad7",
+        "vk_numpad8",
+        "vk_numpad9",
+    ];
+    for (n, name) in numpad_names.into_iter().enumerate() {
+        virtual_keys.push((name, VirtualKey::numpad(n as u8)));
+    }
+    let function_names = [
+        "vk_f1", "vk_f2", "vk_f3", "vk_f4", "vk_f5", "vk_f6", "vk_f7", "vk_f8", "vk_f9", "vk_f10",
+        "vk_f11", "vk_f12",
+    ];
+    for (n, name) in function_names.into_iter().enumerate() {
+        virtual_keys.push((name, VirtualKey::function(n as u8 + 1)));
+    }
+
+    for (name, key) in virtual_keys {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), key.0 as i64)
+            .unwrap();
+    }
+
+    fn key_check<'gc>(
+        ctx: vm::Context<'gc>,
+        check: impl Fn(&KeyboardState, VirtualKey) -> bool + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let key: i64 = exec.stack().consume(ctx)?;
+            InputState::ctx_with(ctx, |input| {
+                let res =
+                    u8::try_from(key).is_ok_and(|key| check(&input.keyboard, VirtualKey(key)));
+                exec.stack().replace(ctx, res);
+                Ok(())
+            })?
+        })
+    }
+
+    for (name, callback) in [
+        ("keyboard_check", key_check(ctx, KeyboardState::is_held)),
+        (
+            "keyboard_check_direct",
+            key_check(ctx, KeyboardState::is_held),
+        ),
+        (
+            "keyboard_check_pressed",
+            key_check(ctx, KeyboardState::is_pressed),
+        ),
+        (
+            "keyboard_check_released",
+            key_check(ctx, KeyboardState::is_released),
+        ),
+    ] {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), callback)
+            .unwrap();
+    }
+
+    let keyboard_lastkey_magic = create_magic_rw(
+        ctx,
+        |ctx| {
+            InputState::ctx_with(ctx, |input| {
+                let key = input.keyboard.last_key().unwrap_or(VirtualKey::NO_KEY);
+                Ok((key.0 as i64).into())
+            })?
+        },
+        |ctx, value| {
+            let key: i64 = vm::FromValue::from_value(ctx, value)?;
+            let key = u8::try_from(key)
+                .ok()
+                .map(VirtualKey)
+                .filter(|&key| key != VirtualKey::NO_KEY);
+            InputState::ctx_with_mut(ctx, |input| input.keyboard.set_last_key(key))?;
+            Ok(())
+        },
+    );
+    magic
+        .add(
+            ctx.intern_static("keyboard_lastkey"),
+            keyboard_lastkey_magic,
+        )
+        .unwrap();
+
+    let keyboard_lastchar_magic = create_magic_rw(
+        ctx,
+        |ctx| {
+            InputState::ctx_with(ctx, |input| {
+                let mut buf = [0; 4];
+                let last_char = match input.keyboard.last_char() {
+                    Some(c) => c.encode_utf8(&mut buf),
+                    None => "",
+                };
+                Ok(ctx.intern(last_char).into())
+            })?
+        },
+        |ctx, value| {
+            let last_char: vm::String = vm::FromValue::from_value(ctx, value)?;
+            let last_char = last_char.as_str().chars().next();
+            InputState::ctx_with_mut(ctx, |input| input.keyboard.set_last_char(last_char))?;
+            Ok(())
+        },
+    );
+    magic
+        .add(
+            ctx.intern_static("keyboard_lastchar"),
+            keyboard_lastchar_magic,
+        )
+        .unwrap();
+
+    let keyboard_string_magic = create_magic_rw(
+        ctx,
+        |ctx| InputState::ctx_with(ctx, |input| Ok(ctx.intern(input.keyboard.string()).into()))?,
+        |ctx, value| {
+            let string: vm::String = vm::FromValue::from_value(ctx, value)?;
+            InputState::ctx_with_mut(ctx, |input| input.keyboard.set_string(string.as_str()))?;
+            Ok(())
+        },
+    );
+    magic
+        .add(ctx.intern_static("keyboard_string"), keyboard_string_magic)
+        .unwrap();
+
+    for (name, button) in [
+        ("gp_face1", GamepadButton::FACE1),
+        ("gp_face2", GamepadButton::FACE2),
+        ("gp_face3", GamepadButton::FACE3),
+        ("gp_face4", GamepadButton::FACE4),
+        ("gp_shoulderl", GamepadButton::SHOULDER_L),
+        ("gp_shoulderr", GamepadButton::SHOULDER_R),
+        ("gp_shoulderlb", GamepadButton::SHOULDER_LB),
+        ("gp_shoulderrb", GamepadButton::SHOULDER_RB),
+        ("gp_select", GamepadButton::SELECT),
+        ("gp_start", GamepadButton::START),
+        ("gp_stickl", GamepadButton::STICK_L),
+        ("gp_stickr", GamepadButton::STICK_R),
+        ("gp_padu", GamepadButton::PAD_U),
+        ("gp_padd", GamepadButton::PAD_D),
+        ("gp_padl", GamepadButton::PAD_L),
+        ("gp_padr", GamepadButton::PAD_R),
+        ("gp_paddler", GamepadButton::PADDLE_R),
+        ("gp_paddlel", GamepadButton::PADDLE_L),
+        ("gp_paddlerb", GamepadButton::PADDLE_RB),
+        ("gp_paddlelb", GamepadButton::PADDLE_LB),
+    ] {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), button.0 as i64)
+            .unwrap();
+    }
+
+    for (name, axis) in [
+        ("gp_axislh", GamepadAxis::LEFT_H),
+        ("gp_axislv", GamepadAxis::LEFT_V),
+        ("gp_axisrh", GamepadAxis::RIGHT_H),
+        ("gp_axisrv", GamepadAxis::RIGHT_V),
+    ] {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), axis.0 as i64)
+            .unwrap();
+    }
+
+    let gamepad_get_device_count = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        exec.stack().replace(ctx, GAMEPAD_SLOTS as i64);
+        Ok(())
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("gamepad_get_device_count"),
+            gamepad_get_device_count,
+        )
+        .unwrap();
+
+    let gamepad_is_supported = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        exec.stack().replace(ctx, true);
+        Ok(())
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("gamepad_is_supported"),
+            gamepad_is_supported,
+        )
+        .unwrap();
+
+    let gamepad_is_connected = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let device: i64 = exec.stack().consume(ctx)?;
+        InputState::ctx_with(ctx, |input| {
+            let connected =
+                usize::try_from(device).is_ok_and(|device| input.gamepads.get(device).is_some());
+            exec.stack().replace(ctx, connected);
+            Ok(())
+        })?
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("gamepad_is_connected"),
+            gamepad_is_connected,
+        )
+        .unwrap();
+
+    // Checking a disconnected or invalid device is not an error, it is the same as checking a
+    // gamepad with nothing pressed.
+    fn button_check<'gc>(
+        ctx: vm::Context<'gc>,
+        check: impl Fn(&Gamepad, GamepadButton) -> bool + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let (device, button): (i64, i64) = exec.stack().consume(ctx)?;
+            InputState::ctx_with(ctx, |input| {
+                let res = usize::try_from(device)
+                    .ok()
+                    .and_then(|device| input.gamepads.get(device))
+                    .zip(u16::try_from(button).ok())
+                    .is_some_and(|(gamepad, button)| check(gamepad, GamepadButton(button)));
+                exec.stack().replace(ctx, res);
+                Ok(())
+            })?
+        })
+    }
+
+    for (name, callback) in [
+        ("gamepad_button_check", button_check(ctx, Gamepad::is_held)),
+        (
+            "gamepad_button_check_pressed",
+            button_check(ctx, Gamepad::is_pressed),
+        ),
+        (
+            "gamepad_button_check_released",
+            button_check(ctx, Gamepad::is_released),
+        ),
+    ] {
+        magic
+            .add_constant(ctx, ctx.intern_static(name), callback)
+            .unwrap();
+    }
+
+    let gamepad_axis_value = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (device, axis): (i64, i64) = exec.stack().consume(ctx)?;
+        let Ok(device) = usize::try_from(device) else {
+            exec.stack().replace(ctx, 0.0);
+            return Ok(());
+        };
+        let deadzone = State::ctx_with(ctx, |state| {
+            state.gamepad_deadzones.get(device).copied().unwrap_or(0.0)
+        })?;
+        InputState::ctx_with(ctx, |input| {
+            let value = input
+                .gamepads
+                .get(device)
+                .zip(u16::try_from(axis).ok())
+                .map_or(0.0, |(gamepad, axis)| {
+                    gamepad.axis(GamepadAxis(axis), deadzone)
+                });
+            exec.stack().replace(ctx, value);
+            Ok(())
+        })?
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("gamepad_axis_value"),
+            gamepad_axis_value,
+        )
+        .unwrap();
+
+    let gamepad_set_axis_deadzone = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (device, deadzone): (i64, f64) = exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| {
+            if let Some(d) = usize::try_from(device)
+                .ok()
+                .and_then(|device| state.gamepad_deadzones.get_mut(device))
+            {
+                *d = deadzone.clamp(0.0, 1.0);
+            }
+        })?;
+        Ok(())
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("gamepad_set_axis_deadzone"),
+            gamepad_set_axis_deadzone,
+        )
+        .unwrap();
+
+    let gamepad_get_axis_deadzone = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let device: i64 = exec.stack().consume(ctx)?;
+        let deadzone = State::ctx_with(ctx, |state| {
+            usize::try_from(device)
+                .ok()
+                .and_then(|device| state.gamepad_deadzones.get(device).copied())
+                .unwrap_or(0.0)
+        })?;
+        exec.stack().replace(ctx, deadzone);
+        Ok(())
+    });
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("gamepad_get_axis_deadzone"),
+            gamepad_get_axis_deadzone,
+        )
+        .unwrap();
+
     magic
 }
@@ -1,14 +1,109 @@
+use fabricator_math::Vec3;
 use fabricator_vm as vm;
 
 use crate::{
     api::{
-        id_user_data::NamedIdUserData,
+        id_user_data::{IdUserData, NamedIdUserData},
         magic::{DuplicateMagicName, MagicExt as _},
     },
-    state::{Configuration, State, configuration::SoundId},
+    state::{
+        AudioState, Configuration, Emitter, EmitterId, State, Voice, VoiceId,
+        audio::{Falloff, FalloffModel, Gain, VoicePosition},
+        configuration::SoundId,
+    },
 };
 
 pub type SoundUserData<'gc> = NamedIdUserData<'gc, SoundId>;
+pub type VoiceUserData = IdUserData<VoiceId>;
+pub type EmitterUserData = IdUserData<EmitterId>;
+
+pub fn find_emitter<'gc>(
+    state: &State,
+    emitter: vm::UserData<'gc>,
+) -> Result<EmitterId, vm::RuntimeError> {
+    let id = EmitterUserData::downcast(emitter)?.id;
+    if state.audio.emitters.contains(id) {
+        Ok(id)
+    } else {
+        Err(vm::RuntimeError::msg("expired audio emitter ID"))
+    }
+}
+
+/// Most audio functions accept either a sound asset, which affects every voice of that sound, or a
+/// single voice returned by one of the `audio_play_sound` functions.
+#[derive(Debug, Copy, Clone)]
+enum SoundOrVoice {
+    Sound(SoundId),
+    Voice(VoiceId),
+}
+
+impl SoundOrVoice {
+    fn from_userdata(ud: vm::UserData<'_>) -> Result<Self, vm::RuntimeError> {
+        if let Ok(voice) = VoiceUserData::downcast(ud) {
+            Ok(SoundOrVoice::Voice(voice.id))
+        } else {
+            Ok(SoundOrVoice::Sound(SoundUserData::downcast(ud)?.id))
+        }
+    }
+
+    /// Call `f` on every voice this refers to, voices which have finished playing are ignored.
+    fn for_each_voice(self, audio: &mut AudioState, mut f: impl FnMut(&mut Voice)) {
+        match self {
+            SoundOrVoice::Sound(sound_id) => {
+                for voice in audio.voices.values_mut() {
+                    if voice.sound == sound_id {
+                        f(voice);
+                    }
+                }
+            }
+            SoundOrVoice::Voice(voice_id) => {
+                if let Some(voice) = audio.voices.get_mut(voice_id) {
+                    f(voice);
+                }
+            }
+        }
+    }
+}
+
+struct PlayOptions {
+    sound: SoundId,
+    priority: f64,
+    looping: bool,
+    gain: Option<f64>,
+    /// Start position in seconds.
+    offset: Option<f64>,
+    pitch: Option<f64>,
+    position: Option<VoicePosition>,
+}
+
+/// Start playing a sound, returning the new voice or -1 if the sound could not be played.
+fn play_sound<'gc>(
+    ctx: vm::Context<'gc>,
+    state: &mut State,
+    options: PlayOptions,
+) -> vm::Value<'gc> {
+    let sound_file = &state.config.sounds[options.sound].sound_file;
+    let Some(data) = state.audio.sound_data(options.sound, sound_file) else {
+        return vm::Value::Integer(-1);
+    };
+
+    let voice_id = state.audio.play(options.priority, |id| {
+        let track_position = options.offset.unwrap_or(0.0).max(0.0) * data.sample_rate as f64;
+        let mut voice = Voice::new(ctx.stash(VoiceUserData::new(ctx, id)), options.sound, data);
+        voice.priority = options.priority;
+        voice.looping = options.looping;
+        voice.gain = Gain::new(options.gain.unwrap_or(1.0).max(0.0));
+        voice.pitch = options.pitch.unwrap_or(1.0);
+        voice.track_position = track_position;
+        voice.position = options.position;
+        voice
+    });
+
+    match voice_id {
+        Some(voice_id) => ctx.fetch(&state.audio.voices[voice_id].this).into(),
+        None => vm::Value::Integer(-1),
+    }
+}
 
 pub fn sound_api<'gc>(
     ctx: vm::Context<'gc>,
@@ -28,8 +123,674 @@ pub fn sound_api<'gc>(
         Ok(())
     });
     magic
-        .add_constant(ctx, ctx.intern_static("audio_sound_length"), audio_sound_length)
+        .add_constant(
+            ctx,
+            ctx.intern_static("audio_sound_length"),
+            audio_sound_length,
+        )
         .unwrap();
 
+    let audio_play_sound = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sound, priority, looping, gain, offset, pitch): (
+            vm::UserData,
+            f64,
+            bool,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+        ) = exec.stack().consume(ctx)?;
+        let sound = SoundUserData::downcast(sound)?.id;
+
+        let voice = State::ctx_with_mut(ctx, |state| {
+            play_sound(
+                ctx,
+                state,
+                PlayOptions {
+                    sound,
+                    priority,
+                    looping,
+                    gain,
+                    offset,
+                    pitch,
+                    position: None,
+                },
+            )
+        })?;
+        exec.stack().replace(ctx, voice);
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("audio_play_sound"), audio_play_sound)?;
+
+    let audio_play_sound_at = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (
+            sound,
+            x,
+            y,
+            z,
+            falloff_ref,
+            falloff_max,
+            falloff_factor,
+            looping,
+            priority,
+            gain,
+            offset,
+            pitch,
+        ): (
+            vm::UserData,
+            f64,
+            f64,
+            f64,
+            f64,
+            f64,
+            f64,
+            bool,
+            f64,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+        ) = exec.stack().consume(ctx)?;
+        let sound = SoundUserData::downcast(sound)?.id;
+
+        let falloff = Falloff {
+            reference: falloff_ref,
+            max: falloff_max,
+            factor: falloff_factor,
+        };
+        let voice = State::ctx_with_mut(ctx, |state| {
+            play_sound(
+                ctx,
+                state,
+                PlayOptions {
+                    sound,
+                    priority,
+                    looping,
+                    gain,
+                    offset,
+                    pitch,
+                    position: Some(VoicePosition::Point(Vec3::new(x, y, z), falloff)),
+                },
+            )
+        })?;
+        exec.stack().replace(ctx, voice);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_play_sound_at"),
+        audio_play_sound_at,
+    )?;
+
+    let audio_play_sound_on = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (emitter, sound, looping, priority, gain, offset, pitch): (
+            vm::UserData,
+            vm::UserData,
+            bool,
+            f64,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+        ) = exec.stack().consume(ctx)?;
+        let sound = SoundUserData::downcast(sound)?.id;
+
+        let voice = State::ctx_with_mut(ctx, |state| {
+            let emitter_id = find_emitter(state, emitter)?;
+            Ok::<_, vm::RuntimeError>(play_sound(
+                ctx,
+                state,
+                PlayOptions {
+                    sound,
+                    priority,
+                    looping,
+                    gain,
+                    offset,
+                    pitch,
+                    position: Some(VoicePosition::Emitter(emitter_id)),
+                },
+            ))
+        })??;
+        exec.stack().replace(ctx, voice);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_play_sound_on"),
+        audio_play_sound_on,
+    )?;
+
+    let audio_play_sound_ext = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let params: vm::Object = exec.stack().consume(ctx)?;
+        let params = params.try_borrow()?;
+
+        let sound: vm::UserData = params.get_field(ctx, "sound")?;
+        let sound = SoundUserData::downcast(sound)?.id;
+        let emitter: Option<vm::UserData> = params.get_field(ctx, "emitter")?;
+        let position = match params.get_field::<Option<vm::Object>>(ctx, "position")? {
+            Some(position) => {
+                let position = position.try_borrow()?;
+                let falloff = Falloff {
+                    reference: position
+                        .get_field::<Option<f64>>(ctx, "falloff_ref")?
+                        .unwrap_or(Falloff::default().reference),
+                    max: position
+                        .get_field::<Option<f64>>(ctx, "falloff_max")?
+                        .unwrap_or(Falloff::default().max),
+                    factor: position
+                        .get_field::<Option<f64>>(ctx, "falloff_factor")?
+                        .unwrap_or(Falloff::default().factor),
+                };
+                Some((
+                    Vec3::new(
+                        position.get_field::<Option<f64>>(ctx, "x")?.unwrap_or(0.0),
+                        position.get_field::<Option<f64>>(ctx, "y")?.unwrap_or(0.0),
+                        position.get_field::<Option<f64>>(ctx, "z")?.unwrap_or(0.0),
+                    ),
+                    falloff,
+                ))
+            }
+            None => None,
+        };
+        let priority: Option<f64> = params.get_field(ctx, "priority")?;
+        let looping: Option<bool> = params.get_field(ctx, "loop")?;
+        let gain = params.get_field(ctx, "gain")?;
+        let offset = params.get_field(ctx, "offset")?;
+        let pitch = params.get_field(ctx, "pitch")?;
+
+        let voice = State::ctx_with_mut(ctx, |state| {
+            // An emitter takes precedence over a position, as in GML.
+            let position = match emitter {
+                Some(emitter) => Some(VoicePosition::Emitter(find_emitter(state, emitter)?)),
+                None => position.map(|(position, falloff)| VoicePosition::Point(position, falloff)),
+            };
+            Ok::<_, vm::RuntimeError>(play_sound(
+                ctx,
+                state,
+                PlayOptions {
+                    sound,
+                    priority: priority.unwrap_or(0.0),
+                    looping: looping.unwrap_or(false),
+                    gain,
+                    offset,
+                    pitch,
+                    position,
+                },
+            ))
+        })??;
+        exec.stack().replace(ctx, voice);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_play_sound_ext"),
+        audio_play_sound_ext,
+    )?;
+
+    let audio_stop_sound = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let sound: vm::UserData = exec.stack().consume(ctx)?;
+        let sound = SoundOrVoice::from_userdata(sound)?;
+        State::ctx_with_mut(ctx, |state| match sound {
+            SoundOrVoice::Sound(sound_id) => state.audio.stop_sound(sound_id),
+            SoundOrVoice::Voice(voice_id) => {
+                state.audio.voices.remove(voice_id);
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("audio_stop_sound"), audio_stop_sound)?;
+
+    let audio_stop_all = vm::Callback::from_fn(ctx, |ctx, _| {
+        State::ctx_with_mut(ctx, |state| state.audio.voices.retain(|_, _| false))?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("audio_stop_all"), audio_stop_all)?;
+
+    fn pause_callback<'gc>(ctx: vm::Context<'gc>, paused: bool) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let sound: vm::UserData = exec.stack().consume(ctx)?;
+            let sound = SoundOrVoice::from_userdata(sound)?;
+            State::ctx_with_mut(ctx, |state| {
+                sound.for_each_voice(&mut state.audio, |voice| voice.paused = paused);
+            })?;
+            Ok(())
+        })
+    }
+
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_pause_sound"),
+        pause_callback(ctx, true),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_resume_sound"),
+        pause_callback(ctx, false),
+    )?;
+
+    fn pause_all_callback<'gc>(ctx: vm::Context<'gc>, paused: bool) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, _| {
+            State::ctx_with_mut(ctx, |state| {
+                for voice in state.audio.voices.values_mut() {
+                    voice.paused = paused;
+                }
+            })?;
+            Ok(())
+        })
+    }
+
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_pause_all"),
+        pause_all_callback(ctx, true),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_resume_all"),
+        pause_all_callback(ctx, false),
+    )?;
+
+    fn voice_check<'gc>(
+        ctx: vm::Context<'gc>,
+        check: impl Fn(&Voice) -> bool + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let sound: vm::UserData = exec.stack().consume(ctx)?;
+            let sound = SoundOrVoice::from_userdata(sound)?;
+            let result = State::ctx_with_mut(ctx, |state| {
+                let mut result = false;
+                sound.for_each_voice(&mut state.audio, |voice| result |= check(voice));
+                result
+            })?;
+            exec.stack().replace(ctx, result);
+            Ok(())
+        })
+    }
+
+    // Paused voices are still playing.
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_is_playing"),
+        voice_check(ctx, |_| true),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_is_paused"),
+        voice_check(ctx, |voice| voice.paused),
+    )?;
+
+    let audio_sound_gain = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sound, gain, time): (vm::UserData, f64, f64) = exec.stack().consume(ctx)?;
+        let sound = SoundOrVoice::from_userdata(sound)?;
+        let (gain, duration) = (gain.max(0.0), time / 1000.0);
+        State::ctx_with_mut(ctx, |state| match sound {
+            SoundOrVoice::Sound(sound_id) => state
+                .audio
+                .sound_gains
+                .get_or_insert_default(sound_id)
+                .fade_to(gain, duration),
+            SoundOrVoice::Voice(voice_id) => {
+                if let Some(voice) = state.audio.voices.get_mut(voice_id) {
+                    voice.gain.fade_to(gain, duration);
+                }
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(ctx, ctx.intern_static("audio_sound_gain"), audio_sound_gain)?;
+
+    let audio_sound_get_gain = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let sound: vm::UserData = exec.stack().consume(ctx)?;
+        let sound = SoundOrVoice::from_userdata(sound)?;
+        let gain = State::ctx_with(ctx, |state| match sound {
+            SoundOrVoice::Sound(sound_id) => state
+                .audio
+                .sound_gains
+                .get(sound_id)
+                .map_or(1.0, Gain::value),
+            SoundOrVoice::Voice(voice_id) => state
+                .audio
+                .voices
+                .get(voice_id)
+                .map_or(0.0, |voice| voice.gain.value()),
+        })?;
+        exec.stack().replace(ctx, gain);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_sound_get_gain"),
+        audio_sound_get_gain,
+    )?;
+
+    let audio_sound_pitch = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (sound, pitch): (vm::UserData, f64) = exec.stack().consume(ctx)?;
+        let sound = SoundOrVoice::from_userdata(sound)?;
+        State::ctx_with_mut(ctx, |state| match sound {
+            SoundOrVoice::Sound(sound_id) => {
+                state.audio.sound_pitches.insert(sound_id, pitch);
+            }
+            SoundOrVoice::Voice(voice_id) => {
+                if let Some(voice) = state.audio.voices.get_mut(voice_id) {
+                    voice.pitch = pitch;
+                }
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_sound_pitch"),
+        audio_sound_pitch,
+    )?;
+
+    let audio_sound_get_pitch = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let sound: vm::UserData = exec.stack().consume(ctx)?;
+        let sound = SoundOrVoice::from_userdata(sound)?;
+        let pitch = State::ctx_with(ctx, |state| match sound {
+            SoundOrVoice::Sound(sound_id) => state
+                .audio
+                .sound_pitches
+                .get(sound_id)
+                .copied()
+                .unwrap_or(1.0),
+            SoundOrVoice::Voice(voice_id) => state
+                .audio
+                .voices
+                .get(voice_id)
+                .map_or(1.0, |voice| voice.pitch),
+        })?;
+        exec.stack().replace(ctx, pitch);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_sound_get_pitch"),
+        audio_sound_get_pitch,
+    )?;
+
+    let audio_sound_get_track_position = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let voice: vm::UserData = exec.stack().consume(ctx)?;
+        let voice_id = VoiceUserData::downcast(voice)?.id;
+        let position = State::ctx_with(ctx, |state| {
+            state.audio.voices.get(voice_id).map_or(0.0, |voice| {
+                voice.track_position / voice.data.sample_rate as f64
+            })
+        })?;
+        exec.stack().replace(ctx, position);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_sound_get_track_position"),
+        audio_sound_get_track_position,
+    )?;
+
+    let audio_sound_set_track_position = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (voice, position): (vm::UserData, f64) = exec.stack().consume(ctx)?;
+        let voice_id = VoiceUserData::downcast(voice)?.id;
+        State::ctx_with_mut(ctx, |state| {
+            if let Some(voice) = state.audio.voices.get_mut(voice_id) {
+                voice.track_position = position.max(0.0) * voice.data.sample_rate as f64;
+            }
+        })?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_sound_set_track_position"),
+        audio_sound_set_track_position,
+    )?;
+
+    let audio_master_gain = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let gain: f64 = exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| {
+            state.audio.master_gain = Gain::new(gain.max(0.0));
+        })?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_master_gain"),
+        audio_master_gain,
+    )?;
+
+    let audio_get_master_gain = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        // There is only ever one listener, so the listener index is ignored.
+        let _listener: Option<i64> = exec.stack().consume(ctx)?;
+        let gain = State::ctx_with(ctx, |state| state.audio.master_gain.value())?;
+        exec.stack().replace(ctx, gain);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_get_master_gain"),
+        audio_get_master_gain,
+    )?;
+
+    let audio_listener_position = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (x, y, z): (f64, f64, f64) = exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| state.audio.listener = Vec3::new(x, y, z))?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_listener_position"),
+        audio_listener_position,
+    )?;
+
+    // Voices are not panned, only attenuated by distance, so the listener orientation has no
+    // effect.
+    let audio_listener_orientation = vm::Callback::from_fn(ctx, |_, mut exec| {
+        exec.stack().clear();
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_listener_orientation"),
+        audio_listener_orientation,
+    )?;
+
+    for (name, model) in [
+        ("audio_falloff_none", FalloffModel::None),
+        (
+            "audio_falloff_inverse_distance",
+            FalloffModel::InverseDistance,
+        ),
+        (
+            "audio_falloff_inverse_distance_clamped",
+            FalloffModel::InverseDistanceClamped,
+        ),
+        (
+            "audio_falloff_linear_distance",
+            FalloffModel::LinearDistance,
+        ),
+        (
+            "audio_falloff_linear_distance_clamped",
+            FalloffModel::LinearDistanceClamped,
+        ),
+        (
+            "audio_falloff_exponent_distance",
+            FalloffModel::ExponentDistance,
+        ),
+        (
+            "audio_falloff_exponent_distance_clamped",
+            FalloffModel::ExponentDistanceClamped,
+        ),
+    ] {
+        magic.add_constant(ctx, ctx.intern_static(name), model as i64)?;
+    }
+
+    let audio_falloff_set_model = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let model: i64 = exec.stack().consume(ctx)?;
+        let model = match model {
+            0 => FalloffModel::None,
+            1 => FalloffModel::InverseDistance,
+            2 => FalloffModel::InverseDistanceClamped,
+            3 => FalloffModel::LinearDistance,
+            4 => FalloffModel::LinearDistanceClamped,
+            5 => FalloffModel::ExponentDistance,
+            6 => FalloffModel::ExponentDistanceClamped,
+            _ => {
+                return Err(vm::RuntimeError::msg(format!(
+                    "invalid falloff model {model}"
+                )));
+            }
+        };
+        State::ctx_with_mut(ctx, |state| state.audio.falloff_model = model)?;
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_falloff_set_model"),
+        audio_falloff_set_model,
+    )?;
+
+    let audio_emitter_create = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let emitter = State::ctx_with_mut(ctx, |state| {
+            let emitter_id = state
+                .audio
+                .emitters
+                .insert_with_id(|id| Emitter::new(ctx.stash(EmitterUserData::new(ctx, id))));
+            ctx.fetch(&state.audio.emitters[emitter_id].this)
+        })?;
+        exec.stack().replace(ctx, emitter);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_create"),
+        audio_emitter_create,
+    )?;
+
+    let audio_emitter_free = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let emitter: vm::UserData = exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| {
+            let emitter_id = find_emitter(state, emitter)?;
+            state.audio.free_emitter(emitter_id);
+            Ok(())
+        })?
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_free"),
+        audio_emitter_free,
+    )?;
+
+    let audio_emitter_exists = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let emitter: vm::Value = exec.stack().consume(ctx)?;
+        let exists = match emitter {
+            vm::Value::UserData(ud) => match EmitterUserData::downcast(ud) {
+                Ok(emitter) => {
+                    State::ctx_with(ctx, |state| state.audio.emitters.contains(emitter.id))?
+                }
+                Err(_) => false,
+            },
+            _ => false,
+        };
+        exec.stack().replace(ctx, exists);
+        Ok(())
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_exists"),
+        audio_emitter_exists,
+    )?;
+
+    let audio_emitter_position = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (emitter, x, y, z): (vm::UserData, f64, f64, f64) = exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| {
+            let emitter_id = find_emitter(state, emitter)?;
+            state.audio.emitters[emitter_id].position = Vec3::new(x, y, z);
+            Ok(())
+        })?
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_position"),
+        audio_emitter_position,
+    )?;
+
+    let audio_emitter_falloff = vm::Callback::from_fn(ctx, |ctx, mut exec| {
+        let (emitter, reference, max, factor): (vm::UserData, f64, f64, f64) =
+            exec.stack().consume(ctx)?;
+        State::ctx_with_mut(ctx, |state| {
+            let emitter_id = find_emitter(state, emitter)?;
+            state.audio.emitters[emitter_id].falloff = Falloff {
+                reference,
+                max,
+                factor,
+            };
+            Ok(())
+        })?
+    });
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_falloff"),
+        audio_emitter_falloff,
+    )?;
+
+    fn emitter_setter<'gc>(
+        ctx: vm::Context<'gc>,
+        set: impl Fn(&mut Emitter, f64) + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let (emitter, value): (vm::UserData, f64) = exec.stack().consume(ctx)?;
+            State::ctx_with_mut(ctx, |state| {
+                let emitter_id = find_emitter(state, emitter)?;
+                set(&mut state.audio.emitters[emitter_id], value);
+                Ok(())
+            })?
+        })
+    }
+
+    fn emitter_getter<'gc>(
+        ctx: vm::Context<'gc>,
+        get: impl Fn(&Emitter) -> f64 + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let emitter: vm::UserData = exec.stack().consume(ctx)?;
+            State::ctx_with(ctx, |state| {
+                let emitter_id = find_emitter(state, emitter)?;
+                exec.stack()
+                    .replace(ctx, get(&state.audio.emitters[emitter_id]));
+                Ok(())
+            })?
+        })
+    }
+
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_gain"),
+        emitter_setter(ctx, |emitter, gain| emitter.gain = gain.max(0.0)),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_pitch"),
+        emitter_setter(ctx, |emitter, pitch| emitter.pitch = pitch),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_get_gain"),
+        emitter_getter(ctx, |emitter| emitter.gain),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_get_pitch"),
+        emitter_getter(ctx, |emitter| emitter.pitch),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_get_x"),
+        emitter_getter(ctx, |emitter| emitter.position[0]),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_get_y"),
+        emitter_getter(ctx, |emitter| emitter.position[1]),
+    )?;
+    magic.add_constant(
+        ctx,
+        ctx.intern_static("audio_emitter_get_z"),
+        emitter_getter(ctx, |emitter| emitter.position[2]),
+    )?;
+
     Ok(magic)
 }
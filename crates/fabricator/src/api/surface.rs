@@ -0,0 +1,636 @@
+use std::path::{Component, Path, PathBuf};
+
+use fabricator_math::{Affine2, Box2, Vec2, Vec3, Vec4};
+use fabricator_vm as vm;
+
+use crate::{
+    api::{drawing::current_depth, id_user_data::IdUserData, magic::MagicExt as _},
+    game::{DrawCommand, SurfacePass, SurfaceQuad, color_rgb},
+    state::{DrawingState, State, Surface, SurfaceId},
+};
+
+pub type SurfaceUserData = IdUserData<SurfaceId>;
+
+pub fn find_surface<'gc>(
+    state: &State,
+    surface: vm::UserData<'gc>,
+) -> Result<SurfaceId, vm::RuntimeError> {
+    let id = SurfaceUserData::downcast(surface)?.id;
+    if state.surfaces.contains(id) {
+        Ok(id)
+    } else {
+        Err(vm::RuntimeError::msg("expired surface ID"))
+    }
+}
+
+/// Free a surface if it still exists, returning whether it did.
+pub fn free_surface(
+    state: &mut State,
+    drawing_state: &mut DrawingState,
+    surface_id: SurfaceId,
+) -> bool {
+    if state.surfaces.remove(surface_id).is_none() {
+        return false;
+    }
+    drawing_state.target_stack.retain(|&s| s != surface_id);
+    drawing_state.surface_passes.push(SurfacePass::Free {
+        surface: surface_id,
+    });
+    true
+}
+
+fn surface_size(width: f64, height: f64) -> Result<Vec2<u32>, vm::RuntimeError> {
+    if width >= 1.0 && height >= 1.0 {
+        Ok(Vec2::new(width as u32, height as u32))
+    } else {
+        Err(vm::RuntimeError::msg(format!(
+            "invalid surface size {width}x{height}"
+        )))
+    }
+}
+
+/// A region of a surface given by script coordinates, clamped to the surface bounds.
+fn surface_region(size: Vec2<u32>, x: f64, y: f64, width: f64, height: f64) -> Box2<u32> {
+    let min = Vec2::new(x, y)
+        .map(|v| v.max(0.0) as u32)
+        .zip(size, u32::min);
+    let max = Vec2::new(x + width, y + height)
+        .map(|v| v.max(0.0) as u32)
+        .zip(size, u32::min)
+        .zip(min, u32::max);
+    Box2::new(min, max)
+}
+
+fn push_surface_pass(ctx: vm::Context<'_>, pass: SurfacePass) -> Result<(), vm::RuntimeError> {
+    DrawingState::ctx_with_mut(ctx, |drawing_state| {
+        drawing_state.surface_passes.push(pass);
+    })?;
+    Ok(())
+}
+
+/// Issue a quad which draws a whole surface.
+///
+/// The callback is given the size of the surface and should fill in the quad transform, it may
+/// also change any other quad property.
+fn draw_surface_quad<'gc>(
+    ctx: vm::Context<'gc>,
+    surface: vm::UserData<'gc>,
+    f: impl FnOnce(Vec2<u32>, &mut SurfaceQuad),
+) -> Result<(), vm::RuntimeError> {
+    let depth = current_depth(ctx)?;
+    let quad = State::ctx_with(ctx, |state| -> Result<_, vm::RuntimeError> {
+        let surface_id = find_surface(state, surface)?;
+        let size = state.surfaces[surface_id].size;
+        let mut quad = SurfaceQuad {
+            surface: surface_id,
+            transform: Affine2::new(),
+            region: Box2::with_size(Vec2::zero(), size.cast()),
+            depth,
+            tint: Vec3::splat(1.0),
+            alpha: 1.0,
+        };
+        f(size, &mut quad);
+        Ok(quad)
+    })??;
+
+    DrawingState::ctx_with_mut(ctx, |drawing_state| {
+        drawing_state.push(DrawCommand::Surface(quad));
+    })?;
+    Ok(())
+}
+
+/// Resolve a file name given to `surface_save` or `surface_save_part` against the project data
+/// directory.
+///
+/// Absolute paths and paths containing `..` are rejected, so that scripts can only write images
+/// inside the data directory.
+fn data_file_path(data_path: &Path, file_name: &str) -> Result<PathBuf, vm::RuntimeError> {
+    let file_path = Path::new(file_name);
+    if !file_path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(vm::RuntimeError::msg(format!(
+            "file path {file_name:?} is outside of the data directory"
+        )));
+    }
+    Ok(data_path.join(file_path))
+}
+
+fn save_surface<'gc>(
+    ctx: vm::Context<'gc>,
+    surface: vm::UserData<'gc>,
+    file_name: vm::String<'gc>,
+    region: impl FnOnce(Vec2<u32>) -> Box2<u32>,
+) -> Result<(), vm::RuntimeError> {
+    let pass = State::ctx_with(ctx, |state| {
+        let surface_id = find_surface(state, surface)?;
+        Ok::<_, vm::RuntimeError>(SurfacePass::Save {
+            surface: surface_id,
+            region: region(state.surfaces[surface_id].size),
+            path: data_file_path(&state.config.data_path, file_name.as_str())?,
+        })
+    })??;
+    push_surface_pass(ctx, pass)
+}
+
+/// Read a single pixel of a surface as `[r, g, b, a]`.
+///
+/// Surface contents are only known once the renderer has reported them, so pixels read as
+/// transparent black until then, and never include drawing from the current tick.
+fn surface_pixel<'gc>(
+    ctx: vm::Context<'gc>,
+    surface: vm::UserData<'gc>,
+    x: f64,
+    y: f64,
+) -> Result<[u8; 4], vm::RuntimeError> {
+    State::ctx_with(ctx, |state| {
+        let surface_id = find_surface(state, surface)?;
+        let Some(contents) = &state.surfaces[surface_id].contents else {
+            return Ok([0; 4]);
+        };
+        if x < 0.0 || y < 0.0 {
+            return Ok([0; 4]);
+        }
+        Ok(contents
+            .get_pixel_checked(x as u32, y as u32)
+            .map(|p| p.0)
+            .unwrap_or([0; 4]))
+    })?
+}
+
+pub fn surface_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
+    let mut magic = vm::MagicSet::new();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_create"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (width, height): (f64, f64) = exec.stack().consume(ctx)?;
+                let size = surface_size(width, height)?;
+
+                let (surface_id, surface_ud) = State::ctx_with_mut(ctx, |state| {
+                    let surface_id = state.surfaces.insert_with_id(|id| {
+                        Surface::new(ctx.stash(SurfaceUserData::new(ctx, id)), size)
+                    });
+                    (surface_id, ctx.fetch(&state.surfaces[surface_id].this))
+                })?;
+                push_surface_pass(
+                    ctx,
+                    SurfacePass::Allocate {
+                        surface: surface_id,
+                        size,
+                    },
+                )?;
+
+                exec.stack().replace(ctx, surface_ud);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_free"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let surface_ud: vm::UserData = exec.stack().consume(ctx)?;
+                State::ctx_with_mut(ctx, |state| {
+                    let surface_id = find_surface(state, surface_ud)?;
+                    DrawingState::ctx_with_mut(ctx, |drawing_state| {
+                        free_surface(state, drawing_state, surface_id);
+                    })?;
+                    Ok::<_, vm::RuntimeError>(())
+                })??;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    // Not a GML function. Scripts which keep a surface in a struct can use this to have the
+    // surface freed once the struct has been collected, rather than needing to free it by hand.
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_free_on_collect"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface_ud, owner): (vm::UserData, vm::Value) = exec.stack().consume(ctx)?;
+                State::ctx_with(ctx, |state| find_surface(state, surface_ud))??;
+                let owner = vm::WeakValue::try_from(owner)?;
+                ctx.finalizers().register(&ctx, owner, surface_ud);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_exists"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let surface: vm::Value = exec.stack().consume(ctx)?;
+                // Scripts commonly store -1 (or `undefined`) in place of a surface before creating
+                // one, so any value which is not a live surface simply does not exist.
+                let exists = match surface {
+                    vm::Value::UserData(ud) => match SurfaceUserData::downcast(ud) {
+                        Ok(surface) => {
+                            State::ctx_with(ctx, |state| state.surfaces.contains(surface.id))?
+                        }
+                        Err(_) => false,
+                    },
+                    _ => false,
+                };
+                exec.stack().replace(ctx, exists);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    fn surface_getter<'gc>(
+        ctx: vm::Context<'gc>,
+        get: impl Fn(&Surface) -> i64 + 'static,
+    ) -> vm::Callback<'gc> {
+        vm::Callback::from_fn(ctx, move |ctx, mut exec| {
+            let surface_ud: vm::UserData = exec.stack().consume(ctx)?;
+            State::ctx_with(ctx, |state| {
+                let surface_id = find_surface(state, surface_ud)?;
+                exec.stack().replace(ctx, get(&state.surfaces[surface_id]));
+                Ok(())
+            })?
+        })
+    }
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_get_width"),
+            surface_getter(ctx, |surface| surface.size[0] as i64),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_get_height"),
+            surface_getter(ctx, |surface| surface.size[1] as i64),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_resize"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface_ud, width, height): (vm::UserData, f64, f64) =
+                    exec.stack().consume(ctx)?;
+                let size = surface_size(width, height)?;
+
+                let surface_id = State::ctx_with_mut(ctx, |state| {
+                    let surface_id = find_surface(state, surface_ud)?;
+                    let surface = &mut state.surfaces[surface_id];
+                    surface.size = size;
+                    surface.contents = None;
+                    Ok::<_, vm::RuntimeError>(surface_id)
+                })??;
+                push_surface_pass(
+                    ctx,
+                    SurfacePass::Allocate {
+                        surface: surface_id,
+                        size,
+                    },
+                )?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_set_target"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let surface_ud: vm::UserData = exec.stack().consume(ctx)?;
+                let surface_id = State::ctx_with(ctx, |state| find_surface(state, surface_ud))??;
+                DrawingState::ctx_with_mut(ctx, |drawing_state| {
+                    drawing_state.target_stack.push(surface_id);
+                })?;
+                exec.stack().replace(ctx, true);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_reset_target"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let popped = DrawingState::ctx_with_mut(ctx, |drawing_state| {
+                    drawing_state.target_stack.pop().is_some()
+                })?;
+                exec.stack().replace(ctx, popped);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_get_target"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let target = DrawingState::ctx_with(ctx, |drawing_state| {
+                    drawing_state.target_stack.last().copied()
+                })?;
+                let target: vm::Value = State::ctx_with(ctx, |state| {
+                    match target.and_then(|id| state.surfaces.get(id)) {
+                        Some(surface) => ctx.fetch(&surface.this).into(),
+                        None => (-1).into(),
+                    }
+                })?;
+                exec.stack().replace(ctx, target);
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("draw_surface"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, x, y): (vm::UserData, f64, f64) = exec.stack().consume(ctx)?;
+                draw_surface_quad(ctx, surface, |_, quad| {
+                    quad.transform = Affine2::new().translate(Vec2::new(x, y)).cast();
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("draw_surface_ext"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, x, y, xscale, yscale, angle, color, alpha): (
+                    vm::UserData,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    u32,
+                    f64,
+                ) = exec.stack().consume(ctx)?;
+                draw_surface_quad(ctx, surface, |_, quad| {
+                    quad.transform = Affine2::new()
+                        .scale(Vec2::new(xscale, yscale))
+                        .rotate(-angle.to_radians())
+                        .translate(Vec2::new(x, y))
+                        .cast();
+                    quad.tint = color_rgb(color);
+                    quad.alpha = alpha.clamp(0.0, 1.0) as f32;
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("draw_surface_stretched"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, x, y, width, height): (vm::UserData, f64, f64, f64, f64) =
+                    exec.stack().consume(ctx)?;
+                draw_surface_quad(ctx, surface, |size, quad| {
+                    quad.transform = Affine2::new()
+                        .scale(Vec2::new(width, height) / size.cast::<f64>())
+                        .translate(Vec2::new(x, y))
+                        .cast();
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("draw_surface_part"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, left, top, width, height, x, y): (
+                    vm::UserData,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                ) = exec.stack().consume(ctx)?;
+                // The top left of the part is drawn at (x, y).
+                draw_surface_quad(ctx, surface, |_, quad| {
+                    let part = Box2::with_size(Vec2::new(left, top), Vec2::new(width, height));
+                    quad.region = quad.region.fintersection(part.cast());
+                    quad.transform = Affine2::new()
+                        .translate(-part.min)
+                        .translate(Vec2::new(x, y))
+                        .cast();
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_getpixel"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, x, y): (vm::UserData, f64, f64) = exec.stack().consume(ctx)?;
+                let [r, g, b, _] = surface_pixel(ctx, surface, x, y)?;
+                exec.stack()
+                    .replace(ctx, i64::from_le_bytes([r, g, b, 0, 0, 0, 0, 0]));
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_getpixel_ext"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, x, y): (vm::UserData, f64, f64) = exec.stack().consume(ctx)?;
+                let [r, g, b, a] = surface_pixel(ctx, surface, x, y)?;
+                exec.stack()
+                    .replace(ctx, i64::from_le_bytes([r, g, b, a, 0, 0, 0, 0]));
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    fn copy_surface<'gc>(
+        ctx: vm::Context<'gc>,
+        dest: vm::UserData<'gc>,
+        x: f64,
+        y: f64,
+        source: vm::UserData<'gc>,
+        region: impl FnOnce(Vec2<u32>) -> Box2<u32>,
+    ) -> Result<(), vm::RuntimeError> {
+        let pass = State::ctx_with(ctx, |state| {
+            let dest_id = find_surface(state, dest)?;
+            let source_id = find_surface(state, source)?;
+            Ok::<_, vm::RuntimeError>(SurfacePass::Copy {
+                surface: dest_id,
+                position: Vec2::new(x, y).map(|v| v.max(0.0) as u32),
+                source: source_id,
+                region: region(state.surfaces[source_id].size),
+            })
+        })??;
+        push_surface_pass(ctx, pass)
+    }
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_copy"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (dest, x, y, source): (vm::UserData, f64, f64, vm::UserData) =
+                    exec.stack().consume(ctx)?;
+                copy_surface(ctx, dest, x, y, source, |size| {
+                    Box2::with_size(Vec2::zero(), size)
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_copy_part"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (dest, x, y, source, xs, ys, width, height): (
+                    vm::UserData,
+                    f64,
+                    f64,
+                    vm::UserData,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                ) = exec.stack().consume(ctx)?;
+                copy_surface(ctx, dest, x, y, source, |size| {
+                    surface_region(size, xs, ys, width, height)
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    // Clearing only applies to surfaces, the screen is always cleared before drawing.
+    fn clear_target(ctx: vm::Context<'_>, color: u32, alpha: f64) -> Result<(), vm::RuntimeError> {
+        let rgb = color_rgb(color);
+        DrawingState::ctx_with_mut(ctx, |drawing_state| {
+            if let Some(&target) = drawing_state.target_stack.last() {
+                drawing_state.surface_passes.push(SurfacePass::Clear {
+                    surface: target,
+                    color: Vec4::new(rgb[0], rgb[1], rgb[2], alpha.clamp(0.0, 1.0) as f32),
+                });
+            }
+        })?;
+        Ok(())
+    }
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("draw_clear"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let color: u32 = exec.stack().consume(ctx)?;
+                clear_target(ctx, color, 1.0)?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("draw_clear_alpha"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (color, alpha): (u32, f64) = exec.stack().consume(ctx)?;
+                clear_target(ctx, color, alpha)?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+}
+
+/// Surface functions which write to the filesystem, and so are not available to sandboxed scripts.
+pub fn surface_host_api<'gc>(ctx: vm::Context<'gc>) -> vm::MagicSet<'gc> {
+    let mut magic = vm::MagicSet::new();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_save"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, file_name): (vm::UserData, vm::String) = exec.stack().consume(ctx)?;
+                save_surface(ctx, surface, file_name, |size| {
+                    Box2::with_size(Vec2::zero(), size)
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+        .add_constant(
+            ctx,
+            ctx.intern_static("surface_save_part"),
+            vm::Callback::from_fn(ctx, |ctx, mut exec| {
+                let (surface, file_name, x, y, width, height): (
+                    vm::UserData,
+                    vm::String,
+                    f64,
+                    f64,
+                    f64,
+                    f64,
+                ) = exec.stack().consume(ctx)?;
+                save_surface(ctx, surface, file_name, |size| {
+                    surface_region(size, x, y, width, height)
+                })?;
+                Ok(())
+            }),
+        )
+        .unwrap();
+
+    magic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_file_path() {
+        let data_path = Path::new("project/datafiles");
+        assert_eq!(
+            data_file_path(data_path, "save.png").unwrap(),
+            data_path.join("save.png")
+        );
+        assert_eq!(
+            data_file_path(data_path, "./shots/save.png").unwrap(),
+            data_path.join("./shots/save.png")
+        );
+        assert!(data_file_path(data_path, "/etc/passwd").is_err());
+        assert!(data_file_path(data_path, "../save.png").is_err());
+        assert!(data_file_path(data_path, "shots/../../save.png").is_err());
+    }
+}
@@ -4,11 +4,13 @@ use std::{
     fs::{self, File},
     io::{self, Read as _},
     rc::Rc,
-    time::Instant,
 };
 
 use anyhow::{Context as _, Error, anyhow, bail, ensure};
-use fabricator_compiler as compiler;
+use fabricator_compiler::{
+    self as compiler,
+    cache::{CacheStatus, CodeCache},
+};
 use fabricator_math::{Box2, Vec2};
 use fabricator_stdlib::StdlibContext as _;
 use fabricator_util::typed_id_map::{IdMap, SecondaryMap};
@@ -21,32 +23,35 @@ use sha2::{Digest as _, Sha256};
 use crate::{
     api::{
         asset::assets_api,
+        camera::camera_api,
         collision::collision_api,
         drawing::{
             ShaderUserData, SpriteUserData, TexturePageUserData, TileSetUserData, drawing_api,
         },
+        exception::exception_api,
         font::{FontUserData, font_api},
         instance::instance_api,
         layer::layers_api,
         magic::MagicExt as _,
         object::{ObjectUserData, object_api},
-        os::os_api,
+        os::{os_api, os_host_api},
         platform::platform_api,
         room::{RoomUserData, room_api},
         sound::{SoundUserData, sound_api},
-        stub::stub_api,
+        stub::{stub_api, stub_host_api},
+        surface::{surface_api, surface_host_api},
         tile::tiles_api,
     },
     ffi::load_extension_file,
     game::maxrects::MaxRects,
-    project::{CollisionKind, LayerType, ObjectEvent, Project, ScriptMode},
+    project::{CollisionKind, LayerType, ObjectEvent, Project},
     state::{
-        AnimationFrame, Configuration, InstanceTemplate, InstanceTemplateId, Object, ObjectId,
-        Room, RoomId, RoomLayer, Scripts, Sprite, SpriteCollision, SpriteCollisionKind, SpriteId,
-        State, Texture, TextureId, TexturePage, TexturePageId,
+        AnimationFrame, Clock, Configuration, GAMEPAD_SLOTS, InstanceTemplate, InstanceTemplateId,
+        Object, ObjectId, Room, RoomId, RoomLayer, Scripts, Sprite, SpriteCollision,
+        SpriteCollisionKind, SpriteId, State, Texture, TextureId, TexturePage, TexturePageId,
         configuration::{
-            Font, FontId, RoomLayerType, RoomTileLayer, Shader, ShaderId, Sound, SoundId, TileSet,
-            TileSetId,
+            Font, FontId, Glyph, RoomLayerType, RoomTileLayer, RoomView, Shader, ShaderId, Sound,
+            SoundId, TileSet, TileSetId,
         },
     },
 };
@@ -55,7 +60,44 @@ pub fn create_state(
     interpreter: &mut vm::Interpreter,
     project: &Project,
     config_name: &str,
+    code_cache: Option<&CodeCache>,
 ) -> Result<State, Error> {
+    let config = create_configuration(interpreter, project)?;
+    let first_room = config.first_room;
+
+    let scripts = load_scripts(project, &config, config_name, interpreter, code_cache)?;
+
+    Ok(State {
+        clock: Clock::new(),
+        config,
+        scripts,
+        current_room: None,
+        next_room: Some(first_room),
+        layers: Default::default(),
+        named_layers: Default::default(),
+        tile_maps: Default::default(),
+        cameras: Default::default(),
+        views: Default::default(),
+        gui_size: None,
+        window_size: None,
+        gamepad_deadzones: [0.0; GAMEPAD_SLOTS],
+        surfaces: Default::default(),
+        audio: Default::default(),
+        instances: Default::default(),
+        instance_for_template: Default::default(),
+        instances_for_object: Default::default(),
+        instances_for_layer: Default::default(),
+        instance_bound_tree: Default::default(),
+    })
+}
+
+/// Load every asset in a project and create the runner configuration for it.
+///
+/// Userdata for every asset is allocated in the given interpreter.
+pub fn create_configuration(
+    interpreter: &mut vm::Interpreter,
+    project: &Project,
+) -> Result<Configuration, Error> {
     // TODO: Hard coded tick rate, normally configured by 'options/main/options_main.yy'.
     const TICK_RATE: f64 = 60.0;
 
@@ -159,11 +201,57 @@ pub fn create_state(
 
     let mut fonts = IdMap::<FontId, Rc<Font>>::new();
     for font in project.fonts.values() {
+        let (width, height) = image::image_dimensions(&font.font_image_path)
+            .with_context(|| anyhow!("could not read image for font {:?}", font.name))?;
+        let texture = textures.insert(Rc::new(Texture {
+            texture_group: font.texture_group.clone(),
+            image_path: font.font_image_path.clone(),
+            size: Vec2::new(width, height),
+            cropped_size: Vec2::new(width, height),
+            cropped_offset: Vec2::zero(),
+        }));
+
+        let glyphs = font
+            .glyphs
+            .values()
+            .map(|glyph| {
+                (
+                    glyph.character,
+                    Glyph {
+                        texture,
+                        region: Box2::with_size(
+                            Vec2::new(glyph.x, glyph.y),
+                            Vec2::new(glyph.width, glyph.height),
+                        )
+                        .cast(),
+                        offset: glyph.offset.into(),
+                        shift: glyph.shift.into(),
+                    },
+                )
+            })
+            .collect();
+
+        let kerning = font
+            .kerning_pairs
+            .iter()
+            .map(|pair| ((pair.first, pair.second), pair.amount.into()))
+            .collect();
+
+        let line_height = font
+            .glyphs
+            .values()
+            .map(|glyph| glyph.height as u32)
+            .max()
+            .unwrap_or(0);
+
         fonts.insert_with_id(|id| {
             let userdata = interpreter
                 .enter(|ctx| ctx.stash(FontUserData::new(ctx, id, ctx.intern(&font.name))));
             Rc::new(Font {
                 name: font.name.clone(),
+                glyphs,
+                kerning,
+                line_height,
                 userdata,
             })
         });
@@ -177,6 +265,7 @@ pub fn create_state(
             Rc::new(Sound {
                 name: sound.name.clone(),
                 duration: sound.duration,
+                sound_file: sound.sound_file.clone(),
                 userdata,
             })
         });
@@ -220,16 +309,19 @@ pub fn create_state(
     let mut object_dict = HashMap::<String, ObjectId>::new();
 
     for (object_name, object) in &project.objects {
-        let sprite = object
-            .sprite
-            .as_ref()
-            .map(|sprite_name| {
-                sprite_dict
-                    .get(sprite_name)
-                    .copied()
-                    .with_context(|| anyhow!("missing sprite named {:?}", sprite_name))
-            })
-            .transpose()?;
+        let find_sprite = |sprite_name: &Option<String>| {
+            sprite_name
+                .as_ref()
+                .map(|sprite_name| {
+                    sprite_dict
+                        .get(sprite_name)
+                        .copied()
+                        .with_context(|| anyhow!("missing sprite named {:?}", sprite_name))
+                })
+                .transpose()
+        };
+        let sprite = find_sprite(&object.sprite)?;
+        let mask = find_sprite(&object.mask)?;
 
         let object_id = objects.insert_with_id(|id| {
             let userdata = interpreter
@@ -238,6 +330,7 @@ pub fn create_state(
                 name: object_name.clone(),
                 parent: None,
                 sprite,
+                mask,
                 persistent: object.persistent,
                 userdata,
                 tags: object.tags.clone(),
@@ -279,6 +372,8 @@ pub fn create_state(
                                 format!("missing object named {:?}", instance.object)
                             })?,
                             position: Vec2::new(instance.x, instance.y),
+                            scale: Vec2::new(instance.scale_x, instance.scale_y),
+                            rotation: -instance.rotation.to_radians(),
                         });
                         template_ids.push(template_id);
                     }
@@ -317,12 +412,38 @@ pub fn create_state(
             );
         }
 
+        let mut views = Vec::new();
+        for view in &room.views {
+            let follow = match &view.follow {
+                Some(object_name) => Some(*object_dict.get(object_name).with_context(|| {
+                    format!("missing object named {object_name:?} for room view")
+                })?),
+                None => None,
+            };
+            views.push(RoomView {
+                visible: view.visible,
+                view: Box2::with_size(
+                    Vec2::new(view.x, view.y),
+                    Vec2::new(view.width, view.height),
+                ),
+                port: Box2::with_size(
+                    Vec2::new(view.port_x, view.port_y),
+                    Vec2::new(view.port_width, view.port_height),
+                ),
+                border: Vec2::new(view.border_x, view.border_y),
+                speed: Vec2::new(view.speed_x, view.speed_y),
+                follow,
+            });
+        }
+
         let room_id = rooms.insert_with_id(|id| {
             let room_ud = interpreter
                 .enter(|ctx| ctx.stash(RoomUserData::new(ctx, id, ctx.intern(&room.name))));
             Rc::new(Room {
                 name: room.name.clone(),
                 size: Vec2::new(room.width, room.height),
+                enable_views: room.enable_views,
+                views,
                 layers,
                 userdata: room_ud,
                 tags: room.tags.clone(),
@@ -374,7 +495,7 @@ pub fn create_state(
         }
     }
 
-    let config = Configuration {
+    Ok(Configuration {
         data_path: project.base_path.join("datafiles"),
         tick_rate: TICK_RATE,
         sprites,
@@ -393,24 +514,6 @@ pub fn create_state(
         room_dict,
         first_room,
         last_room,
-    };
-
-    let scripts = load_scripts(project, &config, config_name, interpreter)?;
-
-    Ok(State {
-        start_instant: Instant::now(),
-        config,
-        scripts,
-        current_room: None,
-        next_room: Some(first_room),
-        layers: Default::default(),
-        named_layers: Default::default(),
-        tile_maps: Default::default(),
-        instances: Default::default(),
-        instance_for_template: Default::default(),
-        instances_for_object: Default::default(),
-        instances_for_layer: Default::default(),
-        instance_bound_tree: Default::default(),
     })
 }
 
@@ -520,32 +623,76 @@ fn read_sprite_frame_meta(
         .collect()
 }
 
+/// Create the set of magic variables provided to untrusted scripts, the stdlib along with every
+/// runner API that cannot access the filesystem or the host OS environment.
+///
+/// Functions with host access are registered in their own sets (such as [`os_host_api`]) which are
+/// only merged by [`runner_magic`], so nothing needs to be filtered out here. This never includes
+/// functions from project extensions, which would allow calling arbitrary native code.
+pub fn sandbox_magic<'gc>(
+    ctx: vm::Context<'gc>,
+    config: &Configuration,
+) -> Result<vm::MagicSet<'gc>, Error> {
+    let mut magic = vm::MagicSet::new();
+
+    magic.merge_unique(&ctx.sandboxed_stdlib())?;
+
+    magic.merge_unique(&os_api(ctx))?;
+    magic.merge_unique(&exception_api(ctx))?;
+    magic.merge_unique(&platform_api(ctx))?;
+    magic.merge_unique(&collision_api(ctx))?;
+    magic.merge_unique(&stub_api(ctx))?;
+    magic.merge_unique(&object_api(ctx, config)?)?;
+    magic.merge_unique(&instance_api(ctx))?;
+    magic.merge_unique(&room_api(ctx, config)?)?;
+    magic.merge_unique(&drawing_api(ctx, config)?)?;
+    magic.merge_unique(&font_api(ctx, config)?)?;
+    magic.merge_unique(&sound_api(ctx, config)?)?;
+    magic.merge_unique(&assets_api(ctx, config)?)?;
+    magic.merge_unique(&tiles_api(ctx))?;
+    magic.merge_unique(&layers_api(ctx))?;
+    magic.merge_unique(&camera_api(ctx))?;
+    magic.merge_unique(&surface_api(ctx))?;
+
+    Ok(magic)
+}
+
+/// Create the set of magic variables provided to every script by the runner, which is
+/// [`sandbox_magic`] along with every function that can access the filesystem or the host OS
+/// environment.
+///
+/// This does not include functions from project extensions, since those require loading native
+/// libraries.
+pub fn runner_magic<'gc>(
+    ctx: vm::Context<'gc>,
+    config: &Configuration,
+) -> Result<vm::MagicSet<'gc>, Error> {
+    let mut magic = sandbox_magic(ctx, config)?;
+
+    let sandboxed_stdlib = ctx.sandboxed_stdlib();
+    magic.merge_unique(
+        &ctx.stdlib()
+            .filter(|name| sandboxed_stdlib.find(name).is_none()),
+    )?;
+
+    magic.merge_unique(&os_host_api(ctx))?;
+    magic.merge_unique(&stub_host_api(ctx))?;
+    magic.merge_unique(&surface_host_api(ctx))?;
+
+    Ok(magic)
+}
+
 fn load_scripts(
     project: &Project,
     config: &Configuration,
     config_name: &str,
     interpreter: &mut vm::Interpreter,
+    code_cache: Option<&CodeCache>,
 ) -> Result<Scripts, Error> {
     let scripts = interpreter.enter(|ctx| -> Result<_, Error> {
         let mut object_events =
             HashMap::<ObjectId, HashMap<ObjectEvent, vm::StashedClosure>>::new();
-        let mut magic = vm::MagicSet::new();
-
-        magic.merge_unique(&ctx.stdlib())?;
-
-        magic.merge_unique(&os_api(ctx))?;
-        magic.merge_unique(&platform_api(ctx))?;
-        magic.merge_unique(&collision_api(ctx))?;
-        magic.merge_unique(&stub_api(ctx))?;
-        magic.merge_unique(&object_api(ctx, &config)?)?;
-        magic.merge_unique(&instance_api(ctx))?;
-        magic.merge_unique(&room_api(ctx, &config)?)?;
-        magic.merge_unique(&drawing_api(ctx, &config)?)?;
-        magic.merge_unique(&font_api(ctx, &config)?)?;
-        magic.merge_unique(&sound_api(ctx, &config)?)?;
-        magic.merge_unique(&assets_api(ctx, &config)?)?;
-        magic.merge_unique(&tiles_api(ctx))?;
-        magic.merge_unique(&layers_api(ctx))?;
+        let mut magic = runner_magic(ctx, config)?;
 
         for extension in project.extensions.values() {
             for file in &extension.files {
@@ -557,6 +704,19 @@ fn load_scripts(
             }
         }
 
+        // Compiled scripts refer to magic variables by index. Project assets are stored in hash maps
+        // with a random iteration order, so we sort all magic variables by name to give them the
+        // same indexes on every run, otherwise the code cache would never be hit.
+        let magic = {
+            let mut names = magic.names().collect::<Vec<_>>();
+            names.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+            let mut sorted = vm::MagicSet::new();
+            for (name, index) in names {
+                sorted.insert(name, magic.get(index).unwrap());
+            }
+            sorted
+        };
+
         let magic = Gc::new(&ctx, magic);
 
         log::info!("compiling all global scripts...");
@@ -576,37 +736,44 @@ fn load_scripts(
             code_buf.clear();
             File::open(&script.path)?.read_to_string(&mut code_buf)?;
             script_compiler.add_chunk(
-                match script.mode {
-                    ScriptMode::Compat => compiler::CompileSettings::compat(),
-                    ScriptMode::Modern => compiler::CompileSettings::strict(),
-                },
+                script.mode.compile_settings(),
                 vm::SharedStr::new(&script.path.to_string_lossy()),
                 &code_buf,
-            )?;
+            );
         }
 
-        let script_output = script_compiler.compile()?;
+        let script_output = compile_unit(script_compiler, code_cache)?;
         log::info!("finished compiling all global scripts!");
 
         log::info!("compiling all object scripts...");
         for (object_name, proj_object) in &project.objects {
-            for (&event, script) in &proj_object.event_scripts {
+            let mut event_scripts = proj_object
+                .event_scripts
+                .iter()
+                .map(|(&event, script)| (event, script))
+                .collect::<Vec<_>>();
+            for (target_name, script) in &proj_object.collision_scripts {
+                let &target = config.object_dict.get(target_name).with_context(|| {
+                    anyhow!("no such collision target object named {:?}", target_name)
+                })?;
+                event_scripts.push((ObjectEvent::Collision(target), script));
+            }
+
+            for (event, script) in event_scripts {
                 code_buf.clear();
                 File::open(&script.path)?.read_to_string(&mut code_buf)?;
                 let name = script.path.to_string_lossy();
-                let proto_output = compiler::Compiler::compile_chunk(
-                    ctx,
-                    config_name,
-                    script_output.exported_imports,
-                    match script.mode {
-                        ScriptMode::Compat => compiler::CompileSettings::compat(),
-                        ScriptMode::Modern => compiler::CompileSettings::strict(),
-                    }
-                    .export_top_level_functions(false),
+                let mut event_compiler =
+                    compiler::Compiler::new(ctx, config_name, script_output.exported_imports);
+                event_compiler.add_chunk(
+                    script
+                        .mode
+                        .compile_settings()
+                        .export_top_level_functions(false),
                     vm::SharedStr::new(&name),
                     &code_buf,
-                )?;
-                let proto = proto_output.chunk_prototype;
+                );
+                let proto = compile_unit(event_compiler, code_cache)?.chunks[0];
                 object_events
                     .entry(config.object_dict[object_name])
                     .or_default()
@@ -631,6 +798,30 @@ fn load_scripts(
     Ok(scripts)
 }
 
+fn compile_unit<'gc>(
+    compiler: compiler::Compiler<'gc>,
+    code_cache: Option<&CodeCache>,
+) -> Result<compiler::compiler::CompileOutput<'gc>, compiler::CompileErrors> {
+    let output = match code_cache {
+        Some(code_cache) => {
+            let (output, status) = compiler.compile_with_cache(code_cache)?;
+            match status {
+                CacheStatus::Hit => log::debug!("loaded compiled scripts from code cache"),
+                CacheStatus::Stored | CacheStatus::Uncacheable => {}
+                CacheStatus::Failed(err) => log::warn!("could not write to code cache: {err}"),
+            }
+            output
+        }
+        None => compiler.compile()?,
+    };
+
+    for warning in &output.warnings {
+        log::warn!("{warning}");
+    }
+
+    Ok(output)
+}
+
 struct TexturePlacement<'a> {
     texture_id: TextureId,
     size: Vec2<u32>,
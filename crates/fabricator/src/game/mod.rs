@@ -1,48 +1,67 @@
 mod create;
 mod maxrects;
+mod recording;
+mod render;
 mod tick;
 
+#[cfg(test)]
+mod tests;
+
+use std::vec;
+
 use anyhow::Error;
-use fabricator_math::{Affine2, Vec2};
+use fabricator_compiler::cache::CodeCache;
+use fabricator_math::{Affine2, Box2, Vec2};
+use fabricator_profiler::Profiler;
+use fabricator_stdlib::math::Rng;
 use fabricator_vm as vm;
+use gc_arena::{RefLock, Rootable};
+use image::RgbaImage;
+use rand::RngExt as _;
 
 use crate::{
-    project::Project,
+    api::exception::run_handled,
+    project::{ObjectEvent, Project},
     state::{
-        DrawingState, DrawnSpriteFrame, InputState, State, Texture, TextureId, TexturePage,
+        Clock, DrawingState, InputState, State, SurfaceId, Texture, TextureId, TexturePage,
         TexturePageId,
     },
 };
 
-use self::{create::create_state, tick::tick_state};
-
-#[derive(Debug)]
-pub struct Quad {
-    pub texture: TextureId,
-    // Transforms the quad (0, 0, texture_width, texture_height) into room coordinates.
-    pub transform: Affine2<f32>,
-    pub depth: i32,
-}
+pub use self::{
+    create::{create_configuration, runner_magic, sandbox_magic},
+    recording::{RecordedTick, Recording},
+    render::{
+        ColorVertex, DrawCommand, Quad, Render, RenderView, SurfacePass, SurfaceQuad, Triangles,
+        color_rgb,
+    },
+};
 
-#[derive(Debug)]
-pub struct Render {
-    pub quads: Vec<Quad>,
-    pub room_size: Vec2<u32>,
-}
+use self::{create::create_state, recording::hash_state, tick::tick_state};
 
-impl Default for Render {
-    fn default() -> Self {
-        Self {
-            quads: Vec::new(),
-            room_size: Vec2::zero(),
-        }
-    }
+/// Options for [`Game::with_options`].
+#[derive(Default)]
+pub struct GameOptions<'a> {
+    /// Load compiled scripts from this cache when their source is unchanged, and store any newly
+    /// compiled scripts in it.
+    pub code_cache: Option<&'a CodeCache>,
+    /// Called with the main script thread before any scripts are run, for example to install a
+    /// `vm::Hook`.
+    pub init_thread: Option<Box<dyn for<'gc> FnOnce(vm::Context<'gc>, vm::Thread<'gc>) + 'a>>,
+    /// Record the input and clock readings of every tick, available from [`Game::recording`].
+    pub record: bool,
+    /// Replay a previous recording with [`Game::replay_tick`].
+    ///
+    /// The game must be created from the same project and configuration as the recording.
+    pub replay: Option<Recording>,
 }
 
-impl Render {
-    pub fn clear(&mut self) {
-        self.quads.clear();
-        self.room_size = Vec2::zero();
+impl<'a> GameOptions<'a> {
+    pub fn set_init_thread(
+        &mut self,
+        init_thread: impl for<'gc> FnOnce(vm::Context<'gc>, vm::Thread<'gc>) + 'a,
+    ) {
+        self.init_thread = Some(Box::new(init_thread));
     }
 }
 
@@ -51,35 +70,98 @@ pub struct Game {
     main_thread: vm::StashedThread,
     state: State,
     drawing_state: DrawingState,
+    recording: Option<Recording>,
+    replay: Option<vec::IntoIter<RecordedTick>>,
 }
 
 impl Game {
     pub fn new(project: Project, config: &str) -> Result<Self, Error> {
+        Self::with_options(project, config, GameOptions::default())
+    }
+
+    /// Create a new `Game`, loading compiled scripts from the given cache when their source is
+    /// unchanged, and storing any newly compiled scripts in it.
+    pub fn with_code_cache(
+        project: Project,
+        config: &str,
+        code_cache: &CodeCache,
+    ) -> Result<Self, Error> {
+        Self::with_options(
+            project,
+            config,
+            GameOptions {
+                code_cache: Some(code_cache),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_options(
+        project: Project,
+        config: &str,
+        options: GameOptions<'_>,
+    ) -> Result<Self, Error> {
         let mut interpreter = vm::Interpreter::new();
-        let main_thread = interpreter.enter(|ctx| ctx.stash(vm::Thread::new(&ctx)));
+        let main_thread = interpreter.enter(|ctx| {
+            // Heap snapshots taken by scripts run while the state is frozen, so they can include
+            // everything the state holds.
+            vm::HeapSnapshotBuilder::register_roots(ctx, |ctx, builder| {
+                let _ = State::ctx_with(ctx, |state| state.add_heap_roots(ctx, builder));
+            });
+
+            let thread = vm::Thread::new(&ctx);
+            if let Some(init_thread) = options.init_thread {
+                init_thread(ctx, thread);
+            }
+            ctx.stash(thread)
+        });
 
         log::info!("creating new game state...");
-        let mut state = create_state(&mut interpreter, &project, config)?;
+        let mut state = create_state(&mut interpreter, &project, config, options.code_cache)?;
         log::info!("finished creating new game state!");
 
+        // Recording and replaying both need the game to be deterministic, so the pRNG is seeded
+        // and the clock is virtualized before any scripts run.
+        let mut recording = None;
+        let mut replay = None;
+        if let Some(replaying) = options.replay {
+            interpreter.enter(|ctx| Rng::singleton(ctx).make_deterministic(replaying.seed))?;
+            state.clock = Clock::replaying();
+            state.clock.set_readings(replaying.clock);
+            replay = Some(replaying.ticks.into_iter());
+        } else if options.record {
+            let seed = rand::rng().random();
+            interpreter.enter(|ctx| Rng::singleton(ctx).make_deterministic(seed))?;
+            state.clock = Clock::recording();
+            recording = Some(Recording::new(seed));
+        }
+
         log::info!("execuing all global scripts...");
         for closure in state.scripts.scripts.clone() {
             interpreter.enter(|ctx| -> Result<_, Error> {
                 let closure = ctx.fetch(&closure);
                 let thread = ctx.fetch(&main_thread);
                 log::debug!("executing script {}", closure.prototype().identifier());
-                State::ctx_cell(ctx).freeze(&mut state, || thread.run(ctx, closure))?;
+                State::ctx_cell(ctx).freeze(&mut state, || {
+                    run_handled(ctx, thread, |exec| exec.call(ctx, closure))
+                })?;
                 Ok(())
             })?;
         }
         interpreter.gc_collect_debt();
         log::info!("finished executing all global scripts!");
 
+        if let Some(recording) = &mut recording {
+            recording.clock = state.clock.take_readings();
+        }
+
         Ok(Game {
             interpreter,
             main_thread,
             state,
             drawing_state: DrawingState::default(),
+            recording,
+            replay,
         })
     }
 
@@ -99,76 +181,183 @@ impl Game {
         self.state.config.tick_rate
     }
 
-    pub fn tick(&mut self, input: &InputState, render: &mut Render) -> Result<(), Error> {
+    /// Report the contents of a surface after a backend has executed its surface passes, which is
+    /// what `surface_getpixel` reads.
+    ///
+    /// Contents are ignored if the surface has since been freed or resized.
+    pub fn set_surface_contents(&mut self, surface_id: SurfaceId, contents: RgbaImage) {
+        if let Some(surface) = self.state.surfaces.get_mut(surface_id)
+            && surface.size == Vec2::new(contents.width(), contents.height())
+        {
+            surface.contents = Some(contents);
+        }
+    }
+
+    /// Take all of the audio mixed since the last call, as interleaved stereo samples at
+    /// [`SAMPLE_RATE`](crate::SAMPLE_RATE).
+    ///
+    /// Every tick mixes exactly the audio for that tick, so this should be called regularly even
+    /// if the audio is not played.
+    pub fn take_audio(&mut self) -> Vec<f32> {
+        self.state.audio.take_output()
+    }
+
+    /// Everything recorded so far, if the game was created with [`GameOptions::record`].
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Run the next tick of the recording given by [`GameOptions::replay`].
+    ///
+    /// Returns false without ticking once every recorded tick has been replayed, or if the game is
+    /// not replaying a recording.
+    pub fn replay_tick(&mut self, render: &mut Render) -> Result<bool, Error> {
+        let Some(mut tick) = self.replay.as_mut().and_then(|replay| replay.next()) else {
+            return Ok(false);
+        };
+        self.state.clock.set_readings(tick.clock);
+        self.tick(&mut tick.input, render)?;
+        Ok(true)
+    }
+
+    /// A hash of the current game state, which should be identical at the same tick of a recording
+    /// and of its replay.
+    pub fn state_hash(&mut self) -> u64 {
+        hash_state(&mut self.interpreter, &self.state)
+    }
+
+    /// Take a snapshot of everything reachable from the globals, the main thread, and every value
+    /// held by the game state.
+    pub fn heap_snapshot(&self) -> vm::HeapSnapshot {
+        self.interpreter.heap_snapshot(|ctx, builder| {
+            builder.add_thread("main thread", ctx.fetch(&self.main_thread));
+            self.state.add_heap_roots(ctx, builder);
+        })
+    }
+
+    /// Run a single tick exactly like [`Game::tick`], recording every script call made during it
+    /// into `profiler`.
+    ///
+    /// Any hook already installed on the main thread is suspended for this tick and restored
+    /// afterwards.
+    pub fn profile_tick(
+        &mut self,
+        input: &mut InputState,
+        render: &mut Render,
+        profiler: &Profiler,
+    ) -> Result<(), Error> {
+        let saved_hook = self.interpreter.enter(|ctx| {
+            // Every script is compiled with the same magic, so the first one is enough to name all
+            // of the API callbacks.
+            if let Some(script) = self.state.scripts.scripts.first() {
+                profiler.name_callbacks(ctx, &ctx.fetch(script).prototype().magic());
+            }
+
+            let previous = ctx
+                .fetch(&self.main_thread)
+                .replace_hook(&ctx, Some(Box::new(profiler.hook())));
+            ctx.stash(vm::UserData::new::<
+                Rootable![RefLock<Option<Box<dyn vm::Hook<'_>>>>],
+            >(&ctx, RefLock::new(previous)))
+        });
+
+        let res = self.tick(input, render);
+
+        self.interpreter.enter(|ctx| {
+            let previous = ctx
+                .fetch(&saved_hook)
+                .downcast_write::<Rootable![RefLock<Option<Box<dyn vm::Hook<'_>>>>]>(&ctx)
+                .unwrap()
+                .unlock()
+                .take();
+            ctx.fetch(&self.main_thread).replace_hook(&ctx, previous);
+        });
+
+        res
+    }
+
+    /// Run a single game tick with the given input.
+    ///
+    /// Scripts may change the input state, for example by assigning `keyboard_string`, so the same
+    /// input state should be passed to every tick.
+    pub fn tick(&mut self, input: &mut InputState, render: &mut Render) -> Result<(), Error> {
         self.drawing_state.clear();
 
-        tick_state(
+        // Record the input as it was before scripts could change it, so that a replay starts each
+        // tick from the same input.
+        let recorded_input = self.recording.is_some().then(|| input.clone());
+
+        let res = tick_state(
             &mut self.state,
             &mut self.drawing_state,
             input,
             &mut self.interpreter,
             &self.main_thread,
-        )?;
+        );
+        // Ticks which error are still recorded, they are usually the ones worth replaying.
+        if let Some(recording) = &mut self.recording
+            && let Some(input) = recorded_input
+        {
+            recording.ticks.push(RecordedTick {
+                input,
+                clock: self.state.clock.take_readings(),
+            });
+        }
+        res?;
+        self.state.audio.mix_tick(self.state.config.tick_rate);
 
         render.clear();
-        render.room_size = self.state.config.rooms[self.state.current_room.unwrap()].size;
+        render.screen_size = self.state.screen_size();
+        render.gui_size = self.state.current_gui_size();
+        let views = self
+            .state
+            .views
+            .active(&self.state.cameras)
+            .map(|(view, transform)| RenderView {
+                transform: transform.cast(),
+                port: view.port,
+            });
+        render.views.extend(views);
 
+        // Instances of objects with a draw event draw themselves, with `draw_self` if they want
+        // their sprite drawn.
         for instance in self.state.instances.values() {
-            let object = &self.state.config.objects[instance.object];
-            if let Some(sprite_id) = object.sprite {
+            if !instance.visible || instance.event_closures.contains_key(&ObjectEvent::Draw) {
+                continue;
+            }
+
+            if let Some(sprite_id) = instance.sprite {
                 let sprite = &self.state.config.sprites[sprite_id];
-                let frame_index = match sprite
-                    .frames
-                    .binary_search_by(|f| f.frame_start.total_cmp(&instance.animation_time))
-                {
-                    Ok(i) => i,
-                    Err(i) => i.checked_sub(1).unwrap(),
-                };
-
-                let texture = sprite.frames[frame_index].texture;
+                let texture = sprite.frames[sprite.frame_at(instance.animation_time)].texture;
                 let transform = Affine2::new()
                     .translate(-sprite.origin.cast::<f64>())
+                    .scale(instance.scale)
                     .rotate(instance.rotation)
                     .translate(instance.position)
                     .cast();
                 let depth = self.state.layers[instance.layer].depth;
 
-                render.quads.push(Quad {
+                render.commands.push(DrawCommand::Quad(Quad {
                     texture,
                     transform,
+                    region: Box2::with_size(
+                        Vec2::zero(),
+                        self.state.config.textures[texture].size.cast(),
+                    ),
                     depth,
-                });
+                    tint: color_rgb(instance.blend),
+                    alpha: instance.alpha as f32,
+                }));
             }
         }
 
-        for drawn_sprite in &self.drawing_state.drawn_sprites {
-            let sprite = &self.state.config.sprites[drawn_sprite.sprite];
-            if let Some(instance) = self.state.instances.get(drawn_sprite.instance) {
-                let frame_index = match drawn_sprite.sub_img {
-                    DrawnSpriteFrame::CurrentAnimation => match sprite
-                        .frames
-                        .binary_search_by(|f| f.frame_start.total_cmp(&instance.animation_time))
-                    {
-                        Ok(i) => i,
-                        Err(i) => i.checked_sub(1).unwrap(),
-                    },
-                    DrawnSpriteFrame::Frame(i) => i % sprite.frames.len(),
-                };
-
-                let texture = sprite.frames[frame_index].texture;
-                let transform = Affine2::new()
-                    .translate(-sprite.origin.cast::<f64>())
-                    .translate(drawn_sprite.position)
-                    .cast();
-                let depth = self.state.layers[instance.layer].depth;
-
-                render.quads.push(Quad {
-                    texture,
-                    transform,
-                    depth,
-                });
-            }
-        }
+        render.commands.append(&mut self.drawing_state.commands);
+        render
+            .gui_commands
+            .append(&mut self.drawing_state.gui_commands);
+        render
+            .surface_passes
+            .append(&mut self.drawing_state.surface_passes);
 
         Ok(())
     }
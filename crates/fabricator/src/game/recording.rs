@@ -0,0 +1,212 @@
+use std::{
+    fs::File,
+    io::{BufRead as _, BufReader, BufWriter, Write as _},
+    path::Path,
+};
+
+use anyhow::{Context as _, Error};
+use fabricator_vm as vm;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::state::{InputState, State};
+
+/// Everything that a [`Game`](crate::Game) needs to deterministically replay a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// The pRNG seed the game was started with.
+    pub seed: u64,
+    /// Clock readings taken while running global scripts, before the first tick.
+    pub clock: Vec<u64>,
+    pub ticks: Vec<RecordedTick>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordedTick {
+    pub input: InputState,
+    /// Every clock reading taken by `get_timer` or `current_time` during the tick, in order, in
+    /// microseconds.
+    pub clock: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    seed: u64,
+    clock: Vec<u64>,
+}
+
+impl Recording {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            clock: Vec::new(),
+            ticks: Vec::new(),
+        }
+    }
+
+    /// Load a recording written by [`Recording::save`].
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header: Header = serde_json::from_str(&lines.next().context("empty recording")??)?;
+        let mut ticks = Vec::new();
+        for (i, line) in lines.enumerate() {
+            ticks.push(
+                serde_json::from_str(&line?)
+                    .with_context(|| format!("could not parse recorded tick {i}"))?,
+            );
+        }
+        Ok(Self {
+            seed: header.seed,
+            clock: header.clock,
+            ticks,
+        })
+    }
+
+    /// Save the recording as JSON lines, a header line followed by one line per tick.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(
+            &mut writer,
+            &Header {
+                seed: self.seed,
+                clock: self.clock.clone(),
+            },
+        )?;
+        writeln!(writer)?;
+        for tick in &self.ticks {
+            serde_json::to_writer(&mut writer, tick)?;
+            writeln!(writer)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A hash of the simulation state of a game, to find the first tick where a replay diverges.
+///
+/// This covers the globals, every instance and any of their properties which are booleans,
+/// numbers or strings. Other values only contribute their type, since following references could
+/// visit an unbounded amount of state.
+pub(super) fn hash_state(interpreter: &mut vm::Interpreter, state: &State) -> u64 {
+    let mut hasher = StateHasher(Sha256::new());
+
+    hasher.option_str(
+        state
+            .current_room
+            .map(|room_id| state.config.rooms[room_id].name.as_str()),
+    );
+
+    interpreter.enter(|ctx| {
+        hasher.object(ctx.globals());
+
+        for instance in state.instances.values() {
+            hasher.str(&state.config.objects[instance.object].name);
+            hasher.option_str(
+                instance
+                    .sprite
+                    .map(|sprite_id| state.config.sprites[sprite_id].name.as_str()),
+            );
+            for f in [
+                instance.position[0],
+                instance.position[1],
+                instance.scale[0],
+                instance.scale[1],
+                instance.rotation,
+                instance.alpha,
+                instance.image_speed,
+                instance.animation_time,
+                instance.motion.speed,
+                instance.motion.direction,
+                instance.motion.gravity,
+                instance.motion.gravity_direction,
+                instance.motion.friction,
+            ] {
+                hasher.f64(f);
+            }
+            hasher.bool(instance.active);
+            hasher.bool(instance.dead);
+            hasher.bool(instance.visible);
+            hasher.u64(instance.blend.into());
+            for alarm in instance.alarms {
+                hasher.u64(alarm as u64);
+            }
+
+            hasher.object(ctx.fetch(&instance.properties));
+        }
+    });
+
+    hasher.finish()
+}
+
+/// Feeds state into SHA-256 with a fixed encoding, so that the same state has the same hash on
+/// every platform and with every Rust version.
+struct StateHasher(Sha256);
+
+impl StateHasher {
+    fn u64(&mut self, v: u64) {
+        self.0.update(v.to_le_bytes());
+    }
+
+    fn f64(&mut self, f: f64) {
+        self.u64(f.to_bits());
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.0.update([b as u8]);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.u64(s.len() as u64);
+        self.0.update(s.as_bytes());
+    }
+
+    fn option_str(&mut self, s: Option<&str>) {
+        self.bool(s.is_some());
+        if let Some(s) = s {
+            self.str(s);
+        }
+    }
+
+    fn value(&mut self, value: vm::Value<'_>) {
+        match value {
+            vm::Value::Undefined => self.u64(0),
+            vm::Value::Boolean(b) => {
+                self.u64(1);
+                self.bool(b);
+            }
+            vm::Value::Integer(i) => {
+                self.u64(2);
+                self.u64(i as u64);
+            }
+            vm::Value::Float(f) => {
+                self.u64(3);
+                self.f64(f);
+            }
+            vm::Value::String(s) => {
+                self.u64(4);
+                self.str(s.as_str());
+            }
+            vm::Value::Object(_) => self.u64(5),
+            vm::Value::Array(_) => self.u64(6),
+            vm::Value::Closure(_) => self.u64(7),
+            vm::Value::Callback(_) => self.u64(8),
+            vm::Value::UserData(_) => self.u64(9),
+        }
+    }
+
+    /// Hash every field of an object in order of their names.
+    fn object(&mut self, object: vm::Object<'_>) {
+        let fields = object.borrow();
+        let mut fields = fields.iter().collect::<Vec<_>>();
+        fields.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        self.u64(fields.len() as u64);
+        for (key, value) in fields {
+            self.str(key.as_str());
+            self.value(value);
+        }
+    }
+
+    fn finish(self) -> u64 {
+        u64::from_le_bytes(self.0.finalize()[0..8].try_into().unwrap())
+    }
+}
@@ -0,0 +1,256 @@
+use std::{mem, path::PathBuf};
+
+use fabricator_math::{Affine2, Box2, Vec2, Vec3, Vec4};
+
+use crate::state::{SurfaceId, TextureId};
+
+#[derive(Debug, Clone)]
+pub struct Quad {
+    pub texture: TextureId,
+    // Transforms the quad (0, 0, texture_width, texture_height) into room coordinates.
+    pub transform: Affine2<f32>,
+    /// The part of the texture which is drawn, in the same coordinates that `transform` maps from.
+    ///
+    /// This is usually the whole texture, but may be smaller (for example, for
+    /// `draw_sprite_part`).
+    pub region: Box2<f32>,
+    pub depth: i32,
+    /// RGB color multiplied with the texture color, white leaves the texture unchanged.
+    pub tint: Vec3<f32>,
+    /// Opacity multiplied with the texture alpha.
+    pub alpha: f32,
+}
+
+/// A quad which draws part of a surface, the same as [`Quad`] but textured with a surface.
+#[derive(Debug, Clone)]
+pub struct SurfaceQuad {
+    pub surface: SurfaceId,
+    // Transforms the quad (0, 0, surface_width, surface_height) into room coordinates.
+    pub transform: Affine2<f32>,
+    /// The part of the surface which is drawn, in the same coordinates that `transform` maps from.
+    pub region: Box2<f32>,
+    pub depth: i32,
+    pub tint: Vec3<f32>,
+    pub alpha: f32,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ColorVertex {
+    pub position: Vec2<f32>,
+    /// Straight (not premultiplied) RGBA color.
+    pub color: Vec4<f32>,
+}
+
+/// A list of untextured triangles, every three vertices form one triangle.
+#[derive(Debug, Clone)]
+pub struct Triangles {
+    pub vertices: Vec<ColorVertex>,
+    pub depth: i32,
+}
+
+impl Triangles {
+    pub fn new(depth: i32) -> Self {
+        Self {
+            vertices: Vec::new(),
+            depth,
+        }
+    }
+
+    pub fn push_triangle(&mut self, points: [Vec2<f32>; 3], color: Vec4<f32>) {
+        self.vertices.extend(
+            points
+                .into_iter()
+                .map(|position| ColorVertex { position, color }),
+        );
+    }
+
+    pub fn push_rect(&mut self, rect: Box2<f32>, color: Vec4<f32>) {
+        let [a, b, c, d] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]].map(|p| rect.eval(p));
+        self.push_triangle([a, b, c], color);
+        self.push_triangle([c, d, a], color);
+    }
+
+    /// Push a line segment from `a` to `b`, `width` is measured perpendicular to the line.
+    pub fn push_line(&mut self, a: Vec2<f32>, b: Vec2<f32>, width: f32, color: Vec4<f32>) {
+        let dir = b - a;
+        let len = dir.length();
+        if len == 0.0 {
+            return;
+        }
+
+        let offset = Vec2::new(-dir[1], dir[0]) * (width / (2.0 * len));
+        self.push_triangle([a + offset, b + offset, b - offset], color);
+        self.push_triangle([b - offset, a - offset, a + offset], color);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DrawCommand {
+    Quad(Quad),
+    Triangles(Triangles),
+    Surface(SurfaceQuad),
+}
+
+impl DrawCommand {
+    pub fn depth(&self) -> i32 {
+        match self {
+            DrawCommand::Quad(quad) => quad.depth,
+            DrawCommand::Triangles(triangles) => triangles.depth,
+            DrawCommand::Surface(quad) => quad.depth,
+        }
+    }
+}
+
+/// An operation on a surface.
+///
+/// Surfaces keep their contents between ticks, so backends must execute every pass in order, and
+/// must keep the contents of every surface until it is freed.
+#[derive(Debug, Clone)]
+pub enum SurfacePass {
+    /// Create a surface, or resize an existing one. In both cases the surface is filled with
+    /// transparent black.
+    Allocate {
+        surface: SurfaceId,
+        size: Vec2<u32>,
+    },
+    Free {
+        surface: SurfaceId,
+    },
+    /// Draw commands into the surface in the order they were issued, in surface pixel coordinates.
+    ///
+    /// Depth is ignored.
+    Draw {
+        surface: SurfaceId,
+        commands: Vec<DrawCommand>,
+    },
+    /// Replace the whole contents of a surface with a straight (not premultiplied) RGBA color.
+    Clear {
+        surface: SurfaceId,
+        color: Vec4<f32>,
+    },
+    /// Copy `region` of `source` into `surface` at `position`, replacing what was there.
+    Copy {
+        surface: SurfaceId,
+        position: Vec2<u32>,
+        source: SurfaceId,
+        region: Box2<u32>,
+    },
+    /// Save `region` of a surface to a PNG file.
+    Save {
+        surface: SurfaceId,
+        region: Box2<u32>,
+        path: PathBuf,
+    },
+}
+
+/// A view of the room drawn into part of the screen.
+#[derive(Debug, Copy, Clone)]
+pub struct RenderView {
+    /// Transforms room coordinates into screen coordinates.
+    pub transform: Affine2<f32>,
+    /// The area of the screen the view is drawn into, nothing is drawn outside of it.
+    pub port: Box2<u32>,
+}
+
+#[derive(Debug)]
+pub struct Render {
+    pub commands: Vec<DrawCommand>,
+    pub screen_size: Vec2<u32>,
+    /// Every command is drawn once for each view, in order.
+    ///
+    /// If there are no views, the commands are drawn untransformed to the whole screen, so room
+    /// coordinates are screen coordinates.
+    pub views: Vec<RenderView>,
+    /// Commands drawn after every view, in GUI space.
+    pub gui_commands: Vec<DrawCommand>,
+    /// The size of GUI space, which is stretched to cover the whole screen.
+    pub gui_size: Vec2<u32>,
+    /// Every surface operation issued so far, which should be executed before the screen is
+    /// drawn.
+    ///
+    /// Surface commands on the screen use the contents of a surface after every pass.
+    ///
+    /// Unlike every other field, these are kept by [`Render::clear`] so that no pass is lost if a
+    /// backend skips drawing some ticks. Backends remove them with
+    /// [`Render::take_surface_passes`].
+    pub surface_passes: Vec<SurfacePass>,
+}
+
+impl Default for Render {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            screen_size: Vec2::zero(),
+            views: Vec::new(),
+            gui_commands: Vec::new(),
+            gui_size: Vec2::zero(),
+            surface_passes: Vec::new(),
+        }
+    }
+}
+
+impl Render {
+    /// Clear everything for the next tick, except for surface passes.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.screen_size = Vec2::zero();
+        self.views.clear();
+        self.gui_commands.clear();
+        self.gui_size = Vec2::zero();
+    }
+
+    pub fn take_surface_passes(&mut self) -> Vec<SurfacePass> {
+        mem::take(&mut self.surface_passes)
+    }
+
+    /// Returns every view to draw, a single view covering the whole screen if `views` is empty.
+    pub fn passes(&self) -> Vec<RenderView> {
+        if self.views.is_empty() {
+            vec![RenderView {
+                transform: Affine2::new(),
+                port: Box2::with_size(Vec2::zero(), self.screen_size),
+            }]
+        } else {
+            self.views.clone()
+        }
+    }
+
+    /// Returns the view that GUI commands are drawn with, which stretches GUI space over the whole
+    /// screen.
+    pub fn gui_pass(&self) -> RenderView {
+        let transform = if self.gui_size[0] == 0 || self.gui_size[1] == 0 {
+            Affine2::new()
+        } else {
+            Affine2::new().scale(self.screen_size.cast::<f32>() / self.gui_size.cast::<f32>())
+        };
+        RenderView {
+            transform,
+            port: Box2::with_size(Vec2::zero(), self.screen_size),
+        }
+    }
+
+    /// Returns every command in the order it should be drawn.
+    ///
+    /// Commands are drawn in order of decreasing depth, commands with the same depth are drawn in
+    /// the order they were issued.
+    pub fn sorted_commands(&self) -> Vec<&DrawCommand> {
+        sort_commands(&self.commands)
+    }
+
+    /// Returns every GUI command in the order it should be drawn, the same order as
+    /// [`Render::sorted_commands`].
+    pub fn sorted_gui_commands(&self) -> Vec<&DrawCommand> {
+        sort_commands(&self.gui_commands)
+    }
+}
+
+fn sort_commands(commands: &[DrawCommand]) -> Vec<&DrawCommand> {
+    let mut commands = commands.iter().collect::<Vec<_>>();
+    commands.sort_by_key(|command| -(command.depth() as i64));
+    commands
+}
+
+/// Convert a GML color, stored as `0xBBGGRR`, into an RGB color.
+pub fn color_rgb(color: u32) -> Vec3<f32> {
+    Vec3::new(color & 0xff, (color >> 8) & 0xff, (color >> 16) & 0xff).cast::<f32>() / 255.0
+}
@@ -0,0 +1,658 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs, mem,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use fabricator_math::Vec2;
+use fabricator_vm as vm;
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    game::{
+        DrawCommand, Game, GameOptions, Render, create_configuration, runner_magic, sandbox_magic,
+    },
+    project::{
+        AnimationFrame, CollisionKind, EventScript, Frame, Instance, Layer, LayerType, Object,
+        ObjectEvent, Project, Room, Script, ScriptMode, Sprite, TextureGroup,
+    },
+    state::{InputState, VirtualKey},
+};
+
+/// Builds a single room project in a temporary directory, for testing whole game ticks.
+///
+/// Every project has a global script which sets `global.log` to the empty string, which event
+/// scripts can append to and tests can read back with [`log`].
+struct TestProject {
+    dir: PathBuf,
+    project: Project,
+    instances: Vec<Instance>,
+}
+
+impl TestProject {
+    fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "fabricator-game-test-{}-{}",
+            process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut project = TestProject {
+            project: empty_project(&dir),
+            dir,
+            instances: Vec::new(),
+        };
+        project.project.texture_groups.insert(
+            "Default".to_owned(),
+            TextureGroup {
+                name: "Default".to_owned(),
+                auto_crop: false,
+                border: 0,
+            },
+        );
+        project.project.room_order.push("room".to_owned());
+
+        let path = project.write("init.gml", "global.log = \"\";");
+        project.project.scripts.insert(
+            "init".to_owned(),
+            Script {
+                name: "init".to_owned(),
+                path,
+                mode: ScriptMode::Compat,
+            },
+        );
+
+        project
+    }
+
+    fn write(&self, name: &str, source: &str) -> PathBuf {
+        let path = self.dir.join(name);
+        fs::write(&path, source).unwrap();
+        path
+    }
+
+    /// Add a solid white `size` x `size` sprite with its origin at its top left corner.
+    fn sprite(&mut self, name: &str, size: u32) -> &mut Self {
+        let image_path = self.dir.join(format!("{name}.png"));
+        RgbaImage::from_pixel(size, size, Rgba([255; 4]))
+            .save(&image_path)
+            .unwrap();
+
+        self.project.sprites.insert(
+            name.to_owned(),
+            Sprite {
+                name: name.to_owned(),
+                base_path: self.dir.clone(),
+                texture_group: "Default".to_owned(),
+                frames: HashMap::from([(
+                    "frame".to_owned(),
+                    Frame {
+                        name: "frame".to_owned(),
+                        image_path,
+                    },
+                )]),
+                width: size,
+                height: size,
+                playback_speed: 0.0,
+                playback_length: 1.0,
+                origin_x: 0,
+                origin_y: 0,
+                collision_kind: CollisionKind::Rectangle,
+                bbox_bottom: size as i32 - 1,
+                bbox_left: 0,
+                bbox_top: 0,
+                bbox_right: size as i32 - 1,
+                animation_frames: vec![AnimationFrame {
+                    frame: "frame".to_owned(),
+                    length: 1.0,
+                }],
+            },
+        );
+        self
+    }
+
+    /// Add an object with the given parent and sprite, and with the given event scripts.
+    fn object(
+        &mut self,
+        name: &str,
+        parent: Option<&str>,
+        sprite: Option<&str>,
+        events: &[(ObjectEvent, &str)],
+    ) -> &mut Self {
+        let event_scripts = events
+            .iter()
+            .enumerate()
+            .map(|(i, &(event, source))| {
+                let path = self.write(&format!("{name}_{i}.gml"), source);
+                (
+                    event,
+                    EventScript {
+                        path,
+                        mode: ScriptMode::Compat,
+                    },
+                )
+            })
+            .collect();
+
+        self.project.objects.insert(
+            name.to_owned(),
+            Object {
+                name: name.to_owned(),
+                parent_object: parent.map(str::to_owned),
+                base_path: self.dir.clone(),
+                persistent: false,
+                sprite: sprite.map(str::to_owned),
+                mask: None,
+                event_scripts,
+                collision_scripts: HashMap::new(),
+                tags: HashSet::new(),
+            },
+        );
+        self
+    }
+
+    /// Add a collision event to an object which has already been added.
+    fn collision(&mut self, name: &str, target: &str, source: &str) -> &mut Self {
+        let path = self.write(&format!("{name}_collision_{target}.gml"), source);
+        self.project
+            .objects
+            .get_mut(name)
+            .unwrap()
+            .collision_scripts
+            .insert(
+                target.to_owned(),
+                EventScript {
+                    path,
+                    mode: ScriptMode::Compat,
+                },
+            );
+        self
+    }
+
+    /// Place an instance of an object in the room.
+    fn instance(&mut self, object: &str, x: f64, y: f64) -> &mut Self {
+        self.instances.push(Instance {
+            object: object.to_owned(),
+            x,
+            y,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotation: 0.0,
+        });
+        self
+    }
+
+    fn build(&mut self) -> Game {
+        self.build_with_options(GameOptions::default())
+    }
+
+    fn build_with_options(&mut self, options: GameOptions<'_>) -> Game {
+        Game::with_options(self.project(), "default", options).unwrap()
+    }
+
+    /// Finish the room and take the project, leaving this empty.
+    fn project(&mut self) -> Project {
+        self.project.rooms.insert(
+            "room".to_owned(),
+            Room {
+                name: "room".to_owned(),
+                base_path: self.dir.clone(),
+                width: 640,
+                height: 480,
+                enable_views: false,
+                views: Vec::new(),
+                layers: HashMap::from([(
+                    "Instances".to_owned(),
+                    Layer {
+                        name: "Instances".to_owned(),
+                        visible: true,
+                        depth: 0,
+                        layer_type: LayerType::Instances(self.instances.drain(..).collect()),
+                    },
+                )]),
+                tags: HashSet::new(),
+            },
+        );
+
+        mem::replace(&mut self.project, empty_project(&self.dir))
+    }
+}
+
+fn empty_project(dir: &Path) -> Project {
+    Project {
+        name: "test".to_owned(),
+        base_path: dir.to_owned(),
+        texture_groups: HashMap::new(),
+        room_order: Vec::new(),
+        sprites: HashMap::new(),
+        objects: HashMap::new(),
+        rooms: HashMap::new(),
+        scripts: HashMap::new(),
+        extensions: HashMap::new(),
+        fonts: HashMap::new(),
+        shaders: HashMap::new(),
+        sounds: HashMap::new(),
+        tile_sets: HashMap::new(),
+    }
+}
+
+impl Drop for TestProject {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Run the given number of ticks, returning the render from the last one.
+fn run_ticks(game: &mut Game, ticks: usize) -> Render {
+    let mut input = InputState::default();
+    let mut render = Render::default();
+    for _ in 0..ticks {
+        game.tick(&mut input, &mut render).unwrap();
+    }
+    render
+}
+
+/// The x positions of every quad drawn in room space, in ascending order.
+fn quad_positions(render: &Render) -> Vec<f32> {
+    let mut positions = render
+        .commands
+        .iter()
+        .filter_map(|command| match command {
+            DrawCommand::Quad(quad) => Some(quad.transform.translation[0]),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    positions.sort_by(f32::total_cmp);
+    positions
+}
+
+/// Read back the current value of `global.log`.
+fn log(game: &mut Game) -> String {
+    game.interpreter
+        .enter(|ctx| match ctx.globals().find_field(ctx, "log") {
+            Some(vm::Value::String(log)) => log.as_str().to_owned(),
+            other => panic!("`global.log` is not a string: {other:?}"),
+        })
+}
+
+#[test]
+fn test_alarm_countdown() {
+    let mut game = TestProject::new()
+        .object(
+            "obj",
+            None,
+            None,
+            &[
+                (ObjectEvent::Create, "alarm[0] = 3;"),
+                (ObjectEvent::Step, "global.log += \"step;\";"),
+                (
+                    ObjectEvent::Alarm(0),
+                    "global.log += \"alarm \" + string(alarm[0]) + \";\";",
+                ),
+            ],
+        )
+        .instance("obj", 0.0, 0.0)
+        .build();
+
+    run_ticks(&mut game, 2);
+    assert_eq!(log(&mut game), "step;step;");
+
+    // The alarm fires on the tick it reaches 0, and is reset to -1 before its event runs.
+    run_ticks(&mut game, 1);
+    assert_eq!(log(&mut game), "step;step;alarm -1;step;");
+
+    run_ticks(&mut game, 3);
+    assert_eq!(log(&mut game), "step;step;alarm -1;step;step;step;step;");
+}
+
+#[test]
+fn test_alarm_rearm() {
+    let mut game = TestProject::new()
+        .object(
+            "obj",
+            None,
+            None,
+            &[
+                (ObjectEvent::Create, "alarm[1] = 2;"),
+                (
+                    ObjectEvent::Alarm(1),
+                    "global.log += \"alarm;\"; alarm[1] = 2;",
+                ),
+            ],
+        )
+        .instance("obj", 0.0, 0.0)
+        .build();
+
+    run_ticks(&mut game, 6);
+    assert_eq!(log(&mut game), "alarm;alarm;alarm;");
+}
+
+#[test]
+fn test_alarm_event_order() {
+    let mut game = TestProject::new()
+        .object(
+            "obj",
+            None,
+            None,
+            &[
+                (ObjectEvent::Create, "alarm[0] = 1;"),
+                (ObjectEvent::BeginStep, "global.log += \"begin;\";"),
+                (ObjectEvent::Alarm(0), "global.log += \"alarm;\";"),
+                (ObjectEvent::Step, "global.log += \"step;\";"),
+                (ObjectEvent::EndStep, "global.log += \"end;\";"),
+            ],
+        )
+        .instance("obj", 0.0, 0.0)
+        .build();
+
+    run_ticks(&mut game, 2);
+    assert_eq!(log(&mut game), "begin;alarm;step;end;begin;step;end;");
+}
+
+#[test]
+fn test_alarm_skips_destroyed_instance() {
+    let mut game = TestProject::new()
+        .object(
+            "obj",
+            None,
+            None,
+            &[
+                (ObjectEvent::Create, "alarm[0] = 1;"),
+                (ObjectEvent::BeginStep, "instance_destroy();"),
+                (ObjectEvent::Alarm(0), "global.log += \"alarm;\";"),
+            ],
+        )
+        .instance("obj", 0.0, 0.0)
+        .build();
+
+    run_ticks(&mut game, 2);
+    assert_eq!(log(&mut game), "");
+}
+
+#[test]
+fn test_collision_with_parent_object() {
+    let mut game = TestProject::new()
+        .sprite("spr", 16)
+        .object("wall_parent", None, None, &[])
+        .object(
+            "wall",
+            Some("wall_parent"),
+            Some("spr"),
+            &[(ObjectEvent::Create, "name = \"wall\";")],
+        )
+        .object(
+            "player",
+            None,
+            Some("spr"),
+            &[(ObjectEvent::Create, "name = \"player\";")],
+        )
+        .collision(
+            "player",
+            "wall_parent",
+            "global.log += self.name + \" hit \" + other.name + \";\";",
+        )
+        .instance("player", 0.0, 0.0)
+        .instance("wall", 8.0, 8.0)
+        .instance("wall", 100.0, 100.0)
+        .build();
+
+    run_ticks(&mut game, 1);
+    assert_eq!(log(&mut game), "player hit wall;");
+}
+
+#[test]
+fn test_collision_destroyed_mid_dispatch() {
+    // Instances are dispatched in creation order, so the player's collision event runs first and
+    // destroys it. Neither the player's second collision nor either wall's collision with the
+    // destroyed player may run.
+    let mut game = TestProject::new()
+        .sprite("spr", 16)
+        .object("player", None, Some("spr"), &[])
+        .object("wall", None, Some("spr"), &[])
+        .collision(
+            "player",
+            "wall",
+            "global.log += \"player;\"; instance_destroy();",
+        )
+        .collision("wall", "player", "global.log += \"wall;\";")
+        .instance("player", 0.0, 0.0)
+        .instance("wall", 8.0, 0.0)
+        .instance("wall", 0.0, 8.0)
+        .build();
+
+    run_ticks(&mut game, 2);
+    assert_eq!(log(&mut game), "player;");
+}
+
+#[test]
+fn test_auto_draw() {
+    let mut game = TestProject::new()
+        .sprite("spr", 16)
+        .object("plain", None, Some("spr"), &[])
+        .object(
+            "hidden",
+            None,
+            Some("spr"),
+            &[(ObjectEvent::Create, "visible = false;")],
+        )
+        .object(
+            "custom",
+            None,
+            Some("spr"),
+            &[(ObjectEvent::Draw, "global.log += \"draw;\";")],
+        )
+        .object(
+            "custom_self",
+            None,
+            Some("spr"),
+            &[(ObjectEvent::Draw, "draw_self();")],
+        )
+        .instance("plain", 0.0, 0.0)
+        .instance("hidden", 100.0, 0.0)
+        .instance("custom", 200.0, 0.0)
+        .instance("custom_self", 300.0, 0.0)
+        .build();
+
+    // Only instances without a draw event are drawn automatically, the others only draw what
+    // their draw event does.
+    let render = run_ticks(&mut game, 1);
+    assert_eq!(quad_positions(&render), [0.0, 300.0]);
+    assert_eq!(log(&mut game), "draw;");
+}
+
+#[test]
+fn test_draw_events_visibility() {
+    // Every object logs its draw events, prefixed with the `name` set by its create event.
+    let draw_events = |create: &'static str| {
+        [
+            (ObjectEvent::PreDraw, "global.log += name + \" pre;\";"),
+            (ObjectEvent::Draw, "global.log += name + \" draw;\";"),
+            (ObjectEvent::DrawGui, "global.log += name + \" gui;\";"),
+            (ObjectEvent::Create, create),
+        ]
+    };
+
+    let mut game = TestProject::new()
+        .object("shown", None, None, &draw_events("name = \"shown\";"))
+        .object(
+            "hidden",
+            None,
+            None,
+            &draw_events("name = \"hidden\"; visible = false;"),
+        )
+        .object(
+            "destroyed",
+            None,
+            None,
+            &[
+                (ObjectEvent::Step, "instance_destroy();"),
+                (ObjectEvent::Draw, "global.log += \"destroyed draw;\";"),
+            ],
+        )
+        .instance("shown", 0.0, 0.0)
+        .instance("hidden", 0.0, 0.0)
+        .instance("destroyed", 0.0, 0.0)
+        .build();
+
+    run_ticks(&mut game, 1);
+    assert_eq!(log(&mut game), "shown pre;shown draw;shown gui;");
+}
+
+#[test]
+fn test_window_resize_skips_destroyed_instance() {
+    let mut game = TestProject::new()
+        .object(
+            "kept",
+            None,
+            None,
+            &[(ObjectEvent::WindowResize, "global.log += \"kept resize;\";")],
+        )
+        .object(
+            "destroyed",
+            None,
+            None,
+            &[
+                (ObjectEvent::Create, "ticks = 0;"),
+                (
+                    ObjectEvent::Step,
+                    "ticks += 1; if (ticks == 2) instance_destroy();",
+                ),
+                (
+                    ObjectEvent::WindowResize,
+                    "global.log += \"destroyed resize;\";",
+                ),
+            ],
+        )
+        .instance("kept", 0.0, 0.0)
+        .instance("destroyed", 0.0, 0.0)
+        .build();
+
+    let mut input = InputState::default();
+    let mut render = Render::default();
+
+    input.window_size = Vec2::new(640, 480);
+    game.tick(&mut input, &mut render).unwrap();
+    assert_eq!(log(&mut game), "");
+
+    // The window is resized on the same tick that the instance destroys itself.
+    input.window_size = Vec2::new(800, 600);
+    game.tick(&mut input, &mut render).unwrap();
+    assert_eq!(log(&mut game), "kept resize;");
+}
+
+#[test]
+fn test_record_replay_hashes() {
+    // Uses the pRNG, the clock and the keyboard, which are everything a replay must reproduce.
+    let project = || {
+        let mut project = TestProject::new();
+        project
+            .object(
+                "obj",
+                None,
+                None,
+                &[
+                    (
+                        ObjectEvent::Create,
+                        "x = irandom(100); hspeed = random(2); gravity = 0.1; global.timer = 0;",
+                    ),
+                    (
+                        ObjectEvent::Step,
+                        "global.timer = get_timer(); if (keyboard_check(vk_space)) { y -= 5; }",
+                    ),
+                ],
+            )
+            .instance("obj", 0.0, 0.0)
+            .instance("obj", 0.0, 0.0);
+        project
+    };
+
+    let mut game = project().build_with_options(GameOptions {
+        record: true,
+        ..Default::default()
+    });
+    let mut input = InputState::default();
+    let mut render = Render::default();
+    let mut hashes = Vec::new();
+    for tick in 0..20 {
+        if tick % 4 == 0 {
+            input.keyboard.key_down(VirtualKey::SPACE);
+        } else if tick % 4 == 2 {
+            input.keyboard.key_up(VirtualKey::SPACE);
+        }
+        game.tick(&mut input, &mut render).unwrap();
+        input.end_tick();
+        hashes.push(game.state_hash());
+    }
+    assert!(hashes.windows(2).all(|w| w[0] != w[1]));
+
+    let mut replay = project().build_with_options(GameOptions {
+        replay: Some(game.recording().unwrap().clone()),
+        ..Default::default()
+    });
+    let mut replayed_hashes = Vec::new();
+    while replay.replay_tick(&mut render).unwrap() {
+        replayed_hashes.push(replay.state_hash());
+    }
+    assert_eq!(replayed_hashes, hashes);
+}
+
+#[test]
+fn test_sandbox_magic() {
+    // Every runner function which exposes the filesystem, the host OS environment, or other host
+    // state such as raw memory addresses.
+    const HOST_MAGIC: &[&str] = &[
+        "buffer_get_address",
+        "environment_get_variable",
+        "file_exists",
+        "buffer_load",
+        "game_save_id",
+        "buffer_save",
+        "file_rename",
+        "directory_exists",
+        "directory_destroy",
+        "file_find_first",
+        "file_find_close",
+        "surface_save",
+        "surface_save_part",
+    ];
+
+    let mut test_project = TestProject::new();
+    let project = test_project.project();
+    let mut interpreter = vm::Interpreter::new();
+    let config = create_configuration(&mut interpreter, &project).unwrap();
+
+    interpreter.enter(|ctx| {
+        let runner = runner_magic(ctx, &config).unwrap();
+        let sandbox = sandbox_magic(ctx, &config).unwrap();
+
+        for &name in HOST_MAGIC {
+            let name = ctx.intern_static(name);
+            assert!(
+                runner.find(name).is_some(),
+                "{name} missing from runner magic"
+            );
+            assert!(
+                sandbox.find(name).is_none(),
+                "{name} exposed by sandbox magic"
+            );
+        }
+
+        // Everything else the runner provides is available in the sandbox, so any new function
+        // with host access must be added to the list above.
+        for (name, _) in sandbox.names() {
+            assert!(
+                runner.find(name).is_some(),
+                "{name} missing from runner magic"
+            );
+        }
+        assert_eq!(
+            runner.names().count(),
+            sandbox.names().count() + HOST_MAGIC.len()
+        );
+    });
+}
@@ -1,13 +1,21 @@
 use anyhow::Error;
-use fabricator_collision::support_ext::SupportMapExt as _;
+use fabricator_collision::{bound_box_tree::BoundBoxQuery, support_ext::SupportMapExt as _};
 use fabricator_util::{freeze::FreezeMany, index_containers::IndexSet};
 use fabricator_vm as vm;
 
 use crate::{
-    api::{instance::InstanceUserData, layer::LayerIdUserData, tile::TileMapUserData},
+    api::{
+        camera::CameraUserData,
+        exception::{run_handled, run_script},
+        instance::InstanceUserData,
+        layer::LayerIdUserData,
+        surface::{SurfaceUserData, free_surface},
+        tile::TileMapUserData,
+    },
     project::ObjectEvent,
     state::{
-        DrawingState, EventState, InputState, Instance, State,
+        Camera, CameraTarget, DrawingState, EventState, InputState, Instance, InstanceId, Motion,
+        State,
         configuration::RoomLayerType,
         state::{Layer, TileMap},
     },
@@ -16,7 +24,7 @@ use crate::{
 pub fn tick_state(
     state: &mut State,
     drawing_state: &mut DrawingState,
-    input_state: &InputState,
+    input_state: &mut InputState,
     interpreter: &mut vm::Interpreter,
     thread: &vm::StashedThread,
 ) -> Result<(), Error> {
@@ -75,9 +83,9 @@ pub fn tick_state(
                             },
                         )
                         .freeze(InputState::ctx_cell(ctx), input_state)
+                        .freeze(DrawingState::ctx_cell(ctx), drawing_state)
                         .in_scope(|| {
-                            ctx.fetch(thread)
-                                .run_with(ctx, room_end_closure, instance_ud)
+                            run_script(ctx, ctx.fetch(thread), room_end_closure, instance_ud)
                         })?;
                 }
 
@@ -106,9 +114,9 @@ pub fn tick_state(
                             },
                         )
                         .freeze(InputState::ctx_cell(ctx), input_state)
+                        .freeze(DrawingState::ctx_cell(ctx), drawing_state)
                         .in_scope(|| {
-                            ctx.fetch(thread)
-                                .run_with(ctx, clean_up_closure, instance_ud)
+                            run_script(ctx, ctx.fetch(thread), clean_up_closure, instance_ud)
                         })?;
                 }
 
@@ -206,6 +214,25 @@ pub fn tick_state(
         state.current_room = Some(next_room);
         let next_room_data = state.config.rooms[next_room].clone();
 
+        // Every view keeps its camera between rooms, but the camera settings are reset to the
+        // view settings of the next room.
+
+        state.views.enabled = next_room_data.enable_views;
+        for (view, room_view) in state.views.views.iter_mut().zip(&next_room_data.views) {
+            let camera_id = match view.camera.filter(|&id| state.cameras.contains(id)) {
+                Some(camera_id) => camera_id,
+                None => state.cameras.insert_with_id(|id| {
+                    let this = interpreter.enter(|ctx| ctx.stash(CameraUserData::new(ctx, id)));
+                    Camera::new(this, room_view.view)
+                }),
+            };
+            state.cameras[camera_id].reset(room_view);
+
+            view.visible = room_view.visible;
+            view.camera = Some(camera_id);
+            view.port = room_view.port;
+        }
+
         for layer in next_room_data.layers.values() {
             let layer_id = state.named_layers[&layer.name];
             if let RoomLayerType::Instances(template_ids) = &layer.layer_type {
@@ -220,6 +247,8 @@ pub fn tick_state(
                         }
 
                         let event_closures = state.event_closures(instance_template.object);
+                        let object = &state.config.objects[instance_template.object];
+                        let (sprite, mask) = (object.sprite, object.mask);
 
                         let instance_id = state.instances.insert_with_id(|instance_id| Instance {
                             this: ctx.stash(InstanceUserData::new(ctx, instance_id)),
@@ -227,11 +256,22 @@ pub fn tick_state(
                             active: true,
                             dead: false,
                             position: instance_template.position,
-                            rotation: 0.0,
+                            previous_position: instance_template.position,
+                            start_position: instance_template.position,
+                            motion: Motion::default(),
+                            sprite,
+                            mask,
+                            scale: instance_template.scale,
+                            rotation: instance_template.rotation,
+                            alpha: 1.0,
+                            blend: 0xffffff,
+                            image_speed: 1.0,
+                            visible: true,
                             layer: layer_id,
                             properties: ctx.stash(vm::Object::new(&ctx)),
                             event_closures,
                             animation_time: 0.0,
+                            alarms: [-1; ObjectEvent::ALARM_COUNT],
                         });
 
                         assert!(
@@ -274,7 +314,8 @@ pub fn tick_state(
                                     },
                                 )
                                 .freeze(InputState::ctx_cell(ctx), input_state)
-                                .in_scope(|| thread.run_with(ctx, closure, this))?;
+                                .freeze(DrawingState::ctx_cell(ctx), drawing_state)
+                                .in_scope(|| run_script(ctx, thread, closure, this))?;
                         }
 
                         Ok(())
@@ -317,9 +358,9 @@ pub fn tick_state(
                             },
                         )
                         .freeze(InputState::ctx_cell(ctx), input_state)
+                        .freeze(DrawingState::ctx_cell(ctx), drawing_state)
                         .in_scope(|| {
-                            ctx.fetch(thread)
-                                .run_with(ctx, room_start_closure, instance_ud)
+                            run_script(ctx, ctx.fetch(thread), room_start_closure, instance_ud)
                         })?;
                 }
 
@@ -334,18 +375,12 @@ pub fn tick_state(
         .filter_map(|(id, instance)| if instance.active { Some(id) } else { None })
         .collect::<Vec<_>>();
 
-    let instance_bounds = to_update
-        .iter()
-        .filter_map(|&instance_id| {
-            Some((
-                state.instance_collision(instance_id)?.bound_box(),
-                instance_id,
-            ))
-        })
-        .collect::<Vec<_>>();
+    rebuild_instance_bound_tree(state, &to_update);
 
-    state.instance_bound_tree.clear();
-    state.instance_bound_tree.fextend(instance_bounds);
+    for &instance_id in &to_update {
+        let instance = &mut state.instances[instance_id];
+        instance.previous_position = instance.position;
+    }
 
     for &instance_id in &to_update {
         let object_id = state.instances[instance_id].object;
@@ -370,23 +405,40 @@ pub fn tick_state(
                         },
                     )
                     .freeze(InputState::ctx_cell(ctx), input_state)
-                    .in_scope(|| thread.run_with(ctx, closure, this))?;
+                    .freeze(DrawingState::ctx_cell(ctx), drawing_state)
+                    .in_scope(|| run_script(ctx, thread, closure, this))?;
             }
             Ok(())
         })?;
     }
 
+    // Alarms only count down if the instance has an event for them. Each alarm is reset to -1
+    // *before* its event is run, so that the event may set the alarm again.
+
     for &instance_id in &to_update {
         let object_id = state.instances[instance_id].object;
 
-        interpreter.enter(|ctx| -> Result<(), Error> {
-            if let Some(step_closure) = state.instances[instance_id]
-                .event_closures
-                .get(&ObjectEvent::Step)
+        for alarm in 0..ObjectEvent::ALARM_COUNT {
+            let event = ObjectEvent::Alarm(alarm as u8);
+            let instance = &mut state.instances[instance_id];
+            if instance.dead
+                || !instance.event_closures.contains_key(&event)
+                || instance.alarms[alarm] <= 0
             {
+                continue;
+            }
+
+            instance.alarms[alarm] -= 1;
+            if instance.alarms[alarm] > 0 {
+                continue;
+            }
+            instance.alarms[alarm] = -1;
+
+            interpreter.enter(|ctx| -> Result<(), Error> {
+                let instance = &state.instances[instance_id];
                 let thread = ctx.fetch(thread);
-                let closure = ctx.fetch(step_closure);
-                let this = ctx.fetch(&state.instances[instance_id].this);
+                let closure = ctx.fetch(&instance.event_closures[&event]);
+                let this = ctx.fetch(&instance.this);
 
                 FreezeMany::new()
                     .freeze(State::ctx_cell(ctx), state)
@@ -395,14 +447,15 @@ pub fn tick_state(
                         &EventState {
                             instance_id,
                             object_id,
-                            current_event: ObjectEvent::Step,
+                            current_event: event,
                         },
                     )
                     .freeze(InputState::ctx_cell(ctx), input_state)
-                    .in_scope(|| thread.run_with(ctx, closure, this))?;
-            }
-            Ok(())
-        })?;
+                    .freeze(DrawingState::ctx_cell(ctx), drawing_state)
+                    .in_scope(|| run_script(ctx, thread, closure, this))?;
+                Ok(())
+            })?;
+        }
     }
 
     for &instance_id in &to_update {
@@ -411,7 +464,7 @@ pub fn tick_state(
         interpreter.enter(|ctx| -> Result<(), Error> {
             if let Some(step_closure) = state.instances[instance_id]
                 .event_closures
-                .get(&ObjectEvent::EndStep)
+                .get(&ObjectEvent::Step)
             {
                 let thread = ctx.fetch(thread);
                 let closure = ctx.fetch(step_closure);
@@ -424,26 +477,120 @@ pub fn tick_state(
                         &EventState {
                             instance_id,
                             object_id,
-                            current_event: ObjectEvent::EndStep,
+                            current_event: ObjectEvent::Step,
                         },
                     )
                     .freeze(InputState::ctx_cell(ctx), input_state)
-                    .in_scope(|| thread.run_with(ctx, closure, this))?;
+                    .freeze(DrawingState::ctx_cell(ctx), drawing_state)
+                    .in_scope(|| run_script(ctx, thread, closure, this))?;
             }
             Ok(())
         })?;
     }
 
+    // Built-in motion is applied after the step event and before collision events.
+
+    for &instance_id in &to_update {
+        let instance = &mut state.instances[instance_id];
+        if !instance.dead {
+            instance.position += instance.motion.step();
+        }
+    }
+
+    // Collision events run after the step event, so instances may have moved since the bound box
+    // tree was last built.
+
+    rebuild_instance_bound_tree(state, &to_update);
+
+    let mut query = BoundBoxQuery::default();
+    for &instance_id in &to_update {
+        let instance = &state.instances[instance_id];
+        if instance.dead {
+            continue;
+        }
+        let object_id = instance.object;
+
+        let mut targets = instance
+            .event_closures
+            .keys()
+            .filter_map(|&event| match event {
+                ObjectEvent::Collision(target) => Some(target),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if targets.is_empty() {
+            continue;
+        }
+        targets.sort();
+
+        let Some(bound_box) = state.instance_collision(instance_id).map(|c| c.bound_box()) else {
+            continue;
+        };
+        let nearby = query
+            .intersects(&state.instance_bound_tree, bound_box)
+            .copied()
+            .filter(|&other_id| other_id != instance_id)
+            .collect::<Vec<_>>();
+
+        for target in targets {
+            let event = ObjectEvent::Collision(target);
+
+            for &other_id in &nearby {
+                // Earlier collision events may have destroyed either instance.
+                let (Some(instance), Some(other)) = (
+                    state.instances.get(instance_id),
+                    state.instances.get(other_id),
+                ) else {
+                    continue;
+                };
+                if instance.dead
+                    || other.dead
+                    || !state.object_inherits_from(other.object, target)
+                    || !state.instances_collide(instance_id, other_id)
+                {
+                    continue;
+                }
+
+                interpreter.enter(|ctx| -> Result<(), Error> {
+                    let instance = &state.instances[instance_id];
+                    let thread = ctx.fetch(thread);
+                    let closure = ctx.fetch(&instance.event_closures[&event]);
+                    let this = ctx.fetch(&instance.this);
+                    let other = ctx.fetch(&state.instances[other_id].this);
+
+                    FreezeMany::new()
+                        .freeze(State::ctx_cell(ctx), state)
+                        .freeze(
+                            EventState::ctx_cell(ctx),
+                            &EventState {
+                                instance_id,
+                                object_id,
+                                current_event: event,
+                            },
+                        )
+                        .freeze(InputState::ctx_cell(ctx), input_state)
+                        .freeze(DrawingState::ctx_cell(ctx), drawing_state)
+                        .in_scope(|| {
+                            run_handled(ctx, thread, |exec| {
+                                exec.with_this(other).with_this(this).call(ctx, closure)
+                            })
+                        })?;
+                    Ok(())
+                })?;
+            }
+        }
+    }
+
     for &instance_id in &to_update {
         let object_id = state.instances[instance_id].object;
 
         interpreter.enter(|ctx| -> Result<(), Error> {
-            if let Some(draw_closure) = state.instances[instance_id]
+            if let Some(step_closure) = state.instances[instance_id]
                 .event_closures
-                .get(&ObjectEvent::Draw)
+                .get(&ObjectEvent::EndStep)
             {
                 let thread = ctx.fetch(thread);
-                let closure = ctx.fetch(draw_closure);
+                let closure = ctx.fetch(step_closure);
                 let this = ctx.fetch(&state.instances[instance_id].this);
 
                 FreezeMany::new()
@@ -453,31 +600,219 @@ pub fn tick_state(
                         &EventState {
                             instance_id,
                             object_id,
-                            current_event: ObjectEvent::Draw,
+                            current_event: ObjectEvent::EndStep,
                         },
                     )
                     .freeze(InputState::ctx_cell(ctx), input_state)
                     .freeze(DrawingState::ctx_cell(ctx), drawing_state)
-                    .in_scope(|| thread.run_with(ctx, closure, this))?;
+                    .in_scope(|| run_script(ctx, thread, closure, this))?;
             }
             Ok(())
         })?;
+    }
+
+    follow_camera_targets(state);
+
+    // The window size seen on the very first tick is the initial size, not a resize.
+    let window_resized = state
+        .window_size
+        .is_some_and(|window_size| window_size != input_state.window_size);
+    state.window_size = Some(input_state.window_size);
+
+    if window_resized {
+        for &instance_id in &to_update {
+            if state.instances[instance_id].dead {
+                continue;
+            }
+
+            run_instance_event(
+                state,
+                drawing_state,
+                input_state,
+                interpreter,
+                thread,
+                instance_id,
+                ObjectEvent::WindowResize,
+            )?;
+        }
+    }
+
+    // Draw events only run for visible instances which have not been destroyed earlier in the tick.
+
+    for event in [
+        ObjectEvent::PreDraw,
+        ObjectEvent::DrawBegin,
+        ObjectEvent::Draw,
+        ObjectEvent::DrawEnd,
+        ObjectEvent::PostDraw,
+    ] {
+        for &instance_id in &to_update {
+            let instance = &state.instances[instance_id];
+            if instance.dead || !instance.visible {
+                continue;
+            }
+
+            run_instance_event(
+                state,
+                drawing_state,
+                input_state,
+                interpreter,
+                thread,
+                instance_id,
+                event,
+            )?;
+        }
+    }
+
+    // Everything drawn during the GUI events is drawn on top of every view, in GUI space.
+
+    drawing_state.gui = true;
+    for event in [
+        ObjectEvent::DrawGuiBegin,
+        ObjectEvent::DrawGui,
+        ObjectEvent::DrawGuiEnd,
+    ] {
+        for &instance_id in &to_update {
+            let instance = &state.instances[instance_id];
+            if instance.dead || !instance.visible {
+                continue;
+            }
+
+            run_instance_event(
+                state,
+                drawing_state,
+                input_state,
+                interpreter,
+                thread,
+                instance_id,
+                event,
+            )?;
+        }
+    }
+    drawing_state.gui = false;
 
+    for &instance_id in &to_update {
         let instance = &mut state.instances[instance_id];
 
-        let object = &state.config.objects[instance.object];
-        if let Some(sprite_id) = object.sprite {
+        if let Some(sprite_id) = instance.sprite {
             let sprite = &state.config.sprites[sprite_id];
 
             instance.animation_time = (instance.animation_time
-                + sprite.playback_speed / state.config.tick_rate)
-                % sprite.playback_length;
+                + instance.image_speed * sprite.playback_speed / state.config.tick_rate)
+                .rem_euclid(sprite.playback_length);
         }
     }
 
     state.instances.retain(|_, instance| !instance.dead);
 
     interpreter.gc_collect_debt();
+    release_finalized(state, drawing_state, interpreter);
 
     Ok(())
 }
+
+/// Release every runner resource whose owner has been collected, see `surface_free_on_collect`.
+///
+/// Only surfaces are ever registered by the runner, any other finalizer payload is ignored.
+fn release_finalized(
+    state: &mut State,
+    drawing_state: &mut DrawingState,
+    interpreter: &vm::Interpreter,
+) {
+    interpreter.enter(|ctx| {
+        for payload in ctx.finalizers().take_ready(&ctx) {
+            if let vm::Value::UserData(ud) = payload
+                && let Ok(surface) = SurfaceUserData::downcast(ud)
+            {
+                free_surface(state, drawing_state, surface.id);
+            }
+        }
+    });
+}
+
+/// Run the given event for a single instance, if its object has a script for that event.
+fn run_instance_event(
+    state: &mut State,
+    drawing_state: &mut DrawingState,
+    input_state: &mut InputState,
+    interpreter: &mut vm::Interpreter,
+    thread: &vm::StashedThread,
+    instance_id: InstanceId,
+    event: ObjectEvent,
+) -> Result<(), Error> {
+    let object_id = state.instances[instance_id].object;
+
+    interpreter.enter(|ctx| -> Result<(), Error> {
+        if let Some(event_closure) = state.instances[instance_id].event_closures.get(&event) {
+            let thread = ctx.fetch(thread);
+            let closure = ctx.fetch(event_closure);
+            let this = ctx.fetch(&state.instances[instance_id].this);
+
+            FreezeMany::new()
+                .freeze(State::ctx_cell(ctx), state)
+                .freeze(
+                    EventState::ctx_cell(ctx),
+                    &EventState {
+                        instance_id,
+                        object_id,
+                        current_event: event,
+                    },
+                )
+                .freeze(InputState::ctx_cell(ctx), input_state)
+                .freeze(DrawingState::ctx_cell(ctx), drawing_state)
+                .in_scope(|| run_script(ctx, thread, closure, this))?;
+        }
+        Ok(())
+    })
+}
+
+fn follow_camera_targets(state: &mut State) {
+    let room_size = state.config.rooms[state.current_room.unwrap()]
+        .size
+        .cast::<f64>();
+
+    let targets = state
+        .cameras
+        .iter()
+        .filter_map(|(camera_id, camera)| {
+            let position = match camera.target? {
+                CameraTarget::Instance(instance_id) => {
+                    state
+                        .instances
+                        .get(instance_id)
+                        .filter(|instance| !instance.dead)?
+                        .position
+                }
+                CameraTarget::Object(object_id) => {
+                    state
+                        .instances
+                        .values()
+                        .find(|instance| {
+                            !instance.dead && state.object_inherits_from(instance.object, object_id)
+                        })?
+                        .position
+                }
+            };
+            Some((camera_id, position))
+        })
+        .collect::<Vec<_>>();
+
+    for (camera_id, position) in targets {
+        state.cameras[camera_id].follow(position, room_size);
+    }
+}
+
+fn rebuild_instance_bound_tree(state: &mut State, instances: &[InstanceId]) {
+    let instance_bounds = instances
+        .iter()
+        .filter_map(|&instance_id| {
+            Some((
+                state.instance_collision(instance_id)?.bound_box(),
+                instance_id,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    state.instance_bound_tree.clear();
+    state.instance_bound_tree.fextend(instance_bounds);
+}
@@ -4,8 +4,17 @@ pub mod game;
 pub mod project;
 pub mod state;
 
+pub use fabricator_compiler::cache::CodeCache;
+pub use fabricator_profiler::Profiler;
+
 pub use self::{
-    game::{Game, Render},
+    game::{
+        ColorVertex, DrawCommand, Game, GameOptions, Quad, RecordedTick, Recording, Render,
+        RenderView, SurfacePass, SurfaceQuad, Triangles,
+    },
     project::Project,
-    state::{InputState, MouseButtons, TextureId, TexturePage, TexturePageId},
+    state::{
+        Gamepad, GamepadAxis, GamepadButton, Gamepads, InputState, KeyboardState, MouseButtons,
+        SAMPLE_RATE, SurfaceId, TextureId, TexturePage, TexturePageId, VirtualKey,
+    },
 };
@@ -15,8 +15,8 @@ use serde_json as json;
 use crate::project::{
     AnimationFrame, CollisionKind, EventScript, Extension, ExtensionFile, ExtensionFunction,
     FfiType, Font, Frame, Glyph, Instance, KerningPair, Layer, LayerType, Object, ObjectEvent,
-    Project, Room, Script, ScriptMode, Shader, Sound, Sprite, TextureGroup, TileLayer, TileSet,
-    strip_json_trailing_commas::StripJsonTrailingCommas,
+    Project, Room, RoomView, Script, ScriptMode, Shader, Sound, Sprite, TextureGroup, TileLayer,
+    TileSet, strip_json_trailing_commas::StripJsonTrailingCommas,
 };
 
 pub fn load_project(project_file: &Path) -> Result<Project, Error> {
@@ -252,6 +252,8 @@ struct YyObject {
     parent_object_id: Option<YyId>,
     #[serde(rename = "spriteId")]
     sprite_id: Option<YyId>,
+    #[serde(rename = "spriteMaskId")]
+    sprite_mask_id: Option<YyId>,
     #[serde(default)]
     tags: Vec<String>,
 }
@@ -332,11 +334,40 @@ struct YyRoomSettings {
     height: u32,
 }
 
+#[derive(Deserialize)]
+struct YyRoomView {
+    visible: bool,
+    xview: f64,
+    yview: f64,
+    wview: f64,
+    hview: f64,
+    xport: u32,
+    yport: u32,
+    wport: u32,
+    hport: u32,
+    hborder: f64,
+    vborder: f64,
+    hspeed: f64,
+    vspeed: f64,
+    #[serde(rename = "objectId")]
+    object_id: Option<YyId>,
+}
+
+#[derive(Deserialize, Default)]
+struct YyViewSettings {
+    #[serde(rename = "enableViews")]
+    enable_views: bool,
+}
+
 #[derive(Deserialize)]
 struct YyRoom {
     name: String,
     #[serde(rename = "roomSettings")]
     room_settings: YyRoomSettings,
+    #[serde(rename = "viewSettings", default)]
+    view_settings: YyViewSettings,
+    #[serde(default)]
+    views: Vec<YyRoomView>,
     layers: Vec<YyLayer>,
     #[serde(default)]
     tags: Vec<String>,
@@ -427,6 +458,8 @@ struct YyFont {
     name: String,
     #[serde(rename = "fontName")]
     font_name: String,
+    #[serde(rename = "textureGroupId")]
+    texture_group: YyId,
     glyphs: HashMap<String, YyGlyph>,
     #[serde(rename = "kerningPairs")]
     kerning_pairs: Vec<YyKerningPair>,
@@ -558,7 +591,10 @@ fn read_sprite(base_path: PathBuf, yy_sprite: YySprite) -> Result<Sprite, Error>
 }
 
 fn read_object(base_path: PathBuf, yy_object: YyObject) -> Result<Object, Error> {
+    const COLLISION_PREFIX: &str = "Collision_";
+
     let mut event_scripts = HashMap::new();
+    let mut collision_scripts = HashMap::new();
     for entry in fs::read_dir(&base_path)? {
         let entry = entry?;
         let path = entry.path();
@@ -567,30 +603,48 @@ fn read_object(base_path: PathBuf, yy_object: YyObject) -> Result<Object, Error>
             continue;
         }
 
-        for event in ObjectEvent::all() {
-            let stem = path.file_stem().unwrap();
-            if stem.eq_ignore_ascii_case(event.file_stem()) {
-                let ext = path.extension().context("missing event script extension")?;
-                if ext.eq_ignore_ascii_case("yy") {
-                    continue;
-                }
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
 
-                let mode = if ext.eq_ignore_ascii_case(ScriptMode::Compat.extension()) {
-                    ScriptMode::Compat
-                } else if ext.eq_ignore_ascii_case(ScriptMode::Modern.extension()) {
-                    ScriptMode::Modern
-                } else {
-                    bail!("unknown event script extension {:?}", ext);
-                };
-                event_scripts.insert(
-                    event,
-                    EventScript {
-                        event,
-                        path: path.clone(),
-                        mode,
-                    },
-                );
-            }
+        let event = ObjectEvent::all().find(|event| {
+            event
+                .file_stem()
+                .is_some_and(|event_stem| stem.eq_ignore_ascii_case(event_stem))
+        });
+
+        // Collision events are named after the object they collide with.
+        let collision_target = stem
+            .get(..COLLISION_PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(COLLISION_PREFIX))
+            .map(|_| &stem[COLLISION_PREFIX.len()..]);
+
+        if event.is_none() && collision_target.is_none() {
+            continue;
+        }
+
+        let ext = path.extension().context("missing event script extension")?;
+        if ext.eq_ignore_ascii_case("yy") {
+            continue;
+        }
+
+        let mode = if ext.eq_ignore_ascii_case(ScriptMode::Compat.extension()) {
+            ScriptMode::Compat
+        } else if ext.eq_ignore_ascii_case(ScriptMode::Modern.extension()) {
+            ScriptMode::Modern
+        } else {
+            bail!("unknown event script extension {:?}", ext);
+        };
+
+        let script = EventScript {
+            path: path.clone(),
+            mode,
+        };
+
+        if let Some(event) = event {
+            event_scripts.insert(event, script);
+        } else if let Some(target) = collision_target {
+            collision_scripts.insert(target.to_owned(), script);
         }
     }
 
@@ -600,7 +654,9 @@ fn read_object(base_path: PathBuf, yy_object: YyObject) -> Result<Object, Error>
         base_path,
         persistent: yy_object.persistent,
         sprite: yy_object.sprite_id.map(|i| i.name),
+        mask: yy_object.sprite_mask_id.map(|i| i.name),
         event_scripts,
+        collision_scripts,
         tags: yy_object.tags.into_iter().collect(),
     })
 }
@@ -806,6 +862,27 @@ fn read_room(base_path: PathBuf, yy_room: YyRoom) -> Result<Room, Error> {
         base_path,
         width: yy_room.room_settings.width,
         height: yy_room.room_settings.height,
+        enable_views: yy_room.view_settings.enable_views,
+        views: yy_room
+            .views
+            .into_iter()
+            .map(|view| RoomView {
+                visible: view.visible,
+                x: view.xview,
+                y: view.yview,
+                width: view.wview,
+                height: view.hview,
+                port_x: view.xport,
+                port_y: view.yport,
+                port_width: view.wport,
+                port_height: view.hport,
+                border_x: view.hborder,
+                border_y: view.vborder,
+                speed_x: view.hspeed,
+                speed_y: view.vspeed,
+                follow: view.object_id.map(|id| id.name),
+            })
+            .collect(),
         layers,
         tags: yy_room.tags.into_iter().collect(),
     })
@@ -899,6 +976,7 @@ fn read_font(base_path: PathBuf, yy_font: YyFont) -> Result<Font, Error> {
     let mut font = Font {
         name: yy_font.name,
         font_name: yy_font.font_name,
+        texture_group: yy_font.texture_group.name,
         font_image_path,
         glyphs: HashMap::new(),
         kerning_pairs: Vec::new(),
@@ -8,6 +8,9 @@ use std::{
 };
 
 use anyhow::Error;
+use fabricator_compiler::CompileSettings;
+
+use crate::state::ObjectId;
 
 use self::loading::load_project;
 
@@ -78,12 +81,27 @@ pub enum ObjectEvent {
     RoomStart,
     RoomEnd,
     BeginStep,
+    Alarm(u8),
     Step,
+    /// A collision with an instance of the given object, or any of its children.
+    Collision(ObjectId),
     EndStep,
+    WindowResize,
+    PreDraw,
+    DrawBegin,
     Draw,
+    DrawEnd,
+    PostDraw,
+    DrawGuiBegin,
+    DrawGui,
+    DrawGuiEnd,
 }
 
 impl ObjectEvent {
+    /// The number of alarms every instance has, alarm events are numbered `0..ALARM_COUNT`.
+    pub const ALARM_COUNT: usize = 12;
+
+    /// All events which are not parameterized by another object.
     pub fn all() -> impl Iterator<Item = ObjectEvent> {
         [
             Self::Create,
@@ -94,23 +112,52 @@ impl ObjectEvent {
             Self::BeginStep,
             Self::Step,
             Self::EndStep,
+            Self::WindowResize,
+            Self::PreDraw,
+            Self::DrawBegin,
             Self::Draw,
+            Self::DrawEnd,
+            Self::PostDraw,
+            Self::DrawGuiBegin,
+            Self::DrawGui,
+            Self::DrawGuiEnd,
         ]
         .into_iter()
+        .chain((0..Self::ALARM_COUNT as u8).map(Self::Alarm))
     }
 
-    pub fn file_stem(self) -> &'static str {
-        match self {
+    /// The file stem of the script for this event.
+    ///
+    /// Returns `None` for collision events, their scripts are named after the target object rather
+    /// than its ID.
+    pub fn file_stem(self) -> Option<&'static str> {
+        Some(match self {
             Self::Create => "Create_0",
             Self::Destroy => "Destroy_0",
             Self::CleanUp => "CleanUp_0",
             Self::RoomStart => "Other_4",
             Self::RoomEnd => "Other_5",
             Self::BeginStep => "Step_1",
+            Self::Alarm(n) => {
+                const ALARM_STEMS: [&str; ObjectEvent::ALARM_COUNT] = [
+                    "Alarm_0", "Alarm_1", "Alarm_2", "Alarm_3", "Alarm_4", "Alarm_5", "Alarm_6",
+                    "Alarm_7", "Alarm_8", "Alarm_9", "Alarm_10", "Alarm_11",
+                ];
+                ALARM_STEMS[n as usize]
+            }
             Self::Step => "Step_0",
+            Self::Collision(_) => return None,
             Self::EndStep => "Step_2",
+            Self::WindowResize => "Draw_65",
+            Self::PreDraw => "Draw_76",
+            Self::DrawBegin => "Draw_72",
             Self::Draw => "Draw_0",
-        }
+            Self::DrawEnd => "Draw_73",
+            Self::PostDraw => "Draw_77",
+            Self::DrawGuiBegin => "Draw_74",
+            Self::DrawGui => "Draw_64",
+            Self::DrawGuiEnd => "Draw_75",
+        })
     }
 }
 
@@ -127,11 +174,17 @@ impl ScriptMode {
             ScriptMode::Modern => "fml",
         }
     }
+
+    pub fn compile_settings(self) -> CompileSettings {
+        match self {
+            ScriptMode::Compat => CompileSettings::compat(),
+            ScriptMode::Modern => CompileSettings::strict(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct EventScript {
-    pub event: ObjectEvent,
     pub path: PathBuf,
     pub mode: ScriptMode,
 }
@@ -143,7 +196,11 @@ pub struct Object {
     pub base_path: PathBuf,
     pub persistent: bool,
     pub sprite: Option<String>,
+    /// The sprite used for collisions instead of `sprite`, if any.
+    pub mask: Option<String>,
     pub event_scripts: HashMap<ObjectEvent, EventScript>,
+    /// Collision event scripts, keyed by the name of the object they collide with.
+    pub collision_scripts: HashMap<String, EventScript>,
     pub tags: HashSet<String>,
 }
 
@@ -185,12 +242,34 @@ pub struct TileLayer {
     pub tile_grid: Vec<Option<u32>>,
 }
 
+#[derive(Debug)]
+pub struct RoomView {
+    pub visible: bool,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub port_x: u32,
+    pub port_y: u32,
+    pub port_width: u32,
+    pub port_height: u32,
+    pub border_x: f64,
+    pub border_y: f64,
+    /// Maximum movement per tick when following an instance, -1 means unlimited.
+    pub speed_x: f64,
+    pub speed_y: f64,
+    /// The name of the object the view follows, if any.
+    pub follow: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct Room {
     pub name: String,
     pub base_path: PathBuf,
     pub width: u32,
     pub height: u32,
+    pub enable_views: bool,
+    pub views: Vec<RoomView>,
     pub layers: HashMap<String, Layer>,
     pub tags: HashSet<String>,
 }
@@ -253,6 +332,7 @@ pub struct KerningPair {
 pub struct Font {
     pub name: String,
     pub font_name: String,
+    pub texture_group: String,
     pub font_image_path: PathBuf,
     pub glyphs: HashMap<char, Glyph>,
     pub kerning_pairs: Vec<KerningPair>,
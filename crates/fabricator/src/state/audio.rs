@@ -0,0 +1,551 @@
+use std::{fs::File, io::BufReader, mem, path::Path, rc::Rc};
+
+use fabricator_math::Vec3;
+use fabricator_util::typed_id_map::{IdMap, SecondaryMap, new_id_type};
+use fabricator_vm as vm;
+use thiserror::Error;
+
+use crate::state::configuration::SoundId;
+
+new_id_type! {
+    pub struct VoiceId;
+    pub struct EmitterId;
+}
+
+/// The sample rate of mixed audio, in frames per second.
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// The maximum number of voices which can play at once.
+pub const MAX_VOICES: usize = 128;
+
+#[derive(Debug, Error)]
+pub enum SoundError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Wav(#[from] hound::Error),
+    #[error(transparent)]
+    Vorbis(#[from] lewton::VorbisError),
+    #[error("unsupported sound file extension {0:?}")]
+    UnsupportedFormat(String),
+}
+
+/// Decoded audio for a single sound.
+#[derive(Debug, Clone)]
+pub struct SoundData {
+    pub sample_rate: u32,
+    /// Stereo frames, mono sounds have the same sample on both channels.
+    pub frames: Vec<[f32; 2]>,
+}
+
+impl SoundData {
+    /// Decode a WAV or Ogg Vorbis file.
+    pub fn load(path: &Path) -> Result<Self, SoundError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            "wav" => {
+                let mut reader = hound::WavReader::open(path)?;
+                let spec = reader.spec();
+                let samples = match spec.sample_format {
+                    hound::SampleFormat::Float => {
+                        reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?
+                    }
+                    hound::SampleFormat::Int => {
+                        let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+                        reader
+                            .samples::<i32>()
+                            .map(|s| s.map(|s| s as f32 * scale))
+                            .collect::<Result<Vec<_>, _>>()?
+                    }
+                };
+                Ok(Self::from_interleaved(
+                    spec.sample_rate,
+                    spec.channels.into(),
+                    &samples,
+                ))
+            }
+            "ogg" => {
+                let mut reader =
+                    lewton::inside_ogg::OggStreamReader::new(BufReader::new(File::open(path)?))?;
+                let mut samples = Vec::new();
+                while let Some(packet) = reader.read_dec_packet_itl()? {
+                    samples.extend(packet.into_iter().map(|s| s as f32 / 32768.0));
+                }
+                Ok(Self::from_interleaved(
+                    reader.ident_hdr.audio_sample_rate,
+                    reader.ident_hdr.audio_channels.into(),
+                    &samples,
+                ))
+            }
+            _ => Err(SoundError::UnsupportedFormat(extension)),
+        }
+    }
+
+    /// Create sound data from interleaved samples, channels past the first two are ignored.
+    pub fn from_interleaved(sample_rate: u32, channels: usize, samples: &[f32]) -> Self {
+        let frames = samples
+            .chunks_exact(channels.max(1))
+            .map(|frame| match *frame {
+                [mono] => [mono, mono],
+                [left, right, ..] => [left, right],
+                [] => unreachable!(),
+            })
+            .collect();
+        Self {
+            sample_rate,
+            frames,
+        }
+    }
+
+    /// The frame at a fractional position, linearly interpolated with the following frame.
+    fn frame_at(&self, position: f64, looping: bool) -> [f32; 2] {
+        let index = position as usize;
+        let Some(&a) = self.frames.get(index) else {
+            return [0.0; 2];
+        };
+        let b = match self.frames.get(index + 1) {
+            Some(&b) => b,
+            None if looping => self.frames[0],
+            None => a,
+        };
+        let t = position.fract() as f32;
+        [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+    }
+}
+
+/// A gain which may be fading towards a new value over time.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gain {
+    pub start: f64,
+    pub target: f64,
+    /// The length of the fade in seconds.
+    pub duration: f64,
+    pub elapsed: f64,
+}
+
+impl Default for Gain {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Gain {
+    pub fn new(gain: f64) -> Self {
+        Self {
+            start: gain,
+            target: gain,
+            duration: 0.0,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value_after(0.0)
+    }
+
+    /// The gain the given number of seconds from now.
+    pub fn value_after(&self, secs: f64) -> f64 {
+        let elapsed = self.elapsed + secs;
+        if elapsed >= self.duration {
+            self.target
+        } else {
+            self.start + (self.target - self.start) * elapsed / self.duration
+        }
+    }
+
+    /// Fade from the current gain to `target` over `duration` seconds, a zero duration changes
+    /// the gain immediately.
+    pub fn fade_to(&mut self, target: f64, duration: f64) {
+        *self = Self {
+            start: self.value(),
+            target,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+        };
+    }
+
+    fn advance(&mut self, secs: f64) {
+        self.elapsed = (self.elapsed + secs).min(self.duration);
+    }
+}
+
+/// How the gain of a positioned sound decreases with distance from the listener.
+///
+/// These are the OpenAL distance models, as used by GML. The clamped variants treat distances
+/// closer than the reference distance as the reference distance, and distances further than the
+/// maximum distance as the maximum distance.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum FalloffModel {
+    #[default]
+    None,
+    InverseDistance,
+    InverseDistanceClamped,
+    LinearDistance,
+    LinearDistanceClamped,
+    ExponentDistance,
+    ExponentDistanceClamped,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Falloff {
+    pub reference: f64,
+    pub max: f64,
+    pub factor: f64,
+}
+
+impl Default for Falloff {
+    fn default() -> Self {
+        Self {
+            reference: 100.0,
+            max: 100000.0,
+            factor: 1.0,
+        }
+    }
+}
+
+impl Falloff {
+    /// The gain of a sound at the given distance from the listener, between 0 and 1.
+    pub fn gain(&self, model: FalloffModel, distance: f64) -> f64 {
+        let distance = match model {
+            FalloffModel::InverseDistanceClamped
+            | FalloffModel::LinearDistanceClamped
+            | FalloffModel::ExponentDistanceClamped => {
+                distance.clamp(self.reference, self.max.max(self.reference))
+            }
+            _ => distance,
+        };
+
+        let gain = match model {
+            FalloffModel::None => 1.0,
+            FalloffModel::InverseDistance | FalloffModel::InverseDistanceClamped => {
+                self.reference / (self.reference + self.factor * (distance - self.reference))
+            }
+            FalloffModel::LinearDistance | FalloffModel::LinearDistanceClamped => {
+                1.0 - self.factor * (distance.min(self.max) - self.reference)
+                    / (self.max - self.reference)
+            }
+            FalloffModel::ExponentDistance | FalloffModel::ExponentDistanceClamped => {
+                (distance / self.reference).powf(-self.factor)
+            }
+        };
+
+        // Degenerate falloff settings divide by zero, in which case there is no falloff.
+        if gain.is_nan() {
+            1.0
+        } else {
+            gain.clamp(0.0, 1.0)
+        }
+    }
+}
+
+pub struct Emitter {
+    pub this: vm::StashedUserData,
+    pub position: Vec3<f64>,
+    pub falloff: Falloff,
+    pub gain: f64,
+    pub pitch: f64,
+}
+
+impl Emitter {
+    pub fn new(this: vm::StashedUserData) -> Self {
+        Self {
+            this,
+            position: Vec3::zero(),
+            falloff: Falloff::default(),
+            gain: 1.0,
+            pitch: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VoicePosition {
+    /// The voice is positioned and attenuated by an emitter, the voice is stopped if the emitter is
+    /// freed.
+    Emitter(EmitterId),
+    Point(Vec3<f64>, Falloff),
+}
+
+/// A single playing instance of a sound.
+pub struct Voice {
+    pub this: vm::StashedUserData,
+    pub sound: SoundId,
+    pub data: Rc<SoundData>,
+    pub priority: f64,
+    pub looping: bool,
+    pub paused: bool,
+    pub gain: Gain,
+    pub pitch: f64,
+    /// The play position in frames of `data`.
+    pub track_position: f64,
+    pub position: Option<VoicePosition>,
+}
+
+impl Voice {
+    pub fn new(this: vm::StashedUserData, sound: SoundId, data: Rc<SoundData>) -> Self {
+        Self {
+            this,
+            sound,
+            data,
+            priority: 0.0,
+            looping: false,
+            paused: false,
+            gain: Gain::default(),
+            pitch: 1.0,
+            track_position: 0.0,
+            position: None,
+        }
+    }
+}
+
+/// Every playing sound, along with the mixer that turns them into PCM audio.
+///
+/// Mixing happens once per tick, so everything here is deterministic for a given sequence of
+/// ticks.
+pub struct AudioState {
+    pub voices: IdMap<VoiceId, Voice>,
+    pub emitters: IdMap<EmitterId, Emitter>,
+    pub listener: Vec3<f64>,
+    pub falloff_model: FalloffModel,
+    pub master_gain: Gain,
+    /// The gain of every voice of a sound is multiplied by the gain of the sound.
+    pub sound_gains: SecondaryMap<SoundId, Gain>,
+    pub sound_pitches: SecondaryMap<SoundId, f64>,
+
+    // Sounds are decoded the first time they are played, `None` if decoding failed.
+    decoded: SecondaryMap<SoundId, Option<Rc<SoundData>>>,
+    // The fractional part of the number of frames to mix per tick, carried over to the next tick.
+    frame_remainder: f64,
+    // Interleaved stereo samples which have been mixed but not yet taken.
+    output: Vec<f32>,
+}
+
+impl Default for AudioState {
+    fn default() -> Self {
+        Self {
+            voices: IdMap::new(),
+            emitters: IdMap::new(),
+            listener: Vec3::zero(),
+            falloff_model: FalloffModel::None,
+            master_gain: Gain::default(),
+            sound_gains: SecondaryMap::new(),
+            sound_pitches: SecondaryMap::new(),
+            decoded: SecondaryMap::new(),
+            frame_remainder: 0.0,
+            output: Vec::new(),
+        }
+    }
+}
+
+impl AudioState {
+    /// Decoded audio for a sound, decoding it from `path` if this is the first time it is needed.
+    ///
+    /// Returns `None` (and logs the error once) if the sound cannot be decoded.
+    pub fn sound_data(&mut self, sound_id: SoundId, path: &Path) -> Option<Rc<SoundData>> {
+        self.decoded
+            .get_or_insert_with(sound_id, || match SoundData::load(path) {
+                Ok(data) => Some(Rc::new(data)),
+                Err(err) => {
+                    log::error!("could not decode sound {path:?}: {err}");
+                    None
+                }
+            })
+            .clone()
+    }
+
+    /// Start playing a new voice.
+    ///
+    /// If [`MAX_VOICES`] are already playing, the voice with the lowest priority is stopped to make
+    /// room. If no voice has a lower priority than the new voice, the new voice is not played and
+    /// this returns `None`.
+    pub fn play(&mut self, priority: f64, voice: impl FnOnce(VoiceId) -> Voice) -> Option<VoiceId> {
+        if self.voices.len() >= MAX_VOICES {
+            let (lowest_id, lowest) = self
+                .voices
+                .iter()
+                .min_by(|(_, a), (_, b)| a.priority.total_cmp(&b.priority))?;
+            if lowest.priority > priority {
+                return None;
+            }
+            self.voices.remove(lowest_id);
+        }
+
+        Some(self.voices.insert_with_id(voice))
+    }
+
+    pub fn stop_sound(&mut self, sound_id: SoundId) {
+        self.voices.retain(|_, voice| voice.sound != sound_id);
+    }
+
+    pub fn free_emitter(&mut self, emitter_id: EmitterId) {
+        self.emitters.remove(emitter_id);
+        self.voices
+            .retain(|_, voice| voice.position != Some(VoicePosition::Emitter(emitter_id)));
+    }
+
+    /// Mix the audio for a single tick.
+    ///
+    /// Ticks do not evenly divide [`SAMPLE_RATE`] in general, so the number of frames mixed may
+    /// differ by one between ticks.
+    pub fn mix_tick(&mut self, tick_rate: f64) {
+        let frames = self.frame_remainder + SAMPLE_RATE as f64 / tick_rate;
+        self.frame_remainder = frames.fract();
+        self.mix(frames as usize);
+    }
+
+    /// Mix the given number of frames of every playing voice.
+    ///
+    /// Gains and fades are applied per frame, but positions and pitches are only evaluated once.
+    pub fn mix(&mut self, frames: usize) {
+        let frame_secs = 1.0 / SAMPLE_RATE as f64;
+        let start = self.output.len();
+        self.output.resize(start + frames * 2, 0.0);
+        let output = &mut self.output[start..];
+
+        let mut finished = Vec::new();
+        for (voice_id, voice) in self.voices.iter_mut() {
+            if voice.paused {
+                continue;
+            }
+
+            let sound_gain = self
+                .sound_gains
+                .get(voice.sound)
+                .copied()
+                .unwrap_or_default();
+            let sound_pitch = self.sound_pitches.get(voice.sound).copied().unwrap_or(1.0);
+            let (spatial_gain, spatial_pitch) = match voice.position {
+                Some(VoicePosition::Emitter(emitter_id)) => {
+                    let emitter = &self.emitters[emitter_id];
+                    let distance = (emitter.position - self.listener).length();
+                    (
+                        emitter.gain * emitter.falloff.gain(self.falloff_model, distance),
+                        emitter.pitch,
+                    )
+                }
+                Some(VoicePosition::Point(position, falloff)) => {
+                    let distance = (position - self.listener).length();
+                    (falloff.gain(self.falloff_model, distance), 1.0)
+                }
+                None => (1.0, 1.0),
+            };
+
+            let pitch = (voice.pitch * sound_pitch * spatial_pitch).max(0.0);
+            let step = voice.data.sample_rate as f64 / SAMPLE_RATE as f64 * pitch;
+            let length = voice.data.frames.len() as f64;
+
+            for (i, out) in output.chunks_exact_mut(2).enumerate() {
+                if voice.track_position >= length {
+                    if voice.looping && length > 0.0 {
+                        voice.track_position %= length;
+                    } else {
+                        finished.push(voice_id);
+                        break;
+                    }
+                }
+
+                let secs = i as f64 * frame_secs;
+                let gain = self.master_gain.value_after(secs)
+                    * sound_gain.value_after(secs)
+                    * voice.gain.value_after(secs)
+                    * spatial_gain;
+                let frame = voice.data.frame_at(voice.track_position, voice.looping);
+                out[0] += frame[0] * gain as f32;
+                out[1] += frame[1] * gain as f32;
+
+                voice.track_position += step;
+            }
+
+            voice.gain.advance(frames as f64 * frame_secs);
+        }
+
+        for voice_id in finished {
+            self.voices.remove(voice_id);
+        }
+
+        self.master_gain.advance(frames as f64 * frame_secs);
+        for gain in self.sound_gains.values_mut() {
+            gain.advance(frames as f64 * frame_secs);
+        }
+    }
+
+    /// Take every mixed sample, as interleaved stereo samples at [`SAMPLE_RATE`].
+    pub fn take_output(&mut self) -> Vec<f32> {
+        mem::take(&mut self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stash_unit() -> vm::StashedUserData {
+        let mut interpreter = vm::Interpreter::new();
+        interpreter.enter(|ctx| ctx.stash(vm::UserData::new_static(&ctx, ())))
+    }
+
+    #[test]
+    fn test_falloff() {
+        let falloff = Falloff {
+            reference: 10.0,
+            max: 110.0,
+            factor: 1.0,
+        };
+
+        assert_eq!(falloff.gain(FalloffModel::None, 1000.0), 1.0);
+        assert_eq!(falloff.gain(FalloffModel::InverseDistance, 20.0), 0.5);
+        assert_eq!(falloff.gain(FalloffModel::InverseDistanceClamped, 5.0), 1.0);
+        assert_eq!(
+            falloff.gain(FalloffModel::InverseDistanceClamped, 1000.0),
+            falloff.gain(FalloffModel::InverseDistance, 110.0),
+        );
+        assert_eq!(falloff.gain(FalloffModel::LinearDistance, 60.0), 0.5);
+        assert_eq!(falloff.gain(FalloffModel::LinearDistance, 1000.0), 0.0);
+        assert_eq!(falloff.gain(FalloffModel::LinearDistanceClamped, 0.0), 1.0);
+        assert_eq!(falloff.gain(FalloffModel::ExponentDistance, 40.0), 0.25);
+    }
+
+    #[test]
+    fn test_mix() {
+        let mut audio = AudioState::default();
+        let sound_id = IdMap::<SoundId, ()>::new().insert(());
+        let data = Rc::new(SoundData::from_interleaved(
+            SAMPLE_RATE,
+            1,
+            &[0.5, 0.5, 0.5, 0.5],
+        ));
+
+        let voice_id = audio
+            .play(0.0, |_| {
+                let mut voice = Voice::new(stash_unit(), sound_id, data.clone());
+                voice.gain = Gain::new(0.5);
+                voice
+            })
+            .unwrap();
+
+        audio.mix(3);
+        assert_eq!(audio.take_output(), vec![0.25; 6]);
+        assert!(audio.voices.contains(voice_id));
+
+        // The voice finishes partway through the mix, and is removed.
+        audio.mix(3);
+        assert_eq!(audio.take_output(), vec![0.25, 0.25, 0.0, 0.0, 0.0, 0.0]);
+        assert!(!audio.voices.contains(voice_id));
+    }
+
+    #[test]
+    fn test_frames_per_tick() {
+        let mut audio = AudioState::default();
+        let mut frames = 0;
+        // 32 ticks per second is 1378.125 frames per tick.
+        for _ in 0..32 {
+            audio.mix_tick(32.0);
+            frames += audio.take_output().len() / 2;
+        }
+        assert_eq!(frames, SAMPLE_RATE as usize);
+    }
+}
@@ -0,0 +1,256 @@
+use fabricator_math::{Affine2, Box2, Vec2};
+use fabricator_util::typed_id_map::{IdMap, new_id_type};
+use fabricator_vm as vm;
+
+use crate::state::{InstanceId, ObjectId, configuration::RoomView};
+
+new_id_type! {
+    pub struct CameraId;
+}
+
+/// The number of views in every room, as in GML.
+pub const VIEW_COUNT: usize = 8;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CameraTarget {
+    Instance(InstanceId),
+    /// Follows the first live instance of the object (or a child object).
+    Object(ObjectId),
+}
+
+pub struct Camera {
+    pub this: vm::StashedUserData,
+    /// The area of the room shown by the camera, before rotation.
+    pub view: Box2<f64>,
+    /// Rotation of the view around its center in degrees, counter-clockwise as in GML.
+    ///
+    /// Since the camera rotates counter-clockwise, the room appears rotated clockwise.
+    pub angle: f64,
+    pub target: Option<CameraTarget>,
+    /// The distance the camera keeps between its target and the edge of the view.
+    pub border: Vec2<f64>,
+    /// The maximum distance the view moves per tick to follow its target, negative values are
+    /// unlimited.
+    pub speed: Vec2<f64>,
+}
+
+impl Camera {
+    pub fn new(this: vm::StashedUserData, view: Box2<f64>) -> Self {
+        Self {
+            this,
+            view,
+            angle: 0.0,
+            target: None,
+            border: Vec2::zero(),
+            speed: Vec2::splat(-1.0),
+        }
+    }
+
+    /// Reset every camera setting to the initial settings of a room view.
+    pub fn reset(&mut self, room_view: &RoomView) {
+        self.view = room_view.view;
+        self.angle = 0.0;
+        self.target = room_view.follow.map(CameraTarget::Object);
+        self.border = room_view.border;
+        self.speed = room_view.speed;
+    }
+
+    /// Transforms room coordinates into camera space, where the origin is the center of the view
+    /// and the axes are aligned with the (rotated) view.
+    pub fn view_matrix(&self) -> Affine2<f64> {
+        Affine2::new()
+            .translate(-self.view.center())
+            .rotate(self.angle.to_radians())
+    }
+
+    /// Transforms camera space so that the view covers the square from (-1, -1) to (1, 1).
+    ///
+    /// The y axis still points down.
+    pub fn projection_matrix(&self) -> Affine2<f64> {
+        Affine2::new().scale(Vec2::splat(2.0) / self.view.size())
+    }
+
+    /// Move the view so that `target` is at least `border` away from the edges of the view,
+    /// without moving faster than `speed` or showing anything outside of the room.
+    pub fn follow(&mut self, target: Vec2<f64>, room_size: Vec2<f64>) {
+        let min = self.view.min;
+        let size = self.view.size();
+        let follow_axis = |i: usize| {
+            let mut pos = min[i];
+            if target[i] - pos < self.border[i] {
+                pos = target[i] - self.border[i];
+            } else if pos + size[i] - target[i] < self.border[i] {
+                pos = target[i] + self.border[i] - size[i];
+            }
+
+            if self.speed[i] >= 0.0 {
+                pos = min[i] + (pos - min[i]).clamp(-self.speed[i], self.speed[i]);
+            }
+
+            pos.clamp(0.0, (room_size[i] - size[i]).max(0.0))
+        };
+
+        self.view = Box2::with_size(Vec2::new(follow_axis(0), follow_axis(1)), size);
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct View {
+    pub visible: bool,
+    pub camera: Option<CameraId>,
+    /// The area of the screen the view is drawn into.
+    pub port: Box2<u32>,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            camera: None,
+            port: Box2::new(Vec2::zero(), Vec2::zero()),
+        }
+    }
+}
+
+impl View {
+    /// Transforms room coordinates into screen coordinates.
+    pub fn transform(&self, camera: &Camera) -> Affine2<f64> {
+        let port = self.port.cast::<f64>();
+        camera
+            .view_matrix()
+            .then(camera.projection_matrix())
+            .translate(Vec2::splat(1.0))
+            .scale(port.size() / 2.0)
+            .translate(port.min)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Views {
+    /// If false, the room is drawn untransformed and every view is ignored.
+    pub enabled: bool,
+    pub views: [View; VIEW_COUNT],
+}
+
+impl Views {
+    /// Every visible view that has a camera, along with the transform from room to screen
+    /// coordinates.
+    ///
+    /// Yields nothing if views are disabled.
+    pub fn active<'a>(
+        &'a self,
+        cameras: &'a IdMap<CameraId, Camera>,
+    ) -> impl Iterator<Item = (&'a View, Affine2<f64>)> + 'a {
+        self.views
+            .iter()
+            .filter(|view| self.enabled && view.visible)
+            .filter_map(|view| Some((view, view.transform(cameras.get(view.camera?)?))))
+    }
+
+    /// The size of the screen, which is the bounding box of all visible view ports, or the room
+    /// size if there are no active views.
+    pub fn screen_size(
+        &self,
+        cameras: &IdMap<CameraId, Camera>,
+        room_size: Vec2<u32>,
+    ) -> Vec2<u32> {
+        self.active(cameras)
+            .map(|(view, _)| view.port.max)
+            .reduce(|a, b| a.zip(b, u32::max))
+            .unwrap_or(room_size)
+    }
+
+    /// Transform a point on the screen into room coordinates.
+    ///
+    /// The first active view whose port contains the point is used, or the first active view if
+    /// none of them do.
+    pub fn screen_to_room(&self, cameras: &IdMap<CameraId, Camera>, point: Vec2<f64>) -> Vec2<f64> {
+        let mut transforms = self.active(cameras).peekable();
+        let Some(&(_, first)) = transforms.peek() else {
+            return point;
+        };
+
+        let transform = transforms
+            .find(|(view, _)| {
+                let port = view.port.cast::<f64>();
+                point[0] >= port.min[0]
+                    && point[1] >= port.min[1]
+                    && point[0] < port.max[0]
+                    && point[1] < port.max[1]
+            })
+            .map_or(first, |(_, transform)| transform);
+        transform.inverse().transform_point(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_camera(view: Box2<f64>) -> Camera {
+        let mut interpreter = vm::Interpreter::new();
+        let this = interpreter.enter(|ctx| ctx.stash(vm::UserData::new_static(&ctx, ())));
+        Camera::new(this, view)
+    }
+
+    fn assert_near(a: Vec2<f64>, b: Vec2<f64>) {
+        assert!((a - b).length() < 1e-9, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn test_view_transform() {
+        let mut camera = test_camera(Box2::with_size(
+            Vec2::new(100.0, 50.0),
+            Vec2::new(200.0, 100.0),
+        ));
+        let view = View {
+            visible: true,
+            camera: None,
+            port: Box2::with_size(Vec2::new(10, 20), Vec2::new(400, 200)),
+        };
+
+        let transform = view.transform(&camera);
+        assert_near(
+            transform.transform_point(Vec2::new(100.0, 50.0)),
+            Vec2::new(10.0, 20.0),
+        );
+        assert_near(
+            transform.transform_point(Vec2::new(300.0, 150.0)),
+            Vec2::new(410.0, 220.0),
+        );
+
+        // The room appears rotated clockwise, so the top left of the view is shown at the top
+        // right of the port.
+        camera.view = Box2::with_size(Vec2::zero(), Vec2::new(100.0, 100.0));
+        camera.angle = 90.0;
+        let transform = view.transform(&camera);
+        assert_near(
+            transform.transform_point(Vec2::new(50.0, 50.0)),
+            Vec2::new(210.0, 120.0),
+        );
+        assert_near(
+            transform.transform_point(Vec2::zero()),
+            Vec2::new(410.0, 20.0),
+        );
+    }
+
+    #[test]
+    fn test_follow() {
+        let mut camera = test_camera(Box2::with_size(Vec2::zero(), Vec2::new(100.0, 100.0)));
+        camera.border = Vec2::splat(20.0);
+        let room_size = Vec2::new(300.0, 300.0);
+
+        camera.follow(Vec2::new(50.0, 50.0), room_size);
+        assert_eq!(camera.view.min, Vec2::zero());
+
+        camera.follow(Vec2::new(150.0, 90.0), room_size);
+        assert_eq!(camera.view.min, Vec2::new(70.0, 10.0));
+
+        camera.follow(Vec2::new(295.0, 5.0), room_size);
+        assert_eq!(camera.view.min, Vec2::new(200.0, 0.0));
+
+        camera.speed = Vec2::new(10.0, -1.0);
+        camera.follow(Vec2::new(50.0, 250.0), room_size);
+        assert_eq!(camera.view.min, Vec2::new(190.0, 170.0));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{collections::VecDeque, mem, time::Instant};
+
+/// The source of time for `get_timer` and `current_time`.
+///
+/// Real time makes a game nondeterministic, so when recording every reading is kept to be stored
+/// alongside the tick's input, and when replaying the recorded readings are returned instead.
+#[derive(Debug)]
+pub struct Clock {
+    start: Instant,
+    mode: ClockMode,
+}
+
+#[derive(Debug)]
+enum ClockMode {
+    Real,
+    Recording(Vec<u64>),
+    Replaying { readings: VecDeque<u64>, last: u64 },
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            mode: ClockMode::Real,
+        }
+    }
+
+    pub fn recording() -> Self {
+        Self {
+            start: Instant::now(),
+            mode: ClockMode::Recording(Vec::new()),
+        }
+    }
+
+    pub fn replaying() -> Self {
+        Self {
+            start: Instant::now(),
+            mode: ClockMode::Replaying {
+                readings: VecDeque::new(),
+                last: 0,
+            },
+        }
+    }
+
+    /// Microseconds since the game started.
+    ///
+    /// If a replay reads the clock more often than the recording did, the game has diverged from
+    /// the recording and the last recorded reading is returned again.
+    pub fn read(&mut self) -> u64 {
+        match &mut self.mode {
+            ClockMode::Real => self.start.elapsed().as_micros() as u64,
+            ClockMode::Recording(readings) => {
+                let now = self.start.elapsed().as_micros() as u64;
+                readings.push(now);
+                now
+            }
+            ClockMode::Replaying { readings, last } => {
+                if let Some(next) = readings.pop_front() {
+                    *last = next;
+                } else {
+                    log::warn!("replay read the clock more often than was recorded");
+                }
+                *last
+            }
+        }
+    }
+
+    /// Take every reading recorded since the last call, empty if not recording.
+    pub fn take_readings(&mut self) -> Vec<u64> {
+        match &mut self.mode {
+            ClockMode::Recording(readings) => mem::take(readings),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Set the readings to return for the next tick of a replay, does nothing if not replaying.
+    pub fn set_readings(&mut self, next: Vec<u64>) {
+        if let ClockMode::Replaying { readings, .. } = &mut self.mode {
+            if !readings.is_empty() {
+                log::warn!("replay read the clock less often than was recorded");
+            }
+            *readings = next.into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_clock() {
+        let mut clock = Clock::recording();
+        let a = clock.read();
+        let b = clock.read();
+        assert!(b >= a);
+        let readings = clock.take_readings();
+        assert_eq!(readings, vec![a, b]);
+        assert!(clock.take_readings().is_empty());
+
+        let mut clock = Clock::replaying();
+        clock.set_readings(readings);
+        assert_eq!(clock.read(), a);
+        assert_eq!(clock.read(), b);
+        assert_eq!(clock.read(), b);
+    }
+}
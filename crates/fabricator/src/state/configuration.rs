@@ -67,8 +67,22 @@ pub struct TexturePage {
     pub userdata: vm::StashedUserData,
 }
 
+pub struct Glyph {
+    pub texture: TextureId,
+    /// The area of the texture which contains the glyph, in uncropped texture coordinates.
+    pub region: Box2<u32>,
+    /// Horizontal offset from the pen position to the left edge of the glyph.
+    pub offset: i32,
+    /// How far the pen moves to the right after drawing the glyph.
+    pub shift: i32,
+}
+
 pub struct Font {
     pub name: String,
+    pub glyphs: HashMap<char, Glyph>,
+    /// Extra horizontal pen movement between pairs of characters.
+    pub kerning: HashMap<(char, char), i32>,
+    pub line_height: u32,
     pub userdata: vm::StashedUserData,
 }
 
@@ -80,6 +94,7 @@ pub struct Shader {
 pub struct Sound {
     pub name: String,
     pub duration: Duration,
+    pub sound_file: PathBuf,
     pub userdata: vm::StashedUserData,
 }
 
@@ -92,11 +107,26 @@ pub struct TileSet {
 pub struct Room {
     pub name: String,
     pub size: Vec2<u32>,
+    /// If false, the room is drawn untransformed and `views` are ignored.
+    pub enable_views: bool,
+    pub views: Vec<RoomView>,
     pub layers: HashMap<String, RoomLayer>,
     pub userdata: vm::StashedUserData,
     pub tags: HashSet<String>,
 }
 
+/// The initial settings of a view and its camera when entering a room.
+pub struct RoomView {
+    pub visible: bool,
+    /// The area of the room shown by the view.
+    pub view: Box2<f64>,
+    /// The area of the screen the view is drawn into.
+    pub port: Box2<u32>,
+    pub border: Vec2<f64>,
+    pub speed: Vec2<f64>,
+    pub follow: Option<ObjectId>,
+}
+
 pub struct RoomLayer {
     pub name: String,
     pub depth: i32,
@@ -127,6 +157,7 @@ pub struct Object {
     pub name: String,
     pub parent: Option<ObjectId>,
     pub sprite: Option<SpriteId>,
+    pub mask: Option<SpriteId>,
     pub persistent: bool,
     pub userdata: vm::StashedUserData,
     pub tags: HashSet<String>,
@@ -136,6 +167,9 @@ pub struct Object {
 pub struct InstanceTemplate {
     pub object: ObjectId,
     pub position: Vec2<f64>,
+    pub scale: Vec2<f64>,
+    /// Rotation in radians, see [`Instance::rotation`](crate::state::Instance::rotation).
+    pub rotation: f64,
 }
 
 pub struct Sprite {
@@ -150,6 +184,24 @@ pub struct Sprite {
     pub userdata: vm::StashedUserData,
 }
 
+impl Sprite {
+    /// The index of the frame shown at the given animation time.
+    pub fn frame_at(&self, animation_time: f64) -> usize {
+        match self
+            .frames
+            .binary_search_by(|f| f.frame_start.total_cmp(&animation_time))
+        {
+            Ok(i) => i,
+            Err(i) => i.saturating_sub(1),
+        }
+    }
+
+    /// The animation time at which the given frame starts, the frame index wraps around.
+    pub fn frame_start(&self, frame: usize) -> f64 {
+        self.frames[frame % self.frames.len()].frame_start
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum SpriteCollisionKind {
     Rect,
@@ -164,6 +216,18 @@ pub struct SpriteCollision {
 }
 
 impl SpriteCollision {
+    /// Scale the collision shape around the sprite origin, negative scales mirror the shape.
+    pub fn scale(self, scale: Vec2<f64>) -> Self {
+        let bounds = self.bounds.scale(scale);
+        Self {
+            kind: self.kind,
+            bounds: Box2::new(
+                bounds.min.zip(bounds.max, f64::min),
+                bounds.min.zip(bounds.max, f64::max),
+            ),
+        }
+    }
+
     pub fn support_map(&self) -> impl SupportMap<f64, Context = Vec2<f64>> {
         enum SM<A, B, C> {
             A(A),
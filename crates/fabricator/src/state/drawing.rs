@@ -1,29 +1,83 @@
-use fabricator_math::Vec2;
 use fabricator_util::freeze::{AccessError, Freeze, FreezeCell};
 use fabricator_vm as vm;
 
-use crate::state::{configuration::SpriteId, state::InstanceId};
+use crate::{
+    game::{DrawCommand, SurfacePass},
+    state::{
+        configuration::FontId,
+        surface::SurfaceId,
+        text::{HAlign, VAlign},
+    },
+};
 
-pub enum DrawnSpriteFrame {
-    CurrentAnimation,
-    Frame(usize),
-}
-
-pub struct DrawnSprite {
-    pub instance: InstanceId,
-    pub sprite: SpriteId,
-    pub sub_img: DrawnSpriteFrame,
-    pub position: Vec2<f64>,
+pub struct DrawingState {
+    /// Commands issued by drawing functions during the current tick, in room space.
+    pub commands: Vec<DrawCommand>,
+    /// Commands issued during GUI draw events, which are drawn in GUI space on top of every view.
+    pub gui_commands: Vec<DrawCommand>,
+    /// Set while GUI draw events are running.
+    pub gui: bool,
+    /// Surfaces set with `surface_set_target`, drawing commands go to the last surface if there is
+    /// one.
+    pub target_stack: Vec<SurfaceId>,
+    /// Surface operations issued during the current tick.
+    pub surface_passes: Vec<SurfacePass>,
+    /// The color set by `draw_set_colour`, in the GML `0xBBGGRR` format.
+    pub color: u32,
+    /// The alpha set by `draw_set_alpha`.
+    pub alpha: f64,
+    /// The font set by `draw_set_font`, if any.
+    pub font: Option<FontId>,
+    pub halign: HAlign,
+    pub valign: VAlign,
 }
 
-#[derive(Default)]
-pub struct DrawingState {
-    pub drawn_sprites: Vec<DrawnSprite>,
+impl Default for DrawingState {
+    fn default() -> Self {
+        Self {
+            commands: Vec::new(),
+            gui_commands: Vec::new(),
+            gui: false,
+            target_stack: Vec::new(),
+            surface_passes: Vec::new(),
+            color: 0xffffff,
+            alpha: 1.0,
+            font: None,
+            halign: HAlign::default(),
+            valign: VAlign::default(),
+        }
+    }
 }
 
 impl DrawingState {
+    /// Clear the issued drawing commands and reset the drawing target, all other drawing settings
+    /// are kept.
     pub fn clear(&mut self) {
-        self.drawn_sprites.clear();
+        self.commands.clear();
+        self.gui_commands.clear();
+        self.gui = false;
+        self.target_stack.clear();
+        self.surface_passes.clear();
+    }
+
+    /// Issue a drawing command to the current target surface, or if there is none then to the
+    /// screen, in GUI space if a GUI draw event is running.
+    pub fn push(&mut self, command: DrawCommand) {
+        if let Some(&target) = self.target_stack.last() {
+            match self.surface_passes.last_mut() {
+                Some(SurfacePass::Draw { surface, commands }) if *surface == target => {
+                    commands.push(command);
+                }
+                _ => self.surface_passes.push(SurfacePass::Draw {
+                    surface: target,
+                    commands: vec![command],
+                }),
+            }
+        } else if self.gui {
+            self.gui_commands.push(command);
+        } else {
+            self.commands.push(command);
+        }
     }
 
     pub fn ctx_cell<'gc>(ctx: vm::Context<'gc>) -> &'gc DrawingStateCell {
@@ -1,7 +1,11 @@
 use bitflags::bitflags;
 use fabricator_math::Vec2;
-use fabricator_util::freeze::{AccessError, Freeze, FreezeCell};
+use fabricator_util::{
+    freeze::{AccessError, Freeze, FreezeCell},
+    index_containers::IndexSet,
+};
 use fabricator_vm as vm;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 bitflags! {
     #[derive(Debug, Copy, Clone, Default)]
@@ -12,10 +16,392 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// A virtual key code, using the same values as the GML `vk_*` constants.
+///
+/// Letter and digit keys use the ASCII code of their uppercase character, so that
+/// `keyboard_check(ord("A"))` works as expected.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct VirtualKey(pub u8);
+
+impl VirtualKey {
+    pub const NO_KEY: VirtualKey = VirtualKey(0);
+    pub const ANY_KEY: VirtualKey = VirtualKey(1);
+    pub const BACKSPACE: VirtualKey = VirtualKey(8);
+    pub const TAB: VirtualKey = VirtualKey(9);
+    pub const ENTER: VirtualKey = VirtualKey(13);
+    pub const SHIFT: VirtualKey = VirtualKey(16);
+    pub const CONTROL: VirtualKey = VirtualKey(17);
+    pub const ALT: VirtualKey = VirtualKey(18);
+    pub const PAUSE: VirtualKey = VirtualKey(19);
+    pub const ESCAPE: VirtualKey = VirtualKey(27);
+    pub const SPACE: VirtualKey = VirtualKey(32);
+    pub const PAGE_UP: VirtualKey = VirtualKey(33);
+    pub const PAGE_DOWN: VirtualKey = VirtualKey(34);
+    pub const END: VirtualKey = VirtualKey(35);
+    pub const HOME: VirtualKey = VirtualKey(36);
+    pub const LEFT: VirtualKey = VirtualKey(37);
+    pub const UP: VirtualKey = VirtualKey(38);
+    pub const RIGHT: VirtualKey = VirtualKey(39);
+    pub const DOWN: VirtualKey = VirtualKey(40);
+    pub const PRINT_SCREEN: VirtualKey = VirtualKey(44);
+    pub const INSERT: VirtualKey = VirtualKey(45);
+    pub const DELETE: VirtualKey = VirtualKey(46);
+    pub const NUMPAD_0: VirtualKey = VirtualKey(96);
+    pub const MULTIPLY: VirtualKey = VirtualKey(106);
+    pub const ADD: VirtualKey = VirtualKey(107);
+    pub const SUBTRACT: VirtualKey = VirtualKey(109);
+    pub const DECIMAL: VirtualKey = VirtualKey(110);
+    pub const DIVIDE: VirtualKey = VirtualKey(111);
+    pub const F1: VirtualKey = VirtualKey(112);
+    pub const LEFT_SHIFT: VirtualKey = VirtualKey(160);
+    pub const RIGHT_SHIFT: VirtualKey = VirtualKey(161);
+    pub const LEFT_CONTROL: VirtualKey = VirtualKey(162);
+    pub const RIGHT_CONTROL: VirtualKey = VirtualKey(163);
+    pub const LEFT_ALT: VirtualKey = VirtualKey(164);
+    pub const RIGHT_ALT: VirtualKey = VirtualKey(165);
+
+    /// The virtual key for an ASCII letter or digit, letters may be either case.
+    pub fn from_ascii(c: char) -> Option<VirtualKey> {
+        if c.is_ascii_alphanumeric() {
+            Some(VirtualKey(c.to_ascii_uppercase() as u8))
+        } else {
+            None
+        }
+    }
+
+    /// The virtual key for the numpad digit `n`, which must be less than 10.
+    pub fn numpad(n: u8) -> VirtualKey {
+        assert!(n < 10);
+        VirtualKey(Self::NUMPAD_0.0 + n)
+    }
+
+    /// The virtual key for the function key `F{n}`, which must be in `1..=12`.
+    pub fn function(n: u8) -> VirtualKey {
+        assert!((1..=12).contains(&n));
+        VirtualKey(Self::F1.0 + n - 1)
+    }
+
+    /// The side-specific keys which also count as this key, if this is a generic modifier key.
+    fn sided(self) -> Option<[VirtualKey; 2]> {
+        match self {
+            Self::SHIFT => Some([Self::LEFT_SHIFT, Self::RIGHT_SHIFT]),
+            Self::CONTROL => Some([Self::LEFT_CONTROL, Self::RIGHT_CONTROL]),
+            Self::ALT => Some([Self::LEFT_ALT, Self::RIGHT_ALT]),
+            _ => None,
+        }
+    }
+}
+
+/// The maximum number of characters kept in `keyboard_string`.
+pub const KEYBOARD_STRING_MAX_LEN: usize = 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyboardState {
+    #[serde(with = "index_set")]
+    held: IndexSet,
+    #[serde(with = "index_set")]
+    pressed: IndexSet,
+    #[serde(with = "index_set")]
+    released: IndexSet,
+    last_key: Option<VirtualKey>,
+    last_char: Option<char>,
+    string: String,
+}
+
+impl KeyboardState {
+    /// Record that a key was pressed.
+    ///
+    /// Repeated presses of a key which is already held are ignored.
+    pub fn key_down(&mut self, key: VirtualKey) {
+        if self.held.insert(key.0 as usize) {
+            self.pressed.insert(key.0 as usize);
+        }
+        self.last_key = Some(key);
+    }
+
+    /// Record that a key was released.
+    pub fn key_up(&mut self, key: VirtualKey) {
+        if self.held.remove(key.0 as usize) {
+            self.released.insert(key.0 as usize);
+        }
+    }
+
+    /// Release every held key, for example when the window loses focus.
+    pub fn release_all(&mut self) {
+        for key in self.held.iter() {
+            self.released.insert(key);
+        }
+        self.held.clear();
+    }
+
+    /// Record a character of text input.
+    ///
+    /// Printable characters are appended to `keyboard_string`, and a backspace character removes
+    /// the last character from it.
+    pub fn input_char(&mut self, c: char) {
+        if c == '\u{8}' {
+            self.string.pop();
+        } else if !c.is_control() {
+            if self.string.chars().count() >= KEYBOARD_STRING_MAX_LEN {
+                self.string.remove(0);
+            }
+            self.string.push(c);
+        } else {
+            return;
+        }
+        self.last_char = Some(c);
+    }
+
+    /// Forget which keys were pressed and released during the last tick.
+    ///
+    /// This should be called after every game tick, so that keys are reported as "pressed" or
+    /// "released" for exactly one tick.
+    pub fn end_tick(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    /// Returns true if the key is currently held down.
+    pub fn is_held(&self, key: VirtualKey) -> bool {
+        Self::check(&self.held, key)
+    }
+
+    /// Returns true if the key was pressed since the last tick.
+    pub fn is_pressed(&self, key: VirtualKey) -> bool {
+        Self::check(&self.pressed, key)
+    }
+
+    /// Returns true if the key was released since the last tick.
+    pub fn is_released(&self, key: VirtualKey) -> bool {
+        Self::check(&self.released, key)
+    }
+
+    /// The most recently pressed key.
+    pub fn last_key(&self) -> Option<VirtualKey> {
+        self.last_key
+    }
+
+    /// The most recently typed character.
+    pub fn last_char(&self) -> Option<char> {
+        self.last_char
+    }
+
+    /// The most recently typed text, up to [`KEYBOARD_STRING_MAX_LEN`] characters.
+    pub fn string(&self) -> &str {
+        &self.string
+    }
+
+    /// Set the most recently pressed key, as scripts do by assigning `keyboard_lastkey`.
+    pub fn set_last_key(&mut self, key: Option<VirtualKey>) {
+        self.last_key = key;
+    }
+
+    /// Set the most recently typed character, as scripts do by assigning `keyboard_lastchar`.
+    pub fn set_last_char(&mut self, c: Option<char>) {
+        self.last_char = c;
+    }
+
+    /// Replace the typed text, as scripts do by assigning `keyboard_string`.
+    ///
+    /// Only the last [`KEYBOARD_STRING_MAX_LEN`] characters are kept.
+    pub fn set_string(&mut self, string: &str) {
+        let skip = string
+            .chars()
+            .count()
+            .saturating_sub(KEYBOARD_STRING_MAX_LEN);
+        self.string = string.chars().skip(skip).collect();
+    }
+
+    fn check(set: &IndexSet, key: VirtualKey) -> bool {
+        match key {
+            VirtualKey::NO_KEY => set.is_empty(),
+            VirtualKey::ANY_KEY => !set.is_empty(),
+            key => {
+                set.contains(key.0 as usize)
+                    || key
+                        .sided()
+                        .is_some_and(|sided| sided.iter().any(|k| set.contains(k.0 as usize)))
+            }
+        }
+    }
+}
+
+/// A gamepad button, using the same values as the GML `gp_*` button constants.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct GamepadButton(pub u16);
+
+impl GamepadButton {
+    pub const FACE1: GamepadButton = GamepadButton(32769);
+    pub const FACE2: GamepadButton = GamepadButton(32770);
+    pub const FACE3: GamepadButton = GamepadButton(32771);
+    pub const FACE4: GamepadButton = GamepadButton(32772);
+    pub const SHOULDER_L: GamepadButton = GamepadButton(32773);
+    pub const SHOULDER_R: GamepadButton = GamepadButton(32774);
+    pub const SHOULDER_LB: GamepadButton = GamepadButton(32775);
+    pub const SHOULDER_RB: GamepadButton = GamepadButton(32776);
+    pub const SELECT: GamepadButton = GamepadButton(32777);
+    pub const START: GamepadButton = GamepadButton(32778);
+    pub const STICK_L: GamepadButton = GamepadButton(32779);
+    pub const STICK_R: GamepadButton = GamepadButton(32780);
+    pub const PAD_U: GamepadButton = GamepadButton(32781);
+    pub const PAD_D: GamepadButton = GamepadButton(32782);
+    pub const PAD_L: GamepadButton = GamepadButton(32783);
+    pub const PAD_R: GamepadButton = GamepadButton(32784);
+    pub const PADDLE_R: GamepadButton = GamepadButton(32789);
+    pub const PADDLE_L: GamepadButton = GamepadButton(32790);
+    pub const PADDLE_RB: GamepadButton = GamepadButton(32791);
+    pub const PADDLE_LB: GamepadButton = GamepadButton(32792);
+
+    /// The position of this button in per-gamepad button sets, if it is a valid button.
+    fn index(self) -> Option<usize> {
+        match self.0 {
+            32769..=32784 | 32789..=32792 => Some((self.0 - Self::FACE1.0) as usize),
+            _ => None,
+        }
+    }
+}
+
+/// A gamepad axis, using the same values as the GML `gp_axis*` constants.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct GamepadAxis(pub u16);
+
+impl GamepadAxis {
+    pub const LEFT_H: GamepadAxis = GamepadAxis(32785);
+    pub const LEFT_V: GamepadAxis = GamepadAxis(32786);
+    pub const RIGHT_H: GamepadAxis = GamepadAxis(32787);
+    pub const RIGHT_V: GamepadAxis = GamepadAxis(32788);
+
+    fn index(self) -> Option<usize> {
+        match self.0 {
+            32785..=32788 => Some((self.0 - Self::LEFT_H.0) as usize),
+            _ => None,
+        }
+    }
+}
+
+/// The state of a single connected gamepad.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Gamepad {
+    #[serde(with = "index_set")]
+    held: IndexSet,
+    #[serde(with = "index_set")]
+    pressed: IndexSet,
+    #[serde(with = "index_set")]
+    released: IndexSet,
+    axes: [f64; 4],
+}
+
+impl Gamepad {
+    /// Record that a button was pressed, repeated presses of a held button are ignored.
+    pub fn button_down(&mut self, button: GamepadButton) {
+        if let Some(index) = button.index()
+            && self.held.insert(index)
+        {
+            self.pressed.insert(index);
+        }
+    }
+
+    /// Record that a button was released.
+    pub fn button_up(&mut self, button: GamepadButton) {
+        if let Some(index) = button.index()
+            && self.held.remove(index)
+        {
+            self.released.insert(index);
+        }
+    }
+
+    /// Set the position of an axis, clamped to `-1.0..=1.0`.
+    ///
+    /// Vertical axes are positive downwards, as in GML.
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f64) {
+        if let Some(index) = axis.index() {
+            self.axes[index] = value.clamp(-1.0, 1.0);
+        }
+    }
+
+    pub fn is_held(&self, button: GamepadButton) -> bool {
+        button.index().is_some_and(|i| self.held.contains(i))
+    }
+
+    /// Returns true if the button was pressed since the last tick.
+    pub fn is_pressed(&self, button: GamepadButton) -> bool {
+        button.index().is_some_and(|i| self.pressed.contains(i))
+    }
+
+    /// Returns true if the button was released since the last tick.
+    pub fn is_released(&self, button: GamepadButton) -> bool {
+        button.index().is_some_and(|i| self.released.contains(i))
+    }
+
+    /// The position of an axis, or zero if it is within `deadzone` of the center.
+    pub fn axis(&self, axis: GamepadAxis, deadzone: f64) -> f64 {
+        let value = axis.index().map_or(0.0, |i| self.axes[i]);
+        if value.abs() < deadzone { 0.0 } else { value }
+    }
+
+    fn end_tick(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+/// The number of gamepad slots reported by `gamepad_get_device_count`.
+pub const GAMEPAD_SLOTS: usize = 4;
+
+/// Every gamepad slot, each of which may or may not have a connected gamepad.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Gamepads {
+    slots: [Option<Gamepad>; GAMEPAD_SLOTS],
+}
+
+impl Gamepads {
+    /// Connect a gamepad to the given slot, if the slot is valid and not already connected.
+    ///
+    /// Returns the gamepad in the slot.
+    pub fn connect(&mut self, slot: usize) -> Option<&mut Gamepad> {
+        Some(self.slots.get_mut(slot)?.get_or_insert_default())
+    }
+
+    /// Disconnect the gamepad in the given slot, forgetting all of its state.
+    pub fn disconnect(&mut self, slot: usize) {
+        if let Some(slot) = self.slots.get_mut(slot) {
+            *slot = None;
+        }
+    }
+
+    /// The gamepad in the given slot, if one is connected.
+    pub fn get(&self, slot: usize) -> Option<&Gamepad> {
+        self.slots.get(slot)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, slot: usize) -> Option<&mut Gamepad> {
+        self.slots.get_mut(slot)?.as_mut()
+    }
+
+    /// Forget which buttons were pressed and released during the last tick.
+    pub fn end_tick(&mut self) {
+        for gamepad in self.slots.iter_mut().flatten() {
+            gamepad.end_tick();
+        }
+    }
+}
+
+/// All of the input for a single tick.
+///
+/// This is serializable so that the input for every tick can be recorded and replayed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InputState {
+    /// The mouse position in screen coordinates, the same coordinates as
+    /// [`Render::screen_size`](crate::Render::screen_size).
+    #[serde(with = "vec2")]
     pub mouse_position: Vec2<f32>,
+    /// The size of the window in logical pixels, a change in size triggers the window resize event.
+    #[serde(with = "vec2")]
+    pub window_size: Vec2<u32>,
+    #[serde(with = "mouse_buttons")]
     pub mouse_pressed: MouseButtons,
+    pub keyboard: KeyboardState,
+    pub gamepads: Gamepads,
 }
 
 impl InputState {
@@ -29,6 +415,171 @@ impl InputState {
     ) -> Result<R, AccessError> {
         Self::ctx_cell(ctx).with(|state| f(state))
     }
+
+    pub fn ctx_with_mut<'gc, R>(
+        ctx: vm::Context<'gc>,
+        f: impl FnOnce(&mut InputState) -> R,
+    ) -> Result<R, AccessError> {
+        Self::ctx_cell(ctx).with_mut(|state| f(state))
+    }
+
+    /// Clear any per-tick input state, should be called after every game tick.
+    pub fn end_tick(&mut self) {
+        self.keyboard.end_tick();
+        self.gamepads.end_tick();
+    }
+}
+
+pub type InputStateCell = FreezeCell<Freeze![&'freeze mut InputState]>;
+
+mod index_set {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(set: &IndexSet, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(set.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IndexSet, D::Error> {
+        let mut set = IndexSet::new();
+        for i in Vec::<usize>::deserialize(deserializer)? {
+            set.insert(i);
+        }
+        Ok(set)
+    }
 }
 
-pub type InputStateCell = FreezeCell<Freeze![&'freeze InputState]>;
+mod vec2 {
+    use super::*;
+
+    pub fn serialize<S: Serializer, T: Serialize + Copy>(
+        v: &Vec2<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        [v[0], v[1]].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<Vec2<T>, D::Error> {
+        Ok(<[T; 2]>::deserialize(deserializer)?.into())
+    }
+}
+
+mod mouse_buttons {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        buttons: &MouseButtons,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        buttons.bits().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<MouseButtons, D::Error> {
+        Ok(MouseButtons::from_bits_truncate(u8::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyboard_state() {
+        let mut keyboard = KeyboardState::default();
+        assert!(keyboard.is_held(VirtualKey::NO_KEY));
+        assert!(!keyboard.is_held(VirtualKey::ANY_KEY));
+
+        keyboard.key_down(VirtualKey::LEFT_SHIFT);
+        keyboard.key_down(VirtualKey::LEFT_SHIFT);
+        assert!(keyboard.is_held(VirtualKey::SHIFT));
+        assert!(keyboard.is_pressed(VirtualKey::LEFT_SHIFT));
+        assert!(keyboard.is_held(VirtualKey::ANY_KEY));
+        assert!(!keyboard.is_held(VirtualKey::RIGHT_SHIFT));
+
+        keyboard.end_tick();
+        assert!(keyboard.is_held(VirtualKey::SHIFT));
+        assert!(!keyboard.is_pressed(VirtualKey::SHIFT));
+
+        keyboard.key_down(VirtualKey::from_ascii('a').unwrap());
+        keyboard.key_up(VirtualKey::from_ascii('a').unwrap());
+        assert!(keyboard.is_pressed(VirtualKey(b'A')));
+        assert!(keyboard.is_released(VirtualKey(b'A')));
+        assert!(!keyboard.is_held(VirtualKey(b'A')));
+        assert_eq!(keyboard.last_key(), Some(VirtualKey(b'A')));
+
+        keyboard.release_all();
+        assert!(keyboard.is_released(VirtualKey::SHIFT));
+        assert!(keyboard.is_held(VirtualKey::NO_KEY));
+
+        for c in "abc\u{8}d\r".chars() {
+            keyboard.input_char(c);
+        }
+        assert_eq!(keyboard.string(), "abd");
+        assert_eq!(keyboard.last_char(), Some('d'));
+
+        keyboard.set_string("");
+        keyboard.input_char('e');
+        assert_eq!(keyboard.string(), "e");
+
+        keyboard.set_string(&"x".repeat(KEYBOARD_STRING_MAX_LEN + 1));
+        assert_eq!(keyboard.string().len(), KEYBOARD_STRING_MAX_LEN);
+    }
+
+    #[test]
+    fn test_gamepads() {
+        let mut gamepads = Gamepads::default();
+        assert!(gamepads.get(0).is_none());
+        assert!(gamepads.connect(GAMEPAD_SLOTS).is_none());
+
+        let gamepad = gamepads.connect(1).unwrap();
+        gamepad.button_down(GamepadButton::FACE1);
+        gamepad.button_down(GamepadButton(0));
+        gamepad.set_axis(GamepadAxis::LEFT_H, -2.0);
+        gamepad.set_axis(GamepadAxis::RIGHT_V, 0.1);
+
+        let gamepad = gamepads.get(1).unwrap();
+        assert!(gamepad.is_held(GamepadButton::FACE1));
+        assert!(gamepad.is_pressed(GamepadButton::FACE1));
+        assert!(!gamepad.is_held(GamepadButton(0)));
+        assert_eq!(gamepad.axis(GamepadAxis::LEFT_H, 0.0), -1.0);
+        assert_eq!(gamepad.axis(GamepadAxis::RIGHT_V, 0.0), 0.1);
+        assert_eq!(gamepad.axis(GamepadAxis::RIGHT_V, 0.2), 0.0);
+
+        gamepads.end_tick();
+        let gamepad = gamepads.get_mut(1).unwrap();
+        assert!(!gamepad.is_pressed(GamepadButton::FACE1));
+        gamepad.button_up(GamepadButton::FACE1);
+        assert!(gamepad.is_released(GamepadButton::FACE1));
+        assert!(!gamepad.is_held(GamepadButton::FACE1));
+
+        gamepads.disconnect(1);
+        assert!(gamepads.get(1).is_none());
+    }
+
+    #[test]
+    fn test_serialize_input() {
+        let mut input = InputState::default();
+        input.mouse_position = Vec2::new(1.5, 2.0);
+        input.mouse_pressed = MouseButtons::Right;
+        input.keyboard.key_down(VirtualKey::SPACE);
+        input.keyboard.input_char('x');
+        input
+            .gamepads
+            .connect(0)
+            .unwrap()
+            .button_down(GamepadButton::START);
+
+        let input: InputState =
+            serde_json::from_str(&serde_json::to_string(&input).unwrap()).unwrap();
+        assert_eq!(input.mouse_position, Vec2::new(1.5, 2.0));
+        assert!(input.mouse_pressed.contains(MouseButtons::Right));
+        assert!(input.keyboard.is_pressed(VirtualKey::SPACE));
+        assert_eq!(input.keyboard.string(), "x");
+        assert!(input.gamepads.get(0).unwrap().is_held(GamepadButton::START));
+    }
+}
@@ -1,17 +1,30 @@
+pub mod audio;
+pub mod camera;
+pub mod clock;
 pub mod configuration;
 pub mod drawing;
 pub mod event;
 pub mod input;
 pub mod state;
+pub mod surface;
+pub mod text;
 
 pub use self::{
+    audio::{AudioState, Emitter, EmitterId, SAMPLE_RATE, Voice, VoiceId},
+    camera::{Camera, CameraId, CameraTarget, View, Views},
+    clock::Clock,
     configuration::{
         AnimationFrame, Configuration, InstanceTemplate, InstanceTemplateId, Object, ObjectId,
         Room, RoomId, RoomLayer, Sprite, SpriteCollision, SpriteCollisionKind, SpriteId, Texture,
         TextureId, TexturePage, TexturePageId,
     },
-    drawing::{DrawingState, DrawnSprite, DrawnSpriteFrame},
+    drawing::DrawingState,
     event::EventState,
-    input::{InputState, MouseButtons},
-    state::{Instance, InstanceId, Layer, LayerId, Scripts, State},
+    input::{
+        GAMEPAD_SLOTS, Gamepad, GamepadAxis, GamepadButton, Gamepads, InputState, KeyboardState,
+        MouseButtons, VirtualKey,
+    },
+    state::{Instance, InstanceId, Layer, LayerId, Motion, Scripts, State},
+    surface::{Surface, SurfaceId},
+    text::{HAlign, TextLayout, TextLayoutOptions, VAlign},
 };
@@ -1,11 +1,10 @@
 use std::{
     collections::{HashMap, HashSet},
     f64,
-    time::Instant,
 };
 
 use fabricator_collision::{
-    bound_box_tree::BoundBoxTree, support::SupportMap, support_ext::SupportMapExt as _,
+    bound_box_tree::BoundBoxTree, gjk, support::SupportMap, support_ext::SupportMapExt as _,
 };
 use fabricator_math::Vec2;
 use fabricator_util::{
@@ -16,7 +15,14 @@ use fabricator_vm as vm;
 
 use crate::{
     project::ObjectEvent,
-    state::configuration::{Configuration, InstanceTemplateId, ObjectId, RoomId, TileSetId},
+    state::{
+        audio::AudioState,
+        camera::{Camera, CameraId, Views},
+        clock::Clock,
+        configuration::{Configuration, InstanceTemplateId, ObjectId, RoomId, SpriteId, TileSetId},
+        input::GAMEPAD_SLOTS,
+        surface::{Surface, SurfaceId},
+    },
 };
 
 new_id_type! {
@@ -45,21 +51,114 @@ pub struct TileMap {
     pub grid: Vec<Option<u32>>,
 }
 
+/// The built-in motion of an instance, applied automatically every tick after the step event.
+///
+/// Directions are in degrees counter-clockwise from the positive x axis, as they are in GML. Since
+/// the y axis points down, a direction of 90 degrees points up.
+#[derive(Debug, Copy, Clone)]
+pub struct Motion {
+    pub speed: f64,
+    pub direction: f64,
+    pub gravity: f64,
+    pub gravity_direction: f64,
+    pub friction: f64,
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Self {
+            speed: 0.0,
+            direction: 0.0,
+            gravity: 0.0,
+            gravity_direction: 270.0,
+            friction: 0.0,
+        }
+    }
+}
+
+impl Motion {
+    /// The movement per tick as `(hspeed, vspeed)`.
+    pub fn velocity(&self) -> Vec2<f64> {
+        direction_vector(self.direction) * self.speed
+    }
+
+    /// Set the movement per tick from `(hspeed, vspeed)`.
+    ///
+    /// If the velocity is zero, the current direction is kept.
+    pub fn set_velocity(&mut self, velocity: Vec2<f64>) {
+        self.speed = velocity[0].hypot(velocity[1]);
+        if self.speed != 0.0 {
+            self.direction = (-velocity[1])
+                .atan2(velocity[0])
+                .to_degrees()
+                .rem_euclid(360.0);
+        }
+    }
+
+    /// Apply friction and gravity to the current speed, and return the resulting velocity.
+    pub fn step(&mut self) -> Vec2<f64> {
+        if self.friction != 0.0 {
+            if self.speed > 0.0 {
+                self.speed = (self.speed - self.friction).max(0.0);
+            } else {
+                self.speed = (self.speed + self.friction).min(0.0);
+            }
+        }
+
+        if self.gravity != 0.0 {
+            self.set_velocity(
+                self.velocity() + direction_vector(self.gravity_direction) * self.gravity,
+            );
+        }
+
+        self.velocity()
+    }
+}
+
+fn direction_vector(direction: f64) -> Vec2<f64> {
+    let (sin, cos) = direction.to_radians().sin_cos();
+    Vec2::new(cos, -sin)
+}
+
 pub struct Instance {
     pub this: vm::StashedUserData,
     pub object: ObjectId,
     pub active: bool,
     pub dead: bool,
     pub position: Vec2<f64>,
+    /// The position at the start of the current tick.
+    pub previous_position: Vec2<f64>,
+    /// The position the instance was created at.
+    pub start_position: Vec2<f64>,
+    pub motion: Motion,
+    /// The sprite drawn for this instance, initially the sprite of its object.
+    pub sprite: Option<SpriteId>,
+    /// The sprite used for collisions instead of `sprite`, if any.
+    pub mask: Option<SpriteId>,
+    pub scale: Vec2<f64>,
+    /// Rotation in radians, clockwise on screen.
+    ///
+    /// This is the negation of the GML `image_angle`, which is in degrees counter-clockwise.
     pub rotation: f64,
+    pub alpha: f64,
+    /// Color which the sprite is multiplied by, in the same BGR format as GML colors.
+    pub blend: u32,
+    /// Multiplier for the playback speed of the sprite animation.
+    pub image_speed: f64,
+    /// Invisible instances are not drawn, and none of their draw events are run.
+    pub visible: bool,
     pub layer: LayerId,
     pub properties: vm::StashedObject,
     pub event_closures: HashMap<ObjectEvent, vm::StashedClosure>,
+    /// The current playback position in the sprite animation, in the same units as
+    /// [`Sprite::playback_length`](crate::state::Sprite::playback_length).
     pub animation_time: f64,
+    /// Ticks remaining until each alarm event fires, or -1 if the alarm is not set.
+    pub alarms: [i32; ObjectEvent::ALARM_COUNT],
 }
 
 pub struct State {
-    pub start_instant: Instant,
+    pub clock: Clock,
     pub config: Configuration,
     pub scripts: Scripts,
 
@@ -71,6 +170,20 @@ pub struct State {
 
     pub tile_maps: IdMap<TileMapId, TileMap>,
 
+    pub cameras: IdMap<CameraId, Camera>,
+    pub views: Views,
+    /// The size of GUI space set by `display_set_gui_size`, if not set then GUI space is the same
+    /// as screen space.
+    pub gui_size: Option<Vec2<u32>>,
+    /// The window size seen on the previous tick, used to detect window resizes.
+    pub window_size: Option<Vec2<u32>>,
+    /// The axis deadzone for every gamepad slot, set by `gamepad_set_axis_deadzone`.
+    pub gamepad_deadzones: [f64; GAMEPAD_SLOTS],
+
+    pub surfaces: IdMap<SurfaceId, Surface>,
+
+    pub audio: AudioState,
+
     pub instances: IdMap<InstanceId, Instance>,
     pub instance_for_template: SecondaryMap<InstanceTemplateId, InstanceId>,
     pub instances_for_object: SecondaryMap<ObjectId, HashSet<InstanceId>>,
@@ -100,6 +213,20 @@ impl State {
         Self::ctx_cell(ctx).with_mut(|state| f(state))
     }
 
+    /// The size of the screen that every view is drawn into, see [`Views::screen_size`].
+    pub fn screen_size(&self) -> Vec2<u32> {
+        let room_size = self
+            .current_room
+            .map(|room_id| self.config.rooms[room_id].size)
+            .unwrap_or(Vec2::zero());
+        self.views.screen_size(&self.cameras, room_size)
+    }
+
+    /// The size of GUI space, which is stretched to cover the whole screen.
+    pub fn current_gui_size(&self) -> Vec2<u32> {
+        self.gui_size.unwrap_or_else(|| self.screen_size())
+    }
+
     pub fn instance_collision(
         &self,
         instance_id: InstanceId,
@@ -109,7 +236,7 @@ impl State {
         let sprite = self
             .config
             .sprites
-            .get(self.config.objects[instance.object].sprite?)?;
+            .get(instance.mask.or(instance.sprite)?)?;
 
         let rotation = if sprite.collision_rotates {
             instance.rotation
@@ -120,12 +247,104 @@ impl State {
         Some(
             sprite
                 .collision
+                .scale(instance.scale)
                 .support_map()
                 .rotate(rotation)
                 .translate(instance.position),
         )
     }
 
+    /// Returns true if the collision shape of an instance overlaps the given shape.
+    ///
+    /// Both collision events and the `collision_*` API test overlap this way, so that they always
+    /// agree. Instances without a collision shape never collide with anything.
+    pub fn instance_touches(&self, instance_id: InstanceId, shape: impl SupportMap<f64>) -> bool {
+        let Some(collision) = self.instance_collision(instance_id) else {
+            return false;
+        };
+
+        let res = gjk::gjk(
+            gjk::Settings {
+                tolerance: 0.1,
+                max_iterations: 12,
+                max_distance: 0.0,
+                find_closest_point: false,
+            },
+            collision.intersect(shape),
+            &mut Default::default(),
+        );
+        matches!(res, gjk::Result::Touching)
+    }
+
+    /// Returns true if the collision shapes of both instances overlap.
+    ///
+    /// Instances without a collision shape never collide with anything.
+    pub fn instances_collide(&self, a: InstanceId, b: InstanceId) -> bool {
+        match self.instance_collision(b) {
+            Some(b) => self.instance_touches(a, b),
+            None => false,
+        }
+    }
+
+    /// Returns true if `object_id` is the same object as `ancestor_id` or inherits from it.
+    pub fn object_inherits_from(&self, mut object_id: ObjectId, ancestor_id: ObjectId) -> bool {
+        loop {
+            if object_id == ancestor_id {
+                return true;
+            }
+
+            match self.config.objects[object_id].parent {
+                Some(parent_id) => object_id = parent_id,
+                None => return false,
+            }
+        }
+    }
+
+    /// Add every script value held by the state as a root of a heap snapshot.
+    pub fn add_heap_roots<'gc>(
+        &self,
+        ctx: vm::Context<'gc>,
+        builder: &mut vm::HeapSnapshotBuilder<'gc>,
+    ) {
+        for (i, script) in self.scripts.scripts.iter().enumerate() {
+            builder.add_root(format!("script {i}"), ctx.fetch(script));
+        }
+
+        for (&object_id, events) in &self.scripts.object_events {
+            let object_name = &self.config.objects[object_id].name;
+            for (event, closure) in events {
+                builder.add_root(format!("{object_name} {event:?}"), ctx.fetch(closure));
+            }
+        }
+
+        for (instance_id, instance) in self.instances.iter() {
+            let name = format!(
+                "instance {} ({})",
+                instance_id.index(),
+                self.config.objects[instance.object].name
+            );
+            builder.add_root(name.clone(), ctx.fetch(&instance.this));
+            builder.add_root(
+                format!("{name} properties"),
+                ctx.fetch(&instance.properties),
+            );
+        }
+
+        for (layer_id, layer) in self.layers.iter() {
+            builder.add_root(
+                format!("layer {}", layer_id.index()),
+                ctx.fetch(&layer.this),
+            );
+        }
+
+        for (tile_map_id, tile_map) in self.tile_maps.iter() {
+            builder.add_root(
+                format!("tile map {}", tile_map_id.index()),
+                ctx.fetch(&tile_map.this),
+            );
+        }
+    }
+
     pub fn event_closures<'gc>(
         &self,
         mut object_id: ObjectId,
@@ -156,3 +375,81 @@ impl State {
 }
 
 pub type StateCell = FreezeCell<Freeze![&'freeze mut State]>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2<f64>, b: Vec2<f64>) {
+        assert!(
+            (a[0] - b[0]).abs() < 1e-9 && (a[1] - b[1]).abs() < 1e-9,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn test_motion_velocity() {
+        let mut motion = Motion {
+            speed: 2.0,
+            direction: 90.0,
+            ..Default::default()
+        };
+        assert_close(motion.velocity(), Vec2::new(0.0, -2.0));
+
+        motion.set_velocity(Vec2::new(3.0, 4.0));
+        assert_eq!(motion.speed, 5.0);
+        assert!((motion.direction - (360.0 - 4.0f64.atan2(3.0).to_degrees())).abs() < 1e-9);
+        assert_close(motion.velocity(), Vec2::new(3.0, 4.0));
+
+        // A zero velocity keeps the previous direction.
+        let direction = motion.direction;
+        motion.set_velocity(Vec2::zero());
+        assert_eq!(motion.speed, 0.0);
+        assert_eq!(motion.direction, direction);
+    }
+
+    #[test]
+    fn test_motion_friction() {
+        let mut motion = Motion {
+            speed: 1.0,
+            friction: 0.4,
+            ..Default::default()
+        };
+        assert_close(motion.step(), Vec2::new(0.6, 0.0));
+        assert_close(motion.step(), Vec2::new(0.2, 0.0));
+        assert_close(motion.step(), Vec2::zero());
+        assert_eq!(motion.speed, 0.0);
+        assert_close(motion.step(), Vec2::zero());
+        assert_eq!(motion.speed, 0.0);
+
+        let mut motion = Motion {
+            speed: -0.5,
+            friction: 0.4,
+            ..Default::default()
+        };
+        motion.step();
+        assert!((motion.speed + 0.1).abs() < 1e-9);
+        motion.step();
+        assert_eq!(motion.speed, 0.0);
+    }
+
+    #[test]
+    fn test_motion_gravity() {
+        let mut motion = Motion {
+            gravity: 0.5,
+            ..Default::default()
+        };
+        assert_close(motion.step(), Vec2::new(0.0, 0.5));
+        assert_close(motion.step(), Vec2::new(0.0, 1.0));
+        assert!((motion.speed - 1.0).abs() < 1e-9);
+        assert!((motion.direction - 270.0).abs() < 1e-9);
+
+        let mut motion = Motion {
+            gravity: 1.0,
+            ..Default::default()
+        };
+        motion.set_velocity(Vec2::new(2.0, 0.0));
+        assert_close(motion.step(), Vec2::new(2.0, 1.0));
+        assert_close(motion.step(), Vec2::new(2.0, 2.0));
+    }
+}
@@ -0,0 +1,29 @@
+use fabricator_math::Vec2;
+use fabricator_util::typed_id_map::new_id_type;
+use fabricator_vm as vm;
+use image::RgbaImage;
+
+new_id_type! {
+    pub struct SurfaceId;
+}
+
+pub struct Surface {
+    pub this: vm::StashedUserData,
+    pub size: Vec2<u32>,
+    /// The contents of the surface as last reported by the renderer with
+    /// [`Game::set_surface_contents`](crate::Game::set_surface_contents).
+    ///
+    /// Surfaces are only drawn to once a tick has finished, so these never include drawing from
+    /// the current tick.
+    pub contents: Option<RgbaImage>,
+}
+
+impl Surface {
+    pub fn new(this: vm::StashedUserData, size: Vec2<u32>) -> Self {
+        Self {
+            this,
+            size,
+            contents: None,
+        }
+    }
+}
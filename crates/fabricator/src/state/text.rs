@@ -0,0 +1,247 @@
+use fabricator_math::{Box2, Vec2};
+
+use crate::state::{TextureId, configuration::Font};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum HAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TextLayoutOptions {
+    /// The distance between the tops of consecutive lines, if not set then the line height of the
+    /// font is used.
+    pub line_separation: Option<f64>,
+    /// If set, lines wider than this are wrapped at spaces.
+    ///
+    /// A single word wider than this is never broken up, it is placed on its own line instead.
+    pub max_width: Option<f64>,
+    /// Each line is aligned horizontally to the text position individually.
+    pub halign: HAlign,
+    /// The block of lines is aligned vertically to the text position as a whole.
+    pub valign: VAlign,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PlacedGlyph {
+    pub texture: TextureId,
+    pub region: Box2<u32>,
+    /// The position of the top left of `region`, relative to the text position.
+    pub position: Vec2<f64>,
+}
+
+/// Text which has been split into lines and had every glyph positioned.
+#[derive(Debug, Clone, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<PlacedGlyph>,
+    /// The width of the widest line and the total height of every line.
+    pub size: Vec2<f64>,
+}
+
+impl TextLayout {
+    pub fn new(font: &Font, text: &str, options: TextLayoutOptions) -> Self {
+        let mut lines = Vec::new();
+        for line in text.split('\n') {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            match options.max_width {
+                Some(max_width) => wrap_line(font, line, max_width, &mut lines),
+                None => lines.push(line.to_owned()),
+            }
+        }
+
+        let line_height = options.line_separation.unwrap_or(font.line_height as f64);
+        let widths = lines
+            .iter()
+            .map(|line| line_width(font, line))
+            .collect::<Vec<_>>();
+        let size = Vec2::new(
+            widths.iter().copied().fold(0.0, f64::max),
+            lines.len() as f64 * line_height,
+        );
+
+        let top = match options.valign {
+            VAlign::Top => 0.0,
+            VAlign::Middle => -size[1] / 2.0,
+            VAlign::Bottom => -size[1],
+        };
+
+        let mut glyphs = Vec::new();
+        for (i, (line, width)) in lines.iter().zip(widths).enumerate() {
+            let y = top + i as f64 * line_height;
+            let mut pen = match options.halign {
+                HAlign::Left => 0.0,
+                HAlign::Center => -width / 2.0,
+                HAlign::Right => -width,
+            };
+
+            let mut prev = None;
+            for c in line.chars() {
+                pen += kerning(font, prev, c);
+                prev = Some(c);
+
+                if let Some(glyph) = font.glyphs.get(&c) {
+                    if glyph.region.min[0] < glyph.region.max[0]
+                        && glyph.region.min[1] < glyph.region.max[1]
+                    {
+                        glyphs.push(PlacedGlyph {
+                            texture: glyph.texture,
+                            region: glyph.region,
+                            position: Vec2::new(pen + glyph.offset as f64, y),
+                        });
+                    }
+                    pen += glyph.shift as f64;
+                }
+            }
+        }
+
+        Self { glyphs, size }
+    }
+}
+
+/// Greedily wrap a single line of text at spaces so that every line fits within `max_width`.
+fn wrap_line(font: &Font, line: &str, max_width: f64, lines: &mut Vec<String>) {
+    let mut current = String::new();
+    for word in line.split(' ') {
+        if current.is_empty() {
+            current.push_str(word);
+            continue;
+        }
+
+        let start = current.len();
+        current.push(' ');
+        current.push_str(word);
+        if line_width(font, &current) > max_width {
+            current.truncate(start);
+            lines.push(current);
+            current = word.to_owned();
+        }
+    }
+    lines.push(current);
+}
+
+fn line_width(font: &Font, line: &str) -> f64 {
+    let mut width = 0.0;
+    let mut prev = None;
+    for c in line.chars() {
+        width += kerning(font, prev, c);
+        prev = Some(c);
+        if let Some(glyph) = font.glyphs.get(&c) {
+            width += glyph.shift as f64;
+        }
+    }
+    width
+}
+
+fn kerning(font: &Font, prev: Option<char>, c: char) -> f64 {
+    prev.and_then(|prev| font.kerning.get(&(prev, c)))
+        .copied()
+        .unwrap_or(0) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use fabricator_util::typed_id_map::IdMap;
+    use fabricator_vm as vm;
+
+    use crate::state::configuration::Glyph;
+
+    use super::*;
+
+    // A font where every glyph is 4 wide and 8 high, and "AB" is kerned by -1.
+    fn test_font() -> Font {
+        let mut textures = IdMap::<TextureId, ()>::new();
+        let texture = textures.insert(());
+
+        let mut interpreter = vm::Interpreter::new();
+        let userdata = interpreter.enter(|ctx| ctx.stash(vm::UserData::new_static(&ctx, ())));
+
+        let glyphs = "AB "
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                (
+                    c,
+                    Glyph {
+                        texture,
+                        region: Box2::with_size(Vec2::new(i as u32 * 4, 0), Vec2::new(4, 8)),
+                        offset: 0,
+                        shift: 4,
+                    },
+                )
+            })
+            .collect();
+
+        Font {
+            name: "test".to_owned(),
+            glyphs,
+            kerning: HashMap::from([(('A', 'B'), -1)]),
+            line_height: 8,
+            userdata,
+        }
+    }
+
+    #[test]
+    fn test_layout() {
+        let font = test_font();
+
+        let layout = TextLayout::new(&font, "AB\nA", TextLayoutOptions::default());
+        assert_eq!(layout.size, Vec2::new(7.0, 16.0));
+        let positions = layout.glyphs.iter().map(|g| g.position).collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(3.0, 0.0),
+                Vec2::new(0.0, 8.0)
+            ]
+        );
+
+        let layout = TextLayout::new(
+            &font,
+            "A",
+            TextLayoutOptions {
+                halign: HAlign::Right,
+                valign: VAlign::Middle,
+                ..Default::default()
+            },
+        );
+        assert_eq!(layout.glyphs[0].position, Vec2::new(-4.0, -4.0));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let font = test_font();
+
+        let layout = TextLayout::new(
+            &font,
+            "A A BBB A",
+            TextLayoutOptions {
+                line_separation: Some(10.0),
+                max_width: Some(12.0),
+                ..Default::default()
+            },
+        );
+
+        // Lines are "A A", "BBB" and "A", the space glyphs are still placed.
+        assert_eq!(layout.size, Vec2::new(12.0, 30.0));
+        let ys = layout
+            .glyphs
+            .iter()
+            .map(|g| g.position[1])
+            .collect::<Vec<_>>();
+        assert_eq!(ys, [0.0, 0.0, 0.0, 10.0, 10.0, 10.0, 20.0]);
+    }
+}
@@ -0,0 +1,37 @@
+use std::{io, path::PathBuf};
+
+use anyhow::Error;
+use clap::Parser;
+use fabricator_lsp::{ServerOptions, run_server};
+
+#[derive(Parser)]
+struct Cli {
+    /// The project file to load, by default the first `.yyp` file found in the root of the client's
+    /// workspace.
+    #[arg(long)]
+    project_file: Option<PathBuf>,
+    #[arg(long, default_value = "default")]
+    config: String,
+    /// Communicate over stdio, which is the only supported transport. Accepted because many clients
+    /// pass it unconditionally.
+    #[arg(long, hide = true)]
+    stdio: bool,
+}
+
+fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+
+    // `env_logger` writes to stderr, which leaves stdout free for the protocol.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    run_server(
+        io::stdin().lock(),
+        io::stdout(),
+        ServerOptions {
+            project_file: cli.project_file,
+            config: cli.config,
+        },
+    )?;
+
+    Ok(())
+}
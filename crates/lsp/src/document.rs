@@ -0,0 +1,206 @@
+use fabricator_vm::Span;
+use serde::{Deserialize, Serialize};
+
+/// A position in a document, as a 0-indexed line and a 0-indexed UTF-16 code unit offset within
+/// that line.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// The text of a source file along with an index of its line starts, for converting between byte
+/// offsets and LSP positions.
+pub struct Document {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let mut line_starts = vec![0];
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                    i += 2;
+                    line_starts.push(i);
+                }
+                b'\r' | b'\n' => {
+                    i += 1;
+                    line_starts.push(i);
+                }
+                _ => i += 1,
+            }
+        }
+
+        Self { text, line_starts }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Convert a byte offset into a position.
+    ///
+    /// Offsets past the end of the text are clamped to the end.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = self.floor_char_boundary(offset);
+        let line = self.line_starts.partition_point(|&s| s <= offset) - 1;
+        let line_start = self.line_starts[line];
+        Position {
+            line: line as u32,
+            character: self.text[line_start..offset].encode_utf16().count() as u32,
+        }
+    }
+
+    /// Convert a position into a byte offset.
+    ///
+    /// Positions past the end of a line are clamped to the end of that line, and lines past the
+    /// end of the text are clamped to the end of the text.
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let line = self.text[line_start..line_end].trim_end_matches(['\r', '\n']);
+
+        let mut utf16_len = 0;
+        for (i, c) in line.char_indices() {
+            if utf16_len >= position.character as usize {
+                return line_start + i;
+            }
+            utf16_len += c.len_utf16();
+        }
+        line_start + line.len()
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        Range {
+            start: self.position(span.start()),
+            end: self.position(span.end()),
+        }
+    }
+
+    /// A range covering the given 0-indexed line, not including the line break.
+    pub fn line_range(&self, line: usize) -> Range {
+        let line = line.min(self.line_starts.len() - 1);
+        let start = self.line_starts[line];
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.text.len());
+        let end = start + self.text[start..end].trim_end_matches(['\r', '\n']).len();
+        self.range(Span::new(start, end))
+    }
+
+    /// Returns the source text for the given span, if it is within this document.
+    pub fn slice(&self, span: Span) -> Option<&str> {
+        if span.is_null() || span.is_everywhere() {
+            return None;
+        }
+        self.text.get(span.start()..span.end())
+    }
+
+    fn floor_char_boundary(&self, mut offset: usize) -> usize {
+        offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_positions() {
+        let doc = Document::new("a\r\nb😀c\rd\n".to_owned());
+
+        assert_eq!(
+            doc.position(0),
+            Position {
+                line: 0,
+                character: 0
+            }
+        );
+        assert_eq!(
+            doc.position(3),
+            Position {
+                line: 1,
+                character: 0
+            }
+        );
+        assert_eq!(
+            doc.position(8),
+            Position {
+                line: 1,
+                character: 3
+            }
+        );
+        assert_eq!(
+            doc.position(10),
+            Position {
+                line: 2,
+                character: 0
+            }
+        );
+        assert_eq!(
+            doc.position(100),
+            Position {
+                line: 3,
+                character: 0
+            }
+        );
+
+        assert_eq!(
+            doc.offset(Position {
+                line: 1,
+                character: 3
+            }),
+            8
+        );
+        assert_eq!(
+            doc.offset(Position {
+                line: 1,
+                character: 100
+            }),
+            9
+        );
+        assert_eq!(
+            doc.offset(Position {
+                line: 100,
+                character: 0
+            }),
+            12
+        );
+
+        assert_eq!(
+            doc.line_range(1),
+            Range {
+                start: Position {
+                    line: 1,
+                    character: 0
+                },
+                end: Position {
+                    line: 1,
+                    character: 4
+                },
+            }
+        );
+    }
+}
@@ -0,0 +1,27 @@
+//! A [Language Server Protocol] server for the scripts in a GameMaker project.
+//!
+//! Every script is run through the same compiler pipeline the runner uses, so diagnostics match
+//! the errors the game would report when loading. The server also provides go-to-definition and
+//! hover for project functions, macros, enums, and globals, and completion which includes both the
+//! stdlib and the runner API.
+//!
+//! Only full document sync is supported.
+//!
+//! [Language Server Protocol]: https://microsoft.github.io/language-server-protocol/
+
+mod document;
+mod protocol;
+mod server;
+mod symbols;
+mod workspace;
+
+pub use self::{
+    document::{Document, Position, Range},
+    protocol::ProtocolError,
+    server::{ServerOptions, run_server},
+    symbols::{Reference, Symbol, SymbolKind, file_symbols, reference_at},
+    workspace::{
+        Builtin, BuiltinKind, Definition, Diagnostic, ScriptUnit, Severity, Workspace,
+        normalize_path,
+    },
+};
@@ -0,0 +1,172 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::json;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProtocolError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("missing Content-Length header")]
+    MissingContentLength,
+    #[error("bad header {0:?}")]
+    BadHeader(String),
+    #[error("bad message: {0}")]
+    BadMessage(#[from] serde_json::Error),
+}
+
+/// JSON-RPC error codes used in responses.
+pub mod error_codes {
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const SERVER_NOT_INITIALIZED: i64 = -32002;
+}
+
+/// A message sent from the client.
+#[derive(Debug, Clone)]
+pub enum Message {
+    Request {
+        id: serde_json::Value,
+        method: String,
+        params: serde_json::Value,
+    },
+    Notification {
+        method: String,
+        params: serde_json::Value,
+    },
+}
+
+/// Read a single JSON-RPC message from the client.
+///
+/// Returns `None` if the reader reaches EOF before the start of a message. Responses to requests
+/// sent by the server are skipped, since the server never sends any.
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<Message>, ProtocolError> {
+    loop {
+        let mut content_length = None;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            let header = line.trim_end_matches(['\r', '\n']);
+            if header.is_empty() {
+                break;
+            }
+
+            let (name, value) = header
+                .split_once(':')
+                .ok_or_else(|| ProtocolError::BadHeader(header.to_owned()))?;
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| ProtocolError::BadHeader(header.to_owned()))?,
+                );
+            }
+        }
+
+        let mut content = vec![0; content_length.ok_or(ProtocolError::MissingContentLength)?];
+        reader.read_exact(&mut content)?;
+
+        let mut message: serde_json::Value = serde_json::from_slice(&content)?;
+        let Some(method) = message["method"].as_str().map(str::to_owned) else {
+            continue;
+        };
+        let params = message["params"].take();
+
+        return Ok(Some(match message.get_mut("id") {
+            Some(id) => Message::Request {
+                id: id.take(),
+                method,
+                params,
+            },
+            None => Message::Notification { method, params },
+        }));
+    }
+}
+
+/// The sending half of a JSON-RPC connection.
+pub struct Connection<W> {
+    writer: W,
+}
+
+impl<W: Write> Connection<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn respond(&mut self, id: serde_json::Value, result: serde_json::Value) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result,
+        }));
+    }
+
+    pub fn respond_error(&mut self, id: serde_json::Value, code: i64, message: impl Into<String>) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": code,
+                "message": message.into(),
+            },
+        }));
+    }
+
+    pub fn notify(&mut self, method: &str, params: serde_json::Value) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }));
+    }
+
+    fn send(&mut self, message: serde_json::Value) {
+        let content = message.to_string();
+        let res = write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .and_then(|_| self.writer.flush());
+        if let Err(err) = res {
+            log::error!("error writing language server message: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_message() {
+        let body_1 = r#"{"jsonrpc":"2.0","id":3,"result":null}"#;
+        let body_2 = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+        let body_3 = r#"{"jsonrpc":"2.0","id":"a","method":"shutdown"}"#;
+        let input = format!(
+            "Content-Length: {}\r\n\r\n{body_1}Content-Length: {}\r\n\r\n{body_2}Content-Length: {}\r\n\r\n{body_3}",
+            body_1.len(),
+            body_2.len(),
+            body_3.len(),
+        );
+
+        let mut reader = input.as_bytes();
+        assert!(matches!(
+            read_message(&mut reader).unwrap(),
+            Some(Message::Notification { method, .. }) if method == "initialized"
+        ));
+        assert!(matches!(
+            read_message(&mut reader).unwrap(),
+            Some(Message::Request { id, method, params })
+                if id == "a" && method == "shutdown" && params.is_null()
+        ));
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+}
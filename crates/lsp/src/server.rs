@@ -0,0 +1,539 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use serde_json::json;
+use url::Url;
+
+use crate::{
+    document::{Document, Position},
+    protocol::{Connection, Message, ProtocolError, error_codes, read_message},
+    symbols::{Reference, SymbolKind, reference_at},
+    workspace::{BuiltinKind, Definition, Severity, Workspace, normalize_path},
+};
+
+/// Settings for a language server, which may be overridden by the client's `initializationOptions`.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// The project file to load. If not set, the first `.yyp` file in the root of the client's
+    /// workspace is used.
+    pub project_file: Option<PathBuf>,
+    /// The configuration name that macros are resolved with.
+    pub config: String,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            project_file: None,
+            config: "default".to_owned(),
+        }
+    }
+}
+
+type RequestError = (i64, String);
+
+/// Run a language server which reads messages from `reader` and writes to `writer`, until the client
+/// sends an `exit` notification or closes the connection.
+pub fn run_server(
+    mut reader: impl BufRead,
+    writer: impl Write,
+    options: ServerOptions,
+) -> Result<(), ProtocolError> {
+    let mut server = Server {
+        connection: Connection::new(writer),
+        options,
+        workspace: None,
+        published: HashSet::new(),
+        initialized: false,
+        shutdown: false,
+    };
+
+    while let Some(message) = read_message(&mut reader)? {
+        match message {
+            Message::Request { id, method, params } => {
+                let res = if !server.initialized && method != "initialize" {
+                    Err((
+                        error_codes::SERVER_NOT_INITIALIZED,
+                        "server not initialized".to_owned(),
+                    ))
+                } else if server.shutdown {
+                    Err((
+                        error_codes::INVALID_REQUEST,
+                        "server is shutting down".to_owned(),
+                    ))
+                } else {
+                    server.request(&method, params)
+                };
+
+                match res {
+                    Ok(result) => server.connection.respond(id, result),
+                    Err((code, message)) => server.connection.respond_error(id, code, message),
+                }
+
+                if method == "initialize" {
+                    server.after_initialize();
+                }
+            }
+            Message::Notification { method, params } => {
+                if method == "exit" {
+                    break;
+                }
+                if server.initialized {
+                    server.notification(&method, params);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Server<W> {
+    connection: Connection<W>,
+    options: ServerOptions,
+    workspace: Option<Workspace>,
+    // Every file which currently has diagnostics published for it.
+    published: HashSet<PathBuf>,
+    initialized: bool,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn request(
+        &mut self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, RequestError> {
+        match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(serde_json::Value::Null)
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            _ => Err((
+                error_codes::METHOD_NOT_FOUND,
+                format!("unsupported request {method:?}"),
+            )),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: serde_json::Value) {
+        let Some(workspace) = &mut self.workspace else {
+            return;
+        };
+
+        let document_path = || uri_to_path(params["textDocument"]["uri"].as_str()?);
+
+        match method {
+            "textDocument/didOpen" => {
+                if let (Some(path), Some(text)) =
+                    (document_path(), params["textDocument"]["text"].as_str())
+                {
+                    workspace.open(path, text.to_owned());
+                }
+            }
+            "textDocument/didChange" => {
+                // We only support full document sync, so the last change is the whole document.
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let (Some(path), Some(text)) = (document_path(), text) {
+                    workspace.open(path, text.to_owned());
+                }
+            }
+            "textDocument/didClose" => {
+                if let Some(path) = document_path() {
+                    workspace.close(&path);
+                }
+            }
+            "textDocument/didSave" => {}
+            "workspace/didChangeWatchedFiles" => {
+                for change in params["changes"].as_array().into_iter().flatten() {
+                    if let Some(path) = change["uri"].as_str().and_then(uri_to_path) {
+                        workspace.file_changed(&path);
+                    }
+                }
+            }
+            _ => return,
+        }
+
+        self.publish_diagnostics();
+    }
+
+    fn initialize(&mut self, params: serde_json::Value) -> serde_json::Value {
+        self.initialized = true;
+
+        let init_options = &params["initializationOptions"];
+        if let Some(project_file) = init_options["projectFile"].as_str() {
+            self.options.project_file = Some(PathBuf::from(project_file));
+        }
+        if let Some(config) = init_options["config"].as_str() {
+            self.options.config = config.to_owned();
+        }
+
+        if self.options.project_file.is_none() {
+            let root = params["workspaceFolders"][0]["uri"]
+                .as_str()
+                .or(params["rootUri"].as_str())
+                .and_then(uri_to_path)
+                .or_else(|| params["rootPath"].as_str().map(PathBuf::from));
+            self.options.project_file = root.as_deref().and_then(find_project_file);
+        }
+
+        json!({
+            "capabilities": {
+                "positionEncoding": "utf-16",
+                "textDocumentSync": {
+                    "openClose": true,
+                    // Full document sync
+                    "change": 1,
+                    "save": true,
+                },
+                "definitionProvider": true,
+                "hoverProvider": true,
+                "completionProvider": {
+                    "triggerCharacters": ["."],
+                },
+            },
+            "serverInfo": {
+                "name": "fabricator-lsp",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    // Loading the project can take some time, so it is done after responding to `initialize`.
+    fn after_initialize(&mut self) {
+        let Some(project_file) = self.options.project_file.clone() else {
+            self.show_message(1, "no GameMaker project file found in workspace");
+            return;
+        };
+
+        match Workspace::load(&project_file, &self.options.config) {
+            Ok((workspace, config_error)) => {
+                self.workspace = Some(workspace);
+                if let Some(err) = config_error {
+                    self.show_message(
+                        2,
+                        &format!(
+                            "could not load project assets, only the stdlib is available: {err:#}"
+                        ),
+                    );
+                }
+                self.publish_diagnostics();
+            }
+            Err(err) => {
+                self.show_message(
+                    1,
+                    &format!("could not load project {project_file:?}: {err:#}"),
+                );
+            }
+        }
+    }
+
+    fn definition(&mut self, params: serde_json::Value) -> Result<serde_json::Value, RequestError> {
+        let Some((workspace, path, document, offset)) = self.text_position(&params)? else {
+            return Ok(serde_json::Value::Null);
+        };
+        let Some(reference) = reference_at(document.text(), offset) else {
+            return Ok(serde_json::Value::Null);
+        };
+
+        let mut locations = Vec::new();
+        for definition in workspace.find_definitions(&path, &reference) {
+            let Definition::Symbol {
+                path,
+                symbol,
+                member,
+            } = definition
+            else {
+                continue;
+            };
+            let Some(target) = workspace.document(&path) else {
+                continue;
+            };
+            let span = member.map_or(symbol.span, |(_, span)| span);
+            locations.push(json!({
+                "uri": path_to_uri(&path),
+                "range": target.range(span),
+            }));
+        }
+
+        Ok(json!(locations))
+    }
+
+    fn hover(&mut self, params: serde_json::Value) -> Result<serde_json::Value, RequestError> {
+        let Some((workspace, path, document, offset)) = self.text_position(&params)? else {
+            return Ok(serde_json::Value::Null);
+        };
+        let Some(reference) = reference_at(document.text(), offset) else {
+            return Ok(serde_json::Value::Null);
+        };
+        let Some(definition) = workspace
+            .find_definitions(&path, &reference)
+            .into_iter()
+            .next()
+        else {
+            return Ok(serde_json::Value::Null);
+        };
+
+        let contents = match definition {
+            Definition::Symbol {
+                path,
+                symbol,
+                member: Some((member, _)),
+            } => format!(
+                "```gml\n{}.{}\n```\nDefined in `{}`",
+                symbol.name,
+                member,
+                file_name(&path)
+            ),
+            Definition::Symbol { path, symbol, .. } => {
+                format!(
+                    "```gml\n{}\n```\nDefined in `{}`",
+                    symbol.detail,
+                    file_name(&path)
+                )
+            }
+            Definition::Builtin(builtin) => {
+                let kind = match builtin.kind {
+                    BuiltinKind::Function => "function",
+                    BuiltinKind::Constant => "constant",
+                    BuiltinKind::Variable => "variable",
+                };
+                format!("```gml\n{}\n```\nBuilt-in {}", builtin.name, kind)
+            }
+        };
+
+        Ok(json!({
+            "contents": {
+                "kind": "markdown",
+                "value": contents,
+            },
+            "range": document.range(reference.span),
+        }))
+    }
+
+    fn completion(&mut self, params: serde_json::Value) -> Result<serde_json::Value, RequestError> {
+        // LSP `CompletionItemKind` values.
+        const FUNCTION: u32 = 3;
+        const VARIABLE: u32 = 6;
+        const ENUM: u32 = 13;
+        const ENUM_MEMBER: u32 = 20;
+        const CONSTANT: u32 = 21;
+
+        let Some((workspace, path, document, offset)) = self.text_position(&params)? else {
+            return Ok(serde_json::Value::Null);
+        };
+        let reference = reference_at(document.text(), offset);
+
+        let mut items = Vec::new();
+
+        if let Some(reference) = reference.filter(|r| r.qualifier.is_some()) {
+            let qualifier = Reference {
+                name: reference.qualifier,
+                span: reference.span,
+                qualifier: None,
+            };
+            for definition in workspace.find_definitions(&path, &qualifier) {
+                if let Definition::Symbol { symbol, .. } = definition {
+                    if symbol.kind == SymbolKind::Enum {
+                        for (member, _) in &symbol.members {
+                            items.push(json!({
+                                "label": member,
+                                "kind": ENUM_MEMBER,
+                                "detail": symbol.detail,
+                            }));
+                        }
+                    }
+                }
+            }
+            return Ok(json!({ "isIncomplete": false, "items": items }));
+        }
+
+        let mut seen = HashSet::new();
+
+        let local = workspace
+            .file_symbols(&path)
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let project = workspace.project_symbols().into_iter().map(|(_, s)| s);
+        for symbol in local.into_iter().chain(project) {
+            if seen.insert(symbol.name.clone()) {
+                let kind = match symbol.kind {
+                    SymbolKind::Function => FUNCTION,
+                    SymbolKind::Macro => CONSTANT,
+                    SymbolKind::Enum => ENUM,
+                    SymbolKind::GlobalVar => VARIABLE,
+                };
+                items.push(json!({
+                    "label": symbol.name,
+                    "kind": kind,
+                    "detail": symbol.detail,
+                }));
+            }
+        }
+
+        for builtin in workspace.builtins() {
+            if seen.insert(builtin.name.clone()) {
+                let kind = match builtin.kind {
+                    BuiltinKind::Function => FUNCTION,
+                    BuiltinKind::Constant => CONSTANT,
+                    BuiltinKind::Variable => VARIABLE,
+                };
+                items.push(json!({
+                    "label": builtin.name,
+                    "kind": kind,
+                }));
+            }
+        }
+
+        Ok(json!({ "isIncomplete": false, "items": items }))
+    }
+
+    // Returns the workspace, file path, current document, and byte offset for a
+    // `TextDocumentPositionParams`, or `None` if there is no project loaded.
+    fn text_position(
+        &mut self,
+        params: &serde_json::Value,
+    ) -> Result<Option<(&mut Workspace, PathBuf, Document, usize)>, RequestError> {
+        let Some(workspace) = &mut self.workspace else {
+            return Ok(None);
+        };
+
+        let path = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(uri_to_path)
+            .ok_or_else(|| {
+                (
+                    error_codes::INVALID_PARAMS,
+                    "missing or invalid document URI".to_owned(),
+                )
+            })?;
+        let position: Position =
+            serde_json::from_value(params["position"].clone()).map_err(|err| {
+                (
+                    error_codes::INVALID_PARAMS,
+                    format!("invalid position: {err}"),
+                )
+            })?;
+
+        let Some(document) = workspace.document(&path) else {
+            return Ok(None);
+        };
+        let offset = document.offset(position);
+        Ok(Some((workspace, path, document, offset)))
+    }
+
+    fn publish_diagnostics(&mut self) {
+        // LSP `DiagnosticSeverity::Error`
+        const ERROR: u32 = 1;
+        const WARNING: u32 = 2;
+
+        let Some(workspace) = &mut self.workspace else {
+            return;
+        };
+
+        let diagnostics = workspace.check();
+
+        let mut notifications = Vec::new();
+        for path in self
+            .published
+            .difference(&diagnostics.keys().cloned().collect())
+        {
+            notifications.push((path.clone(), Vec::new()));
+        }
+
+        for (path, file_diagnostics) in &diagnostics {
+            let Some(document) = workspace.document(path) else {
+                continue;
+            };
+
+            let items = file_diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    let range = match diagnostic.span {
+                        // Spans may point into macro definitions in other files, so only trust
+                        // spans that fit within this file.
+                        Some(span) if document.slice(span).is_some() => document.range(span),
+                        _ => document.line_range(diagnostic.line_number.0),
+                    };
+                    json!({
+                        "range": range,
+                        "severity": match diagnostic.severity {
+                            Severity::Error => ERROR,
+                            Severity::Warning => WARNING,
+                        },
+                        "source": "fabricator",
+                        "message": diagnostic.message,
+                    })
+                })
+                .collect::<Vec<_>>();
+            notifications.push((path.clone(), items));
+        }
+
+        self.published = diagnostics.into_keys().collect();
+
+        for (path, items) in notifications {
+            self.connection.notify(
+                "textDocument/publishDiagnostics",
+                json!({
+                    "uri": path_to_uri(&path),
+                    "diagnostics": items,
+                }),
+            );
+        }
+    }
+
+    fn show_message(&mut self, message_type: u32, message: &str) {
+        log::warn!("{message}");
+        self.connection.notify(
+            "window/showMessage",
+            json!({
+                "type": message_type,
+                "message": message,
+            }),
+        );
+    }
+}
+
+fn find_project_file(root: &Path) -> Option<PathBuf> {
+    let mut project_files = fs::read_dir(root)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("yyp"))
+        })
+        .collect::<Vec<_>>();
+    project_files.sort();
+    project_files.into_iter().next()
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = Url::parse(uri).ok()?.to_file_path().ok()?;
+    Some(normalize_path(&path))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    match Url::from_file_path(path) {
+        Ok(url) => url.to_string(),
+        Err(()) => format!("file://{}", path.display()),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
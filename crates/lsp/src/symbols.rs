@@ -0,0 +1,263 @@
+use fabricator_compiler::{
+    ast,
+    lexer::Lexer,
+    macros::MacroSetBuilder,
+    parser::ParseSettings,
+    string_interner::StdStringInterner,
+    tokens::{Token, TokenKind},
+};
+use fabricator_vm::Span;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Macro,
+    Enum,
+    GlobalVar,
+}
+
+/// An item defined at the top-level of a source file.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// A short, single-line description of the item, such as a function signature.
+    pub detail: String,
+    pub span: Span,
+    /// Enum variants, empty for every other kind of symbol.
+    pub members: Vec<(String, Span)>,
+}
+
+/// Extract every top-level macro, enum, function, and `globalvar` declared in some source.
+///
+/// This runs the compiler front-end on a single file in isolation, so macros defined in other files
+/// are not expanded. Declarations which contain syntax errors are skipped, but every other symbol
+/// is still returned.
+pub fn file_symbols(code: &str, parse_settings: ParseSettings) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+
+    let mut tokens = Vec::new();
+    if Lexer::tokenize(StdStringInterner, code, &mut tokens).is_err() {
+        return symbols;
+    }
+
+    let mut macros = MacroSetBuilder::new();
+    let macro_res = macros.extract(&mut tokens);
+
+    for macro_ in macros.iter() {
+        let detail = match code.get(macro_.span.start()..macro_.span.end()) {
+            Some(text) => text.trim().to_owned(),
+            None => format!("#macro {}", macro_.name),
+        };
+        symbols.push(Symbol {
+            name: macro_.name.clone(),
+            kind: SymbolKind::Macro,
+            detail,
+            span: macro_.span,
+            members: Vec::new(),
+        });
+    }
+
+    // The token buffer is in an unspecified state after a macro error.
+    if macro_res.is_err() {
+        return symbols;
+    }
+
+    let (block, _) = parse_settings.parse_with_recovery(tokens);
+
+    for stmt in block.statements {
+        match stmt {
+            ast::Statement::Enum(enum_stmt) => {
+                let members = enum_stmt
+                    .variants
+                    .iter()
+                    .map(|(ident, _)| (ident.inner.clone(), ident.span))
+                    .collect::<Vec<_>>();
+                let detail = format!(
+                    "enum {} {{ {} }}",
+                    enum_stmt.name.inner,
+                    members
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+                symbols.push(Symbol {
+                    name: enum_stmt.name.inner,
+                    kind: SymbolKind::Enum,
+                    detail,
+                    span: enum_stmt.span,
+                    members,
+                });
+            }
+            ast::Statement::Function(func_stmt) => {
+                let mut parameters = func_stmt
+                    .parameters
+                    .fixed
+                    .iter()
+                    .map(
+                        |param| match code.get(param.span.start()..param.span.end()) {
+                            Some(text) => text.trim().to_owned(),
+                            None => param.name.inner.clone(),
+                        },
+                    )
+                    .collect::<Vec<_>>();
+                if func_stmt.parameters.var_args.is_some() {
+                    parameters.push("...".to_owned());
+                }
+                let detail = format!(
+                    "function {}({}){}",
+                    func_stmt.name.inner,
+                    parameters.join(", "),
+                    if func_stmt.is_constructor {
+                        " constructor"
+                    } else {
+                        ""
+                    },
+                );
+                symbols.push(Symbol {
+                    name: func_stmt.name.inner,
+                    kind: SymbolKind::Function,
+                    detail,
+                    span: func_stmt.span,
+                    members: Vec::new(),
+                });
+            }
+            ast::Statement::GlobalVar(ident) => {
+                symbols.push(Symbol {
+                    detail: format!("globalvar {}", ident.inner),
+                    name: ident.inner,
+                    kind: SymbolKind::GlobalVar,
+                    span: ident.span,
+                    members: Vec::new(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    symbols
+}
+
+/// An identifier in source code, along with the identifier before it if it is the right hand side
+/// of a field access like `Enum.Variant`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reference {
+    /// The identifier, or `None` if the position is immediately after a `.`.
+    pub name: Option<String>,
+    pub span: Span,
+    pub qualifier: Option<String>,
+}
+
+/// Find the identifier which contains or ends at the given byte offset.
+///
+/// Lexing stops at the first error, so this works with incomplete source as long as there are no
+/// lexing errors before `offset`.
+pub fn reference_at(code: &str, offset: usize) -> Option<Reference> {
+    let mut lexer = Lexer::new(StdStringInterner, code);
+    let mut tokens: Vec<Token<String>> = Vec::new();
+    while let Ok(token) = lexer.read_token() {
+        if matches!(token.kind, TokenKind::EndOfStream) || token.span.start() > offset {
+            break;
+        }
+        tokens.push(token);
+    }
+
+    let qualifier = |index: usize| match index.checked_sub(1).map(|i| &tokens[i]) {
+        Some(Token {
+            kind: TokenKind::Identifier(qualifier),
+            ..
+        }) => Some(qualifier.clone()),
+        _ => None,
+    };
+
+    let last = tokens.len().checked_sub(1)?;
+    match &tokens[last] {
+        Token {
+            kind: TokenKind::Identifier(name),
+            span,
+        } if span.end() >= offset => {
+            let dot = last
+                .checked_sub(1)
+                .filter(|&i| matches!(tokens[i].kind, TokenKind::Dot));
+            Some(Reference {
+                name: Some(name.clone()),
+                span: *span,
+                qualifier: dot.and_then(qualifier),
+            })
+        }
+        Token {
+            kind: TokenKind::Dot,
+            span,
+        } if span.end() == offset => Some(Reference {
+            name: None,
+            span: Span::empty(offset),
+            qualifier: qualifier(last),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_symbols() {
+        let code = concat!(
+            "#macro SPEED 4\n",
+            "enum Color { Red, Green = 3 }\n",
+            "globalvar score;\n",
+            "function move(dx, dy = SPEED) {\n",
+            "    x += dx;\n",
+            "}\n",
+        );
+
+        let symbols = file_symbols(code, ParseSettings::compat());
+        let summary = symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind, s.detail.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("SPEED", SymbolKind::Macro, "#macro SPEED 4"),
+                ("Color", SymbolKind::Enum, "enum Color { Red, Green }"),
+                ("score", SymbolKind::GlobalVar, "globalvar score"),
+                (
+                    "move",
+                    SymbolKind::Function,
+                    "function move(dx, dy = SPEED)"
+                ),
+            ]
+        );
+        assert_eq!(
+            symbols[1]
+                .members
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            ["Red", "Green"]
+        );
+    }
+
+    #[test]
+    fn test_reference_at() {
+        let code = "var c = Color.Red;\nColor.";
+
+        let reference = reference_at(code, 16).unwrap();
+        assert_eq!(reference.name.as_deref(), Some("Red"));
+        assert_eq!(reference.qualifier.as_deref(), Some("Color"));
+        assert_eq!(reference.span, Span::new(14, 17));
+
+        let reference = reference_at(code, 10).unwrap();
+        assert_eq!(reference.name.as_deref(), Some("Color"));
+        assert_eq!(reference.qualifier, None);
+
+        let reference = reference_at(code, code.len()).unwrap();
+        assert_eq!(reference.name, None);
+        assert_eq!(reference.qualifier.as_deref(), Some("Color"));
+
+        assert_eq!(reference_at(code, 7), None);
+    }
+}
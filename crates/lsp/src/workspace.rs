@@ -0,0 +1,461 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use fabricator::{
+    api::magic::MagicExt as _,
+    game::{create_configuration, runner_magic},
+    project::{Project, ScriptMode},
+};
+use fabricator_compiler::{
+    CompileError, CompileSettings, Compiler, ImportItems, compiler::CompileWarning,
+};
+use fabricator_stdlib::StdlibContext as _;
+use fabricator_vm as vm;
+use gc_arena::Gc;
+
+use crate::{
+    document::Document,
+    symbols::{Reference, Symbol, SymbolKind, file_symbols},
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BuiltinKind {
+    Function,
+    Constant,
+    Variable,
+}
+
+/// A magic variable provided by the runner or the stdlib.
+#[derive(Debug, Clone)]
+pub struct Builtin {
+    pub name: String,
+    pub kind: BuiltinKind,
+}
+
+/// The item that some identifier refers to.
+#[derive(Debug, Clone)]
+pub enum Definition {
+    Symbol {
+        path: PathBuf,
+        symbol: Symbol,
+        /// The enum variant referred to, if the reference is of the form `Enum.Variant`.
+        member: Option<(String, vm::Span)>,
+    },
+    Builtin(Builtin),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    /// The span of the error or warning within the file, if it is known.
+    pub span: Option<vm::Span>,
+    pub line_number: vm::LineNumber,
+    pub message: String,
+    pub severity: Severity,
+}
+
+/// Where a script file is compiled.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ScriptUnit {
+    /// Part of the single compilation unit for all global scripts.
+    Global,
+    /// An object event, compiled as its own unit after the global scripts.
+    ObjectEvent,
+}
+
+struct ScriptFile {
+    path: PathBuf,
+    mode: ScriptMode,
+    unit: ScriptUnit,
+}
+
+/// A loaded project along with the current contents of every script in it.
+///
+/// Scripts which are open in the editor are read from their in-memory document, every other script
+/// is read from disk.
+pub struct Workspace {
+    config_name: String,
+    scripts: Vec<ScriptFile>,
+    script_indexes: HashMap<PathBuf, usize>,
+    interpreter: vm::Interpreter,
+    magic: vm::StashedMagicSet,
+    builtins: Vec<Builtin>,
+    documents: HashMap<PathBuf, Document>,
+    symbols: HashMap<PathBuf, Vec<Symbol>>,
+}
+
+impl Workspace {
+    /// Load a project and all of its assets.
+    ///
+    /// If the runner configuration cannot be created from the project assets, then only the stdlib
+    /// is made available to scripts, and the error is returned alongside the workspace.
+    pub fn load(project_file: &Path, config_name: &str) -> Result<(Self, Option<Error>), Error> {
+        let project = Project::load(project_file)?;
+        let mut interpreter = vm::Interpreter::new();
+
+        let (config, config_error) = match create_configuration(&mut interpreter, &project) {
+            Ok(config) => (Some(config), None),
+            Err(err) => {
+                log::error!("could not create runner configuration: {err:?}");
+                (None, Some(err))
+            }
+        };
+
+        let (magic, builtins) = interpreter.enter(|ctx| -> Result<_, Error> {
+            let mut magic = match &config {
+                Some(config) => runner_magic(ctx, config)?,
+                None => ctx.stdlib().as_ref().clone(),
+            };
+
+            let mut builtins = Vec::new();
+            for (name, index) in magic.names() {
+                let value = magic.get(index).unwrap();
+                let kind = match value.get(ctx) {
+                    Ok(vm::Value::Closure(_) | vm::Value::Callback(_)) => BuiltinKind::Function,
+                    _ if value.read_only() => BuiltinKind::Constant,
+                    _ => BuiltinKind::Variable,
+                };
+                builtins.push(Builtin {
+                    name: name.as_str().to_owned(),
+                    kind,
+                });
+            }
+
+            // Extensions are never loaded, since that would require loading native libraries, but
+            // their functions must still be known to the compiler.
+            for extension in project.extensions.values() {
+                for file in &extension.files {
+                    for function in &file.functions {
+                        magic.add_constant(
+                            ctx,
+                            ctx.intern(&function.name),
+                            vm::Value::Undefined,
+                        )?;
+                        builtins.push(Builtin {
+                            name: function.name.clone(),
+                            kind: BuiltinKind::Function,
+                        });
+                    }
+                }
+            }
+
+            builtins.sort_by(|a, b| a.name.cmp(&b.name));
+
+            Ok((ctx.stash(Gc::new(&ctx, magic)), builtins))
+        })?;
+
+        let mut scripts = Vec::new();
+
+        let mut global_scripts = project.scripts.values().collect::<Vec<_>>();
+        // Compile global scripts in the same order as the runner does.
+        global_scripts.sort_by_key(|s| &s.name);
+        for script in global_scripts {
+            scripts.push(ScriptFile {
+                path: normalize_path(&script.path),
+                mode: script.mode,
+                unit: ScriptUnit::Global,
+            });
+        }
+
+        for object in project.objects.values() {
+            for script in object.event_scripts.values() {
+                scripts.push(ScriptFile {
+                    path: normalize_path(&script.path),
+                    mode: script.mode,
+                    unit: ScriptUnit::ObjectEvent,
+                });
+            }
+        }
+
+        let script_indexes = scripts
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (s.path.clone(), i))
+            .collect();
+
+        Ok((
+            Self {
+                config_name: config_name.to_owned(),
+                scripts,
+                script_indexes,
+                interpreter,
+                magic,
+                builtins,
+                documents: HashMap::new(),
+                symbols: HashMap::new(),
+            },
+            config_error,
+        ))
+    }
+
+    pub fn builtins(&self) -> &[Builtin] {
+        &self.builtins
+    }
+
+    pub fn find_builtin(&self, name: &str) -> Option<&Builtin> {
+        self.builtins
+            .binary_search_by(|b| b.name.as_str().cmp(name))
+            .ok()
+            .map(|i| &self.builtins[i])
+    }
+
+    /// Returns which compilation unit a file belongs to, or `None` if it is not a project script.
+    pub fn script_unit(&self, path: &Path) -> Option<ScriptUnit> {
+        self.script_indexes.get(path).map(|&i| self.scripts[i].unit)
+    }
+
+    /// Set the in-memory contents of a file that is open in the editor.
+    pub fn open(&mut self, path: PathBuf, text: String) {
+        self.symbols.remove(&path);
+        self.documents.insert(path, Document::new(text));
+    }
+
+    /// Forget the in-memory contents of a file, it will be read from disk from now on.
+    pub fn close(&mut self, path: &Path) {
+        self.symbols.remove(path);
+        self.documents.remove(path);
+    }
+
+    /// Invalidate anything cached for a file that is not open, if it has changed on disk.
+    pub fn file_changed(&mut self, path: &Path) {
+        if !self.documents.contains_key(path) {
+            self.symbols.remove(path);
+        }
+    }
+
+    /// Returns the current contents of a file, either from the editor or from disk.
+    pub fn source(&self, path: &Path) -> Option<String> {
+        match self.documents.get(path) {
+            Some(doc) => Some(doc.text().to_owned()),
+            None => fs::read_to_string(path).ok(),
+        }
+    }
+
+    pub fn document(&self, path: &Path) -> Option<Document> {
+        self.source(path).map(Document::new)
+    }
+
+    /// Returns the top-level symbols defined in a file.
+    pub fn file_symbols(&mut self, path: &Path) -> &[Symbol] {
+        if !self.symbols.contains_key(path) {
+            let parse_settings = match self.script_indexes.get(path) {
+                Some(&i) => self.scripts[i].mode.compile_settings().parse,
+                None => CompileSettings::from_path(path).parse,
+            };
+            let symbols = self
+                .source(path)
+                .map(|source| file_symbols(&source, parse_settings))
+                .unwrap_or_default();
+            self.symbols.insert(path.to_owned(), symbols);
+        }
+        &self.symbols[path]
+    }
+
+    /// Returns every symbol shared across the project, those defined in global scripts, along with
+    /// the path of the file which defines them.
+    pub fn project_symbols(&mut self) -> Vec<(PathBuf, Symbol)> {
+        let paths = self
+            .scripts
+            .iter()
+            .filter(|s| s.unit == ScriptUnit::Global)
+            .map(|s| s.path.clone())
+            .collect::<Vec<_>>();
+
+        let mut symbols = Vec::new();
+        for path in paths {
+            for symbol in self.file_symbols(&path) {
+                symbols.push((path.clone(), symbol.clone()));
+            }
+        }
+        symbols
+    }
+
+    /// Find what an identifier in the given file refers to.
+    ///
+    /// Symbols in the same file take priority over symbols exported from global scripts, which
+    /// take priority over builtins. There may be more than one definition when a macro is defined
+    /// for multiple configurations.
+    pub fn find_definitions(&mut self, path: &Path, reference: &Reference) -> Vec<Definition> {
+        let local = self
+            .file_symbols(path)
+            .iter()
+            .map(|s| (path.to_owned(), s.clone()))
+            .collect::<Vec<_>>();
+        let project = self
+            .project_symbols()
+            .into_iter()
+            .filter(|(p, _)| p != path)
+            .collect::<Vec<_>>();
+
+        let Some(name) = &reference.name else {
+            return Vec::new();
+        };
+
+        if let Some(qualifier) = &reference.qualifier {
+            // Only enum variants can be resolved through a qualifier, anything else is a field
+            // access which can't be known without running the script.
+            return local
+                .into_iter()
+                .chain(project)
+                .filter(|(_, s)| s.kind == SymbolKind::Enum && &s.name == qualifier)
+                .find_map(|(path, symbol)| {
+                    let member = symbol.members.iter().find(|(m, _)| m == name)?.clone();
+                    Some(Definition::Symbol {
+                        path,
+                        symbol,
+                        member: Some(member),
+                    })
+                })
+                .into_iter()
+                .collect();
+        }
+
+        for symbols in [local, project] {
+            let found = symbols
+                .into_iter()
+                .filter(|(_, s)| &s.name == name)
+                .map(|(path, symbol)| Definition::Symbol {
+                    path,
+                    symbol,
+                    member: None,
+                })
+                .collect::<Vec<_>>();
+            if !found.is_empty() {
+                return found;
+            }
+        }
+
+        self.find_builtin(name)
+            .map(|b| Definition::Builtin(b.clone()))
+            .into_iter()
+            .collect()
+    }
+
+    /// Compile every script in the project the same way that the runner does, and return the
+    /// compile errors and warnings for each file.
+    pub fn check(&mut self) -> HashMap<PathBuf, Vec<Diagnostic>> {
+        let mut diagnostics = HashMap::<PathBuf, Vec<Diagnostic>>::new();
+        let mut report = |errors: Vec<CompileError>, warnings: Vec<CompileWarning>| {
+            let errors = errors.into_iter().map(|err| {
+                (
+                    err.chunk_name,
+                    err.span,
+                    err.line_number,
+                    err.kind.to_string(),
+                    Severity::Error,
+                )
+            });
+            let warnings = warnings.into_iter().map(|warning| {
+                (
+                    warning.chunk_name,
+                    warning.span,
+                    warning.line_number,
+                    warning.lint.to_string(),
+                    Severity::Warning,
+                )
+            });
+
+            for (chunk_name, span, line_number, message, severity) in errors.chain(warnings) {
+                let path = normalize_path(Path::new(chunk_name.as_str()));
+                diagnostics.entry(path).or_default().push(Diagnostic {
+                    span: (!span.is_null() && !span.is_everywhere()).then_some(span),
+                    line_number,
+                    message,
+                    severity,
+                });
+            }
+        };
+
+        let sources = self
+            .scripts
+            .iter()
+            .map(|s| self.source(&s.path))
+            .collect::<Vec<_>>();
+
+        let Self {
+            config_name,
+            scripts,
+            interpreter,
+            magic,
+            ..
+        } = self;
+
+        interpreter.enter(|ctx| {
+            let base_imports = ImportItems::with_magic(&ctx, ctx.fetch(&*magic));
+            let mut global_compiler = Compiler::new(ctx, config_name.as_str(), base_imports);
+
+            for (script, source) in scripts.iter().zip(&sources) {
+                if script.unit != ScriptUnit::Global {
+                    continue;
+                }
+                let Some(source) = source else {
+                    continue;
+                };
+                global_compiler.add_chunk(
+                    check_settings(script.mode),
+                    vm::SharedStr::new(&script.path.to_string_lossy()),
+                    source,
+                );
+            }
+
+            // If the global scripts fail to compile, then object events are checked without any of
+            // the global exports. References to exports will then compile as field accesses on
+            // `self`, so this does not produce any extra errors.
+            let imports = match global_compiler.compile() {
+                Ok(output) => {
+                    report(Vec::new(), output.warnings);
+                    output.exported_imports
+                }
+                Err(errors) => {
+                    report(errors.0, Vec::new());
+                    base_imports
+                }
+            };
+
+            for (script, source) in scripts.iter().zip(&sources) {
+                if script.unit != ScriptUnit::ObjectEvent {
+                    continue;
+                }
+                let Some(source) = source else {
+                    continue;
+                };
+                let mut event_compiler = Compiler::new(ctx, config_name.as_str(), imports);
+                event_compiler.add_chunk(
+                    check_settings(script.mode).export_top_level_functions(false),
+                    vm::SharedStr::new(&script.path.to_string_lossy()),
+                    source,
+                );
+                match event_compiler.compile() {
+                    Ok(output) => report(Vec::new(), output.warnings),
+                    Err(errors) => report(errors.0, Vec::new()),
+                }
+            }
+        });
+
+        interpreter.gc_collect_debt();
+
+        diagnostics
+    }
+}
+
+/// Normalize a path for use as a workspace key, so that paths from the project file and paths from
+/// the client can be compared.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+// Settings for checking scripts for errors, there is no need to optimize or verify anything.
+fn check_settings(mode: ScriptMode) -> CompileSettings {
+    mode.compile_settings()
+        .set_optimization_passes(0)
+        .verify_ir(false)
+}
@@ -0,0 +1,300 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    mem,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use fabricator_vm as vm;
+use gc_arena::{Collect, Gc};
+
+use crate::profile::{FunctionInfo, FunctionStats, Profile, TraceEvent};
+
+/// The default maximum number of trace events kept by a [`Profiler`].
+const DEFAULT_EVENT_LIMIT: usize = 1 << 20;
+
+/// How many instructions the VM may execute between calls to [`ProfileHook::on_step`].
+///
+/// The VM always reports every executed instruction before any call or return, so this only limits
+/// how often long running functions are interrupted.
+const STEP_INSTRUCTIONS: u32 = 4096;
+
+// Functions are identified by their prototype or callback function pointer, so that every closure
+// created from the same prototype and every rebound copy of a callback count as the same function.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum FunctionKey {
+    Prototype(usize),
+    Callback(usize),
+}
+
+impl FunctionKey {
+    fn prototype(prototype: Gc<'_, vm::Prototype<'_>>) -> Self {
+        Self::Prototype(Gc::as_ptr(prototype) as *const () as usize)
+    }
+
+    fn callback(callback: vm::Callback<'_>) -> Self {
+        Self::Callback(Gc::as_ptr(callback.function()) as *const () as usize)
+    }
+}
+
+struct ActiveCall {
+    function: usize,
+    start: Instant,
+    instructions: u64,
+    child_instructions: u64,
+    child_time: Duration,
+}
+
+struct Recorder {
+    start: Instant,
+    event_limit: usize,
+    callback_names: HashMap<FunctionKey, String>,
+    function_indexes: HashMap<FunctionKey, usize>,
+    functions: Vec<(FunctionInfo, FunctionStats)>,
+    // How many calls of each function are currently active, used to avoid counting the inclusive
+    // cost of recursive calls more than once.
+    active_counts: Vec<u32>,
+    active: Vec<ActiveCall>,
+    stack: Vec<usize>,
+    stacks: HashMap<Vec<usize>, u64>,
+    events: Vec<TraceEvent>,
+    dropped_events: u64,
+}
+
+impl Recorder {
+    fn new(event_limit: usize) -> Self {
+        Self {
+            start: Instant::now(),
+            event_limit,
+            callback_names: HashMap::new(),
+            function_indexes: HashMap::new(),
+            functions: Vec::new(),
+            active_counts: Vec::new(),
+            active: Vec::new(),
+            stack: Vec::new(),
+            stacks: HashMap::new(),
+            events: Vec::new(),
+            dropped_events: 0,
+        }
+    }
+
+    fn function_index(&mut self, frame: vm::StackFrame<'_>) -> usize {
+        let key = match frame {
+            vm::StackFrame::Closure(frame) => FunctionKey::prototype(frame.closure.prototype()),
+            vm::StackFrame::Callback(callback) => FunctionKey::callback(callback),
+        };
+
+        if let Some(&index) = self.function_indexes.get(&key) {
+            return index;
+        }
+
+        let info = match frame {
+            vm::StackFrame::Closure(frame) => {
+                let prototype = frame.closure.prototype();
+                let chunk = prototype.chunk();
+                let reference = prototype.reference();
+                let name = match reference {
+                    vm::FunctionRef::Named(name, _) => name.to_string(),
+                    vm::FunctionRef::Expression(_) => "<anonymous>".to_owned(),
+                    vm::FunctionRef::Chunk => "<chunk>".to_owned(),
+                };
+                FunctionInfo {
+                    name,
+                    chunk: Some(chunk.name().to_string()),
+                    line: Some(chunk.line_number(reference.span().start()).0 + 1),
+                }
+            }
+            vm::StackFrame::Callback(callback) => {
+                let name = match self.callback_names.get(&key) {
+                    Some(name) => name.clone(),
+                    None => format!("<callback {:p}>", Gc::as_ptr(callback.function())),
+                };
+                FunctionInfo {
+                    name,
+                    chunk: None,
+                    line: None,
+                }
+            }
+        };
+
+        let index = self.functions.len();
+        self.functions.push((info, FunctionStats::default()));
+        self.active_counts.push(0);
+        self.function_indexes.insert(key, index);
+        index
+    }
+
+    fn call(&mut self, frame: vm::StackFrame<'_>) {
+        let function = self.function_index(frame);
+        self.functions[function].1.calls += 1;
+        self.active_counts[function] += 1;
+        self.stack.push(function);
+        self.active.push(ActiveCall {
+            function,
+            start: Instant::now(),
+            instructions: 0,
+            child_instructions: 0,
+            child_time: Duration::ZERO,
+        });
+    }
+
+    fn return_(&mut self) {
+        // The hook may have been installed while there were already frames on the stack, returns
+        // from those frames are ignored.
+        let Some(call) = self.active.pop() else {
+            return;
+        };
+        self.stack.pop();
+
+        let end = Instant::now();
+        let time = end - call.start;
+        let instructions = call.instructions + call.child_instructions;
+
+        self.active_counts[call.function] -= 1;
+        let stats = &mut self.functions[call.function].1;
+        stats.exclusive_instructions += call.instructions;
+        stats.exclusive_time += time.saturating_sub(call.child_time);
+        if self.active_counts[call.function] == 0 {
+            stats.inclusive_instructions += instructions;
+            stats.inclusive_time += time;
+        }
+
+        if let Some(parent) = self.active.last_mut() {
+            parent.child_instructions += instructions;
+            parent.child_time += time;
+        }
+
+        if self.events.len() < self.event_limit {
+            self.events.push(TraceEvent {
+                function: call.function,
+                start: call.start - self.start,
+                duration: time,
+                depth: self.active.len(),
+            });
+        } else {
+            self.dropped_events += 1;
+        }
+    }
+
+    fn step(&mut self, instruction_count: u32) {
+        let Some(call) = self.active.last_mut() else {
+            return;
+        };
+        let instruction_count = u64::from(instruction_count);
+        call.instructions += instruction_count;
+
+        if let Some(count) = self.stacks.get_mut(self.stack.as_slice()) {
+            *count += instruction_count;
+        } else {
+            self.stacks.insert(self.stack.clone(), instruction_count);
+        }
+    }
+}
+
+/// Records calls, returns, and executed instructions from every thread which has its
+/// [`ProfileHook`] installed.
+///
+/// `Profiler` is a cheap handle to shared state, cloning it does not create a new profiler.
+#[derive(Clone)]
+pub struct Profiler(Rc<RefCell<Recorder>>);
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::with_event_limit(DEFAULT_EVENT_LIMIT)
+    }
+
+    /// Create a profiler which keeps at most `event_limit` trace events.
+    ///
+    /// Aggregate statistics are always recorded for every call, only the individual calls past the
+    /// limit are dropped.
+    pub fn with_event_limit(event_limit: usize) -> Self {
+        Self(Rc::new(RefCell::new(Recorder::new(event_limit))))
+    }
+
+    /// Create a hook to install on a `vm::Thread`, which records into this profiler.
+    pub fn hook(&self) -> ProfileHook {
+        ProfileHook(self.0.clone())
+    }
+
+    /// Name callbacks after every read-only magic variable in `magic` which holds one.
+    ///
+    /// Callbacks have no names of their own, so without this they are identified only by address.
+    /// Callbacks which have already been called keep whatever name they were first given.
+    pub fn name_callbacks<'gc>(&self, ctx: vm::Context<'gc>, magic: &vm::MagicSet<'gc>) {
+        let mut recorder = self.0.borrow_mut();
+        for (name, index) in magic.names() {
+            let Ok(var) = magic.get(index) else {
+                continue;
+            };
+            if !var.read_only() {
+                continue;
+            }
+            if let Ok(vm::Value::Callback(callback)) = var.get(ctx) {
+                recorder
+                    .callback_names
+                    .entry(FunctionKey::callback(callback))
+                    .or_insert_with(|| name.to_string());
+            }
+        }
+    }
+
+    /// Everything recorded since the profiler was created or last reset.
+    ///
+    /// Calls which have not yet returned are not included.
+    pub fn profile(&self) -> Profile {
+        let recorder = self.0.borrow();
+        Profile {
+            functions: recorder.functions.clone(),
+            stacks: recorder.stacks.clone(),
+            events: recorder.events.clone(),
+            dropped_events: recorder.dropped_events,
+        }
+    }
+
+    /// Clear all recorded statistics and events, keeping any callback names.
+    ///
+    /// This should not be called while a thread with this profiler's hook is running.
+    pub fn reset(&self) {
+        let mut recorder = self.0.borrow_mut();
+        let callback_names = mem::take(&mut recorder.callback_names);
+        *recorder = Recorder::new(recorder.event_limit);
+        recorder.callback_names = callback_names;
+    }
+}
+
+/// A `vm::Hook` which records into a [`Profiler`].
+#[derive(Collect)]
+#[collect(require_static)]
+pub struct ProfileHook(Rc<RefCell<Recorder>>);
+
+impl<'gc> vm::Hook<'gc> for ProfileHook {
+    fn on_call(
+        &mut self,
+        _ctx: vm::Context<'gc>,
+        frames: vm::FrameStack<'gc, '_>,
+    ) -> Result<(), vm::RuntimeError> {
+        self.0.borrow_mut().call(frames.frame(0));
+        Ok(())
+    }
+
+    fn on_return(&mut self, _ctx: vm::Context<'gc>, _frames: vm::FrameStack<'gc, '_>) {
+        self.0.borrow_mut().return_();
+    }
+
+    fn on_step(
+        &mut self,
+        _ctx: vm::Context<'gc>,
+        _frames: vm::FrameStack<'gc, '_>,
+        instruction_count: u32,
+    ) -> Result<u32, vm::RuntimeError> {
+        self.0.borrow_mut().step(instruction_count);
+        Ok(STEP_INSTRUCTIONS)
+    }
+}
@@ -0,0 +1,14 @@
+//! An instrumenting profiler for FML scripts.
+//!
+//! [`Profiler::hook`] provides a `vm::Hook` which records every call and return along with the
+//! number of VM instructions executed in every call stack. The resulting [`Profile`] has per
+//! function call counts, instruction counts, and wall time, and can be exported as Chrome
+//! trace-event JSON or as folded stacks for flamegraph tools.
+
+mod hook;
+mod profile;
+
+pub use self::{
+    hook::{ProfileHook, Profiler},
+    profile::{FunctionInfo, FunctionStats, Profile, TraceEvent},
+};
@@ -0,0 +1,145 @@
+use std::{collections::HashMap, fmt, io, time::Duration};
+
+use serde_json::json;
+
+/// Where a profiled function came from.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FunctionInfo {
+    pub name: String,
+    /// The chunk a script function was declared in, `None` for callbacks.
+    pub chunk: Option<String>,
+    /// The one-based line a script function was declared on.
+    pub line: Option<usize>,
+}
+
+impl fmt::Display for FunctionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.chunk, self.line) {
+            (Some(chunk), Some(line)) => write!(f, "{} ({chunk}:{line})", self.name),
+            (Some(chunk), None) => write!(f, "{} ({chunk})", self.name),
+            _ => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct FunctionStats {
+    pub calls: u64,
+    /// VM instructions executed by this function and everything it called.
+    ///
+    /// Recursive calls are only counted once, by the outermost call.
+    pub inclusive_instructions: u64,
+    /// VM instructions executed by this function alone.
+    pub exclusive_instructions: u64,
+    /// Time spent in this function and everything it called, recursive calls are only counted
+    /// once.
+    pub inclusive_time: Duration,
+    pub exclusive_time: Duration,
+}
+
+/// A single completed call.
+#[derive(Debug, Copy, Clone)]
+pub struct TraceEvent {
+    /// An index into [`Profile::functions`].
+    pub function: usize,
+    /// When the call started, relative to the start of the profile.
+    pub start: Duration,
+    pub duration: Duration,
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub functions: Vec<(FunctionInfo, FunctionStats)>,
+    /// The number of VM instructions executed with each distinct call stack.
+    ///
+    /// Stacks are lists of indexes into `functions`, from the outermost call to the innermost.
+    pub stacks: HashMap<Vec<usize>, u64>,
+    /// Every completed call in the order they returned, up to the event limit of the
+    /// [`Profiler`](crate::Profiler).
+    pub events: Vec<TraceEvent>,
+    /// The number of calls which were not recorded in `events` because the limit was reached.
+    pub dropped_events: u64,
+}
+
+impl Profile {
+    /// Write every trace event in the Chrome trace-event JSON format, which can be loaded by
+    /// `chrome://tracing` or Perfetto.
+    pub fn write_chrome_trace(&self, mut w: impl io::Write) -> io::Result<()> {
+        let events = self
+            .events
+            .iter()
+            .map(|event| {
+                let (info, _) = &self.functions[event.function];
+                let mut args = serde_json::Map::new();
+                if let Some(chunk) = &info.chunk {
+                    args.insert("chunk".to_owned(), chunk.as_str().into());
+                }
+                if let Some(line) = info.line {
+                    args.insert("line".to_owned(), line.into());
+                }
+                json!({
+                    "name": info.name,
+                    "cat": if info.chunk.is_some() { "script" } else { "callback" },
+                    "ph": "X",
+                    "ts": event.start.as_secs_f64() * 1e6,
+                    "dur": event.duration.as_secs_f64() * 1e6,
+                    "pid": 1,
+                    "tid": 1,
+                    "args": args,
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_writer(&mut w, &json!({ "traceEvents": events }))?;
+        Ok(())
+    }
+
+    /// Write the instruction count of every call stack in the folded stack format used by
+    /// `flamegraph.pl` and `inferno`, one `outer;inner count` line per stack.
+    pub fn write_folded(&self, mut w: impl io::Write) -> io::Result<()> {
+        let mut lines = self
+            .stacks
+            .iter()
+            .map(|(stack, &count)| {
+                let frames = stack
+                    .iter()
+                    .map(|&f| self.functions[f].0.to_string().replace(';', ":"))
+                    .collect::<Vec<_>>();
+                (frames.join(";"), count)
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        for (stack, count) in lines {
+            writeln!(w, "{stack} {count}")?;
+        }
+        Ok(())
+    }
+
+    /// Write a table of every function sorted by exclusive instruction count.
+    pub fn write_summary(&self, mut w: impl io::Write) -> io::Result<()> {
+        let mut functions = self.functions.iter().collect::<Vec<_>>();
+        functions.sort_by(|(_, a), (_, b)| {
+            b.exclusive_instructions
+                .cmp(&a.exclusive_instructions)
+                .then(b.exclusive_time.cmp(&a.exclusive_time))
+        });
+
+        writeln!(
+            w,
+            "{:>10} {:>14} {:>14} {:>12} {:>12}  function",
+            "calls", "excl instrs", "incl instrs", "excl ms", "incl ms"
+        )?;
+        for (info, stats) in functions {
+            writeln!(
+                w,
+                "{:>10} {:>14} {:>14} {:>12.3} {:>12.3}  {info}",
+                stats.calls,
+                stats.exclusive_instructions,
+                stats.inclusive_instructions,
+                stats.exclusive_time.as_secs_f64() * 1e3,
+                stats.inclusive_time.as_secs_f64() * 1e3,
+            )?;
+        }
+        Ok(())
+    }
+}
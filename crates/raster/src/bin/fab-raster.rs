@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use clap::Parser;
+use fabricator as fab;
+use fabricator_raster::{Rasterizer, Sampling};
+
+#[derive(Parser)]
+struct Cli {
+    project_file: PathBuf,
+    /// Where to write the rendered PNG image.
+    output: PathBuf,
+    #[arg(long, default_value = "default")]
+    config: String,
+    /// The number of game ticks to run before rendering.
+    #[arg(long, default_value_t = 1)]
+    ticks: u32,
+    /// Use bilinear filtering when sampling textures, rather than nearest neighbor.
+    #[arg(long)]
+    bilinear: bool,
+    /// Also write the audio mixed during every tick to this WAV file.
+    #[arg(long)]
+    audio: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let project = fab::Project::load(&cli.project_file)?;
+    let mut game = fab::Game::new(project, &cli.config)?;
+
+    let sampling = if cli.bilinear {
+        Sampling::Bilinear
+    } else {
+        Sampling::Nearest
+    };
+    let mut rasterizer = Rasterizer::for_game(&game, sampling)?;
+
+    let mut input = fab::InputState::default();
+    let mut render = fab::Render::default();
+    let mut audio = Vec::new();
+    for _ in 0..cli.ticks {
+        game.tick(&mut input, &mut render)?;
+        rasterizer.run_surface_passes(&mut render);
+        rasterizer.report_surfaces(&mut game);
+        audio.extend(game.take_audio());
+        input.end_tick();
+    }
+
+    rasterizer.render(&render).save(&cli.output)?;
+    log::info!("wrote {:?}", cli.output);
+
+    if let Some(audio_output) = &cli.audio {
+        let mut writer = hound::WavWriter::create(
+            audio_output,
+            hound::WavSpec {
+                channels: 2,
+                sample_rate: fab::SAMPLE_RATE,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            },
+        )?;
+        for sample in audio {
+            writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+        }
+        writer.finalize()?;
+        log::info!("wrote {audio_output:?}");
+    }
+
+    Ok(())
+}
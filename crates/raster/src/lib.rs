@@ -0,0 +1,852 @@
+//! A CPU rasterizer for the [`fab::Render`] output of a game tick.
+//!
+//! This produces the same picture as the `desktop` crate without needing a GPU, which makes it
+//! useful for headless rendering and for golden image tests.
+
+use std::{array, mem, ops::Range, path::Path};
+
+use fabricator as fab;
+use fabricator_math::{Affine2, Box2, Vec2, Vec3};
+use fabricator_util::typed_id_map::SecondaryMap;
+use image::{Rgba, RgbaImage};
+use rayon::iter::{IntoParallelIterator as _, ParallelIterator as _};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Sampling {
+    #[default]
+    Nearest,
+    Bilinear,
+}
+
+struct TextureEntry {
+    page_id: fab::TexturePageId,
+    // The position of the cropped texture within its page.
+    page_position: Vec2<u32>,
+    crop_size: Vec2<u32>,
+    crop_offset: Vec2<u32>,
+}
+
+/// A quad textured with part of an image, which is either a texture page or a surface.
+struct TexturedQuad<'a> {
+    image: &'a RgbaImage,
+    // The position of the cropped texture within `image`.
+    image_position: Vec2<u32>,
+    crop_size: Vec2<u32>,
+    crop_offset: Vec2<u32>,
+    transform: Affine2<f32>,
+    region: Box2<f32>,
+    tint: Vec3<f32>,
+    alpha: f32,
+}
+
+pub struct Rasterizer {
+    sampling: Sampling,
+    clear_color: Rgba<u8>,
+    pages: SecondaryMap<fab::TexturePageId, RgbaImage>,
+    textures: SecondaryMap<fab::TextureId, TextureEntry>,
+    surfaces: SecondaryMap<fab::SurfaceId, RgbaImage>,
+}
+
+impl Rasterizer {
+    pub fn new(sampling: Sampling) -> Self {
+        Self {
+            sampling,
+            clear_color: Rgba([0, 0, 0, 255]),
+            pages: SecondaryMap::new(),
+            textures: SecondaryMap::new(),
+            surfaces: SecondaryMap::new(),
+        }
+    }
+
+    /// Create a new `Rasterizer` and load every texture page of the given game.
+    pub fn for_game(game: &fab::Game, sampling: Sampling) -> Result<Self, image::ImageError> {
+        let mut this = Self::new(sampling);
+
+        for (page_id, page) in game.texture_pages() {
+            this.pages
+                .insert(page_id, RgbaImage::new(page.size[0], page.size[1]));
+
+            let loaded_textures = page
+                .textures
+                .iter()
+                .map(|(texture_id, &position)| (texture_id, position, game.texture(texture_id)))
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(|(texture_id, position, texture)| {
+                    let image = load_texture_image(
+                        &texture.image_path,
+                        texture.cropped_offset,
+                        texture.cropped_size,
+                    )?;
+                    Ok((texture_id, position, texture.cropped_offset, image))
+                })
+                .collect::<Result<Vec<_>, image::ImageError>>()?;
+
+            for (texture_id, position, crop_offset, image) in loaded_textures {
+                this.insert_texture(texture_id, page_id, position, crop_offset, &image);
+            }
+        }
+
+        Ok(this)
+    }
+
+    /// The color that every rendered image starts with, defaults to opaque black.
+    pub fn set_clear_color(&mut self, color: Rgba<u8>) {
+        self.clear_color = color;
+    }
+
+    /// Place the cropped image for a texture onto a texture page at the given position.
+    ///
+    /// The page is created if it does not already exist, and grows to fit the texture if it is
+    /// too small.
+    pub fn insert_texture(
+        &mut self,
+        texture_id: fab::TextureId,
+        page_id: fab::TexturePageId,
+        page_position: Vec2<u32>,
+        crop_offset: Vec2<u32>,
+        image: &RgbaImage,
+    ) {
+        let required = page_position + Vec2::new(image.width(), image.height());
+        let page = self
+            .pages
+            .get_or_insert_with(page_id, || RgbaImage::new(required[0], required[1]));
+        if page.width() < required[0] || page.height() < required[1] {
+            let mut grown = RgbaImage::new(
+                page.width().max(required[0]),
+                page.height().max(required[1]),
+            );
+            image::imageops::replace(&mut grown, &*page, 0, 0);
+            *page = grown;
+        }
+        image::imageops::replace(
+            page,
+            image,
+            page_position[0] as i64,
+            page_position[1] as i64,
+        );
+
+        self.textures.insert(
+            texture_id,
+            TextureEntry {
+                page_id,
+                page_position,
+                crop_size: Vec2::new(image.width(), image.height()),
+                crop_offset,
+            },
+        );
+    }
+
+    /// The current contents of a surface, if it has been allocated and not freed.
+    pub fn surface(&self, surface_id: fab::SurfaceId) -> Option<&RgbaImage> {
+        self.surfaces.get(surface_id)
+    }
+
+    /// Execute and remove every pending [`fab::SurfacePass`], in order.
+    ///
+    /// Surfaces keep their contents between ticks, so this must be called after every tick, even
+    /// ticks which are not rendered.
+    pub fn run_surface_passes(&mut self, render: &mut fab::Render) {
+        for pass in render.take_surface_passes() {
+            match pass {
+                fab::SurfacePass::Allocate { surface, size } => {
+                    self.surfaces
+                        .insert(surface, RgbaImage::new(size[0], size[1]));
+                }
+                fab::SurfacePass::Free { surface } => {
+                    self.surfaces.remove(surface);
+                }
+                fab::SurfacePass::Draw { surface, commands } => {
+                    // The target is taken out while drawing, so drawing a surface into itself
+                    // draws nothing.
+                    let Some(mut image) = self.surfaces.remove(surface) else {
+                        continue;
+                    };
+                    let view = fab::RenderView {
+                        transform: Affine2::new(),
+                        port: Box2::with_size(
+                            Vec2::zero(),
+                            Vec2::new(image.width(), image.height()),
+                        ),
+                    };
+                    self.draw_pass(&mut image, view, &commands.iter().collect::<Vec<_>>());
+                    self.surfaces.insert(surface, image);
+                }
+                fab::SurfacePass::Clear { surface, color } => {
+                    if let Some(image) = self.surfaces.get_mut(surface) {
+                        let color = Rgba(color.into_array().map(to_u8));
+                        for pixel in image.pixels_mut() {
+                            *pixel = color;
+                        }
+                    }
+                }
+                fab::SurfacePass::Copy {
+                    surface,
+                    position,
+                    source,
+                    region,
+                } => {
+                    let Some(source) = self.surfaces.get(source) else {
+                        continue;
+                    };
+                    let part = crop(source, region);
+                    if let Some(image) = self.surfaces.get_mut(surface) {
+                        image::imageops::replace(
+                            image,
+                            &part,
+                            position[0] as i64,
+                            position[1] as i64,
+                        );
+                    }
+                }
+                fab::SurfacePass::Save {
+                    surface,
+                    region,
+                    path,
+                } => {
+                    if let Some(image) = self.surfaces.get(surface)
+                        && let Err(err) = crop(image, region).save(&path)
+                    {
+                        log::error!("could not save surface to {path:?}: {err}");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Report the contents of every surface to the game, so that scripts can read them.
+    pub fn report_surfaces(&self, game: &mut fab::Game) {
+        for (surface_id, image) in self.surfaces.iter() {
+            game.set_surface_contents(surface_id, image.clone());
+        }
+    }
+
+    /// Render to a new image the size of the screen.
+    pub fn render(&self, render: &fab::Render) -> RgbaImage {
+        let mut image = RgbaImage::new(render.screen_size[0], render.screen_size[1]);
+        self.render_into(render, &mut image);
+        image
+    }
+
+    /// Clear the given image and render into it.
+    ///
+    /// Every view in [`fab::Render::passes`] is drawn in turn, and within each view commands are
+    /// drawn in the order given by [`fab::Render::sorted_commands`]. GUI commands are drawn last,
+    /// with [`fab::Render::gui_pass`]. Blending happens directly on the stored sRGB values.
+    ///
+    /// Surfaces are drawn with their contents as of the last call to
+    /// [`Rasterizer::run_surface_passes`]. Quads which refer to unknown textures or surfaces are
+    /// skipped.
+    pub fn render_into(&self, render: &fab::Render, image: &mut RgbaImage) {
+        for pixel in image.pixels_mut() {
+            *pixel = self.clear_color;
+        }
+
+        let commands = render.sorted_commands();
+        for view in render.passes() {
+            self.draw_pass(image, view, &commands);
+        }
+        self.draw_pass(image, render.gui_pass(), &render.sorted_gui_commands());
+    }
+
+    fn draw_pass(
+        &self,
+        image: &mut RgbaImage,
+        view: fab::RenderView,
+        commands: &[&fab::DrawCommand],
+    ) {
+        let image_size = Vec2::new(image.width(), image.height());
+        let clip = Box2::new(
+            view.port.min.zip(image_size, u32::min),
+            view.port.max.zip(image_size, u32::min),
+        );
+
+        for &command in commands {
+            match command {
+                fab::DrawCommand::Quad(quad) => {
+                    if let Some(texture) = self.textures.get(quad.texture) {
+                        draw_quad(
+                            image,
+                            clip,
+                            self.sampling,
+                            view.transform,
+                            TexturedQuad {
+                                image: &self.pages[texture.page_id],
+                                image_position: texture.page_position,
+                                crop_size: texture.crop_size,
+                                crop_offset: texture.crop_offset,
+                                transform: quad.transform,
+                                region: quad.region,
+                                tint: quad.tint,
+                                alpha: quad.alpha,
+                            },
+                        );
+                    }
+                }
+                fab::DrawCommand::Surface(quad) => {
+                    if let Some(surface) = self.surfaces.get(quad.surface) {
+                        draw_quad(
+                            image,
+                            clip,
+                            self.sampling,
+                            view.transform,
+                            TexturedQuad {
+                                image: surface,
+                                image_position: Vec2::zero(),
+                                crop_size: Vec2::new(surface.width(), surface.height()),
+                                crop_offset: Vec2::zero(),
+                                transform: quad.transform,
+                                region: quad.region,
+                                tint: quad.tint,
+                                alpha: quad.alpha,
+                            },
+                        );
+                    }
+                }
+                fab::DrawCommand::Triangles(triangles) => {
+                    for triangle in triangles.vertices.chunks_exact(3) {
+                        let triangle =
+                            [triangle[0], triangle[1], triangle[2]].map(|v| fab::ColorVertex {
+                                position: view.transform.transform_point(v.position),
+                                color: v.color,
+                            });
+                        draw_triangle(image, clip, triangle);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn draw_quad(
+    image: &mut RgbaImage,
+    clip: Box2<u32>,
+    sampling: Sampling,
+    view_transform: Affine2<f32>,
+    quad: TexturedQuad<'_>,
+) {
+    let crop_offset = quad.crop_offset.cast::<f32>();
+
+    // Only the part of the region which is not cropped away has any texels to draw.
+    let drawn =
+        Box2::with_size(crop_offset, quad.crop_size.cast::<f32>()).fintersection(quad.region);
+    if !(drawn.min[0] < drawn.max[0] && drawn.min[1] < drawn.max[1]) {
+        return;
+    }
+
+    let transform = quad.transform.then(view_transform);
+    let Some(bounds) = Box2::from_fpoints(drawn.corners().map(|p| transform.transform_point(p)))
+    else {
+        return;
+    };
+
+    let (x_range, y_range) = pixel_ranges(bounds, clip);
+    let inverse = transform.inverse();
+    for y in y_range {
+        for x in x_range.clone() {
+            let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let local = inverse.transform_point(center);
+            if !(local[0] >= drawn.min[0]
+                && local[1] >= drawn.min[1]
+                && local[0] < drawn.max[0]
+                && local[1] < drawn.max[1])
+            {
+                continue;
+            }
+            let local = local - crop_offset;
+
+            let mut src = match sampling {
+                Sampling::Nearest => sample_nearest(&quad, local),
+                Sampling::Bilinear => sample_bilinear(&quad, local),
+            };
+            for (c, t) in src.iter_mut().zip(quad.tint.into_array()) {
+                *c *= t;
+            }
+            src[3] *= quad.alpha;
+            blend(image.get_pixel_mut(x, y), src);
+        }
+    }
+}
+
+fn draw_triangle(image: &mut RgbaImage, clip: Box2<u32>, [a, mut b, mut c]: [fab::ColorVertex; 3]) {
+    fn edge(a: Vec2<f32>, b: Vec2<f32>, p: Vec2<f32>) -> f32 {
+        (b - a).perp_dot(p - a)
+    }
+
+    // Pixels exactly on an edge are only drawn for "top left" edges, so that pixels on an edge
+    // shared by two triangles are drawn exactly once. Of the two directions an edge can be
+    // traversed in, exactly one counts as "top left".
+    fn includes_edge(a: Vec2<f32>, b: Vec2<f32>) -> bool {
+        let d = b - a;
+        d[1] > 0.0 || (d[1] == 0.0 && d[0] < 0.0)
+    }
+
+    let mut area = edge(a.position, b.position, c.position);
+    if area < 0.0 {
+        mem::swap(&mut b, &mut c);
+        area = -area;
+    }
+    if area == 0.0 {
+        return;
+    }
+
+    let Some(bounds) = Box2::from_fpoints([a.position, b.position, c.position].into_iter()) else {
+        return;
+    };
+
+    let (x_range, y_range) = pixel_ranges(bounds, clip);
+
+    let edges = [(b, c), (c, a), (a, b)];
+    for y in y_range {
+        for x in x_range.clone() {
+            let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+            let mut weights = [0.0; 3];
+            let mut inside = true;
+            for (w, (p, q)) in weights.iter_mut().zip(edges) {
+                *w = edge(p.position, q.position, center);
+                if *w < 0.0 || (*w == 0.0 && !includes_edge(p.position, q.position)) {
+                    inside = false;
+                    break;
+                }
+            }
+            if !inside {
+                continue;
+            }
+
+            let color = (a.color * weights[0] + b.color * weights[1] + c.color * weights[2]) / area;
+            blend(image.get_pixel_mut(x, y), color.into_array());
+        }
+    }
+}
+
+/// The ranges of pixels whose centers may be inside `bounds`, limited to the `clip` box.
+fn pixel_ranges(bounds: Box2<f32>, clip: Box2<u32>) -> (Range<u32>, Range<u32>) {
+    let range = |i: usize| {
+        (bounds.min[i].floor().max(0.0) as u32).max(clip.min[i])
+            ..(bounds.max[i].ceil().max(0.0) as u32).min(clip.max[i])
+    };
+    (range(0), range(1))
+}
+
+/// Load an image from disk and crop it.
+pub fn load_texture_image(
+    path: &Path,
+    crop_offset: Vec2<u32>,
+    crop_size: Vec2<u32>,
+) -> Result<RgbaImage, image::ImageError> {
+    Ok(image::ImageReader::open(path)?
+        .decode()?
+        .crop_imm(crop_offset[0], crop_offset[1], crop_size[0], crop_size[1])
+        .into_rgba8())
+}
+
+fn sample_nearest(quad: &TexturedQuad<'_>, local: Vec2<f32>) -> [f32; 4] {
+    let x = (local[0] as u32).min(quad.crop_size[0] - 1);
+    let y = (local[1] as u32).min(quad.crop_size[1] - 1);
+    texel(quad, x, y)
+}
+
+fn sample_bilinear(quad: &TexturedQuad<'_>, local: Vec2<f32>) -> [f32; 4] {
+    // Texel centers are at half-integer coordinates, samples are clamped to the edge of the
+    // texture so that neighboring textures on the same page never bleed in.
+    let max = quad.crop_size.cast::<f32>() - Vec2::splat(1.0);
+    let s = Vec2::new(
+        (local[0] - 0.5).clamp(0.0, max[0]),
+        (local[1] - 0.5).clamp(0.0, max[1]),
+    );
+
+    let x0 = s[0].floor();
+    let y0 = s[1].floor();
+    let fx = s[0] - x0;
+    let fy = s[1] - y0;
+    let (x0, y0) = (x0 as u32, y0 as u32);
+    let x1 = (x0 + 1).min(quad.crop_size[0] - 1);
+    let y1 = (y0 + 1).min(quad.crop_size[1] - 1);
+
+    let t00 = texel(quad, x0, y0);
+    let t10 = texel(quad, x1, y0);
+    let t01 = texel(quad, x0, y1);
+    let t11 = texel(quad, x1, y1);
+
+    array::from_fn(|i| {
+        let top = t00[i] + (t10[i] - t00[i]) * fx;
+        let bottom = t01[i] + (t11[i] - t01[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+fn texel(quad: &TexturedQuad<'_>, x: u32, y: u32) -> [f32; 4] {
+    let Rgba(c) = *quad
+        .image
+        .get_pixel(quad.image_position[0] + x, quad.image_position[1] + y);
+    c.map(|c| c as f32 / 255.0)
+}
+
+// Source-over blending with straight (non-premultiplied) alpha.
+fn blend(dst: &mut Rgba<u8>, src: [f32; 4]) {
+    let src_a = src[3];
+    if src_a <= 0.0 {
+        return;
+    }
+
+    let dst_c = dst.0.map(|c| c as f32 / 255.0);
+    let out_a = src_a + dst_c[3] * (1.0 - src_a);
+
+    let out: [f32; 4] = array::from_fn(|i| {
+        if i == 3 {
+            out_a
+        } else if out_a > 0.0 {
+            (src[i] * src_a + dst_c[i] * dst_c[3] * (1.0 - src_a)) / out_a
+        } else {
+            0.0
+        }
+    });
+
+    dst.0 = out.map(to_u8);
+}
+
+fn to_u8(c: f32) -> u8 {
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Copy part of an image, the region is clamped to the image bounds.
+fn crop(image: &RgbaImage, region: Box2<u32>) -> RgbaImage {
+    let size = region.size();
+    image::imageops::crop_imm(image, region.min[0], region.min[1], size[0], size[1]).to_image()
+}
+
+#[cfg(test)]
+mod tests {
+    use fabricator_math::{Vec3, Vec4};
+    use fabricator_util::typed_id_map::IdMap;
+
+    use super::*;
+
+    fn checker() -> RgbaImage {
+        RgbaImage::from_fn(2, 2, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 255, 128])
+            }
+        })
+    }
+
+    #[test]
+    fn test_render_quads() {
+        let mut textures = IdMap::<fab::TextureId, ()>::new();
+        let mut pages = IdMap::<fab::TexturePageId, ()>::new();
+        let texture_id = textures.insert(());
+        let page_id = pages.insert(());
+
+        let mut rasterizer = Rasterizer::new(Sampling::Nearest);
+        rasterizer.insert_texture(
+            texture_id,
+            page_id,
+            Vec2::new(3, 1),
+            Vec2::new(1, 1),
+            &checker(),
+        );
+
+        let render = fab::Render {
+            commands: vec![
+                fab::DrawCommand::Quad(fab::Quad {
+                    texture: texture_id,
+                    transform: Affine2::new().scale(Vec2::splat(2.0)),
+                    region: Box2::with_size(Vec2::zero(), Vec2::splat(3.0)),
+                    depth: 0,
+                    tint: Vec3::splat(1.0),
+                    alpha: 1.0,
+                }),
+                fab::DrawCommand::Quad(fab::Quad {
+                    texture: texture_id,
+                    transform: Affine2::new().translate(Vec2::new(1.0, 3.0)),
+                    region: Box2::with_size(Vec2::zero(), Vec2::splat(3.0)),
+                    depth: 10,
+                    tint: Vec3::splat(1.0),
+                    alpha: 1.0,
+                }),
+            ],
+            screen_size: Vec2::new(8, 8),
+            ..Default::default()
+        };
+
+        let image = rasterizer.render(&render);
+        assert_eq!(image.dimensions(), (8, 8));
+
+        // The texture has a crop offset of (1, 1), so it covers (2, 2)..(6, 6) when scaled by 2.
+        assert_eq!(*image.get_pixel(1, 1), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(2, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 3), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(4, 2), Rgba([0, 0, 128, 255]));
+        assert_eq!(*image.get_pixel(6, 6), Rgba([0, 0, 0, 255]));
+
+        // The deeper quad covers (2, 4)..(4, 6) and is drawn first, so the half transparent blue
+        // texels of the shallower quad are blended over its red texels.
+        assert_eq!(*image.get_pixel(2, 4), Rgba([127, 0, 128, 255]));
+        assert_eq!(*image.get_pixel(3, 5), Rgba([127, 0, 128, 255]));
+    }
+
+    #[test]
+    fn test_bilinear_stays_in_texture() {
+        let mut textures = IdMap::<fab::TextureId, ()>::new();
+        let mut pages = IdMap::<fab::TexturePageId, ()>::new();
+        let red_id = textures.insert(());
+        let green_id = textures.insert(());
+        let page_id = pages.insert(());
+
+        let mut rasterizer = Rasterizer::new(Sampling::Bilinear);
+        rasterizer.insert_texture(
+            red_id,
+            page_id,
+            Vec2::new(0, 0),
+            Vec2::zero(),
+            &RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255])),
+        );
+        rasterizer.insert_texture(
+            green_id,
+            page_id,
+            Vec2::new(2, 0),
+            Vec2::zero(),
+            &RgbaImage::from_pixel(2, 2, Rgba([0, 255, 0, 255])),
+        );
+
+        let render = fab::Render {
+            commands: vec![fab::DrawCommand::Quad(fab::Quad {
+                texture: red_id,
+                transform: Affine2::new().scale(Vec2::splat(4.0)),
+                region: Box2::with_size(Vec2::zero(), Vec2::splat(2.0)),
+                depth: 0,
+                tint: Vec3::splat(1.0),
+                alpha: 1.0,
+            })],
+            screen_size: Vec2::new(8, 8),
+            ..Default::default()
+        };
+
+        let image = rasterizer.render(&render);
+        for pixel in image.pixels() {
+            assert_eq!(*pixel, Rgba([255, 0, 0, 255]));
+        }
+    }
+
+    #[test]
+    fn test_tint_and_alpha() {
+        let mut textures = IdMap::<fab::TextureId, ()>::new();
+        let mut pages = IdMap::<fab::TexturePageId, ()>::new();
+        let texture_id = textures.insert(());
+        let page_id = pages.insert(());
+
+        let mut rasterizer = Rasterizer::new(Sampling::Nearest);
+        rasterizer.insert_texture(
+            texture_id,
+            page_id,
+            Vec2::zero(),
+            Vec2::zero(),
+            &RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])),
+        );
+
+        let render = fab::Render {
+            commands: vec![fab::DrawCommand::Quad(fab::Quad {
+                texture: texture_id,
+                transform: Affine2::new(),
+                region: Box2::with_size(Vec2::zero(), Vec2::splat(1.0)),
+                depth: 0,
+                tint: Vec3::new(1.0, 0.0, 1.0),
+                alpha: 0.5,
+            })],
+            screen_size: Vec2::new(1, 1),
+            ..Default::default()
+        };
+
+        let image = rasterizer.render(&render);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([128, 0, 128, 255]));
+    }
+
+    #[test]
+    fn test_quad_region() {
+        let mut textures = IdMap::<fab::TextureId, ()>::new();
+        let mut pages = IdMap::<fab::TexturePageId, ()>::new();
+        let texture_id = textures.insert(());
+        let page_id = pages.insert(());
+
+        let mut rasterizer = Rasterizer::new(Sampling::Nearest);
+        rasterizer.insert_texture(texture_id, page_id, Vec2::zero(), Vec2::zero(), &checker());
+
+        // Only the bottom right texel of the checker texture is drawn, at (0, 0).
+        let render = fab::Render {
+            commands: vec![fab::DrawCommand::Quad(fab::Quad {
+                texture: texture_id,
+                transform: Affine2::new().translate(Vec2::splat(-1.0)),
+                region: Box2::with_size(Vec2::splat(1.0), Vec2::splat(1.0)),
+                depth: 0,
+                tint: Vec3::splat(1.0),
+                alpha: 1.0,
+            })],
+            screen_size: Vec2::new(2, 2),
+            ..Default::default()
+        };
+
+        let image = rasterizer.render(&render);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(1, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(0, 1), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(1, 1), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_triangles() {
+        let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+        let green = Vec4::new(0.0, 1.0, 0.0, 0.5);
+
+        let mut triangles = fab::Triangles::new(0);
+        triangles.push_rect(Box2::new(Vec2::zero(), Vec2::splat(4.0)), red);
+        triangles.push_rect(Box2::new(Vec2::splat(2.0), Vec2::splat(4.0)), green);
+
+        let render = fab::Render {
+            commands: vec![fab::DrawCommand::Triangles(triangles)],
+            screen_size: Vec2::new(6, 6),
+            ..Default::default()
+        };
+
+        let image = Rasterizer::new(Sampling::Nearest).render(&render);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(0, 3), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(4, 4), Rgba([0, 0, 0, 255]));
+
+        // Pixels on the diagonal shared by the two triangles of the green rectangle must only be
+        // blended once.
+        assert_eq!(*image.get_pixel(2, 2), Rgba([128, 128, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 3), Rgba([128, 128, 0, 255]));
+        assert_eq!(*image.get_pixel(2, 3), Rgba([128, 128, 0, 255]));
+    }
+
+    #[test]
+    fn test_views() {
+        let mut triangles = fab::Triangles::new(0);
+        triangles.push_rect(
+            Box2::new(Vec2::zero(), Vec2::splat(2.0)),
+            Vec4::new(1.0, 0.0, 0.0, 1.0),
+        );
+
+        // The first view is scaled up and clipped to its port, the second is only moved.
+        let render = fab::Render {
+            commands: vec![fab::DrawCommand::Triangles(triangles)],
+            screen_size: Vec2::new(6, 6),
+            views: vec![
+                fab::RenderView {
+                    transform: Affine2::new().scale(Vec2::splat(2.0)),
+                    port: Box2::new(Vec2::zero(), Vec2::splat(3)),
+                },
+                fab::RenderView {
+                    transform: Affine2::new().translate(Vec2::splat(4.0)),
+                    port: Box2::new(Vec2::splat(4), Vec2::splat(6)),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let image = Rasterizer::new(Sampling::Nearest).render(&render);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(2, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 3), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(4, 4), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(5, 5), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_gui() {
+        let mut room = fab::Triangles::new(0);
+        room.push_rect(
+            Box2::new(Vec2::zero(), Vec2::splat(6.0)),
+            Vec4::new(1.0, 0.0, 0.0, 1.0),
+        );
+
+        // GUI commands are drawn over room commands regardless of depth, and GUI space is
+        // stretched over the whole screen.
+        let mut gui = fab::Triangles::new(100);
+        gui.push_rect(
+            Box2::new(Vec2::zero(), Vec2::splat(1.0)),
+            Vec4::new(0.0, 1.0, 0.0, 1.0),
+        );
+
+        let render = fab::Render {
+            commands: vec![fab::DrawCommand::Triangles(room)],
+            screen_size: Vec2::new(6, 6),
+            gui_commands: vec![fab::DrawCommand::Triangles(gui)],
+            gui_size: Vec2::new(3, 3),
+            ..Default::default()
+        };
+
+        let image = Rasterizer::new(Sampling::Nearest).render(&render);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(*image.get_pixel(1, 1), Rgba([0, 255, 0, 255]));
+        assert_eq!(*image.get_pixel(2, 2), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_surfaces() {
+        let mut surfaces = IdMap::<fab::SurfaceId, ()>::new();
+        let surface_id = surfaces.insert(());
+
+        let mut red = fab::Triangles::new(0);
+        red.push_rect(
+            Box2::new(Vec2::zero(), Vec2::splat(1.0)),
+            Vec4::new(1.0, 0.0, 0.0, 1.0),
+        );
+
+        // The surface is cleared to transparent green, then a red texel is drawn into its top
+        // left corner, then it is drawn twice its size onto the screen.
+        let mut render = fab::Render {
+            commands: vec![fab::DrawCommand::Surface(fab::SurfaceQuad {
+                surface: surface_id,
+                transform: Affine2::new()
+                    .scale(Vec2::splat(2.0))
+                    .translate(Vec2::splat(1.0)),
+                region: Box2::with_size(Vec2::zero(), Vec2::splat(2.0)),
+                depth: 0,
+                tint: Vec3::splat(1.0),
+                alpha: 1.0,
+            })],
+            screen_size: Vec2::new(6, 6),
+            surface_passes: vec![
+                fab::SurfacePass::Allocate {
+                    surface: surface_id,
+                    size: Vec2::new(2, 2),
+                },
+                fab::SurfacePass::Clear {
+                    surface: surface_id,
+                    color: Vec4::new(0.0, 1.0, 0.0, 0.0),
+                },
+                fab::SurfacePass::Draw {
+                    surface: surface_id,
+                    commands: vec![fab::DrawCommand::Triangles(red)],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let mut rasterizer = Rasterizer::new(Sampling::Nearest);
+        rasterizer.run_surface_passes(&mut render);
+        assert!(render.surface_passes.is_empty());
+
+        let surface = rasterizer.surface(surface_id).unwrap();
+        assert_eq!(*surface.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(*surface.get_pixel(1, 1), Rgba([0, 255, 0, 0]));
+
+        let image = rasterizer.render(&render);
+        assert_eq!(*image.get_pixel(0, 0), Rgba([0, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(1, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(2, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(*image.get_pixel(3, 3), Rgba([0, 0, 0, 255]));
+
+        render.surface_passes.push(fab::SurfacePass::Free {
+            surface: surface_id,
+        });
+        rasterizer.run_surface_passes(&mut render);
+        assert!(rasterizer.surface(surface_id).is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use std::{env, path::PathBuf};
+
+use fabricator as fab;
+use fabricator_raster::{Rasterizer, Sampling};
+
+// The number of ticks to run every sample project for before rendering.
+const TICKS: u32 = 10;
+
+fn samples_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../samples")
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+// Rendered images which do not match their golden image are written here, outside of the source
+// tree.
+fn actual_dir() -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden-actual");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Render every project in `samples/` and compare it to its golden image.
+///
+/// A missing golden image is a failure. Set `FABRICATOR_BLESS=1` to write the golden images from
+/// the current output instead, which is the only time this test writes into the source tree.
+#[test]
+fn test_golden_images() {
+    let bless = env::var_os("FABRICATOR_BLESS").is_some_and(|v| v != "0");
+    let mut failures = Vec::new();
+
+    let mut samples = samples_dir()
+        .read_dir()
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    samples.sort();
+
+    for sample in samples {
+        let name = sample.file_name().unwrap().to_str().unwrap().to_owned();
+        let project_file = sample.join(format!("{name}.yyp"));
+
+        let project = fab::Project::load(&project_file).unwrap();
+        let mut game = fab::Game::new(project, "default").unwrap();
+        let mut rasterizer = Rasterizer::for_game(&game, Sampling::Nearest).unwrap();
+
+        let mut input = fab::InputState::default();
+        let mut render = fab::Render::default();
+        for _ in 0..TICKS {
+            game.tick(&mut input, &mut render).unwrap();
+            rasterizer.run_surface_passes(&mut render);
+            rasterizer.report_surfaces(&mut game);
+            input.end_tick();
+        }
+        let image = rasterizer.render(&render);
+
+        let golden_path = golden_dir().join(format!("{name}.png"));
+        if bless {
+            std::fs::create_dir_all(golden_dir()).unwrap();
+            image.save(&golden_path).unwrap();
+            eprintln!("wrote golden image {golden_path:?}");
+            continue;
+        }
+
+        if !golden_path.exists() {
+            failures.push(format!("{name}: missing golden image {golden_path:?}"));
+            continue;
+        }
+
+        let golden = image::open(&golden_path).unwrap().into_rgba8();
+        if golden != image {
+            let actual_path = actual_dir().join(format!("{name}.png"));
+            image.save(&actual_path).unwrap();
+            failures.push(format!(
+                "{name}: rendered image differs from {golden_path:?}, see {actual_path:?}"
+            ));
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "golden image failures (set FABRICATOR_BLESS=1 to accept the current output):\n{}",
+            failures.join("\n")
+        );
+    }
+}
@@ -528,9 +528,14 @@ pub fn buffer_lib<'gc>(ctx: vm::Context<'gc>, lib: &mut vm::MagicSet<'gc>) {
     lib.insert_callback(ctx, "buffer_seek", buffer_seek);
     lib.insert_callback(ctx, "buffer_read", buffer_read);
     lib.insert_callback(ctx, "buffer_delete", buffer_delete);
-    lib.insert_callback(ctx, "buffer_get_address", buffer_get_address);
     lib.insert_callback(ctx, "buffer_get_size", buffer_get_size);
     lib.insert_callback(ctx, "buffer_fill", buffer_fill);
     lib.insert_callback(ctx, "buffer_sizeof", buffer_sizeof);
     lib.insert_callback(ctx, "buffer_peek", buffer_peek);
 }
+
+/// Buffer functions which expose host state to scripts, and so are not part of the sandboxed
+/// stdlib.
+pub fn buffer_host_lib<'gc>(ctx: vm::Context<'gc>, lib: &mut vm::MagicSet<'gc>) {
+    lib.insert_callback(ctx, "buffer_get_address", buffer_get_address);
+}
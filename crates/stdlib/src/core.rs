@@ -4,7 +4,10 @@ use fabricator_vm as vm;
 
 use crate::util::{MagicExt as _, Pointer, resolve_array_range};
 
-/// `gml_pragma` is currently ignored by fabricator.
+/// `gml_pragma` does nothing at runtime.
+///
+/// Lint pragmas of the form `gml_pragma("lint", <level>, <lint>...)` are handled by the compiler,
+/// every other pragma is ignored.
 pub fn gml_pragma<'gc>(_ctx: vm::Context<'gc>, _args: ()) -> Result<(), vm::RuntimeError> {
     Ok(())
 }
@@ -105,6 +108,44 @@ pub fn debug_get_callstack<'gc>(
     Ok(())
 }
 
+/// Returns a JSON heap snapshot of everything reachable from the globals and the calling thread.
+///
+/// If a value is given, the snapshot also includes a shortest path from a root to that value.
+pub fn debug_heap_snapshot<'gc>(
+    ctx: vm::Context<'gc>,
+    mut exec: vm::Execution<'gc, '_>,
+) -> Result<(), vm::RuntimeError> {
+    let target: Option<vm::Value> = exec.stack().consume(ctx)?;
+
+    let mut builder = vm::HeapSnapshotBuilder::new(ctx);
+    builder.add_execution("thread", &exec);
+    let snapshot = builder.finish();
+
+    let json = heap_snapshot_json(&snapshot, target);
+    exec.stack()
+        .replace(ctx, ctx.intern(&serde_json::to_string(&json)?));
+    Ok(())
+}
+
+/// The JSON returned by `debug_heap_snapshot`, with a `target` field holding the node and
+/// retaining path of `target` if it is given and was reached.
+pub fn heap_snapshot_json(
+    snapshot: &vm::HeapSnapshot,
+    target: Option<vm::Value<'_>>,
+) -> serde_json::Value {
+    let mut json = snapshot.to_json();
+    if let Some(target) = target {
+        json["target"] = match snapshot.find(target) {
+            Some(node) => serde_json::json!({
+                "node": node,
+                "retaining_path": snapshot.retaining_path(node),
+            }),
+            None => serde_json::Value::Null,
+        };
+    }
+    json
+}
+
 pub fn script_execute<'gc>(
     ctx: vm::Context<'gc>,
     mut exec: vm::Execution<'gc, '_>,
@@ -308,6 +349,7 @@ pub fn core_lib<'gc>(ctx: vm::Context<'gc>, lib: &mut vm::MagicSet<'gc>) {
     lib.insert_callback(ctx, "is_array", is_array);
     lib.insert_callback(ctx, "is_ptr", is_ptr);
     lib.insert_exec_callback(ctx, "debug_get_callstack", debug_get_callstack);
+    lib.insert_exec_callback(ctx, "debug_heap_snapshot", debug_heap_snapshot);
     lib.insert_exec_callback(ctx, "script_execute", script_execute);
     lib.insert_exec_callback(ctx, "script_execute_ext", script_execute_ext);
     lib.insert_exec_callback(ctx, "method_call", method_call);
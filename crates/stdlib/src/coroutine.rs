@@ -0,0 +1,159 @@
+use std::cell::Cell;
+
+use fabricator_vm as vm;
+use gc_arena::{Collect, Mutation, Rootable};
+
+use crate::util::MagicExt as _;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CoroutineStatus {
+    /// The coroutine has either not been started yet or is suspended in a call to
+    /// `coroutine_yield`.
+    Suspended,
+    /// The coroutine is currently executing, either directly or because it has resumed another
+    /// coroutine.
+    Running,
+    /// The coroutine has returned or errored, and cannot be resumed again.
+    Dead,
+}
+
+impl CoroutineStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CoroutineStatus::Suspended => "suspended",
+            CoroutineStatus::Running => "running",
+            CoroutineStatus::Dead => "dead",
+        }
+    }
+}
+
+/// A function running on its own [`vm::Thread`] which can be suspended and resumed.
+#[derive(Collect)]
+#[collect(no_drop)]
+pub struct Coroutine<'gc> {
+    function: vm::Function<'gc>,
+    thread: vm::Thread<'gc>,
+    #[collect(require_static)]
+    started: Cell<bool>,
+}
+
+impl<'gc> Coroutine<'gc> {
+    pub fn new(mc: &Mutation<'gc>, function: vm::Function<'gc>) -> Self {
+        Self {
+            function,
+            thread: vm::Thread::new(mc),
+            started: Cell::new(false),
+        }
+    }
+
+    pub fn into_userdata(self, mc: &Mutation<'gc>) -> vm::UserData<'gc> {
+        vm::UserData::new::<Rootable![Coroutine<'_>]>(mc, self)
+    }
+
+    #[inline]
+    pub fn downcast(ud: vm::UserData<'gc>) -> Result<&'gc Coroutine<'gc>, vm::BadUserDataType> {
+        ud.downcast::<Rootable![Coroutine<'_>]>()
+    }
+
+    pub fn status(&self) -> CoroutineStatus {
+        match self.thread.status() {
+            vm::ThreadStatus::Running => CoroutineStatus::Running,
+            vm::ThreadStatus::Suspended => CoroutineStatus::Suspended,
+            vm::ThreadStatus::Stopped if self.started.get() => CoroutineStatus::Dead,
+            vm::ThreadStatus::Stopped => CoroutineStatus::Suspended,
+        }
+    }
+
+    /// Start or resume this coroutine from within a callback.
+    ///
+    /// Values in the stack of the given execution are passed either as the arguments to the
+    /// coroutine function or as the return values of the suspended `coroutine_yield` call. Once the
+    /// coroutine yields or returns, the stack is filled with the yielded or returned values.
+    ///
+    /// The coroutine runs under the limits of the calling thread, and everything it executes counts
+    /// towards them.
+    pub fn resume(
+        &self,
+        ctx: vm::Context<'gc>,
+        mut exec: vm::Execution<'gc, '_>,
+    ) -> Result<vm::ThreadExit, vm::VmError<'gc>> {
+        match self.status() {
+            CoroutineStatus::Suspended => {
+                if self.started.replace(true) {
+                    exec.resume_thread(ctx, self.thread)
+                } else {
+                    exec.start_thread(ctx, self.thread, self.function)
+                }
+            }
+            CoroutineStatus::Running => {
+                Err(vm::RuntimeError::msg("cannot resume a running coroutine").into())
+            }
+            CoroutineStatus::Dead => {
+                Err(vm::RuntimeError::msg("cannot resume a dead coroutine").into())
+            }
+        }
+    }
+}
+
+/// Create a new coroutine from the given function.
+///
+/// If the function does not have a bound `self` value, it will be bound to the `self` value of the
+/// caller.
+pub fn coroutine_create<'gc>(
+    ctx: vm::Context<'gc>,
+    mut exec: vm::Execution<'gc, '_>,
+) -> Result<(), vm::RuntimeError> {
+    let mut function: vm::Function = exec.stack().consume(ctx)?;
+    if function.this().is_none() {
+        function = function.rebind(&ctx, Some(exec.this(ctx, 0)));
+    }
+    let coroutine = Coroutine::new(&ctx, function);
+    exec.stack().replace(ctx, coroutine.into_userdata(&ctx));
+    Ok(())
+}
+
+/// Start or resume the given coroutine, passing every other argument into it.
+///
+/// Returns the values passed to `coroutine_yield` if the coroutine yields, or the return values of
+/// the coroutine function if it returns. Any error raised inside of the coroutine is propagated to
+/// the caller, and the coroutine is dead afterwards.
+pub fn coroutine_resume<'gc>(
+    ctx: vm::Context<'gc>,
+    mut exec: vm::Execution<'gc, '_>,
+) -> Result<(), vm::VmError<'gc>> {
+    let coroutine: vm::UserData = exec.stack().from_index(ctx, 0)?;
+    let coroutine = Coroutine::downcast(coroutine)?;
+    exec.stack().remove(0);
+    coroutine.resume(ctx, exec)?;
+    Ok(())
+}
+
+/// Suspend the currently running coroutine, passing all arguments to the matching
+/// `coroutine_resume` call.
+///
+/// When the coroutine is resumed, returns the extra arguments given to `coroutine_resume`.
+///
+/// It is an error to yield outside of a coroutine, or to yield from within a function called by a
+/// Rust callback (such as `pcall` or `array_foreach`).
+pub fn coroutine_yield<'gc>(
+    _ctx: vm::Context<'gc>,
+    mut exec: vm::Execution<'gc, '_>,
+) -> Result<(), vm::SuspendError> {
+    exec.suspend()
+}
+
+/// Returns the status of the given coroutine as a string, one of `"suspended"`, `"running"`, or
+/// `"dead"`.
+pub fn coroutine_status<'gc>(
+    _ctx: vm::Context<'gc>,
+    coroutine: vm::UserData<'gc>,
+) -> Result<&'static str, vm::BadUserDataType> {
+    Ok(Coroutine::downcast(coroutine)?.status().as_str())
+}
+
+pub fn coroutine_lib<'gc>(ctx: vm::Context<'gc>, lib: &mut vm::MagicSet<'gc>) {
+    lib.insert_exec_callback(ctx, "coroutine_create", coroutine_create);
+    lib.insert_exec_callback(ctx, "coroutine_resume", coroutine_resume);
+    lib.insert_exec_callback(ctx, "coroutine_yield", coroutine_yield);
+    lib.insert_callback(ctx, "coroutine_status", coroutine_status);
+}
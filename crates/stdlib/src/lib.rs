@@ -1,6 +1,7 @@
 pub mod array;
 pub mod buffer;
 pub mod core;
+pub mod coroutine;
 pub mod ds_grid;
 pub mod ds_list;
 pub mod ds_map;
@@ -9,6 +10,7 @@ pub mod json;
 pub mod math;
 pub mod string;
 pub mod util;
+pub mod weak_ref;
 
 pub use self::util::Pointer;
 
@@ -16,40 +18,69 @@ use fabricator_vm as vm;
 use gc_arena::{Collect, Gc, Rootable};
 
 use crate::{
-    array::array_lib, buffer::buffer_lib, core::core_lib, ds_grid::ds_grid_lib,
-    ds_list::ds_list_lib, ds_map::ds_map_lib, ds_priority::ds_priority_lib, json::json_lib,
-    math::math_lib, string::string_lib,
+    array::array_lib,
+    buffer::{buffer_host_lib, buffer_lib},
+    core::core_lib,
+    coroutine::coroutine_lib,
+    ds_grid::ds_grid_lib,
+    ds_list::ds_list_lib,
+    ds_map::ds_map_lib,
+    ds_priority::ds_priority_lib,
+    json::json_lib,
+    math::math_lib,
+    string::string_lib,
+    weak_ref::weak_ref_lib,
 };
 
 pub trait StdlibContext<'gc> {
+    /// The full stdlib.
     fn stdlib(self) -> Gc<'gc, vm::MagicSet<'gc>>;
+
+    /// The stdlib without any function which exposes host state to scripts, such as the raw
+    /// memory address of a buffer.
+    fn sandboxed_stdlib(self) -> Gc<'gc, vm::MagicSet<'gc>>;
+}
+
+#[derive(Collect)]
+#[collect(no_drop)]
+struct StdlibSingleton<'gc> {
+    full: Gc<'gc, vm::MagicSet<'gc>>,
+    sandboxed: Gc<'gc, vm::MagicSet<'gc>>,
+}
+
+impl<'gc> vm::Singleton<'gc> for StdlibSingleton<'gc> {
+    fn create(ctx: vm::Context<'gc>) -> Self {
+        let mut sandboxed = vm::MagicSet::builtins(ctx);
+
+        core_lib(ctx, &mut sandboxed);
+        coroutine_lib(ctx, &mut sandboxed);
+        string_lib(ctx, &mut sandboxed);
+        math_lib(ctx, &mut sandboxed);
+        array_lib(ctx, &mut sandboxed);
+        buffer_lib(ctx, &mut sandboxed);
+        json_lib(ctx, &mut sandboxed);
+        ds_list_lib(ctx, &mut sandboxed);
+        ds_grid_lib(ctx, &mut sandboxed);
+        ds_map_lib(ctx, &mut sandboxed);
+        ds_priority_lib(ctx, &mut sandboxed);
+        weak_ref_lib(ctx, &mut sandboxed);
+
+        let mut full = sandboxed.clone();
+        buffer_host_lib(ctx, &mut full);
+
+        Self {
+            full: Gc::new(&ctx, full),
+            sandboxed: Gc::new(&ctx, sandboxed),
+        }
+    }
 }
 
 impl<'gc> StdlibContext<'gc> for vm::Context<'gc> {
     fn stdlib(self) -> Gc<'gc, vm::MagicSet<'gc>> {
-        #[derive(Collect)]
-        #[collect(no_drop)]
-        struct StdlibSingleton<'gc>(Gc<'gc, vm::MagicSet<'gc>>);
-
-        impl<'gc> vm::Singleton<'gc> for StdlibSingleton<'gc> {
-            fn create(ctx: vm::Context<'gc>) -> Self {
-                let mut stdlib = vm::MagicSet::builtins(ctx);
-
-                core_lib(ctx, &mut stdlib);
-                string_lib(ctx, &mut stdlib);
-                math_lib(ctx, &mut stdlib);
-                array_lib(ctx, &mut stdlib);
-                buffer_lib(ctx, &mut stdlib);
-                json_lib(ctx, &mut stdlib);
-                ds_list_lib(ctx, &mut stdlib);
-                ds_grid_lib(ctx, &mut stdlib);
-                ds_map_lib(ctx, &mut stdlib);
-                ds_priority_lib(ctx, &mut stdlib);
-
-                Self(Gc::new(&ctx, stdlib))
-            }
-        }
+        self.singleton::<Rootable![StdlibSingleton<'_>]>().full
+    }
 
-        self.singleton::<Rootable![StdlibSingleton<'_>]>().0
+    fn sandboxed_stdlib(self) -> Gc<'gc, vm::MagicSet<'gc>> {
+        self.singleton::<Rootable![StdlibSingleton<'_>]>().sandboxed
     }
 }
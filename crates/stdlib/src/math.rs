@@ -99,12 +99,15 @@ pub fn clamp<'gc>(
 #[collect(require_static)]
 pub struct Rng {
     rng: RefCell<SmallRng>,
+    // The source of new seeds for `randomize`, if `None` then seeds come from system entropy.
+    seeds: RefCell<Option<SmallRng>>,
 }
 
 impl Default for Rng {
     fn default() -> Self {
         Self {
             rng: RefCell::new(rand::make_rng()),
+            seeds: RefCell::new(None),
         }
     }
 }
@@ -119,6 +122,24 @@ impl Rng {
             self.rng.try_borrow_mut().map_err(|_| RngLockError)?,
         ))
     }
+
+    /// Make the pRNG entirely deterministic, starting from the given seed.
+    ///
+    /// This seeds the pRNG, and also makes every later call to [`randomize`] pick its new seed
+    /// from a sequence determined by `seed` rather than from system entropy.
+    pub fn make_deterministic(&self, seed: u64) -> Result<(), RngLockError> {
+        self.lock()?.set_seed(seed as i64);
+        *self.seeds.try_borrow_mut().map_err(|_| RngLockError)? =
+            Some(SmallRng::seed_from_u64(!seed));
+        Ok(())
+    }
+
+    fn new_seed(&self) -> Result<i64, RngLockError> {
+        match &mut *self.seeds.try_borrow_mut().map_err(|_| RngLockError)? {
+            Some(seeds) => Ok(seeds.random()),
+            None => Ok(rand::rng().random()),
+        }
+    }
 }
 
 pub struct RngLock<'a>(RefMut<'a, SmallRng>);
@@ -152,8 +173,9 @@ impl<'a> RngLock<'a> {
 
 /// Pick a random i64 as a new pRNG seed, initialize the pRNG with it and return it.
 pub fn randomize<'gc>(ctx: vm::Context<'gc>, (): ()) -> Result<i64, RngLockError> {
-    let seed = rand::rng().random();
-    Rng::singleton(ctx).lock()?.set_seed(seed);
+    let rng = Rng::singleton(ctx);
+    let seed = rng.new_seed()?;
+    rng.lock()?.set_seed(seed);
     Ok(seed)
 }
 
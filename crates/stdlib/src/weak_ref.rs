@@ -0,0 +1,102 @@
+use fabricator_vm as vm;
+use gc_arena::{Collect, Gc, Rootable};
+
+use crate::util::{MagicExt as _, resolve_array_range};
+
+/// A weak reference to a script value, as returned by `weak_ref_create`.
+///
+/// Reading the `ref` field returns the referenced value, or `undefined` if it has been collected.
+#[derive(Copy, Clone, Collect)]
+#[collect(no_drop)]
+pub struct WeakRef<'gc>(pub vm::WeakValue<'gc>);
+
+impl<'gc> WeakRef<'gc> {
+    pub fn into_userdata(self, ctx: vm::Context<'gc>) -> vm::UserData<'gc> {
+        struct WeakRefMethods;
+
+        impl<'gc> vm::UserDataMethods<'gc> for WeakRefMethods {
+            fn get_field(
+                &self,
+                ud: vm::UserData<'gc>,
+                ctx: vm::Context<'gc>,
+                key: vm::String<'gc>,
+            ) -> Result<vm::Value<'gc>, vm::RuntimeError> {
+                if key.as_str() == "ref" {
+                    Ok(WeakRef::downcast(ud)
+                        .unwrap()
+                        .0
+                        .upgrade(&ctx)
+                        .unwrap_or_default())
+                } else {
+                    Err(vm::RuntimeError::msg(format!(
+                        "no such field {key:?} on weak reference"
+                    )))
+                }
+            }
+        }
+
+        #[derive(Collect)]
+        #[collect(no_drop)]
+        struct WeakRefMethodsSingleton<'gc>(Gc<'gc, dyn vm::UserDataMethods<'gc>>);
+
+        impl<'gc> vm::Singleton<'gc> for WeakRefMethodsSingleton<'gc> {
+            fn create(ctx: vm::Context<'gc>) -> Self {
+                let methods = ctx.alloc_static(WeakRefMethods);
+                WeakRefMethodsSingleton(gc_arena::unsize!(methods => dyn vm::UserDataMethods<'gc>))
+            }
+        }
+
+        let methods = ctx.singleton::<Rootable![WeakRefMethodsSingleton<'_>]>().0;
+        let ud = vm::UserData::new::<Rootable![WeakRef<'_>]>(&ctx, self);
+        ud.set_methods(&ctx, Some(methods));
+        ud
+    }
+
+    #[inline]
+    pub fn downcast(ud: vm::UserData<'gc>) -> Result<&'gc WeakRef<'gc>, vm::BadUserDataType> {
+        ud.downcast::<Rootable![WeakRef<'_>]>()
+    }
+}
+
+/// Create a weak reference to the given struct, array, function, or userdata.
+pub fn weak_ref_create<'gc>(
+    ctx: vm::Context<'gc>,
+    value: vm::Value<'gc>,
+) -> Result<vm::UserData<'gc>, vm::BadWeakValueType> {
+    Ok(WeakRef(value.try_into()?).into_userdata(ctx))
+}
+
+/// Returns true if the value referenced by the given weak reference has not been collected.
+pub fn weak_ref_alive<'gc>(
+    ctx: vm::Context<'gc>,
+    weak_ref: vm::UserData<'gc>,
+) -> Result<bool, vm::BadUserDataType> {
+    Ok(WeakRef::downcast(weak_ref)?.0.is_alive(&ctx))
+}
+
+/// Returns true if any weak reference in the given range of an array references a value which has
+/// not been collected.
+pub fn weak_ref_any_alive<'gc>(
+    ctx: vm::Context<'gc>,
+    (array, index, count): (vm::Array<'gc>, Option<isize>, Option<isize>),
+) -> Result<bool, vm::RuntimeError> {
+    let array = array.try_borrow()?;
+    let (range, _) = resolve_array_range(array.len(), index, count)?;
+    for &value in &array[range] {
+        let vm::Value::UserData(ud) = value else {
+            return Err(vm::RuntimeError::msg(
+                "array element is not a weak reference",
+            ));
+        };
+        if WeakRef::downcast(ud)?.0.is_alive(&ctx) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+pub fn weak_ref_lib<'gc>(ctx: vm::Context<'gc>, lib: &mut vm::MagicSet<'gc>) {
+    lib.insert_callback(ctx, "weak_ref_create", weak_ref_create);
+    lib.insert_callback(ctx, "weak_ref_alive", weak_ref_alive);
+    lib.insert_callback(ctx, "weak_ref_any_alive", weak_ref_any_alive);
+}
@@ -2,6 +2,7 @@ use std::{
     any::TypeId,
     fmt,
     hash::{Hash, Hasher},
+    mem,
 };
 
 use gc_arena::{
@@ -56,6 +57,7 @@ pub struct Any<'gc, M: 'gc = ()>(Gc<'gc, AnyInner<M>>);
 pub struct AnyInner<M> {
     metadata: M,
     type_id: TypeId,
+    size: usize,
 }
 
 #[derive(Collect)]
@@ -123,6 +125,7 @@ impl<'gc, M> Any<'gc, M> {
                 header: AnyInner {
                     metadata,
                     type_id: TypeId::of::<R>(),
+                    size: mem::size_of::<Value<M, Root<'gc, R>>>(),
                 },
                 value: data,
             },
@@ -158,6 +161,14 @@ impl<'gc, M> Any<'gc, M> {
         self.0.type_id
     }
 
+    /// The size in bytes of the allocation holding the metadata and value.
+    ///
+    /// This does not include any memory owned by the value outside of the allocation.
+    #[inline]
+    pub fn size(self) -> usize {
+        self.0.size
+    }
+
     #[inline]
     pub fn is<R>(self) -> bool
     where
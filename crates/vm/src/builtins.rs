@@ -8,7 +8,7 @@ use crate::{
     magic::{MagicConstant, MagicSet},
     object::Object,
     registry::Singleton,
-    thread::{Backtrace, OpError, VmError},
+    thread::{Backtrace, OpError, ThreadLimits, VmError},
     user_data::{UserData, UserDataIter},
     value::{Function, Value},
 };
@@ -60,6 +60,13 @@ pub struct BuiltIns<'gc> {
     /// ```
     pub pcall: Callback<'gc>,
 
+    /// Call the given function and catch any errors, as used by `try` / `catch` statements.
+    ///
+    /// Behaves like `_pcall`, except that the error is converted with [`VmError::to_exception`]
+    /// and no backtrace is returned: thrown values are caught unchanged, and runtime errors are
+    /// caught as an exception struct.
+    pub try_catch: Callback<'gc>,
+
     /// Get the parent (super) of an object if it exists.
     pub get_super: Callback<'gc>,
 
@@ -119,6 +126,7 @@ impl<'gc> BuiltIns<'gc> {
 
     pub const ERROR: &'static str = "_error";
     pub const PCALL: &'static str = "_pcall";
+    pub const TRY_CATCH: &'static str = "_try_catch";
 
     pub const GET_SUPER: &'static str = "_get_super";
     pub const SET_SUPER: &'static str = "_set_super";
@@ -167,6 +175,9 @@ impl<'gc> BuiltIns<'gc> {
                     Ok(_) => {
                         exec.stack()[0] = true.into();
                     }
+                    // Exceeding a thread limit must stop the entire thread, so these errors are
+                    // never caught.
+                    Err(err) if ThreadLimits::is_limit_error(&err.error) => return Err(err),
                     Err(err) => {
                         exec.stack().replace(
                             ctx,
@@ -181,6 +192,29 @@ impl<'gc> BuiltIns<'gc> {
                 Ok(())
             }),
 
+            try_catch: Callback::from_fn(ctx, |ctx, mut exec| {
+                let function: Function = exec.stack().from_index(ctx, 0)?;
+                let res = exec.with_stack_bottom(1).call(ctx, function);
+                match res {
+                    Ok(_) => {
+                        exec.stack()[0] = true.into();
+                    }
+                    Err(err) if ThreadLimits::is_limit_error(&err.error) => return Err(err),
+                    Err(mut err) => {
+                        if err.backtrace.is_none() {
+                            err.backtrace = Some(
+                                (0..exec.frame_depth())
+                                    .rev()
+                                    .map(|i| exec.upper_frame(i))
+                                    .collect(),
+                            );
+                        }
+                        exec.stack().replace(ctx, (false, err.to_exception(ctx)));
+                    }
+                }
+                Ok(())
+            }),
+
             get_super: Callback::from_fn(ctx, |ctx, mut exec| {
                 let obj: Object = exec.stack().consume(ctx)?;
                 exec.stack().replace(ctx, obj.parent());
@@ -424,6 +458,11 @@ impl<'gc> BuiltIns<'gc> {
             MagicConstant::new_ptr(&ctx, self.pcall),
         );
 
+        magic_set.insert(
+            ctx.intern_static(Self::TRY_CATCH),
+            MagicConstant::new_ptr(&ctx, self.try_catch),
+        );
+
         magic_set.insert(
             ctx.intern_static(Self::GET_SUPER),
             MagicConstant::new_ptr(&ctx, self.get_super),
@@ -4,7 +4,7 @@ use gc_arena::{Collect, Gc, GcSlice, Lock, Mutation};
 use thiserror::Error;
 
 use crate::{
-    debug::{Chunk, FunctionIdentifier, FunctionRef},
+    debug::{Chunk, FunctionIdentifier, FunctionRef, LocalVariable},
     instructions::{
         ByteCode, ConstIdx, HeapIdx, IndexType as _, InstIdx, Instruction, MagicIdx, ProtoIdx,
         RegIdx,
@@ -105,6 +105,7 @@ pub struct Prototype<'gc> {
     prototypes: Box<[Gc<'gc, Prototype<'gc>>]>,
     static_vars: Box<[SharedValue<'gc>]>,
     heap_vars: Box<[HeapVarDescriptor]>,
+    local_variables: Box<[LocalVariable<SharedStr>]>,
     max_used_register: Option<RegIdx>,
     max_owned_heap: Option<HeapIdx>,
     constructor_super: Gc<'gc, Lock<Option<Object<'gc>>>>,
@@ -457,12 +458,23 @@ impl<'gc> Prototype<'gc> {
             prototypes,
             static_vars,
             heap_vars,
+            local_variables: Box::new([]),
             max_used_register,
             max_owned_heap,
             constructor_super,
         })
     }
 
+    /// Attach debug info for the named local variables of this prototype.
+    #[must_use]
+    pub fn with_local_variables(
+        mut self,
+        local_variables: Box<[LocalVariable<SharedStr>]>,
+    ) -> Self {
+        self.local_variables = local_variables;
+        self
+    }
+
     #[must_use]
     #[inline]
     pub fn chunk(&self) -> Chunk<'gc> {
@@ -517,6 +529,24 @@ impl<'gc> Prototype<'gc> {
         &self.heap_vars
     }
 
+    /// Debug info for every named local variable, if it was provided when this prototype was
+    /// created.
+    #[must_use]
+    #[inline]
+    pub fn local_variables(&self) -> &[LocalVariable<SharedStr>] {
+        &self.local_variables
+    }
+
+    /// Every named local variable which holds a value at the given instruction index.
+    pub fn local_variables_at(
+        &self,
+        instruction: usize,
+    ) -> impl Iterator<Item = &LocalVariable<SharedStr>> + '_ {
+        self.local_variables
+            .iter()
+            .filter(move |var| var.contains(instruction))
+    }
+
     /// If it is not already created, associate a new `Object` with this prototype that defines the
     /// super-object of all constructed objects and return it.
     ///
@@ -4,6 +4,7 @@ use gc_arena::{Collect, Gc, Mutation, Rootable, arena::Root, barrier};
 
 use crate::{
     any::{Any, AnyInner},
+    instructions::{HeapIdx, RegIdx},
     string::SharedStr,
 };
 
@@ -318,6 +319,49 @@ impl<S> FunctionRef<S> {
     }
 }
 
+/// Where the value of a named local variable is stored.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Collect)]
+#[collect(require_static)]
+pub enum VariableLocation {
+    Register(RegIdx),
+    Heap(HeapIdx),
+}
+
+/// Debug info for a named local variable, giving where its value is held over a range of
+/// instructions in a prototype.
+///
+/// A variable may have several entries if it moves between locations or is only live in disjoint
+/// parts of the function. Variables that have been optimized away, or which are not live at some
+/// instruction, have no entry covering that instruction.
+#[derive(Debug, Clone, Collect)]
+#[collect(no_drop)]
+pub struct LocalVariable<S> {
+    pub name: S,
+    pub location: VariableLocation,
+    /// The first instruction at which `location` holds this variable.
+    pub start: usize,
+    /// One past the last instruction at which `location` holds this variable.
+    pub end: usize,
+}
+
+impl<S> LocalVariable<S> {
+    /// Returns true if this entry covers the given instruction index.
+    #[must_use]
+    pub fn contains(&self, instruction: usize) -> bool {
+        (self.start..self.end).contains(&instruction)
+    }
+
+    #[must_use]
+    pub fn map_string<S2>(self, map: impl Fn(S) -> S2) -> LocalVariable<S2> {
+        LocalVariable {
+            name: map(self.name),
+            location: self.location,
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
 pub struct FunctionIdentifier<S> {
     chunk_name: S,
     line_number: Option<LineNumber>,
@@ -0,0 +1,639 @@
+//! Heap snapshots for finding out what is using memory and what is keeping it alive.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt, mem,
+};
+
+use gc_arena::{Collect, Gc, Rootable};
+use serde_json::json;
+
+use crate::{
+    array::ArrayInner,
+    callback::CallbackInner,
+    closure::{ClosureInner, HeapVar},
+    interpreter::Context,
+    object::ObjectInner,
+    string::SharedStr,
+    thread::{Execution, Thread, ThreadInner, ThreadSlot},
+    value::Value,
+};
+
+/// The type of a [`HeapNode`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum HeapKind {
+    Object,
+    Array,
+    Closure,
+    Callback,
+    String,
+    UserData,
+    Thread,
+}
+
+impl HeapKind {
+    pub const ALL: [HeapKind; 7] = [
+        HeapKind::Object,
+        HeapKind::Array,
+        HeapKind::Closure,
+        HeapKind::Callback,
+        HeapKind::String,
+        HeapKind::UserData,
+        HeapKind::Thread,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HeapKind::Object => "object",
+            HeapKind::Array => "array",
+            HeapKind::Closure => "closure",
+            HeapKind::Callback => "callback",
+            HeapKind::String => "string",
+            HeapKind::UserData => "userdata",
+            HeapKind::Thread => "thread",
+        }
+    }
+}
+
+impl fmt::Display for HeapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A reference from one [`HeapNode`] to another.
+#[derive(Debug, Clone)]
+pub struct HeapEdge {
+    /// A description of where the reference is held, like a field name or an array index.
+    pub name: String,
+    /// The index of the referenced node in [`HeapSnapshot::nodes`].
+    pub node: usize,
+}
+
+/// How a [`HeapNode`] was first reached while walking the heap.
+///
+/// Since the heap is walked breadth first, following retainers always gives a shortest path from
+/// a root.
+#[derive(Debug, Copy, Clone)]
+pub enum Retainer {
+    /// The node is the root at this index in [`HeapSnapshot::roots`].
+    Root(usize),
+    /// The node was reached from another node through the edge at the given index.
+    Edge { node: usize, edge: usize },
+}
+
+/// A single GC allocation.
+#[derive(Debug, Clone)]
+pub struct HeapNode {
+    pub kind: HeapKind,
+    /// The address of the allocation, which identifies the same allocation across snapshots.
+    pub address: usize,
+    /// The approximate number of bytes used by this allocation, not including anything it
+    /// references.
+    pub size: usize,
+    /// A description of closures and strings, and of threads which could not be inspected.
+    pub label: Option<String>,
+    pub edges: Vec<HeapEdge>,
+    pub retainer: Retainer,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct HeapStats {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Every value reachable from a set of roots at some point in time.
+///
+/// Values are found by walking the values the VM knows how to inspect: objects, arrays, closures,
+/// callbacks, and threads. The contents of `UserData` and values held only by Rust code, like
+/// stashed values and registry singletons, cannot be inspected, so anything that should be
+/// included must be added as a root with [`HeapSnapshotBuilder`] or by a function registered with
+/// [`HeapSnapshotBuilder::register_roots`].
+#[derive(Debug, Clone, Default)]
+pub struct HeapSnapshot {
+    roots: Vec<(String, usize)>,
+    nodes: Vec<HeapNode>,
+    addresses: HashMap<(HeapKind, usize), usize>,
+}
+
+impl HeapSnapshot {
+    /// Take a snapshot of everything reachable from `ctx.globals()`.
+    pub fn new(ctx: Context<'_>) -> Self {
+        HeapSnapshotBuilder::new(ctx).finish()
+    }
+
+    /// The name and node index of every root.
+    pub fn roots(&self) -> &[(String, usize)] {
+        &self.roots
+    }
+
+    pub fn nodes(&self) -> &[HeapNode] {
+        &self.nodes
+    }
+
+    /// Find the node for the given value, if it was reached.
+    pub fn find(&self, value: Value<'_>) -> Option<usize> {
+        let (kind, address) = value_address(value)?;
+        self.find_address(kind, address)
+    }
+
+    pub fn find_address(&self, kind: HeapKind, address: usize) -> Option<usize> {
+        self.addresses.get(&(kind, address)).copied()
+    }
+
+    /// Returns the count and total size of every kind of node.
+    pub fn stats(&self) -> BTreeMap<HeapKind, HeapStats> {
+        let mut stats = HeapKind::ALL
+            .into_iter()
+            .map(|kind| (kind, HeapStats::default()))
+            .collect::<BTreeMap<_, _>>();
+        for node in &self.nodes {
+            let stats = stats.get_mut(&node.kind).unwrap();
+            stats.count += 1;
+            stats.bytes += node.size;
+        }
+        stats
+    }
+
+    /// A shortest path from a root to the given node, as the root name followed by the name of
+    /// every edge taken.
+    pub fn retaining_path(&self, mut node: usize) -> Vec<String> {
+        let mut path = Vec::new();
+        loop {
+            match self.nodes[node].retainer {
+                Retainer::Root(root) => {
+                    path.push(self.roots[root].0.clone());
+                    break;
+                }
+                Retainer::Edge { node: parent, edge } => {
+                    path.push(self.nodes[parent].edges[edge].name.clone());
+                    node = parent;
+                }
+            }
+        }
+        path.reverse();
+        path
+    }
+
+    /// Compare this snapshot with a newer one.
+    ///
+    /// Nodes are matched by kind and address. GC allocations never move, but the address of a
+    /// freed allocation may be reused, so this is only approximate.
+    pub fn diff<'a>(&'a self, newer: &'a HeapSnapshot) -> HeapDiff<'a> {
+        let added = (0..newer.nodes.len())
+            .filter(|&i| {
+                let node = &newer.nodes[i];
+                !self.addresses.contains_key(&(node.kind, node.address))
+            })
+            .collect();
+        let removed = (0..self.nodes.len())
+            .filter(|&i| {
+                let node = &self.nodes[i];
+                !newer.addresses.contains_key(&(node.kind, node.address))
+            })
+            .collect();
+        HeapDiff {
+            older: self,
+            newer,
+            added,
+            removed,
+        }
+    }
+
+    /// The whole snapshot as JSON, including every node and edge.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "stats": stats_json(&self.stats()),
+            "roots": self
+                .roots
+                .iter()
+                .map(|(name, node)| json!({ "name": name, "node": node }))
+                .collect::<Vec<_>>(),
+            "nodes": self
+                .nodes
+                .iter()
+                .map(|node| {
+                    let mut json = node_json(node);
+                    json["edges"] = node
+                        .edges
+                        .iter()
+                        .map(|edge| json!({ "name": edge.name, "node": edge.node }))
+                        .collect();
+                    json
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// The difference between two [`HeapSnapshot`]s.
+#[derive(Debug, Clone)]
+pub struct HeapDiff<'a> {
+    pub older: &'a HeapSnapshot,
+    pub newer: &'a HeapSnapshot,
+    /// Indexes of nodes in `newer` which are not in `older`.
+    pub added: Vec<usize>,
+    /// Indexes of nodes in `older` which are not in `newer`.
+    pub removed: Vec<usize>,
+}
+
+impl<'a> HeapDiff<'a> {
+    /// Returns the change in count and size of every kind of node.
+    pub fn stats(&self) -> BTreeMap<HeapKind, (HeapStats, HeapStats)> {
+        let older = self.older.stats();
+        let newer = self.newer.stats();
+        HeapKind::ALL
+            .into_iter()
+            .map(|kind| (kind, (older[&kind], newer[&kind])))
+            .collect()
+    }
+
+    /// The diff as JSON, with the retaining path of every added node.
+    pub fn to_json(&self) -> serde_json::Value {
+        let stats = self
+            .stats()
+            .into_iter()
+            .map(|(kind, (older, newer))| {
+                (
+                    kind.name().to_owned(),
+                    json!({
+                        "count": newer.count as i64 - older.count as i64,
+                        "bytes": newer.bytes as i64 - older.bytes as i64,
+                    }),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        json!({
+            "stats": stats,
+            "added": self
+                .added
+                .iter()
+                .map(|&i| {
+                    let mut json = node_json(&self.newer.nodes[i]);
+                    json["retaining_path"] = self.newer.retaining_path(i).into();
+                    json
+                })
+                .collect::<Vec<_>>(),
+            "removed": self
+                .removed
+                .iter()
+                .map(|&i| node_json(&self.older.nodes[i]))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A function which adds roots to a heap snapshot, see [`HeapSnapshotBuilder::register_roots`].
+pub type HeapRootsFn = for<'gc> fn(Context<'gc>, &mut HeapSnapshotBuilder<'gc>);
+
+#[derive(Default, Collect)]
+#[collect(require_static)]
+struct HeapRoots(RefCell<Vec<HeapRootsFn>>);
+
+/// Collects roots for a [`HeapSnapshot`].
+///
+/// `ctx.globals()` is always the first root, followed by the roots added by every function
+/// registered with [`HeapSnapshotBuilder::register_roots`].
+pub struct HeapSnapshotBuilder<'gc> {
+    ctx: Context<'gc>,
+    snapshot: HeapSnapshot,
+    visited: HashSet<(HeapKind, usize)>,
+    queue: VecDeque<(Item<'gc>, usize)>,
+}
+
+impl<'gc> HeapSnapshotBuilder<'gc> {
+    pub fn new(ctx: Context<'gc>) -> Self {
+        let mut this = Self {
+            ctx,
+            snapshot: HeapSnapshot::default(),
+            visited: HashSet::new(),
+            queue: VecDeque::new(),
+        };
+        this.add_root("globals", ctx.globals());
+
+        let roots_fns = ctx.singleton::<Rootable![HeapRoots]>().0.borrow().clone();
+        for roots_fn in roots_fns {
+            roots_fn(ctx, &mut this);
+        }
+
+        this
+    }
+
+    /// Register a function which adds roots to every heap snapshot taken in this interpreter.
+    ///
+    /// This allows an embedder to include values it has stashed, which otherwise cannot be found,
+    /// even in snapshots taken by scripts.
+    pub fn register_roots(ctx: Context<'gc>, roots_fn: HeapRootsFn) {
+        ctx.singleton::<Rootable![HeapRoots]>()
+            .0
+            .borrow_mut()
+            .push(roots_fn);
+    }
+
+    /// Add a value as a root.
+    ///
+    /// Roots which are not GC values, or which have already been reached from another root, are
+    /// ignored.
+    pub fn add_root(&mut self, name: impl Into<String>, value: impl Into<Value<'gc>>) {
+        if let Some(item) = Item::from_value(value.into()) {
+            self.push_root(name.into(), item);
+        }
+    }
+
+    /// Add a thread as a root.
+    ///
+    /// If the thread is currently running, its frames cannot be inspected. Use
+    /// [`HeapSnapshotBuilder::add_execution`] to include the running thread of a callback.
+    pub fn add_thread(&mut self, name: impl Into<String>, thread: Thread<'gc>) {
+        self.push_root(name.into(), Item::Thread(thread));
+    }
+
+    /// Add the thread running a callback as a root.
+    pub fn add_execution(&mut self, name: impl Into<String>, exec: &Execution<'gc, '_>) {
+        let mut values = Vec::new();
+        exec.visit_thread_values(|slot, value| values.push((slot, value)));
+        let index = self.snapshot.roots.len();
+        self.snapshot
+            .roots
+            .push((name.into(), self.snapshot.nodes.len()));
+        self.push_node(
+            HeapKind::Thread,
+            0,
+            mem::size_of::<ThreadInner<'gc>>(),
+            Some("running".to_owned()),
+            Retainer::Root(index),
+        );
+        let node = self.snapshot.nodes.len() - 1;
+        for (slot, value) in values {
+            self.add_edge(node, slot.to_string(), value);
+        }
+    }
+
+    /// Walk everything reachable from the roots.
+    pub fn finish(mut self) -> HeapSnapshot {
+        while let Some((item, node)) = self.queue.pop_front() {
+            self.visit(item, node);
+        }
+        self.snapshot
+    }
+
+    fn push_root(&mut self, name: String, item: Item<'gc>) {
+        let (kind, address) = item.address();
+        if self.visited.insert((kind, address)) {
+            let index = self.snapshot.roots.len();
+            let node = self.push_item(item, Retainer::Root(index));
+            self.snapshot.roots.push((name, node));
+        }
+    }
+
+    fn push_item(&mut self, item: Item<'gc>, retainer: Retainer) -> usize {
+        let (kind, address) = item.address();
+        let label = match item {
+            Item::Value(Value::String(s)) => Some(s.as_str().to_owned()),
+            Item::Value(Value::Closure(closure)) => {
+                Some(closure.prototype().identifier().to_string())
+            }
+            _ => None,
+        };
+        let node = self.push_node(kind, address, item.size(), label, retainer);
+        self.queue.push_back((item, node));
+        node
+    }
+
+    fn push_node(
+        &mut self,
+        kind: HeapKind,
+        address: usize,
+        size: usize,
+        label: Option<String>,
+        retainer: Retainer,
+    ) -> usize {
+        let node = self.snapshot.nodes.len();
+        self.snapshot.nodes.push(HeapNode {
+            kind,
+            address,
+            size,
+            label,
+            edges: Vec::new(),
+            retainer,
+        });
+        if address != 0 {
+            self.snapshot.addresses.insert((kind, address), node);
+        }
+        node
+    }
+
+    fn add_edge(&mut self, node: usize, name: String, value: Value<'gc>) {
+        let Some(item) = Item::from_value(value) else {
+            return;
+        };
+
+        let target = if self.visited.insert(item.address()) {
+            let edge = self.snapshot.nodes[node].edges.len();
+            self.push_item(item, Retainer::Edge { node, edge })
+        } else {
+            let (kind, address) = item.address();
+            self.snapshot.addresses[&(kind, address)]
+        };
+        self.snapshot.nodes[node]
+            .edges
+            .push(HeapEdge { name, node: target });
+    }
+
+    fn visit(&mut self, item: Item<'gc>, node: usize) {
+        match item {
+            Item::Value(Value::Object(object)) => {
+                let entries = match object.try_borrow() {
+                    Ok(map) => map.iter().collect::<Vec<_>>(),
+                    Err(_) => Vec::new(),
+                };
+                for (key, value) in entries {
+                    self.add_edge(node, key.as_str().to_owned(), value);
+                }
+                if let Some(parent) = object.parent() {
+                    self.add_edge(node, "<parent>".to_owned(), parent.into());
+                }
+            }
+            Item::Value(Value::Array(array)) => {
+                let values = match array.try_borrow() {
+                    Ok(array) => array.iter().collect::<Vec<_>>(),
+                    Err(_) => Vec::new(),
+                };
+                for (i, value) in values.into_iter().enumerate() {
+                    self.add_edge(node, format!("[{i}]"), value);
+                }
+            }
+            Item::Value(Value::Closure(closure)) => {
+                if let Some(this) = closure.this() {
+                    self.add_edge(node, "<self>".to_owned(), this);
+                }
+                for (i, heap_var) in closure.heap().iter().enumerate() {
+                    if let HeapVar::Shared(value) = heap_var {
+                        self.add_edge(node, format!("<upvalue {i}>"), value.get());
+                    }
+                }
+                let prototype = closure.prototype();
+                for (i, value) in prototype.static_vars().iter().enumerate() {
+                    self.add_edge(node, format!("<static {i}>"), value.get());
+                }
+                if let Some(constructor_super) = prototype.constructor_super() {
+                    self.add_edge(node, "<static super>".to_owned(), constructor_super.into());
+                }
+            }
+            Item::Value(Value::Callback(callback)) => {
+                if let Some(this) = callback.this() {
+                    self.add_edge(node, "<self>".to_owned(), this);
+                }
+            }
+            Item::Thread(thread) => {
+                let mut values = Vec::new();
+                if !thread.visit_values(|slot, value| values.push((slot, value))) {
+                    self.snapshot.nodes[node].label = Some("running".to_owned());
+                }
+                for (slot, value) in values {
+                    self.add_edge(node, slot.to_string(), value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Item<'gc> {
+    Value(Value<'gc>),
+    Thread(Thread<'gc>),
+}
+
+impl<'gc> Item<'gc> {
+    fn from_value(value: Value<'gc>) -> Option<Self> {
+        value_address(value).map(|_| Item::Value(value))
+    }
+
+    fn address(self) -> (HeapKind, usize) {
+        match self {
+            Item::Value(value) => value_address(value).unwrap(),
+            Item::Thread(thread) => (HeapKind::Thread, gc_address(thread.into_inner())),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Item::Value(Value::String(s)) => mem::size_of::<SharedStr>() + s.as_str().len(),
+            Item::Value(Value::Object(object)) => {
+                let len = object.try_borrow().map(|map| map.len()).unwrap_or(0);
+                mem::size_of::<ObjectInner<'gc>>()
+                    + len * mem::size_of::<(crate::string::String<'gc>, Value<'gc>)>()
+            }
+            Item::Value(Value::Array(array)) => {
+                let len = array.try_borrow().map(|array| array.len()).unwrap_or(0);
+                mem::size_of::<ArrayInner<'gc>>() + len * mem::size_of::<Value<'gc>>()
+            }
+            Item::Value(Value::Closure(closure)) => {
+                mem::size_of::<ClosureInner<'gc>>()
+                    + closure.heap().len() * mem::size_of::<HeapVar<'gc>>()
+            }
+            Item::Value(Value::Callback(_)) => mem::size_of::<CallbackInner<'gc>>(),
+            Item::Value(Value::UserData(user_data)) => user_data.size(),
+            Item::Value(_) => 0,
+            Item::Thread(_) => mem::size_of::<ThreadInner<'gc>>(),
+        }
+    }
+}
+
+fn gc_address<T: ?Sized>(gc: Gc<'_, T>) -> usize {
+    Gc::as_ptr(gc) as *const () as usize
+}
+
+fn value_address(value: Value<'_>) -> Option<(HeapKind, usize)> {
+    Some(match value {
+        Value::String(s) => (HeapKind::String, gc_address(s.into_inner())),
+        Value::Object(object) => (HeapKind::Object, gc_address(object.into_inner())),
+        Value::Array(array) => (HeapKind::Array, gc_address(array.into_inner())),
+        Value::Closure(closure) => (HeapKind::Closure, gc_address(closure.into_inner())),
+        Value::Callback(callback) => (HeapKind::Callback, gc_address(callback.into_inner())),
+        Value::UserData(user_data) => (HeapKind::UserData, gc_address(user_data.into_inner())),
+        Value::Undefined | Value::Boolean(_) | Value::Integer(_) | Value::Float(_) => return None,
+    })
+}
+
+fn stats_json(stats: &BTreeMap<HeapKind, HeapStats>) -> serde_json::Value {
+    stats
+        .iter()
+        .map(|(kind, stats)| {
+            (
+                kind.name().to_owned(),
+                json!({ "count": stats.count, "bytes": stats.bytes }),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn node_json(node: &HeapNode) -> serde_json::Value {
+    json!({
+        "kind": node.kind.name(),
+        "address": format!("{:#x}", node.address),
+        "size": node.size,
+        "label": node.label,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{array::Array, interpreter::Interpreter, object::Object};
+
+    use super::*;
+
+    #[test]
+    fn test_heap_snapshot() {
+        let interpreter = Interpreter::new();
+        interpreter.enter(|ctx| {
+            let leaked = Object::new(&ctx);
+            let array = Array::from_iter(&ctx, [leaked.into(), leaked.into()]);
+            let holder = Object::new(&ctx);
+            holder.borrow_mut(&ctx).set(ctx.intern("items"), array);
+            ctx.globals()
+                .borrow_mut(&ctx)
+                .set(ctx.intern("holder"), holder);
+
+            let before = HeapSnapshot::new(ctx);
+            let stats = before.stats();
+            assert_eq!(stats[&HeapKind::Object].count, 3);
+            assert_eq!(stats[&HeapKind::Array].count, 1);
+
+            let node = before.find(leaked.into()).unwrap();
+            assert_eq!(
+                before.retaining_path(node),
+                ["globals", "holder", "items", "[0]"]
+            );
+
+            let extra = Object::new(&ctx);
+            leaked.borrow_mut(&ctx).set(ctx.intern("extra"), extra);
+            let other = Object::new(&ctx);
+            let mut builder = HeapSnapshotBuilder::new(ctx);
+            builder.add_root("other", other);
+            builder.add_root("duplicate", holder);
+            let after = builder.finish();
+            assert_eq!(after.roots().len(), 2);
+
+            let diff = before.diff(&after);
+            assert_eq!(diff.added.len(), 2);
+            assert!(diff.removed.is_empty());
+            assert_eq!(diff.stats()[&HeapKind::Object].1.count, 5);
+
+            let extra_node = after.find(extra.into()).unwrap();
+            assert_eq!(
+                after.retaining_path(extra_node),
+                ["globals", "holder", "items", "[0]", "extra"]
+            );
+
+            let json = after.to_json();
+            assert_eq!(json["stats"]["object"]["count"], 5);
+            assert_eq!(json["nodes"].as_array().unwrap().len(), after.nodes().len());
+        });
+    }
+}
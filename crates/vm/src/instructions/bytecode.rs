@@ -10,7 +10,7 @@ use thiserror::Error;
 use crate::{
     debug::Span,
     instructions::instruction::{
-        ConstIdx, HeapIdx, InstIdx, Instruction, MagicIdx, ProtoIdx, RegIdx, StackIdx,
+        ConstIdx, HeapIdx, InstIdx, Instruction, MagicIdx, ProtoIdx, RegIdx, SerialField, StackIdx,
     },
 };
 
@@ -22,6 +22,20 @@ pub enum ByteCodeEncodingError {
     BadLastInstruction,
 }
 
+#[derive(Debug, Error)]
+pub enum ByteCodeDeserializationError {
+    #[error("unexpected end of serialized bytecode")]
+    UnexpectedEnd,
+    #[error("invalid opcode {0}")]
+    BadOpCode(u8),
+    #[error("invalid field for instruction {0}")]
+    BadField(usize),
+    #[error("invalid span for instruction {0}")]
+    BadSpan(usize),
+    #[error(transparent)]
+    Encoding(#[from] ByteCodeEncodingError),
+}
+
 /// An encoded list of [`Instruction`]s.
 ///
 /// Stored in a variable-length, optimized bytecode format internally.
@@ -43,19 +57,6 @@ impl ByteCode {
     pub fn encode(
         insts: impl IntoIterator<Item = (Instruction, Span)>,
     ) -> Result<Self, ByteCodeEncodingError> {
-        fn opcode_for_inst(inst: Instruction) -> OpCode {
-            macro_rules! match_inst {
-                ($(
-                    [$_category:ident] $(#[$_attr:meta])* $snake_name:ident = $name:ident { $($field:ident : $field_ty:ty),* $(,)? };
-                )*) => {
-                    match inst {
-                        $(Instruction::$name { .. } => OpCode::$name),*
-                    }
-                };
-            }
-            for_each_instruction!(match_inst)
-        }
-
         fn op_param_len(opcode: OpCode) -> usize {
             macro_rules! match_opcode {
                 ($(
@@ -205,6 +206,102 @@ impl ByteCode {
         (0..self.instruction_len()).map(|i| (self.instruction(i), self.span(i)))
     }
 
+    /// Serialize this bytecode into a stable binary format, appending it to the given buffer.
+    ///
+    /// Unlike the internal bytecode representation, the serialized format does not depend on the
+    /// host platform. Instructions are identified by their position in the instruction set, so
+    /// serialized bytecode should only be read back by a VM with the same
+    /// [`INSTRUCTION_SET_SIGNATURE`](super::INSTRUCTION_SET_SIGNATURE).
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(self.instruction_len() as u32).to_le_bytes());
+
+        for (inst, span) in self.decode() {
+            buf.push(opcode_for_inst(inst) as u8);
+
+            macro_rules! write_fields {
+                ($(
+                    [$_category:ident] $(#[$_attr:meta])* $snake_name:ident = $name:ident { $( $field:ident : $field_ty:ty ),* $(,)? };
+                )*) => {
+                    match inst {
+                        $(Instruction::$name { $($field),* } => {
+                            $(SerialField::write($field, buf);)*
+                        }),*
+                    }
+                };
+            }
+            for_each_instruction!(write_fields);
+
+            buf.extend_from_slice(&(span.start() as u64).to_le_bytes());
+            buf.extend_from_slice(&(span.end() as u64).to_le_bytes());
+        }
+    }
+
+    /// Deserialize bytecode previously written by [`ByteCode::serialize`].
+    ///
+    /// Reads from the front of the given slice, and advances it past the end of the serialized
+    /// bytecode. The deserialized instructions are validated exactly as with
+    /// [`ByteCode::encode`].
+    pub fn deserialize(bytes: &mut &[u8]) -> Result<Self, ByteCodeDeserializationError> {
+        fn read_u32(bytes: &mut &[u8]) -> Result<u32, ByteCodeDeserializationError> {
+            let (head, rest) = bytes
+                .split_first_chunk()
+                .ok_or(ByteCodeDeserializationError::UnexpectedEnd)?;
+            *bytes = rest;
+            Ok(u32::from_le_bytes(*head))
+        }
+
+        fn read_u64(bytes: &mut &[u8]) -> Result<u64, ByteCodeDeserializationError> {
+            let (head, rest) = bytes
+                .split_first_chunk()
+                .ok_or(ByteCodeDeserializationError::UnexpectedEnd)?;
+            *bytes = rest;
+            Ok(u64::from_le_bytes(*head))
+        }
+
+        let len = read_u32(bytes)? as usize;
+        // Every instruction takes at least 17 bytes, so don't trust a length which could not
+        // possibly fit.
+        let mut insts = Vec::with_capacity(len.min(bytes.len() / 17));
+
+        for i in 0..len {
+            let (&opcode, rest) = bytes
+                .split_first()
+                .ok_or(ByteCodeDeserializationError::UnexpectedEnd)?;
+            *bytes = rest;
+            let opcode =
+                OpCode::from_u8(opcode).ok_or(ByteCodeDeserializationError::BadOpCode(opcode))?;
+
+            macro_rules! read_fields {
+                ($(
+                    [$_category:ident] $(#[$_attr:meta])* $snake_name:ident = $name:ident { $( $field:ident : $field_ty:ty ),* $(,)? };
+                )*) => {
+                    match opcode {
+                        $(OpCode::$name => {
+                            $(
+                                let $field = <$field_ty as SerialField>::read(bytes)
+                                    .ok_or(ByteCodeDeserializationError::BadField(i))?;
+                            )*
+                            Instruction::$name { $($field),* }
+                        }),*
+                    }
+                };
+            }
+            let inst = for_each_instruction!(read_fields);
+
+            let start = read_u64(bytes)?;
+            let end = read_u64(bytes)?;
+            let span = match (usize::try_from(start), usize::try_from(end)) {
+                (Ok(start), Ok(end)) if start <= end => Span::new(start, end),
+                (Ok(usize::MAX), Ok(0)) => Span::null(),
+                _ => return Err(ByteCodeDeserializationError::BadSpan(i)),
+            };
+
+            insts.push((inst, span));
+        }
+
+        Ok(Self::encode(insts)?)
+    }
+
     pub fn pretty_print(&self, f: &mut dyn fmt::Write, indent: u8) -> fmt::Result {
         for (i, inst) in self.decode().map(|(i, _)| i).enumerate() {
             write!(f, "{:indent$}", "", indent = indent as usize)?;
@@ -505,10 +602,30 @@ macro_rules! define_opcode {
         enum OpCode {
             $($name),*
         }
+
+        impl OpCode {
+            fn from_u8(v: u8) -> Option<Self> {
+                const OPCODES: &[OpCode] = &[$(OpCode::$name),*];
+                OPCODES.get(v as usize).copied()
+            }
+        }
     };
 }
 for_each_instruction!(define_opcode);
 
+fn opcode_for_inst(inst: Instruction) -> OpCode {
+    macro_rules! match_inst {
+        ($(
+            [$_category:ident] $(#[$_attr:meta])* $snake_name:ident = $name:ident { $($field:ident : $field_ty:ty),* $(,)? };
+        )*) => {
+            match inst {
+                $(Instruction::$name { .. } => OpCode::$name),*
+            }
+        };
+    }
+    for_each_instruction!(match_inst)
+}
+
 #[inline]
 fn bytecode_write<T: Copy>(buf: &mut Vec<MaybeUninit<u8>>, val: T) {
     const { assert!(mem::align_of::<T>() == 1) }
@@ -565,4 +682,51 @@ mod tests {
         let decoded = bytecode.decode().map(|(i, _)| i).collect::<Vec<_>>();
         assert_eq!(insts, decoded.as_slice());
     }
+
+    #[test]
+    fn test_serialize_deserialize() {
+        let insts = &[
+            (
+                Instruction::LoadConstant {
+                    constant: ConstIdx(1),
+                    dest: RegIdx(2),
+                },
+                Span::new(0, 4),
+            ),
+            (
+                Instruction::JumpIfUndefined {
+                    target: InstIdx(3),
+                    arg: RegIdx(2),
+                    is_undefined: false,
+                },
+                Span::null(),
+            ),
+            (
+                Instruction::Call {
+                    func: RegIdx(2),
+                    this: Some(RegIdx(3)),
+                },
+                Span::new(5, 9),
+            ),
+            (
+                Instruction::GetMagic {
+                    dest: RegIdx(4),
+                    magic: MagicIdx(70000),
+                },
+                Span::new(9, 12),
+            ),
+            (Instruction::Return {}, Span::everywhere()),
+        ];
+
+        let bytecode = ByteCode::encode(insts.iter().copied()).unwrap();
+        let mut buf = Vec::new();
+        bytecode.serialize(&mut buf);
+
+        let mut bytes = buf.as_slice();
+        let deserialized = ByteCode::deserialize(&mut bytes).unwrap();
+        assert!(bytes.is_empty());
+        assert_eq!(insts, deserialized.decode().collect::<Vec<_>>().as_slice());
+
+        assert!(ByteCode::deserialize(&mut &buf[..buf.len() - 1]).is_err());
+    }
 }
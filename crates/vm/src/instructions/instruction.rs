@@ -53,6 +53,18 @@ macro_rules! make_idx {
                 write!(f, "{}{}", $prefix, self.0)
             }
         }
+
+        impl SerialField for $name {
+            fn write(self, buf: &mut Vec<u8>) {
+                buf.extend_from_slice(&self.0.to_le_bytes());
+            }
+
+            fn read(bytes: &mut &[u8]) -> Option<Self> {
+                let (head, rest) = bytes.split_first_chunk()?;
+                *bytes = rest;
+                Some(Self(<$ty>::from_le_bytes(*head)))
+            }
+        }
     };
 }
 
@@ -338,6 +350,65 @@ impl<T: PrettyField> PrettyField for Option<T> {
     }
 }
 
+/// A field of an [`Instruction`] which can be written to and read from a stable, portable binary
+/// format.
+pub(crate) trait SerialField: Sized {
+    fn write(self, buf: &mut Vec<u8>);
+    fn read(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+impl SerialField for bool {
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.push(self as u8);
+    }
+
+    fn read(bytes: &mut &[u8]) -> Option<Self> {
+        let (&b, rest) = bytes.split_first()?;
+        *bytes = rest;
+        match b {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl<T: SerialField> SerialField for Option<T> {
+    fn write(self, buf: &mut Vec<u8>) {
+        match self {
+            Some(v) => {
+                buf.push(1);
+                v.write(buf);
+            }
+            None => buf.push(0),
+        }
+    }
+
+    fn read(bytes: &mut &[u8]) -> Option<Self> {
+        match bool::read(bytes)? {
+            true => Some(Some(T::read(bytes)?)),
+            false => Some(None),
+        }
+    }
+}
+
+macro_rules! define_instruction_set_signature {
+    ($(
+        [$category:ident] $(#[$_attr:meta])* $snake_name:ident = $name:ident { $($field:ident: $field_ty:ty),* $(,)? };
+    )*) => {
+        /// A string describing the name, category and fields of every instruction, in order.
+        ///
+        /// Any change to the instruction set will change this string, so it can be used to detect
+        /// serialized instructions that were produced by an incompatible version of the VM.
+        pub const INSTRUCTION_SET_SIGNATURE: &str = concat!($(
+            "[", stringify!($category), "]",
+            stringify!($name),
+            "{", $(stringify!($field), ":", stringify!($field_ty), ",",)* "};"
+        ),*);
+    };
+}
+for_each_instruction!(define_instruction_set_signature);
+
 impl Instruction {
     pub fn pretty_print(self, f: &mut dyn fmt::Write) -> fmt::Result {
         macro_rules! impl_debug {
@@ -3,8 +3,11 @@ mod instruction;
 mod bytecode;
 
 pub use self::{
-    bytecode::{ByteCode, ByteCodeEncodingError, Dispatch, Dispatcher},
+    bytecode::{
+        ByteCode, ByteCodeDeserializationError, ByteCodeEncodingError, Dispatch, Dispatcher,
+    },
     instruction::{
-        ConstIdx, HeapIdx, IndexType, InstIdx, Instruction, MagicIdx, ProtoIdx, RegIdx, StackIdx,
+        ConstIdx, HeapIdx, INSTRUCTION_SET_SIGNATURE, IndexType, InstIdx, Instruction, MagicIdx,
+        ProtoIdx, RegIdx, StackIdx,
     },
 };
@@ -8,10 +8,12 @@ use gc_arena::{
 };
 
 use crate::{
+    heap::{HeapSnapshot, HeapSnapshotBuilder},
     object::Object,
     registry::{Registry, Singleton},
     stash::{Fetchable, Stashable},
     string::{InternedStrings, String},
+    weak::Finalizers,
 };
 
 pub const MAX_ZST_CACHE_ALIGN: usize = 16;
@@ -73,6 +75,12 @@ impl<'gc> Context<'gc> {
         self.state.registry.fetch(f)
     }
 
+    /// The finalizer queue for this interpreter, see [`Finalizers`].
+    #[inline]
+    pub fn finalizers(self) -> Finalizers<'gc> {
+        self.state.finalizers
+    }
+
     #[inline]
     pub fn interned_strings(self) -> InternedStrings<'gc> {
         self.state.interned_strings
@@ -138,23 +146,50 @@ impl Interpreter {
         self.arena.mutate(move |mc, state| f(state.ctx(mc)))
     }
 
+    /// Take a snapshot of everything reachable from the globals and from any roots added by `f`.
+    ///
+    /// See [`HeapSnapshot`] for what can and cannot be found automatically.
+    pub fn heap_snapshot(
+        &self,
+        f: impl for<'gc> FnOnce(Context<'gc>, &mut HeapSnapshotBuilder<'gc>),
+    ) -> HeapSnapshot {
+        self.enter(|ctx| {
+            let mut builder = HeapSnapshotBuilder::new(ctx);
+            f(ctx, &mut builder);
+            builder.finish()
+        })
+    }
+
     pub fn gc_metrics(&self) -> &GcMetrics {
         self.arena.metrics()
     }
 
     /// Collect any outstanding GC debt according to the configured GC pacing.
+    ///
+    /// If this leaves the GC between collection cycles, any finalizers whose owners have been
+    /// collected become ready.
     pub fn gc_collect_debt(&mut self) {
         self.arena.collect_debt();
+        if matches!(self.arena.collection_phase(), GcCollectionPhase::Sleeping) {
+            self.poll_finalizers();
+        }
     }
 
     /// Finish the current GC cycle
+    ///
+    /// Any finalizers whose owners were collected become ready.
     pub fn gc_finish_cycle(&mut self) {
         self.arena.finish_cycle();
+        self.poll_finalizers();
     }
 
     pub fn gc_collection_phase(&self) -> GcCollectionPhase {
         self.arena.collection_phase()
     }
+
+    fn poll_finalizers(&self) {
+        self.enter(|ctx| ctx.finalizers().poll(&ctx));
+    }
 }
 
 #[derive(Copy, Clone, Collect)]
@@ -164,6 +199,7 @@ struct State<'gc> {
     registry: Registry<'gc>,
     interned_strings: InternedStrings<'gc>,
     zst_cache: ZstCache<'gc, MAX_ZST_CACHE_ALIGN>,
+    finalizers: Finalizers<'gc>,
 }
 
 impl<'gc> State<'gc> {
@@ -173,6 +209,7 @@ impl<'gc> State<'gc> {
             registry: Registry::new(mc),
             interned_strings: InternedStrings::new(mc),
             zst_cache: ZstCache::new(mc),
+            finalizers: Finalizers::new(mc),
         }
     }
 
@@ -6,6 +6,7 @@ pub mod closure;
 pub mod conversion;
 pub mod debug;
 pub mod error;
+pub mod heap;
 pub mod instructions;
 pub mod interpreter;
 pub mod magic;
@@ -16,6 +17,7 @@ pub mod string;
 pub mod thread;
 pub mod user_data;
 pub mod value;
+pub mod weak;
 
 pub use self::{
     array::{Array, ArrayVec},
@@ -23,8 +25,9 @@ pub use self::{
     callback::{Callback, CallbackFn},
     closure::{Closure, Constant, Prototype},
     conversion::{FromMultiValue, FromValue, IntoMultiValue, IntoValue, TypeError, Variadic},
-    debug::{Chunk, FunctionRef, LineNumber, Span},
+    debug::{Chunk, FunctionRef, LineNumber, LocalVariable, Span, VariableLocation},
     error::{Error, ExternError, ExternScriptError, ExternValue, RuntimeError, ScriptError},
+    heap::{HeapDiff, HeapKind, HeapSnapshot, HeapSnapshotBuilder},
     instructions::ByteCode,
     interpreter::{Context, Interpreter},
     magic::{Magic, MagicConstant, MagicSet},
@@ -37,9 +40,11 @@ pub use self::{
     },
     string::{InternedStrings, SharedStr, String, StringMap, StringSet},
     thread::{
-        Backtrace, Execution, ExternBacktrace, ExternVmError, FrameStack, Hook, Stack, StackFrame,
-        Thread, VmError,
+        Backtrace, Execution, ExternBacktrace, ExternVmError, FrameDepthLimitError, FrameStack,
+        Hook, InstructionLimitError, MemoryLimitError, NestedThreadsError, Stack, StackFrame,
+        SuspendError, Thread, ThreadExit, ThreadLimits, ThreadStatus, VmError,
     },
     user_data::{BadUserDataType, UserData, UserDataIter, UserDataMeta, UserDataMethods},
     value::{Function, Value},
+    weak::{BadWeakValueType, Finalizers, WeakValue},
 };
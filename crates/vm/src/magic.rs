@@ -134,20 +134,41 @@ impl<'gc> MagicSet<'gc> {
         }
     }
 
+    /// Iterate over the name and index of every magic variable, in index order.
+    ///
+    /// Strings hash by pointer, so the iteration order must not depend on the internal name map.
+    /// Keeping a stable order here means that merging magic sets results in stable indexes, which
+    /// is required for compiled bytecode to be reusable.
     pub fn names(&self) -> impl Iterator<Item = (String<'gc>, usize)> {
-        self.names.iter().map(|(n, i)| (*n, *i))
+        let mut names = self.names.iter().map(|(n, i)| (*n, *i)).collect::<Vec<_>>();
+        names.sort_unstable_by_key(|&(_, i)| i);
+        names.into_iter()
     }
 
     /// Merge the given `MagicSet`, overwriting any existing values.
     ///
     /// No existing variable will change its index, even if it is overwritten. All newly inserted
-    /// variables will be assigned new indexes.
+    /// variables will be assigned new indexes, in the same relative order as in `other`.
     pub fn merge(&mut self, other: &MagicSet<'gc>) {
-        for (&name, &index) in &other.names {
+        for (name, index) in other.names() {
             self.insert(name, other.registered[index].get());
         }
     }
 
+    /// Create a new `MagicSet` containing only the variables for which `f` returns true.
+    ///
+    /// Kept variables are inserted in the same relative order as in `self`, but their indexes will
+    /// change if any variable before them is removed.
+    pub fn filter(&self, mut f: impl FnMut(String<'gc>) -> bool) -> MagicSet<'gc> {
+        let mut filtered = MagicSet::new();
+        for (name, index) in self.names() {
+            if f(name) {
+                filtered.insert(name, self.registered[index].get());
+            }
+        }
+        filtered
+    }
+
     /// Find the index for a magic variable with the given name, if it exists.
     pub fn find(&self, name: String<'gc>) -> Option<usize> {
         self.names.get(&name).copied()
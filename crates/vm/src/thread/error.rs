@@ -1,14 +1,19 @@
 use std::{error::Error as StdError, fmt, ops, rc::Rc, sync::Arc};
 
-use gc_arena::{Collect, Mutation, Rootable};
+use gc_arena::{Collect, Gc, Mutation, Rootable};
 
 use crate::{
+    array::Array,
+    builtins::BuiltIns,
     callback::Callback,
     closure::Closure,
-    debug::LineNumber,
+    debug::{FunctionRef, LineNumber},
     error::{Error, ExternError, RawGc, RuntimeError, ScriptError},
+    interpreter::Context,
+    object::Object,
     string::SharedStr,
     user_data::{BadUserDataType, UserData},
+    value::Value,
 };
 
 #[derive(Debug)]
@@ -45,6 +50,58 @@ impl<'gc> fmt::Display for VmError<'gc> {
 }
 
 impl<'gc> VmError<'gc> {
+    /// Convert this error into the value caught by a script `catch` block.
+    ///
+    /// Script errors are caught as the original thrown value. Runtime errors are converted into an
+    /// exception struct with the same fields as a GameMaker exception:
+    ///
+    /// - `message`: the error message.
+    /// - `longMessage`: the error message followed by the stack trace.
+    /// - `script`: the name of the function the error occurred in.
+    /// - `line`: the line the error occurred on, or -1 if it did not occur in a script function.
+    /// - `stacktrace`: an array of `"function (line N)"` strings, innermost frame first.
+    pub fn to_exception(&self, ctx: Context<'gc>) -> Value<'gc> {
+        let runtime_error = match &self.error {
+            Error::Script(err) => return err.0,
+            Error::Runtime(err) => err,
+        };
+
+        let message = runtime_error.to_string();
+        let frames = match &self.backtrace {
+            Some(backtrace) => backtrace.script_frames(ctx),
+            None => Vec::new(),
+        };
+        let stacktrace = frames
+            .iter()
+            .map(|(function_name, line)| format!("{function_name} (line {line})"))
+            .collect::<Vec<_>>();
+
+        let mut long_message = message.clone();
+        for frame in &stacktrace {
+            long_message.push_str("\n\t");
+            long_message.push_str(frame);
+        }
+
+        let (script, line) = match frames.first() {
+            Some(&(function_name, line)) => (function_name, (line.0 + 1) as i64),
+            None => ("", -1),
+        };
+
+        let exception = Object::new(&ctx);
+        {
+            let mut exception = exception.borrow_mut(&ctx);
+            exception.set(ctx.intern_static("message"), ctx.intern(&message));
+            exception.set(ctx.intern_static("longMessage"), ctx.intern(&long_message));
+            exception.set(ctx.intern_static("script"), ctx.intern(script));
+            exception.set(ctx.intern_static("line"), line);
+            exception.set(
+                ctx.intern_static("stacktrace"),
+                Array::from_iter(&ctx, stacktrace.iter().map(|f| ctx.intern(f).into())),
+            );
+        }
+        exception.into()
+    }
+
     pub fn into_extern(self) -> ExternVmError {
         ExternVmError {
             error: self.error.into_extern(),
@@ -120,6 +177,38 @@ impl<'gc> Backtrace<'gc> {
     pub fn to_extern(&self) -> ExternBacktrace {
         self.0.iter().map(|f| f.to_extern()).collect()
     }
+
+    /// Describe every script function frame as its function name and current line, innermost frame
+    /// first.
+    ///
+    /// Callback frames have no line information and are skipped. The closures which `try` blocks
+    /// are desugared into are not shown as frames of their own. Instead, each one stands in for the
+    /// frame of the function containing the `try` block, so errors inside of a `try` block are
+    /// reported at their line within that function.
+    pub fn script_frames(&self, ctx: Context<'gc>) -> Vec<(&'gc str, LineNumber)> {
+        let try_catch = BuiltIns::singleton(ctx).try_catch;
+
+        let mut frames: Vec<(&'gc str, LineNumber)> = Vec::new();
+        let mut in_try_block = false;
+        for frame in self.0.iter() {
+            match frame {
+                StackFrame::Closure(closure_frame) => {
+                    if in_try_block && let Some((_, line)) = frames.last_mut() {
+                        *line = closure_frame.line_number();
+                    } else {
+                        frames.push((closure_frame.function_name(), closure_frame.line_number()));
+                    }
+                    in_try_block = false;
+                }
+                StackFrame::Callback(callback) => {
+                    in_try_block = *callback == try_catch;
+                }
+            }
+        }
+
+        frames.reverse();
+        frames
+    }
 }
 
 #[derive(Debug, Copy, Clone, Collect)]
@@ -134,6 +223,17 @@ impl<'gc> ClosureStackFrame<'gc> {
         self.closure.prototype().chunk().name()
     }
 
+    /// The name of the executing function, `<anonymous>` for function expressions, or the chunk
+    /// name for top-level chunk code.
+    pub fn function_name(&self) -> &'gc str {
+        let prototype = Gc::as_ref(self.closure.prototype());
+        match prototype.reference() {
+            FunctionRef::Named(name, _) => name.as_str(),
+            FunctionRef::Expression(_) => "<anonymous>",
+            FunctionRef::Chunk => prototype.chunk().name().as_str(),
+        }
+    }
+
     pub fn line_number(&self) -> LineNumber {
         let chunk = self.closure.prototype().chunk();
         let prototype = self.closure.prototype();
@@ -0,0 +1,198 @@
+use gc_arena::{Collect, Mutation};
+use thiserror::Error;
+
+use crate::error::{Error, RuntimeError};
+
+/// The most instructions that may run between memory checks.
+const MEMORY_CHECK_INSTRUCTIONS: u32 = 1024;
+
+/// The most threads that may be running inside of each other with `Execution::start_thread` or
+/// `Execution::resume_thread`, whether or not any limits are set.
+///
+/// Each nested thread runs deeper on the Rust stack, so this must be bounded to avoid overflowing
+/// it.
+const MAX_NESTED_THREADS: usize = 100;
+
+/// Resource limits enforced on every run of a [`Thread`](super::Thread).
+///
+/// Exceeding any limit raises a distinct [`RuntimeError`] which cannot be caught by `pcall` or by
+/// script `try` / `catch`. This makes it possible to run untrusted scripts without them being able
+/// to loop forever, recurse without bound, or allocate without bound.
+///
+/// A thread started or resumed from within a callback with
+/// [`Execution::start_thread`](super::Execution::start_thread) or
+/// [`Execution::resume_thread`](super::Execution::resume_thread) runs under the limits of the
+/// calling thread instead of its own, and shares its budget: every instruction it executes counts
+/// towards the caller's instruction limit, and its frames are added to the caller's frame depth.
+/// Threads run in any other way only have their own limits.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct ThreadLimits {
+    /// The maximum number of VM instructions that may execute in a single call to `Thread::run`,
+    /// `Thread::start`, `Thread::resume`, or `Thread::exec`, including those executed by any
+    /// threads started or resumed from it with `Execution::start_thread` or
+    /// `Execution::resume_thread`.
+    pub max_instructions: Option<u64>,
+    /// The maximum number of frames, both script closures and Rust callbacks, that may be on the
+    /// thread's frame stack at once, along with the frame stacks of every thread it is running
+    /// with `Execution::start_thread` or `Execution::resume_thread`.
+    pub max_frame_depth: Option<usize>,
+    /// The maximum total allocation of the entire GC arena in bytes, as reported by
+    /// [`Interpreter::gc_metrics`](crate::Interpreter::gc_metrics).
+    ///
+    /// Only allocations tracked by the arena count towards this, and garbage counts until it has
+    /// been collected. This is checked periodically as instructions execute and whenever a
+    /// function is called, so a single large allocation may exceed it before the error is raised.
+    pub max_memory: Option<usize>,
+}
+
+impl ThreadLimits {
+    /// Returns true if the given error was raised because a thread exceeded one of its limits.
+    pub fn is_limit_error(error: &Error<'_>) -> bool {
+        match error {
+            Error::Script(_) => false,
+            Error::Runtime(err) => {
+                err.is::<InstructionLimitError>()
+                    || err.is::<FrameDepthLimitError>()
+                    || err.is::<MemoryLimitError>()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Error)]
+#[error("exceeded the instruction limit of {0}")]
+pub struct InstructionLimitError(pub u64);
+
+#[derive(Debug, Copy, Clone, Error)]
+#[error("exceeded the frame depth limit of {0}")]
+pub struct FrameDepthLimitError(pub usize);
+
+#[derive(Debug, Copy, Clone, Error)]
+#[error("exceeded the memory limit of {0} bytes")]
+pub struct MemoryLimitError(pub usize);
+
+#[derive(Debug, Copy, Clone, Error)]
+#[error("exceeded the maximum of {MAX_NESTED_THREADS} nested threads")]
+pub struct NestedThreadsError;
+
+/// Tracks the configured limits for a single thread along with how much of them has been used.
+///
+/// While a thread runs another with `Execution::start_thread` or `Execution::resume_thread`, the
+/// other thread uses a limiter lent from this one, see [`Limiter::lend`].
+#[derive(Debug, Default, Collect)]
+#[collect(require_static)]
+pub(super) struct Limiter {
+    limits: ThreadLimits,
+    instructions: u64,
+    // The number of frames on the stacks of every thread this limiter was lent from.
+    frame_base: usize,
+    // The number of threads this limiter was lent through.
+    nested: usize,
+}
+
+impl Limiter {
+    pub(super) fn limits(&self) -> ThreadLimits {
+        self.limits
+    }
+
+    pub(super) fn set_limits(&mut self, limits: ThreadLimits) {
+        self.limits = limits;
+    }
+
+    /// Returns true if executed instructions must be reported to [`Limiter::step`].
+    pub(super) fn is_metered(&self) -> bool {
+        self.limits.max_instructions.is_some() || self.limits.max_memory.is_some()
+    }
+
+    /// Start a new run, resetting the instruction count.
+    pub(super) fn reset(&mut self) {
+        self.instructions = 0;
+    }
+
+    /// Create a limiter for a thread run by the thread which owns this one, which has `frame_depth`
+    /// frames on its stack.
+    ///
+    /// The new limiter has the same limits and has used the same amount of them, once the other
+    /// thread stops running it must be returned with [`Limiter::repay`].
+    pub(super) fn lend(&self, frame_depth: usize) -> Result<Limiter, RuntimeError> {
+        if self.nested >= MAX_NESTED_THREADS {
+            return Err(NestedThreadsError.into());
+        }
+
+        Ok(Limiter {
+            limits: self.limits,
+            instructions: self.instructions,
+            frame_base: self.frame_base + frame_depth,
+            nested: self.nested + 1,
+        })
+    }
+
+    /// Charge everything used through a limiter created by [`Limiter::lend`] to this one.
+    pub(super) fn repay(&mut self, lent: Limiter) {
+        self.instructions = lent.instructions;
+    }
+
+    /// Record `instruction_count` executed instructions and check every limit.
+    ///
+    /// Returns how many more instructions may execute before this must be called again, or 0 if it
+    /// never needs to be called.
+    pub(super) fn step(
+        &mut self,
+        mc: &Mutation<'_>,
+        instruction_count: u32,
+    ) -> Result<u32, RuntimeError> {
+        self.check_memory(mc)?;
+
+        let mut next = 0;
+
+        if let Some(max_instructions) = self.limits.max_instructions {
+            self.instructions = self
+                .instructions
+                .saturating_add(u64::from(instruction_count));
+            if self.instructions > max_instructions {
+                return Err(InstructionLimitError(max_instructions).into());
+            }
+
+            // Pause only once the limit has been exceeded, a run which uses exactly its budget
+            // should not error.
+            next = (max_instructions - self.instructions)
+                .saturating_add(1)
+                .try_into()
+                .unwrap_or(u32::MAX);
+        }
+
+        if self.limits.max_memory.is_some() {
+            next = if next == 0 {
+                MEMORY_CHECK_INSTRUCTIONS
+            } else {
+                next.min(MEMORY_CHECK_INSTRUCTIONS)
+            };
+        }
+
+        Ok(next)
+    }
+
+    /// Check the limits that apply when pushing a new frame, which will make the frame stack
+    /// `frame_depth` frames deep.
+    pub(super) fn check_call(
+        &self,
+        mc: &Mutation<'_>,
+        frame_depth: usize,
+    ) -> Result<(), RuntimeError> {
+        if let Some(max_frame_depth) = self.limits.max_frame_depth {
+            if self.frame_base + frame_depth > max_frame_depth {
+                return Err(FrameDepthLimitError(max_frame_depth).into());
+            }
+        }
+        self.check_memory(mc)
+    }
+
+    fn check_memory(&self, mc: &Mutation<'_>) -> Result<(), RuntimeError> {
+        if let Some(max_memory) = self.limits.max_memory {
+            if mc.metrics().total_allocation() > max_memory {
+                return Err(MemoryLimitError(max_memory).into());
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 mod dispatch;
 mod error;
+mod limits;
 mod stack;
 mod thread;
 mod vec_end_slice;
@@ -10,6 +11,15 @@ pub use self::{
         Backtrace, ClosureStackFrame, ExternBacktrace, ExternClosureStackFrame, ExternStackFrame,
         ExternVmError, StackFrame, VmError,
     },
+    limits::{
+        FrameDepthLimitError, InstructionLimitError, MemoryLimitError, NestedThreadsError,
+        ThreadLimits,
+    },
     stack::Stack,
-    thread::{Execution, FrameStack, Hook, Thread, ThreadInner, ThreadState},
+    thread::{
+        Execution, FrameStack, Hook, SuspendError, Thread, ThreadExit, ThreadInner, ThreadState,
+        ThreadStatus,
+    },
 };
+
+pub(crate) use self::thread::ThreadSlot;
@@ -1,17 +1,21 @@
+use std::{fmt, mem};
+
 use gc_arena::{
     Collect, Gc, Lock, Mutation, RefLock,
     collect::{DynCollect, dyn_collect},
 };
+use thiserror::Error;
 
 use crate::{
     callback::Callback,
-    closure::{Closure, SharedValue},
+    closure::{Closure, HeapVar, HeapVarDescriptor, SharedValue},
     error::RuntimeError,
-    instructions,
+    instructions::{self, IndexType as _},
     interpreter::Context,
     thread::{
         dispatch,
         error::{ClosureStackFrame, ExternVmError, StackFrame},
+        limits::{Limiter, ThreadLimits},
         stack::Stack,
         vec_end_slice::VecEndSlice,
     },
@@ -36,6 +40,42 @@ pub struct ThreadState<'gc> {
     this: Vec<Value<'gc>>,
     heap: Vec<OwnedHeapVar<'gc>>,
     hook: Option<Box<dyn Hook<'gc>>>,
+    limiter: Limiter,
+    // Set while the thread is being run by `Thread::start` or `Thread::resume`, which are the only
+    // ways to run a thread that allow it to be suspended.
+    resumable: bool,
+    // Set when the thread is suspended (or a callback has requested suspension), holds the position
+    // in the stack of the suspending callback's arguments.
+    suspended: Option<usize>,
+}
+
+/// The current status of a [`Thread`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ThreadStatus {
+    /// The thread is not executing anything and has no suspended frames.
+    Stopped,
+    /// The thread has been suspended with [`Execution::suspend`] and may be resumed with
+    /// [`Thread::resume`].
+    Suspended,
+    /// The thread is currently executing.
+    Running,
+}
+
+/// The reason that [`Thread::start`] or [`Thread::resume`] stopped executing.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ThreadExit {
+    /// A callback suspended the thread, the returned values are the arguments to that callback.
+    Suspended,
+    /// The started function returned, the returned values are its return values.
+    Returned,
+}
+
+#[derive(Debug, Copy, Clone, Error)]
+pub enum SuspendError {
+    #[error("thread is not resumable")]
+    NotResumable,
+    #[error("cannot suspend thread across a callback boundary")]
+    CallbackBoundary,
 }
 
 impl<'gc> Thread<'gc> {
@@ -50,6 +90,9 @@ impl<'gc> Thread<'gc> {
                 this: Vec::new(),
                 heap: Vec::new(),
                 hook: None,
+                limiter: Limiter::default(),
+                resumable: false,
+                suspended: None,
             }),
         ))
     }
@@ -72,6 +115,40 @@ impl<'gc> Thread<'gc> {
         self.0.borrow_mut(mc).hook = None;
     }
 
+    /// Replace the current hook, returning the previous one.
+    ///
+    /// This allows temporarily installing a hook and later restoring whatever hook was set before.
+    pub fn replace_hook(
+        self,
+        mc: &Mutation<'gc>,
+        hook: Option<Box<dyn Hook<'gc>>>,
+    ) -> Option<Box<dyn Hook<'gc>>> {
+        mem::replace(&mut self.0.borrow_mut(mc).hook, hook)
+    }
+
+    /// Returns the limits currently enforced on this thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread is currently running, use [`Execution::limits`] from inside of a
+    /// running thread instead.
+    pub fn limits(self) -> ThreadLimits {
+        self.0.borrow().limiter.limits()
+    }
+
+    /// Set the limits enforced on every future run of this thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread is currently running.
+    pub fn set_limits(self, mc: &Mutation<'gc>, limits: ThreadLimits) {
+        self.0
+            .try_borrow_mut(mc)
+            .expect("thread locked")
+            .limiter
+            .set_limits(limits);
+    }
+
     /// Run a function on this `Thread` and discard all return values.
     pub fn run(
         self,
@@ -94,6 +171,112 @@ impl<'gc> Thread<'gc> {
             .map_err(VmError::into_extern)
     }
 
+    /// Returns the current status of this thread.
+    pub fn status(self) -> ThreadStatus {
+        match self.0.try_borrow() {
+            Ok(state) if state.suspended.is_some() => ThreadStatus::Suspended,
+            Ok(_) => ThreadStatus::Stopped,
+            Err(_) => ThreadStatus::Running,
+        }
+    }
+
+    /// Call `f` with every value held by this thread's frames and stacks.
+    ///
+    /// Returns false without calling `f` if the thread is currently running.
+    pub(crate) fn visit_values(self, f: impl FnMut(ThreadSlot, Value<'gc>)) -> bool {
+        match self.0.try_borrow() {
+            Ok(state) => {
+                state.visit_values(f);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Start running a function on this `Thread` in a way that allows it to be suspended.
+    ///
+    /// Arguments to the function are drained from the provided stack. If the function returns,
+    /// its return values are placed into the stack. If the thread is suspended via
+    /// [`Execution::suspend`], the arguments to the suspending callback are placed into the stack
+    /// instead, and the thread may be continued later with [`Thread::resume`].
+    ///
+    /// If the function errors, the thread is left stopped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread is not [`ThreadStatus::Stopped`].
+    pub fn start(
+        self,
+        ctx: Context<'gc>,
+        function: impl Into<Function<'gc>>,
+        stack: Stack<'gc, '_>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        self.start_limited(ctx, function, stack, None)
+    }
+
+    fn start_limited(
+        self,
+        ctx: Context<'gc>,
+        function: impl Into<Function<'gc>>,
+        mut stack: Stack<'gc, '_>,
+        lent_limiter: Option<&mut Limiter>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        let mut state = self.0.try_borrow_mut(&ctx).expect("thread locked");
+        assert!(
+            state.frames.is_empty() && state.suspended.is_none(),
+            "cannot start thread, thread is not stopped"
+        );
+
+        state.with_limiter(lent_limiter, |state| {
+            state.stack.extend(stack.drain(..));
+            state.resumable = true;
+            let res = match function.into() {
+                Function::Closure(closure) => state.call_closure(ctx, closure, 0),
+                Function::Callback(callback) => {
+                    state.call_callback(ctx, callback, 0, callback.this())
+                }
+            };
+            state.finish_resumable(res, stack)
+        })
+    }
+
+    /// Resume a thread suspended by [`Execution::suspend`].
+    ///
+    /// Values are drained from the provided stack and become the return values of the callback
+    /// which suspended the thread. Otherwise, this behaves exactly like [`Thread::start`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread is not [`ThreadStatus::Suspended`].
+    pub fn resume(
+        self,
+        ctx: Context<'gc>,
+        stack: Stack<'gc, '_>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        self.resume_limited(ctx, stack, None)
+    }
+
+    fn resume_limited(
+        self,
+        ctx: Context<'gc>,
+        mut stack: Stack<'gc, '_>,
+        lent_limiter: Option<&mut Limiter>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        let mut state = self.0.try_borrow_mut(&ctx).expect("thread locked");
+        let suspended_bottom = state
+            .suspended
+            .take()
+            .expect("cannot resume thread, thread is not suspended");
+
+        state.with_limiter(lent_limiter, |state| {
+            state.stack.truncate(suspended_bottom);
+            state.stack.extend(stack.drain(..));
+            state.resumable = true;
+            let res = state.run_closures(ctx, 0);
+            state.finish_resumable(res, stack)
+        })
+    }
+
     /// Create a top-level [`Execution`] context outside of a callback.
     pub fn exec<R>(self, ctx: Context<'gc>, f: impl FnOnce(Execution<'gc, '_>) -> R) -> R {
         self.enter_state(&ctx, |state| {
@@ -116,10 +299,12 @@ impl<'gc> Thread<'gc> {
                 && thread.stack.is_empty()
                 && thread.stack_frame_boundaries.is_empty()
                 && thread.this.is_empty()
-                && thread.heap.is_empty(),
-            "cannot enter thread state, thread is poisoned"
+                && thread.heap.is_empty()
+                && thread.suspended.is_none(),
+            "cannot enter thread state, thread is poisoned or suspended"
         );
 
+        thread.limiter.reset();
         let ret = f(&mut *thread);
 
         thread.registers.clear();
@@ -252,6 +437,69 @@ impl<'gc, 'a> Execution<'gc, 'a> {
         self.thread.frames.len()
     }
 
+    /// Returns the limits enforced on the parent `Thread`.
+    ///
+    /// Callbacks which run other threads should generally do so with [`Execution::start_thread`]
+    /// and [`Execution::resume_thread`], which run them under these limits.
+    #[inline]
+    pub fn limits(&self) -> ThreadLimits {
+        self.thread.limiter.limits()
+    }
+
+    /// Within a callback, start running a function on another `Thread` in a way that allows it to
+    /// be suspended.
+    ///
+    /// This behaves like [`Thread::start`] using this execution's stack, except that the other
+    /// thread runs under the limits of the parent `Thread` and shares its budget, so that scripts
+    /// cannot escape their limits by running code on a different thread. See [`ThreadLimits`].
+    ///
+    /// At most 100 threads may be run inside of each other this way, running any more raises a
+    /// [`NestedThreadsError`](super::NestedThreadsError).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the other thread is not [`ThreadStatus::Stopped`].
+    pub fn start_thread(
+        &mut self,
+        ctx: Context<'gc>,
+        thread: Thread<'gc>,
+        function: impl Into<Function<'gc>>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        let mut lent = self
+            .thread
+            .limiter
+            .lend(self.thread.frames.len())
+            .map_err(|err| self.thread.vm_error(err))?;
+        let stack = Stack::new(&mut self.thread.stack, self.stack_bottom);
+        let res = thread.start_limited(ctx, function, stack, Some(&mut lent));
+        self.thread.limiter.repay(lent);
+        res
+    }
+
+    /// Within a callback, resume another `Thread` suspended by [`Execution::suspend`].
+    ///
+    /// This behaves like [`Thread::resume`] using this execution's stack, with the other thread
+    /// sharing the limits of the parent `Thread` exactly as in [`Execution::start_thread`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the other thread is not [`ThreadStatus::Suspended`].
+    pub fn resume_thread(
+        &mut self,
+        ctx: Context<'gc>,
+        thread: Thread<'gc>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        let mut lent = self
+            .thread
+            .limiter
+            .lend(self.thread.frames.len())
+            .map_err(|err| self.thread.vm_error(err))?;
+        let stack = Stack::new(&mut self.thread.stack, self.stack_bottom);
+        let res = thread.resume_limited(ctx, stack, Some(&mut lent));
+        self.thread.limiter.repay(lent);
+        res
+    }
+
     /// Return a descriptor for this frame or an upper frame.
     ///
     /// The index 0 will return *this* frame, which will always be a callback frame.
@@ -268,11 +516,49 @@ impl<'gc, 'a> Execution<'gc, 'a> {
         assert!(index < self.thread.frames.len());
         self.thread.frames[self.thread.frames.len() - 1 - index].stack_frame()
     }
+
+    /// Call `f` with every value held by the frames and stacks of the parent `Thread`.
+    pub(crate) fn visit_thread_values(&self, f: impl FnMut(ThreadSlot, Value<'gc>)) {
+        self.thread.visit_values(f);
+    }
+
+    /// Request that the parent `Thread` be suspended once the current callback returns.
+    ///
+    /// When the callback returns, rather than continuing execution, the thread will stop and the
+    /// values left on this execution's stack will be given to the caller of [`Thread::start`] or
+    /// [`Thread::resume`]. When the thread is next resumed, the values provided to
+    /// [`Thread::resume`] will become the return values of this callback instead.
+    ///
+    /// A thread can only be suspended if it was run with [`Thread::start`] or [`Thread::resume`],
+    /// and only if every frame above this callback is a closure frame. Rust callbacks call other
+    /// functions recursively and cannot be suspended in the middle of execution.
+    ///
+    /// If the callback returns an error, the suspension request is ignored.
+    pub fn suspend(&mut self) -> Result<(), SuspendError> {
+        if !self.thread.resumable {
+            return Err(SuspendError::NotResumable);
+        }
+
+        let (_, upper_frames) = self
+            .thread
+            .frames
+            .split_last()
+            .ok_or(SuspendError::NotResumable)?;
+        if upper_frames.is_empty() || upper_frames.iter().any(|f| matches!(f, Frame::Callback(_))) {
+            return Err(SuspendError::CallbackBoundary);
+        }
+
+        self.thread.suspended = Some(self.stack_bottom);
+        Ok(())
+    }
 }
 
 /// A stack context for some `Thread`, provided to execution hooks.
 pub struct FrameStack<'gc, 'a> {
     frames: &'a [Frame<'gc>],
+    registers: &'a [Value<'gc>],
+    heap: &'a [OwnedHeapVar<'gc>],
+    this: &'a [Value<'gc>],
 }
 
 impl<'gc, 'a> FrameStack<'gc, 'a> {
@@ -303,6 +589,101 @@ impl<'gc, 'a> FrameStack<'gc, 'a> {
         assert!(index < self.frames.len());
         self.frames[self.frames.len() - 1 - index].stack_frame()
     }
+
+    /// Return the registers used by the given frame, or `None` if it is a callback frame.
+    ///
+    /// Registers are indexed the same way as in bytecode, and the returned slice will be at most as
+    /// long as the number of registers that the closure's prototype uses. The values of registers
+    /// are only meaningful as of the frame's current instruction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given an index that is larger than the return value of [`Execution::frame_depth`].
+    #[track_caller]
+    pub fn registers(&self, index: usize) -> Option<&'a [Value<'gc>]> {
+        let Frame::Closure(frame) = self.nth(index) else {
+            return None;
+        };
+        let len = self.registers.len();
+        let bottom = frame.register_bottom.min(len);
+        let top = (frame.register_bottom + frame.closure.prototype().used_registers()).min(len);
+        Some(&self.registers[bottom..top])
+    }
+
+    /// Return the current value of a heap variable for the given frame, or `None` if it is a
+    /// callback frame or `heap_idx` is out of range of the prototype's heap variables.
+    ///
+    /// The `heap_idx` is an index into [`Prototype::heap_vars`](crate::Prototype::heap_vars) for
+    /// the closure's prototype, and may refer to an owned variable, a static variable, or an
+    /// upvalue.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given an index that is larger than the return value of [`Execution::frame_depth`].
+    #[track_caller]
+    pub fn heap_var(&self, index: usize, heap_idx: usize) -> Option<Value<'gc>> {
+        let Frame::Closure(frame) = self.nth(index) else {
+            return None;
+        };
+        let prototype = frame.closure.prototype();
+        Some(match *prototype.heap_vars().get(heap_idx)? {
+            HeapVarDescriptor::Owned(idx) => self.heap.get(frame.heap_bottom + idx.index())?.get(),
+            HeapVarDescriptor::Static(idx) => prototype.static_vars()[idx.index()].get(),
+            HeapVarDescriptor::UpValue(_) => match frame.closure.heap()[heap_idx] {
+                HeapVar::Shared(shared) => shared.get(),
+                HeapVar::Owned(_) => return None,
+            },
+        })
+    }
+
+    /// Return the current value of `self` for the given frame, or `None` if it is a callback frame.
+    ///
+    /// Like [`Execution::this`], if no `self` value is set then this will return `ctx.globals()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if given an index that is larger than the return value of [`Execution::frame_depth`].
+    #[track_caller]
+    pub fn this(&self, ctx: Context<'gc>, index: usize) -> Option<Value<'gc>> {
+        let Frame::Closure(_) = self.nth(index) else {
+            return None;
+        };
+
+        // Every `self` value pushed by this frame lies below the bottom of the next closure frame
+        // above it.
+        let top = self.frames[self.frames.len() - index..]
+            .iter()
+            .find_map(|f| match f {
+                Frame::Closure(frame) => Some(frame.this_bottom),
+                Frame::Callback(_) => None,
+            })
+            .unwrap_or(self.this.len());
+        Some(
+            self.this[..top]
+                .last()
+                .copied()
+                .unwrap_or(ctx.globals().into()),
+        )
+    }
+
+    #[track_caller]
+    fn nth(&self, index: usize) -> &'a Frame<'gc> {
+        assert!(index < self.frames.len());
+        &self.frames[self.frames.len() - 1 - index]
+    }
+}
+
+// Construct a `FrameStack` from a `ThreadState`, borrowing only the fields that it needs so that
+// the hook may be borrowed mutably at the same time.
+macro_rules! frame_stack {
+    ($state:expr) => {
+        FrameStack {
+            frames: &$state.frames,
+            registers: &$state.registers.registers,
+            heap: &$state.heap,
+            this: &$state.this,
+        }
+    };
 }
 
 pub trait Hook<'gc>: 'gc + DynCollect<'gc> {
@@ -348,9 +729,13 @@ pub trait Hook<'gc>: 'gc + DynCollect<'gc> {
     /// A "VM run" here means running the VM for a closure up until the next function call or
     /// return. For every one of these runs, this hook (assuming it is not disabled) will be called
     /// exactly enough to keep track of all the VM instructions that were executed that run.
+    ///
+    /// The top frame of the provided `FrameStack` is always the running closure, and its current
+    /// instruction is the next one to be executed.
     fn on_step(
         &mut self,
         _ctx: Context<'gc>,
+        _frames: FrameStack<'gc, '_>,
         _instruction_count: u32,
     ) -> Result<u32, RuntimeError> {
         Ok(0)
@@ -359,6 +744,28 @@ pub trait Hook<'gc>: 'gc + DynCollect<'gc> {
 
 dyn_collect!(dyn Hook<'gc>);
 
+/// Where a value visited by `Thread::visit_values` is held.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum ThreadSlot {
+    Frame(usize),
+    Register(usize),
+    Stack(usize),
+    This(usize),
+    Heap(usize),
+}
+
+impl fmt::Display for ThreadSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadSlot::Frame(i) => write!(f, "<frame {i}>"),
+            ThreadSlot::Register(i) => write!(f, "<register {i}>"),
+            ThreadSlot::Stack(i) => write!(f, "<stack {i}>"),
+            ThreadSlot::This(i) => write!(f, "<self {i}>"),
+            ThreadSlot::Heap(i) => write!(f, "<heap {i}>"),
+        }
+    }
+}
+
 #[derive(Debug, Collect)]
 #[collect(no_drop)]
 pub(super) enum OwnedHeapVar<'gc> {
@@ -438,6 +845,52 @@ impl<'gc> Frame<'gc> {
 }
 
 impl<'gc> ThreadState<'gc> {
+    fn visit_values(&self, mut f: impl FnMut(ThreadSlot, Value<'gc>)) {
+        for (i, frame) in self.frames.iter().enumerate() {
+            let function = match frame {
+                Frame::Closure(frame) => Value::Closure(frame.closure),
+                &Frame::Callback(callback) => Value::Callback(callback),
+            };
+            f(ThreadSlot::Frame(i), function);
+        }
+        // Registers past the logical length may be stale, but they are still kept alive.
+        for (i, &value) in self.registers.registers.iter().enumerate() {
+            f(ThreadSlot::Register(i), value);
+        }
+        for (i, &value) in self.stack.iter().enumerate() {
+            f(ThreadSlot::Stack(i), value);
+        }
+        for (i, &value) in self.this.iter().enumerate() {
+            f(ThreadSlot::This(i), value);
+        }
+        for (i, heap_var) in self.heap.iter().enumerate() {
+            f(ThreadSlot::Heap(i), heap_var.get());
+        }
+    }
+
+    // Run `f` with this thread's own limiter reset for a new run, or if a limiter lent by another
+    // thread is given, with that limiter in place of this thread's own.
+    //
+    // Whatever `f` uses of the lent limiter is left in it for the lending thread to repay.
+    fn with_limiter<R>(
+        &mut self,
+        lent_limiter: Option<&mut Limiter>,
+        f: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        match lent_limiter {
+            Some(lent) => {
+                mem::swap(&mut self.limiter, lent);
+                let ret = f(self);
+                mem::swap(&mut self.limiter, lent);
+                ret
+            }
+            None => {
+                self.limiter.reset();
+                f(self)
+            }
+        }
+    }
+
     // Call a closure with arguments starting at `stack_bottom`.
     fn call_closure(
         &mut self,
@@ -447,6 +900,10 @@ impl<'gc> ThreadState<'gc> {
     ) -> Result<(), VmError<'gc>> {
         let bottom_frame = self.frames.len();
 
+        if let Err(err) = self.limiter.check_call(&ctx, bottom_frame + 1) {
+            return Err(self.vm_error(err));
+        }
+
         self.frames.push({
             let register_bottom = self.registers.len();
             // We only need to preserve the registers that the prototype claims to use.
@@ -478,92 +935,50 @@ impl<'gc> ThreadState<'gc> {
             })
         });
 
-        fn unwind_closure_frame<'gc>(this: &mut ThreadState<'gc>, ctx: Context<'gc>) {
-            if let Some(hook) = &mut this.hook {
-                hook.on_return(
-                    ctx,
-                    FrameStack {
-                        frames: &this.frames,
-                    },
-                );
-            }
-
-            match this.frames.pop().unwrap() {
-                Frame::Closure(closure_frame) => {
-                    this.registers.set_len(closure_frame.register_bottom);
-                    this.stack.truncate(closure_frame.stack_bottom);
-                    this.stack_frame_boundaries
-                        .truncate(closure_frame.stack_frame_boundaries_bottom);
-                    this.this.truncate(closure_frame.this_bottom);
-                    this.heap.truncate(closure_frame.heap_bottom);
-                }
-                _ => panic!("not a closure frame"),
-            }
-        }
-
-        fn vm_error<'gc>(thread: &ThreadState<'gc>, err: impl Into<VmError<'gc>>) -> VmError<'gc> {
-            let mut vm_err = err.into();
-            if vm_err.backtrace.is_none() {
-                vm_err.backtrace = Some(thread.frames.iter().map(|f| f.stack_frame()).collect());
-            }
-            vm_err
-        }
-
         if let Some(hook) = &mut self.hook {
-            if let Err(err) = hook.on_call(
-                ctx,
-                FrameStack {
-                    frames: &self.frames,
-                },
-            ) {
-                let err = vm_error(self, err);
-                unwind_closure_frame(self, ctx);
+            if let Err(err) = hook.on_call(ctx, frame_stack!(self)) {
+                let err = self.vm_error(err);
+                self.unwind_closure_frame(ctx);
                 return Err(err);
             }
         }
 
+        self.run_closures(ctx, bottom_frame)
+    }
+
+    // Run the closure frame at the top of the frame stack until the frame at index `bottom_frame`
+    // returns.
+    //
+    // Every frame at or above `bottom_frame` must be a closure frame.
+    //
+    // If `bottom_frame` is 0 and a callback requests that the thread be suspended, this will
+    // instead return early with all closure frames still on the frame stack.
+    fn run_closures(&mut self, ctx: Context<'gc>, bottom_frame: usize) -> Result<(), VmError<'gc>> {
         let err = 'step: loop {
-            let Frame::Closure(frame) = self.frames.last_mut().unwrap() else {
-                unreachable!()
-            };
+            let next = if self.hook.is_some() || self.limiter.is_metered() {
+                // Whether the step hook has been disabled for this VM run, limits must still be
+                // checked even if it has been.
+                let mut hook_enabled = true;
 
-            let registers = self.registers.frame(frame.register_bottom);
-            let stack = VecEndSlice::new(&mut self.stack, frame.stack_bottom);
-            let stack_frame_boundaries = VecEndSlice::new(
-                &mut self.stack_frame_boundaries,
-                frame.stack_frame_boundaries_bottom,
-            );
-            let this = VecEndSlice::new(&mut self.this, frame.this_bottom);
-            let heap = &mut self.heap[frame.heap_bottom..];
-            let mut dispatch = dispatch::Dispatch::new(
-                ctx,
-                frame.closure,
-                registers,
-                stack,
-                stack_frame_boundaries,
-                this,
-                heap,
-            );
-
-            let next = if let Some(hook) = &mut self.hook {
-                let mut remaining_insts = match hook.on_step(ctx, 0) {
+                let mut remaining_insts = match self.step(ctx, 0, &mut hook_enabled) {
                     Ok(next_remaining) => next_remaining,
                     Err(err) => break 'step err.into(),
                 };
 
                 loop {
-                    // if `Hook::on_step` returns 0, this indicates that the step hook is disabled
-                    // for this VM run.
+                    let (dispatcher, mut dispatch) = self.top_dispatch(ctx);
+
+                    // A step count of 0 indicates that the step hook is disabled for this VM run
+                    // and there are no limits that need to be checked.
                     if remaining_insts == 0 {
-                        match frame.dispatcher.dispatch_loop(&mut dispatch) {
+                        match dispatcher.dispatch_loop(&mut dispatch) {
                             Ok(next) => break next,
                             Err(err) => break 'step err.into(),
                         }
                     }
 
-                    if let Some((mut res, remain)) = frame
-                        .dispatcher
-                        .dispatch_count(&mut dispatch, remaining_insts)
+                    if let Some((mut res, remain)) =
+                        dispatcher.dispatch_count(&mut dispatch, remaining_insts)
                     {
                         // The `on_step` hook here takes priority over a script error, because the
                         // contract is that `on_step` should not lose any VM instructions under
@@ -571,7 +986,9 @@ impl<'gc> ThreadState<'gc> {
                         //
                         // If the hook succeeds, we throw away the next requested step count because
                         // the VM is pausing.
-                        if let Err(err) = hook.on_step(ctx, remaining_insts - remain) {
+                        if let Err(err) =
+                            self.step(ctx, remaining_insts - remain, &mut hook_enabled)
+                        {
                             res = Err(err.into());
                         }
 
@@ -580,7 +997,7 @@ impl<'gc> ThreadState<'gc> {
                             Err(err) => break 'step err.into(),
                         }
                     } else {
-                        match hook.on_step(ctx, remaining_insts) {
+                        match self.step(ctx, remaining_insts, &mut hook_enabled) {
                             Ok(next_remaining) => {
                                 remaining_insts = next_remaining;
                             }
@@ -589,12 +1006,18 @@ impl<'gc> ThreadState<'gc> {
                     }
                 }
             } else {
-                match frame.dispatcher.dispatch_loop(&mut dispatch) {
+                let (dispatcher, mut dispatch) = self.top_dispatch(ctx);
+                match dispatcher.dispatch_loop(&mut dispatch) {
                     Ok(next) => next,
                     Err(err) => break err.into(),
                 }
             };
 
+            let frame_depth = self.frames.len();
+            let Frame::Closure(frame) = self.frames.last_mut().unwrap() else {
+                unreachable!()
+            };
+
             match next {
                 dispatch::Next::Call {
                     function,
@@ -603,6 +1026,10 @@ impl<'gc> ThreadState<'gc> {
                 } => {
                     match function {
                         Function::Closure(closure) => {
+                            if let Err(err) = self.limiter.check_call(&ctx, frame_depth + 1) {
+                                break err.into();
+                            }
+
                             let register_bottom = self.registers.len();
                             // We only need to preserve the registers that the prototype claims
                             // to use.
@@ -639,12 +1066,7 @@ impl<'gc> ThreadState<'gc> {
                             }));
 
                             if let Some(hook) = &mut self.hook {
-                                if let Err(err) = hook.on_call(
-                                    ctx,
-                                    FrameStack {
-                                        frames: &self.frames,
-                                    },
-                                ) {
+                                if let Err(err) = hook.on_call(ctx, frame_stack!(self)) {
                                     break err.into();
                                 }
                             }
@@ -657,6 +1079,12 @@ impl<'gc> ThreadState<'gc> {
                             {
                                 break err;
                             }
+
+                            // Suspension is only possible from the outermost `run_closures` call,
+                            // every frame below this one must be a closure frame.
+                            if bottom_frame == 0 && self.suspended.is_some() {
+                                return Ok(());
+                            }
                         }
                     }
                 }
@@ -679,12 +1107,7 @@ impl<'gc> ThreadState<'gc> {
                     self.heap.truncate(frame.heap_bottom);
 
                     if let Some(hook) = &mut self.hook {
-                        hook.on_return(
-                            ctx,
-                            FrameStack {
-                                frames: &self.frames,
-                            },
-                        );
+                        hook.on_return(ctx, frame_stack!(self));
                     }
 
                     // Pop the returning frame.
@@ -698,15 +1121,135 @@ impl<'gc> ThreadState<'gc> {
             }
         };
 
-        let err = vm_error(self, err);
+        let err = self.vm_error(err);
 
         while self.frames.len() > bottom_frame {
-            unwind_closure_frame(self, ctx);
+            self.unwind_closure_frame(ctx);
         }
 
         Err(err)
     }
 
+    // Create a `Dispatch` for the closure frame at the top of the frame stack.
+    fn top_dispatch(
+        &mut self,
+        ctx: Context<'gc>,
+    ) -> (
+        &mut instructions::Dispatcher<'gc>,
+        dispatch::Dispatch<'gc, '_>,
+    ) {
+        let Frame::Closure(frame) = self.frames.last_mut().unwrap() else {
+            unreachable!()
+        };
+
+        let registers = self.registers.frame(frame.register_bottom);
+        let stack = VecEndSlice::new(&mut self.stack, frame.stack_bottom);
+        let stack_frame_boundaries = VecEndSlice::new(
+            &mut self.stack_frame_boundaries,
+            frame.stack_frame_boundaries_bottom,
+        );
+        let this = VecEndSlice::new(&mut self.this, frame.this_bottom);
+        let heap = &mut self.heap[frame.heap_bottom..];
+        let dispatch = dispatch::Dispatch::new(
+            ctx,
+            frame.closure,
+            registers,
+            stack,
+            stack_frame_boundaries,
+            this,
+            heap,
+        );
+        (&mut frame.dispatcher, dispatch)
+    }
+
+    // Report executed instructions to the step hook (if it is enabled) and check limits.
+    //
+    // Returns the number of instructions that may execute before this must be called again, or 0
+    // if it need not be called again for this VM run.
+    fn step(
+        &mut self,
+        ctx: Context<'gc>,
+        instruction_count: u32,
+        hook_enabled: &mut bool,
+    ) -> Result<u32, RuntimeError> {
+        let mut next = 0;
+        if *hook_enabled {
+            if let Some(hook) = &mut self.hook {
+                next = hook.on_step(ctx, frame_stack!(self), instruction_count)?;
+                *hook_enabled = next != 0;
+            }
+        }
+
+        match self.limiter.step(&ctx, instruction_count)? {
+            0 => {}
+            limit_next if next == 0 => next = limit_next,
+            limit_next => next = next.min(limit_next),
+        }
+
+        Ok(next)
+    }
+
+    fn unwind_closure_frame(&mut self, ctx: Context<'gc>) {
+        if let Some(hook) = &mut self.hook {
+            hook.on_return(ctx, frame_stack!(self));
+        }
+
+        match self.frames.pop().unwrap() {
+            Frame::Closure(closure_frame) => {
+                self.registers.set_len(closure_frame.register_bottom);
+                self.stack.truncate(closure_frame.stack_bottom);
+                self.stack_frame_boundaries
+                    .truncate(closure_frame.stack_frame_boundaries_bottom);
+                self.this.truncate(closure_frame.this_bottom);
+                self.heap.truncate(closure_frame.heap_bottom);
+            }
+            _ => panic!("not a closure frame"),
+        }
+    }
+
+    fn vm_error(&self, err: impl Into<VmError<'gc>>) -> VmError<'gc> {
+        let mut vm_err = err.into();
+        if vm_err.backtrace.is_none() {
+            vm_err.backtrace = Some(self.frames.iter().map(|f| f.stack_frame()).collect());
+        }
+        vm_err
+    }
+
+    // Finish a call to `Thread::start` or `Thread::resume`, moving either the suspending callback's
+    // arguments or the function's returns into the given stack.
+    fn finish_resumable(
+        &mut self,
+        res: Result<(), VmError<'gc>>,
+        mut stack: Stack<'gc, '_>,
+    ) -> Result<ThreadExit, VmError<'gc>> {
+        self.resumable = false;
+
+        let exit = match res {
+            Ok(()) => {
+                if let Some(suspended_bottom) = self.suspended {
+                    stack.extend(self.stack.drain(suspended_bottom..));
+                    return Ok(ThreadExit::Suspended);
+                }
+                stack.extend(self.stack.drain(..));
+                Ok(ThreadExit::Returned)
+            }
+            Err(err) => {
+                self.suspended = None;
+                Err(err)
+            }
+        };
+
+        self.registers.clear();
+        self.stack.clear();
+
+        assert!(self.frames.is_empty());
+        assert!(self.stack_frame_boundaries.is_empty());
+        assert!(self.this.is_empty());
+        assert!(self.heap.is_empty());
+
+        exit
+    }
+
     fn call_callback(
         &mut self,
         ctx: Context<'gc>,
@@ -714,22 +1257,16 @@ impl<'gc> ThreadState<'gc> {
         stack_bottom: usize,
         with_this: Option<Value<'gc>>,
     ) -> Result<(), VmError<'gc>> {
+        if let Err(err) = self.limiter.check_call(&ctx, self.frames.len() + 1) {
+            return Err(self.vm_error(err));
+        }
+
         let this_bottom = self.this.len();
         self.frames.push(Frame::Callback(callback));
 
         if let Some(hook) = &mut self.hook {
-            if let Err(err) = hook.on_call(
-                ctx,
-                FrameStack {
-                    frames: &self.frames,
-                },
-            ) {
-                hook.on_return(
-                    ctx,
-                    FrameStack {
-                        frames: &self.frames,
-                    },
-                );
+            if let Err(err) = hook.on_call(ctx, frame_stack!(self)) {
+                hook.on_return(ctx, frame_stack!(self));
                 // Pop the callback frame.
                 assert!(matches!(self.frames.pop(), Some(Frame::Callback(_))));
                 return Err(err.into());
@@ -752,12 +1289,7 @@ impl<'gc> ThreadState<'gc> {
         drop(exec);
 
         if let Some(hook) = &mut self.hook {
-            hook.on_return(
-                ctx,
-                FrameStack {
-                    frames: &self.frames,
-                },
-            );
+            hook.on_return(ctx, frame_stack!(self));
         }
 
         // Guard against the `Execution` not being dropped.
@@ -210,6 +210,15 @@ impl<'gc> UserData<'gc> {
         self.0.into_inner()
     }
 
+    /// The size in bytes of the allocation holding this `UserData`.
+    ///
+    /// Memory owned by the held value outside of the allocation, like the contents of a `Vec`, is
+    /// not included.
+    #[inline]
+    pub fn size(self) -> usize {
+        self.0.size()
+    }
+
     /// Check if a `UserData` was created with the type `R` passed to [`UserData::new`].
     ///
     /// `UserData` is identified by the `TypeId` of the [`trait@Rootable`] impl, NOT the type
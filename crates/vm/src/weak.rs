@@ -0,0 +1,204 @@
+use std::mem;
+
+use gc_arena::{Collect, Gc, GcWeak, Mutation, RefLock};
+use thiserror::Error;
+
+use crate::{
+    array::{Array, ArrayInner},
+    closure::{Closure, ClosureInner},
+    object::{Object, ObjectInner},
+    user_data::{UserData, UserDataInner},
+    value::Value,
+};
+
+#[derive(Debug, Copy, Clone, Error)]
+#[error("cannot create a weak reference to a value of type {0}")]
+pub struct BadWeakValueType(pub &'static str);
+
+/// A weak reference to an [`Object`], [`Array`], [`Closure`], or [`UserData`].
+///
+/// A `WeakValue` does not keep the value it references alive. Once the value has been collected,
+/// [`WeakValue::upgrade`] will always return `None`.
+#[derive(Copy, Clone, Collect)]
+#[collect(no_drop)]
+pub enum WeakValue<'gc> {
+    Object(GcWeak<'gc, ObjectInner<'gc>>),
+    Array(GcWeak<'gc, ArrayInner<'gc>>),
+    Closure(GcWeak<'gc, ClosureInner<'gc>>),
+    UserData(GcWeak<'gc, UserDataInner<'gc>>),
+}
+
+impl<'gc> From<Object<'gc>> for WeakValue<'gc> {
+    fn from(object: Object<'gc>) -> Self {
+        Self::Object(Gc::downgrade(object.into_inner()))
+    }
+}
+
+impl<'gc> From<Array<'gc>> for WeakValue<'gc> {
+    fn from(array: Array<'gc>) -> Self {
+        Self::Array(Gc::downgrade(array.into_inner()))
+    }
+}
+
+impl<'gc> From<Closure<'gc>> for WeakValue<'gc> {
+    fn from(closure: Closure<'gc>) -> Self {
+        Self::Closure(Gc::downgrade(closure.into_inner()))
+    }
+}
+
+impl<'gc> From<UserData<'gc>> for WeakValue<'gc> {
+    fn from(user_data: UserData<'gc>) -> Self {
+        Self::UserData(Gc::downgrade(user_data.into_inner()))
+    }
+}
+
+impl<'gc> TryFrom<Value<'gc>> for WeakValue<'gc> {
+    type Error = BadWeakValueType;
+
+    fn try_from(value: Value<'gc>) -> Result<Self, Self::Error> {
+        match value {
+            Value::Object(object) => Ok(object.into()),
+            Value::Array(array) => Ok(array.into()),
+            Value::Closure(closure) => Ok(closure.into()),
+            Value::UserData(user_data) => Ok(user_data.into()),
+            other => Err(BadWeakValueType(other.type_name())),
+        }
+    }
+}
+
+impl<'gc> WeakValue<'gc> {
+    /// Returns the referenced value if it has not been collected.
+    pub fn upgrade(self, mc: &Mutation<'gc>) -> Option<Value<'gc>> {
+        Some(match self {
+            WeakValue::Object(weak) => Object::from_inner(weak.upgrade(mc)?).into(),
+            WeakValue::Array(weak) => Array::from_inner(weak.upgrade(mc)?).into(),
+            WeakValue::Closure(weak) => Closure::from_inner(weak.upgrade(mc)?).into(),
+            WeakValue::UserData(weak) => UserData::from_inner(weak.upgrade(mc)?).into(),
+        })
+    }
+
+    /// Returns true if the referenced value has not been collected.
+    pub fn is_alive(self, mc: &Mutation<'gc>) -> bool {
+        self.upgrade(mc).is_some()
+    }
+
+    /// Returns true if the referenced value has been collected *and* freed.
+    ///
+    /// Unlike [`WeakValue::is_alive`], this only becomes true once the GC has finished sweeping the
+    /// value.
+    pub fn is_dropped(self) -> bool {
+        match self {
+            WeakValue::Object(weak) => weak.is_dropped(),
+            WeakValue::Array(weak) => weak.is_dropped(),
+            WeakValue::Closure(weak) => weak.is_dropped(),
+            WeakValue::UserData(weak) => weak.is_dropped(),
+        }
+    }
+}
+
+/// A queue which hands values back to the embedder once some owning value has been collected.
+///
+/// This allows releasing resources held outside of the GC (for example, by the game runner) once
+/// the script value that owns them is gone. A finalizer is registered with an owner and a payload
+/// value. The payload is kept alive until the owner has been collected, after which it is moved to
+/// the ready queue to be retrieved with [`Finalizers::take_ready`].
+///
+/// Ownership is only checked after [`Interpreter::gc_collect_debt`] or
+/// [`Interpreter::gc_finish_cycle`] completes a collection cycle. The payload must not reference
+/// the owner, directly or indirectly, otherwise the owner will never be collected.
+///
+/// [`Interpreter::gc_collect_debt`]: crate::Interpreter::gc_collect_debt
+/// [`Interpreter::gc_finish_cycle`]: crate::Interpreter::gc_finish_cycle
+#[derive(Copy, Clone, Collect)]
+#[collect(no_drop)]
+pub struct Finalizers<'gc>(Gc<'gc, RefLock<FinalizersState<'gc>>>);
+
+#[derive(Default, Collect)]
+#[collect(no_drop)]
+struct FinalizersState<'gc> {
+    pending: Vec<(WeakValue<'gc>, Value<'gc>)>,
+    ready: Vec<Value<'gc>>,
+}
+
+impl<'gc> Finalizers<'gc> {
+    pub(crate) fn new(mc: &Mutation<'gc>) -> Self {
+        Self(Gc::new(mc, RefLock::new(FinalizersState::default())))
+    }
+
+    /// Register `payload` to be moved to the ready queue once `owner` has been collected.
+    pub fn register(
+        self,
+        mc: &Mutation<'gc>,
+        owner: impl Into<WeakValue<'gc>>,
+        payload: impl Into<Value<'gc>>,
+    ) {
+        self.0
+            .borrow_mut(mc)
+            .pending
+            .push((owner.into(), payload.into()));
+    }
+
+    /// The number of registered finalizers which have not yet become ready.
+    pub fn pending_len(self) -> usize {
+        self.0.borrow().pending.len()
+    }
+
+    /// Take every payload whose owner has been collected, in the order they became ready.
+    pub fn take_ready(self, mc: &Mutation<'gc>) -> Vec<Value<'gc>> {
+        mem::take(&mut self.0.borrow_mut(mc).ready)
+    }
+
+    /// Move the payload of every finalizer whose owner has been dropped to the ready queue.
+    pub(crate) fn poll(self, mc: &Mutation<'gc>) {
+        if self.0.borrow().pending.is_empty() {
+            return;
+        }
+
+        let mut state = self.0.borrow_mut(mc);
+        let FinalizersState { pending, ready } = &mut *state;
+        pending.retain(|&(owner, payload)| {
+            if owner.is_dropped() {
+                ready.push(payload);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::Interpreter, object::Object};
+
+    use super::*;
+
+    #[test]
+    fn test_finalizers() {
+        let mut interpreter = Interpreter::new();
+        interpreter.enter(|ctx| {
+            let kept = Object::new(&ctx);
+            ctx.globals().borrow_mut(&ctx).set(ctx.intern("kept"), kept);
+            let dropped = Object::new(&ctx);
+
+            assert!(WeakValue::from(dropped).is_alive(&ctx));
+            assert!(WeakValue::try_from(Value::Integer(1)).is_err());
+
+            ctx.finalizers()
+                .register(&ctx, kept, ctx.intern("kept payload"));
+            ctx.finalizers()
+                .register(&ctx, dropped, ctx.intern("dropped payload"));
+            assert_eq!(ctx.finalizers().pending_len(), 2);
+        });
+
+        interpreter.gc_finish_cycle();
+
+        interpreter.enter(|ctx| {
+            assert_eq!(ctx.finalizers().pending_len(), 1);
+            let ready = ctx.finalizers().take_ready(&ctx);
+            assert_eq!(ready.len(), 1);
+            assert_eq!(ready[0].as_string().unwrap().as_str(), "dropped payload");
+            assert!(ctx.finalizers().take_ready(&ctx).is_empty());
+        });
+    }
+}